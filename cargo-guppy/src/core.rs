@@ -6,11 +6,13 @@
 use clap::{ArgEnum, Parser};
 use color_eyre::eyre::{ensure, eyre, Result, WrapErr};
 use guppy::{
-    graph::{DependencyDirection, DependencyReq, PackageGraph, PackageLink, PackageQuery},
+    graph::{
+        DependencyDirection, DependencyReq, PackageGraph, PackageLink, PackageQuery, PackageSet,
+    },
     platform::EnabledTernary,
     PackageId,
 };
-use guppy_cmdlib::string_to_platform_spec;
+use guppy_cmdlib::{package_expr::PackageExpr, string_to_platform_spec};
 use std::collections::HashSet;
 
 #[derive(ArgEnum, Copy, Clone, Debug)]
@@ -43,6 +45,10 @@ pub struct QueryOptions {
     #[clap(rename_all = "screaming_snake_case")]
     /// The root packages to start the query from
     roots: Vec<String>,
+
+    #[clap(long, conflicts_with_all = &["ROOTS", "direction"])]
+    /// Select packages with an expression instead, e.g. "deps(foo) - rdeps(bar)"
+    expr: Option<String>,
 }
 
 impl QueryOptions {
@@ -63,6 +69,19 @@ impl QueryOptions {
             Ok(pkg_graph.query_workspace())
         }
     }
+
+    /// Resolves the packages selected by these options, following links for which `resolver_fn`
+    /// returns true.
+    pub fn resolve_with_fn<'g>(
+        &self,
+        pkg_graph: &'g PackageGraph,
+        resolver_fn: impl Fn(&PackageQuery<'g>, PackageLink<'g>) -> bool,
+    ) -> Result<PackageSet<'g>> {
+        match &self.expr {
+            Some(expr) => PackageExpr::parse(expr)?.eval_with_fn(pkg_graph, resolver_fn),
+            None => Ok(self.apply(pkg_graph)?.resolve_with_fn(resolver_fn)),
+        }
+    }
 }
 
 #[derive(Debug, Parser)]
@@ -1,201 +1,197 @@
 // Copyright (c) The cargo-guppy Contributors
 // SPDX-License-Identifier: MIT OR Apache-2.0
 
-use guppy::{graph::PackageMetadata, PackageId};
-use serde::{ser::SerializeStruct, Serialize, Serializer};
-use std::{collections::HashMap, ops::Deref};
-
-#[derive(Debug, Default)]
-pub struct DiffOptions;
+//! Implementation for `cargo guppy diff`.
+
+mod packages;
+
+use camino::{Utf8Path, Utf8PathBuf};
+use clap::{ArgEnum, Parser};
+use color_eyre::eyre::{bail, Result, WrapErr};
+use guppy::graph::{
+    cargo::{CargoOptions, CargoSet},
+    diff::GraphDiff,
+    summaries::{diff::SummaryDiff, Summary},
+    PackageGraph,
+};
+use guppy_cmdlib::{
+    string_to_platform_spec, CargoMetadataOptions, CargoResolverOpts, PackagesAndFeatures,
+};
+use serde::Serialize;
+use std::fs;
+
+#[derive(Debug, Parser)]
+pub struct DiffOptions {
+    /// The old `cargo metadata` JSON file
+    #[clap(
+        name = "OLD",
+        requires = "NEW",
+        required_unless_present = "base",
+        conflicts_with = "base"
+    )]
+    old: Option<Utf8PathBuf>,
+
+    /// The new `cargo metadata` JSON file
+    #[clap(name = "NEW")]
+    new: Option<Utf8PathBuf>,
+
+    /// Git revision to diff from, checked out into a temporary worktree
+    #[clap(long)]
+    base: Option<String>,
+
+    /// Git revision to diff to (default: the current checkout, including uncommitted changes)
+    #[clap(long, requires = "base")]
+    head: Option<String>,
+
+    /// Output the diff in JSON format
+    #[clap(long)]
+    json: bool,
+
+    /// The JSON format to output
+    ///
+    /// "packages" only lists added, removed and updated packages. "graph" also includes changes
+    /// to features, dependency edges and build targets.
+    #[clap(long, arg_enum, requires = "json", default_value_t = JsonFormat::Packages)]
+    json_format: JsonFormat,
+
+    /// Also diff summaries of the packages and features Cargo would build
+    ///
+    /// With --json-format graph, the output becomes an object with "graph" and "summary" keys.
+    #[clap(long)]
+    summary: bool,
+
+    #[clap(flatten)]
+    pf: PackagesAndFeatures,
+
+    #[clap(flatten)]
+    resolver_opts: CargoResolverOpts,
+
+    #[clap(long = "target-platform")]
+    /// Evaluate summaries against target platform, "current" or "any" (default: any)
+    target_platform: Option<String>,
+
+    #[clap(long = "host-platform")]
+    /// Evaluate summaries against host platform, "current" or "any" (default: any)
+    host_platform: Option<String>,
+
+    #[clap(flatten)]
+    metadata_opts: CargoMetadataOptions,
+}
 
 impl DiffOptions {
-    pub fn diff<'a>(
-        &self,
-        old_packages: &'a [PackageMetadata<'a>],
-        new_packages: &'a [PackageMetadata<'a>],
-    ) -> Diff<'a> {
-        let mut new: HashMap<&PackageId, Package> =
-            new_packages.iter().map(|p| (p.id(), Package(p))).collect();
-
-        let mut removed = old_packages
-            .iter()
-            .filter_map(|package| {
-                if new.remove(package.id()).is_none() {
-                    Some(Package(package))
-                } else {
-                    None
-                }
-            })
-            .map(|removed_package| {
-                let remaining_packages = new_packages
-                    .iter()
-                    .filter_map(|package| {
-                        if (package.id() != removed_package.id())
-                            && (package.name() == removed_package.name())
-                        {
-                            Some(Package(package))
-                        } else {
-                            None
-                        }
-                    })
-                    .collect::<Vec<_>>();
-
-                if remaining_packages.is_empty() {
-                    (removed_package.id().clone(), (removed_package, None))
-                } else {
-                    (
-                        removed_package.id().clone(),
-                        (removed_package, Some(remaining_packages)),
-                    )
-                }
-            })
-            .collect::<HashMap<_, _>>();
-
-        let mut added = new
-            .into_iter()
-            .map(|(added_package_id, added_package)| {
-                let existing_packages = new_packages
-                    .iter()
-                    .filter_map(|package| {
-                        if (package.id() != added_package_id)
-                            && (package.name() == added_package.name())
-                        {
-                            Some(Package(package))
-                        } else {
-                            None
-                        }
-                    })
-                    .collect::<Vec<_>>();
-
-                if existing_packages.is_empty() {
-                    (added_package_id, (added_package, None))
-                } else {
-                    (added_package_id, (added_package, Some(existing_packages)))
-                }
-            })
-            .collect::<HashMap<_, _>>();
-
-        let mut updated = removed
-            .iter()
-            .filter_map(|(_, (removed_package, _remaining_packages))| {
-                if let Some((_updated_package_id, (updated_package, _))) =
-                    added
-                        .iter()
-                        .find(|(_added_package_id, (added_package, _))| {
-                            removed_package.name() == added_package.name()
-                        })
-                {
-                    Some((removed_package.clone(), updated_package.clone()))
-                } else {
-                    None
-                }
-            })
-            .collect::<Vec<_>>();
-        updated.sort_by(|a, b| a.1.name().cmp(b.1.name()));
-
-        // Remove entries from Added and Removed
-        for (removed_pkg, added_pkg) in &updated {
-            removed.remove(removed_pkg.id());
-            added.remove(added_pkg.id());
+    pub fn exec(&self) -> Result<()> {
+        let (old_graph, new_graph) = match (&self.old, &self.new, &self.base) {
+            (Some(old), Some(new), _) => (read_json_graph(old)?, read_json_graph(new)?),
+            (_, _, Some(base)) => {
+                let old_graph = self.metadata_opts.build_graph_at(base)?;
+                let new_graph = match &self.head {
+                    Some(head) => self.metadata_opts.build_graph_at(head)?,
+                    None => self.metadata_opts.build_graph()?,
+                };
+                (old_graph, new_graph)
+            }
+            _ => bail!("either OLD and NEW or --base must be specified"),
+        };
+
+        if self.json && self.json_format == JsonFormat::Packages {
+            if self.summary {
+                bail!("--summary with --json requires --json-format graph");
+            }
+            let diff = packages::packages_diff(&old_graph, &new_graph);
+            println!("{}", serde_json::to_string_pretty(&diff)?);
+            return Ok(());
         }
 
-        let mut removed = removed.into_iter().map(|x| x.1).collect::<Vec<_>>();
-        removed.sort_by(|(a, _), (b, _)| a.name().cmp(b.name()));
-        let mut added = added.into_iter().map(|x| x.1).collect::<Vec<_>>();
-        added.sort_by(|(a, _), (b, _)| a.name().cmp(b.name()));
+        let diff = old_graph.diff(&new_graph);
+        if !self.summary {
+            if self.json {
+                println!("{}", serde_json::to_string_pretty(&diff)?);
+            } else {
+                print!("{}", diff.report());
+            }
+            return Ok(());
+        }
 
-        Diff {
-            updated,
-            removed,
-            added,
+        let old_summary = self.make_summary(&old_graph)?;
+        let new_summary = self.make_summary(&new_graph)?;
+        let summary_diff = old_summary.diff(&new_summary);
+        if self.json {
+            let json = DiffJson {
+                graph: &diff,
+                summary: &summary_diff,
+            };
+            println!("{}", serde_json::to_string_pretty(&json)?);
+        } else {
+            print!("{}", diff.report());
+            println!("{}", summary_diff.report());
         }
+        Ok(())
     }
-}
-
-#[derive(Clone, Debug)]
-struct Package<'a>(pub &'a PackageMetadata<'a>);
 
-impl<'a> Deref for Package<'a> {
-    type Target = PackageMetadata<'a>;
-
-    fn deref(&self) -> &Self::Target {
-        self.0
+    fn make_summary(&self, graph: &PackageGraph) -> Result<Summary> {
+        let mut cargo_opts = CargoOptions::new();
+        cargo_opts
+            .set_include_dev(self.resolver_opts.include_dev)
+            .set_resolver(self.resolver_opts.resolver_version.to_guppy())
+            .set_initials_platform(self.resolver_opts.initials_platform.to_guppy())
+            .set_target_platform(string_to_platform_spec(self.target_platform.as_deref())?)
+            .set_host_platform(string_to_platform_spec(self.host_platform.as_deref())?);
+
+        let (initials, features_only) = self.pf.make_feature_sets(graph)?;
+        let cargo_set = CargoSet::new(initials, features_only, &cargo_opts)?;
+        Ok(cargo_set.to_summary(&cargo_opts)?)
     }
 }
 
-impl<'a> Serialize for Package<'a> {
-    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
-    where
-        S: Serializer,
-    {
-        let mut state = serializer.serialize_struct("Package", 3)?;
-        state.serialize_field("id", self.0.id().repr())?;
-        state.serialize_field("name", self.0.name())?;
-        state.serialize_field("version", self.0.version())?;
-        state.end()
-    }
+#[derive(ArgEnum, Copy, Clone, Debug, Eq, PartialEq)]
+enum JsonFormat {
+    Packages,
+    Graph,
 }
 
-#[derive(Debug, Serialize)]
-pub struct Diff<'a> {
-    updated: Vec<(Package<'a>, Package<'a>)>,
-    removed: Vec<(Package<'a>, Option<Vec<Package<'a>>>)>,
-    added: Vec<(Package<'a>, Option<Vec<Package<'a>>>)>,
+#[derive(Serialize)]
+struct DiffJson<'a> {
+    graph: &'a GraphDiff,
+    summary: &'a SummaryDiff<'a>,
 }
 
-impl<'a> ::std::fmt::Display for Diff<'a> {
-    fn fmt(&self, f: &mut ::std::fmt::Formatter<'_>) -> ::std::fmt::Result {
-        fn write_dups(
-            f: &mut ::std::fmt::Formatter<'_>,
-            dups: &Option<Vec<Package>>,
-        ) -> ::std::fmt::Result {
-            if let Some(dups) = dups {
-                write!(f, " ({}", dups[0].version())?;
-                for p in &dups[1..] {
-                    write!(f, ", {}", p.version())?;
-                }
-                write!(f, ")")?;
-            }
-
-            Ok(())
-        }
-
-        if !self.added.is_empty() {
-            writeln!(f, "Added Packages (Duplicate versions in '()'):")?;
-            for (added, dups) in &self.added {
-                write!(f, "\t{} {}", added.name(), added.version(),)?;
-
-                write_dups(f, dups)?;
-                writeln!(f)?;
-            }
-            writeln!(f)?;
-        }
-
-        if !self.removed.is_empty() {
-            writeln!(f, "Removed Packages (Remaining versions in '()'):")?;
-            for (removed, dups) in &self.removed {
-                write!(f, "\t{} {}", removed.name(), removed.version(),)?;
-
-                write_dups(f, dups)?;
-                writeln!(f)?;
-            }
-            writeln!(f)?;
-        }
+fn read_json_graph(path: &Utf8Path) -> Result<PackageGraph> {
+    let json =
+        fs::read_to_string(path).wrap_err_with(|| format!("reading metadata {} failed", path))?;
+    PackageGraph::from_json(&json).wrap_err_with(|| format!("parsing metadata {} failed", path))
+}
 
-        if !self.updated.is_empty() {
-            writeln!(f, "Updated Packages:")?;
-            for (removed, added) in &self.updated {
-                writeln!(
-                    f,
-                    "\t{}: {} -> {}",
-                    removed.name(),
-                    removed.version(),
-                    added.version(),
-                )?;
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use fixtures::json::JsonFixture;
+
+    #[test]
+    fn packages_json() {
+        let diff = packages::packages_diff(
+            JsonFixture::metadata1().graph(),
+            JsonFixture::metadata2().graph(),
+        );
+        let json = serde_json::to_value(&diff).expect("diff serialized");
+        let object = json.as_object().expect("diff is an object");
+        assert_eq!(
+            object.keys().collect::<Vec<_>>(),
+            vec!["added", "removed", "updated"],
+        );
+        for (key, value) in object {
+            for entry in value.as_array().expect("entries are arrays") {
+                let entry = entry.as_array().expect("entries are pairs");
+                assert_eq!(entry.len(), 2, "{} entries are pairs", key);
+                let package = entry[0].as_object().expect("packages are objects");
+                assert_eq!(
+                    package.keys().collect::<Vec<_>>(),
+                    vec!["id", "name", "version"],
+                );
             }
-            writeln!(f)?;
         }
-
-        Ok(())
+        assert!(
+            !object["added"].as_array().unwrap().is_empty(),
+            "metadata2 adds packages"
+        );
     }
 }
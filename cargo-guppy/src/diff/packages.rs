@@ -0,0 +1,98 @@
+// Copyright (c) The cargo-guppy Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! The package-only JSON diff format output by `cargo guppy diff --json`.
+
+use guppy::{
+    graph::{PackageGraph, PackageMetadata},
+    PackageId,
+};
+use serde::{ser::SerializeStruct, Serialize, Serializer};
+use std::collections::HashMap;
+
+/// Diffs the packages in two graphs.
+pub(super) fn packages_diff<'a>(
+    old_graph: &'a PackageGraph,
+    new_graph: &'a PackageGraph,
+) -> PackagesDiff<'a> {
+    let old_packages: Vec<_> = old_graph.packages().collect();
+    let new_packages: Vec<_> = new_graph.packages().collect();
+
+    let mut new: HashMap<&PackageId, Package> = new_packages
+        .iter()
+        .map(|package| (package.id(), Package(*package)))
+        .collect();
+
+    // Packages with the same name as `package` in the new graph.
+    let same_name = |package: &Package<'a>| {
+        let packages: Vec<_> = new_packages
+            .iter()
+            .filter(|other| other.id() != package.0.id() && other.name() == package.0.name())
+            .map(|other| Package(*other))
+            .collect();
+        (!packages.is_empty()).then(|| packages)
+    };
+
+    let mut removed: HashMap<_, _> = old_packages
+        .iter()
+        .filter(|package| new.remove(package.id()).is_none())
+        .map(|package| {
+            let removed = Package(*package);
+            (package.id(), (removed, same_name(&removed)))
+        })
+        .collect();
+    let mut added: HashMap<_, _> = new
+        .into_iter()
+        .map(|(package_id, added)| (package_id, (added, same_name(&added))))
+        .collect();
+
+    let mut updated: Vec<_> = removed
+        .values()
+        .filter_map(|(removed, _)| {
+            added
+                .values()
+                .find(|(added, _)| removed.0.name() == added.0.name())
+                .map(|(added, _)| (*removed, *added))
+        })
+        .collect();
+    updated.sort_by(|a, b| a.1 .0.name().cmp(b.1 .0.name()));
+
+    for (removed_package, added_package) in &updated {
+        removed.remove(removed_package.0.id());
+        added.remove(added_package.0.id());
+    }
+
+    let mut removed: Vec<_> = removed.into_values().collect();
+    removed.sort_by(|(a, _), (b, _)| a.0.name().cmp(b.0.name()));
+    let mut added: Vec<_> = added.into_values().collect();
+    added.sort_by(|(a, _), (b, _)| a.0.name().cmp(b.0.name()));
+
+    PackagesDiff {
+        updated,
+        removed,
+        added,
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Package<'a>(PackageMetadata<'a>);
+
+impl<'a> Serialize for Package<'a> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut state = serializer.serialize_struct("Package", 3)?;
+        state.serialize_field("id", self.0.id().repr())?;
+        state.serialize_field("name", self.0.name())?;
+        state.serialize_field("version", self.0.version())?;
+        state.end()
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub(super) struct PackagesDiff<'a> {
+    updated: Vec<(Package<'a>, Package<'a>)>,
+    removed: Vec<(Package<'a>, Option<Vec<Package<'a>>>)>,
+    added: Vec<(Package<'a>, Option<Vec<Package<'a>>>)>,
+}
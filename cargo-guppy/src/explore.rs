@@ -0,0 +1,254 @@
+// Copyright (c) The cargo-guppy Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Implementation for `cargo guppy explore`.
+
+use crate::{
+    core::BaseFilterOptions,
+    find_dups,
+    tree::{BuildGraph, EdgeKind},
+};
+use camino::Utf8PathBuf;
+use clap::Parser;
+use color_eyre::eyre::{Result, WrapErr};
+use guppy::{
+    graph::{
+        cargo::{BuildPlatform, CargoOptions, CargoSet},
+        feature::FeatureLabel,
+        PackageGraph, PackageMetadata,
+    },
+    PackageId,
+};
+use guppy_cmdlib::{
+    string_to_platform_spec, CargoMetadataOptions, CargoResolverOpts, PackagesAndFeatures,
+};
+use serde::Serialize;
+use std::{
+    collections::{BTreeSet, HashMap},
+    fs,
+};
+
+/// The HTML page, with a placeholder for the data.
+static TEMPLATE: &str = include_str!("explore.html");
+static DATA_PLACEHOLDER: &str = "/* GUPPY_EXPLORE_DATA */ null";
+
+#[derive(Debug, Parser)]
+pub struct ExploreOptions {
+    #[clap(flatten)]
+    pf: PackagesAndFeatures,
+
+    #[clap(flatten)]
+    resolver_opts: CargoResolverOpts,
+
+    #[clap(flatten)]
+    base_filter_opts: BaseFilterOptions,
+
+    #[clap(long = "target-platform")]
+    /// Evaluate against target platform, "current" or "any" (default: any)
+    target_platform: Option<String>,
+
+    #[clap(long = "host-platform")]
+    /// Evaluate against host platform, "current" or "any" (default: any)
+    host_platform: Option<String>,
+
+    #[clap(long, short = 'o')]
+    /// Path to write the HTML file to
+    output: Utf8PathBuf,
+
+    #[clap(flatten)]
+    metadata_opts: CargoMetadataOptions,
+}
+
+impl ExploreOptions {
+    pub fn exec(&self) -> Result<()> {
+        let target_platform = string_to_platform_spec(self.target_platform.as_deref())?;
+        let host_platform = string_to_platform_spec(self.host_platform.as_deref())?;
+        let pkg_graph = self.metadata_opts.build_graph()?;
+
+        let mut cargo_opts = CargoOptions::new();
+        cargo_opts
+            .set_include_dev(self.resolver_opts.include_dev)
+            .set_resolver(self.resolver_opts.resolver_version.to_guppy())
+            .set_initials_platform(self.resolver_opts.initials_platform.to_guppy())
+            .set_target_platform(target_platform)
+            .set_host_platform(host_platform)
+            .add_omitted_packages(self.base_filter_opts.omitted_package_ids(&pkg_graph));
+
+        let (initials, features_only) = self.pf.make_feature_sets(&pkg_graph)?;
+        let cargo_set = CargoSet::new(initials, features_only, &cargo_opts)?;
+
+        let build_graph = BuildGraph::new(
+            &cargo_set,
+            self.resolver_opts.initials_platform.to_guppy(),
+            self.base_filter_opts.kind,
+        );
+
+        let data = ExploreJson::new(&pkg_graph, &cargo_set, &build_graph);
+        // Escape "</" so that the data can't close the surrounding <script> tag.
+        let json = serde_json::to_string(&data)?.replace("</", "<\\/");
+        let html = TEMPLATE.replace(DATA_PLACEHOLDER, &json);
+        fs::write(&self.output, html)
+            .wrap_err_with(|| format!("writing explorer to {} failed", self.output))?;
+
+        eprintln!(
+            "wrote {} packages ({} with duplicate versions) to {}",
+            data.packages.len(),
+            data.duplicates
+                .iter()
+                .map(|dup| dup.packages.len())
+                .sum::<usize>(),
+            self.output
+        );
+        Ok(())
+    }
+}
+
+/// The data embedded into the HTML page.
+///
+/// Packages are referred to by their index in `packages`.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "kebab-case")]
+struct ExploreJson {
+    title: String,
+    roots: Vec<usize>,
+    packages: Vec<PackageJson>,
+    duplicates: Vec<DuplicateJson>,
+}
+
+impl ExploreJson {
+    fn new<'g>(
+        pkg_graph: &'g PackageGraph,
+        cargo_set: &CargoSet<'g>,
+        build_graph: &BuildGraph<'g>,
+    ) -> Self {
+        // A package built on both the target and the host is shown once.
+        let mut packages: Vec<_> = build_graph
+            .nodes()
+            .map(|node| (node.package.id(), node.package))
+            .collect::<HashMap<_, _>>()
+            .into_values()
+            .collect();
+        packages.sort_by_key(|package| (package.name(), package.version()));
+        let indexes: HashMap<&PackageId, usize> = packages
+            .iter()
+            .enumerate()
+            .map(|(idx, package)| (package.id(), idx))
+            .collect();
+
+        let mut deps: Vec<HashMap<usize, BTreeSet<EdgeKind>>> =
+            vec![HashMap::new(); packages.len()];
+        for node in build_graph.nodes() {
+            let from = indexes[node.package.id()];
+            for (kind, to) in build_graph.edges(node, false) {
+                deps[from]
+                    .entry(indexes[to.package.id()])
+                    .or_default()
+                    .insert(kind);
+            }
+        }
+
+        let mut roots = vec![];
+        for root in &build_graph.roots {
+            let idx = indexes[root.package.id()];
+            if !roots.contains(&idx) {
+                roots.push(idx);
+            }
+        }
+
+        let mut duplicates: Vec<_> = find_dups(packages.iter().copied())
+            .into_iter()
+            .map(|(name, dupes)| DuplicateJson {
+                name: name.to_owned(),
+                packages: dupes.iter().map(|package| indexes[package.id()]).collect(),
+            })
+            .collect();
+        duplicates.sort_by(|a, b| a.name.cmp(&b.name));
+        let is_duplicate: BTreeSet<_> = duplicates
+            .iter()
+            .flat_map(|dup| dup.packages.iter().copied())
+            .collect();
+
+        let packages = packages
+            .iter()
+            .zip(deps)
+            .enumerate()
+            .map(|(idx, (package, deps))| {
+                let mut deps: Vec<_> = deps
+                    .into_iter()
+                    .map(|(package, kinds)| DepJson {
+                        package,
+                        kinds: kinds.into_iter().map(|kind| kind.as_str()).collect(),
+                    })
+                    .collect();
+                deps.sort_by_key(|dep| dep.package);
+                PackageJson {
+                    name: package.name().to_owned(),
+                    version: package.version().to_string(),
+                    source: package.source().to_string(),
+                    workspace: package.in_workspace(),
+                    proc_macro: package.is_proc_macro(),
+                    duplicate: is_duplicate.contains(&idx),
+                    target_features: features(cargo_set, *package, BuildPlatform::Target),
+                    host_features: features(cargo_set, *package, BuildPlatform::Host),
+                    deps,
+                }
+            })
+            .collect();
+
+        Self {
+            title: pkg_graph.workspace().root().to_string(),
+            roots,
+            packages,
+            duplicates,
+        }
+    }
+}
+
+/// Returns the features enabled for a package on a build platform, or `None` if it isn't built
+/// there.
+fn features(
+    cargo_set: &CargoSet<'_>,
+    package: PackageMetadata<'_>,
+    build_platform: BuildPlatform,
+) -> Option<Vec<String>> {
+    let feature_list = cargo_set
+        .platform_features(build_platform)
+        .features_for(package.id())
+        .expect("valid package ID")?;
+    Some(
+        feature_list
+            .labels()
+            .iter()
+            .filter(|label| **label != FeatureLabel::Base)
+            .map(|label| label.to_string())
+            .collect(),
+    )
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "kebab-case")]
+struct PackageJson {
+    name: String,
+    version: String,
+    source: String,
+    workspace: bool,
+    proc_macro: bool,
+    duplicate: bool,
+    target_features: Option<Vec<String>>,
+    host_features: Option<Vec<String>>,
+    deps: Vec<DepJson>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "kebab-case")]
+struct DepJson {
+    package: usize,
+    kinds: Vec<&'static str>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "kebab-case")]
+struct DuplicateJson {
+    name: String,
+    packages: Vec<usize>,
+}
@@ -0,0 +1,123 @@
+// Copyright (c) The cargo-guppy Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Implementation for `cargo guppy feature-hygiene`.
+
+use clap::Parser;
+use color_eyre::eyre::Result;
+use guppy::graph::{
+    cargo::CargoOptions,
+    feature::{FeatureHygiene, StandardFeatures},
+};
+use guppy_cmdlib::{string_to_platform_spec, CargoMetadataOptions, CargoResolverOpts};
+use serde::Serialize;
+
+#[derive(Debug, Parser)]
+pub struct FeatureHygieneOptions {
+    /// Output findings in JSON format
+    #[clap(long)]
+    json: bool,
+
+    /// Build workspace members with all features activated
+    #[clap(long = "all-features")]
+    all_features: bool,
+
+    /// Build workspace members without the `default` feature
+    #[clap(long = "no-default-features", conflicts_with = "all-features")]
+    no_default_features: bool,
+
+    #[clap(flatten)]
+    resolver_opts: CargoResolverOpts,
+
+    #[clap(long = "target-platform")]
+    /// Evaluate against target platform, "current" or "any" (default: any)
+    target_platform: Option<String>,
+
+    #[clap(long = "host-platform")]
+    /// Evaluate against host platform, "current" or "any" (default: any)
+    host_platform: Option<String>,
+
+    #[clap(flatten)]
+    metadata_opts: CargoMetadataOptions,
+}
+
+impl FeatureHygieneOptions {
+    pub fn exec(&self) -> Result<()> {
+        let pkg_graph = self.metadata_opts.build_graph()?;
+
+        let mut cargo_opts = CargoOptions::new();
+        cargo_opts
+            .set_include_dev(self.resolver_opts.include_dev)
+            .set_resolver(self.resolver_opts.resolver_version.to_guppy())
+            .set_initials_platform(self.resolver_opts.initials_platform.to_guppy())
+            .set_target_platform(string_to_platform_spec(self.target_platform.as_deref())?)
+            .set_host_platform(string_to_platform_spec(self.host_platform.as_deref())?);
+        let features = match (self.all_features, self.no_default_features) {
+            (true, _) => StandardFeatures::All,
+            (false, false) => StandardFeatures::Default,
+            (false, true) => StandardFeatures::None,
+        };
+
+        let hygiene = pkg_graph.feature_graph().hygiene(features, &cargo_opts)?;
+        let json = LinkJson::new_all(&hygiene);
+        if self.json {
+            println!("{}", serde_json::to_string_pretty(&json)?);
+            return Ok(());
+        }
+
+        if json.is_empty() {
+            println!("no redundant features found");
+            return Ok(());
+        }
+        for link in &json {
+            println!("{} -> {} ({}):", link.package, link.dependency, link.kind);
+            if !link.redundant_features.is_empty() {
+                println!(
+                    "  already enabled through other paths: {}",
+                    link.redundant_features.join(", ")
+                );
+            }
+            if link.redundant_default {
+                println!(
+                    "  default features are already enabled through other paths, \
+                     `default-features = false` would make no difference"
+                );
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "kebab-case")]
+struct LinkJson {
+    package: String,
+    dependency: String,
+    version: String,
+    kind: &'static str,
+    redundant_features: Vec<String>,
+    redundant_default: bool,
+}
+
+impl LinkJson {
+    fn new_all(hygiene: &FeatureHygiene<'_>) -> Vec<Self> {
+        hygiene
+            .links()
+            .map(|link_hygiene| {
+                let link = link_hygiene.link();
+                LinkJson {
+                    package: link.from().name().to_owned(),
+                    dependency: link.dep_name().to_owned(),
+                    version: link.to().version().to_string(),
+                    kind: link_hygiene.kind().to_str(),
+                    redundant_features: link_hygiene
+                        .redundant_features()
+                        .iter()
+                        .map(|feature| feature.to_string())
+                        .collect(),
+                    redundant_default: link_hygiene.is_default_redundant(),
+                }
+            })
+            .collect()
+    }
+}
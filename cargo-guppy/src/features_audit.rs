@@ -0,0 +1,258 @@
+// Copyright (c) The cargo-guppy Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Implementation for `cargo guppy features-audit`.
+
+use clap::Parser;
+use color_eyre::eyre::Result;
+use guppy::graph::{
+    cargo::CargoOptions,
+    feature::{FeatureAudit, FeatureAuditOptions, FeatureLabel, FeatureMetadata},
+    PackageMetadata,
+};
+use guppy_cmdlib::{string_to_platform_spec, CargoMetadataOptions, CargoResolverOpts};
+use serde::Serialize;
+use std::fmt;
+
+#[derive(Debug, Parser)]
+pub struct FeaturesAuditOptions {
+    /// Output findings in JSON format
+    #[clap(long)]
+    json: bool,
+
+    /// Only report findings for workspace packages
+    #[clap(long = "workspace-only")]
+    workspace_only: bool,
+
+    /// Report `default` features that pull in at least this many extra packages
+    #[clap(
+        long = "large-default-threshold",
+        default_value_t = FeatureAuditOptions::DEFAULT_LARGE_DEFAULT_THRESHOLD
+    )]
+    large_default_threshold: usize,
+
+    #[clap(flatten)]
+    resolver_opts: CargoResolverOpts,
+
+    #[clap(long = "target-platform")]
+    /// Evaluate against target platform, "current" or "any" (default: any)
+    target_platform: Option<String>,
+
+    #[clap(long = "host-platform")]
+    /// Evaluate against host platform, "current" or "any" (default: any)
+    host_platform: Option<String>,
+
+    #[clap(flatten)]
+    metadata_opts: CargoMetadataOptions,
+}
+
+impl FeaturesAuditOptions {
+    pub fn exec(&self) -> Result<()> {
+        let pkg_graph = self.metadata_opts.build_graph()?;
+
+        let mut cargo_opts = CargoOptions::new();
+        cargo_opts
+            .set_include_dev(self.resolver_opts.include_dev)
+            .set_resolver(self.resolver_opts.resolver_version.to_guppy())
+            .set_initials_platform(self.resolver_opts.initials_platform.to_guppy())
+            .set_target_platform(string_to_platform_spec(self.target_platform.as_deref())?)
+            .set_host_platform(string_to_platform_spec(self.host_platform.as_deref())?);
+        let mut audit_opts = FeatureAuditOptions::new();
+        audit_opts
+            .set_cargo_options(cargo_opts)
+            .set_large_default_threshold(self.large_default_threshold);
+
+        let audit = pkg_graph.feature_graph().audit(&audit_opts)?;
+        let json = AuditJson::new(&audit, self.workspace_only);
+        if self.json {
+            println!("{}", serde_json::to_string_pretty(&json)?);
+        } else {
+            json.print();
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "kebab-case")]
+struct AuditJson {
+    cycles: Vec<Vec<FeatureJson>>,
+    unreachable_features: Vec<FeatureJson>,
+    aliases: Vec<AliasJson>,
+    always_enabled_optional_deps: Vec<AlwaysEnabledJson>,
+    large_defaults: Vec<LargeDefaultJson>,
+}
+
+impl AuditJson {
+    fn new(audit: &FeatureAudit<'_>, workspace_only: bool) -> Self {
+        let include = |package: PackageMetadata<'_>| !workspace_only || package.in_workspace();
+
+        Self {
+            cycles: audit
+                .cycles()
+                .iter()
+                .filter(|cycle| cycle.iter().any(|feature| include(feature.package())))
+                .map(|cycle| cycle.iter().copied().map(FeatureJson::new).collect())
+                .collect(),
+            unreachable_features: audit
+                .unreachable_features()
+                .filter(|feature| include(feature.package()))
+                .map(FeatureJson::new)
+                .collect(),
+            aliases: audit
+                .aliases()
+                .filter(|alias| include(alias.alias().package()))
+                .map(|alias| AliasJson {
+                    package: PackageJson::new(alias.alias().package()),
+                    alias: alias.alias().label().to_string(),
+                    target: alias.target().label().to_string(),
+                })
+                .collect(),
+            always_enabled_optional_deps: audit
+                .always_enabled_optional_deps()
+                .filter(|dep| include(dep.feature().package()))
+                .map(|dep| AlwaysEnabledJson {
+                    package: PackageJson::new(dep.feature().package()),
+                    dependency: match dep.feature().label() {
+                        FeatureLabel::OptionalDependency(dep_name) => dep_name.to_owned(),
+                        other => other.to_string(),
+                    },
+                    builds: dep.builds(),
+                })
+                .collect(),
+            large_defaults: audit
+                .large_defaults()
+                .filter(|large| include(large.package()))
+                .map(|large| LargeDefaultJson {
+                    package: PackageJson::new(large.package()),
+                    extra_packages: large
+                        .extra_packages()
+                        .iter()
+                        .map(|package| PackageJson::new(*package))
+                        .collect(),
+                })
+                .collect(),
+        }
+    }
+
+    fn print(&self) {
+        if self.cycles.is_empty()
+            && self.unreachable_features.is_empty()
+            && self.aliases.is_empty()
+            && self.always_enabled_optional_deps.is_empty()
+            && self.large_defaults.is_empty()
+        {
+            println!("no feature issues found");
+            return;
+        }
+        if !self.cycles.is_empty() {
+            println!("feature cycles:");
+            for cycle in &self.cycles {
+                let cycle: Vec<_> = cycle.iter().map(|feature| feature.to_string()).collect();
+                println!("  {}", cycle.join(" -> "));
+            }
+        }
+        if !self.unreachable_features.is_empty() {
+            println!("features not enabled by any build:");
+            for feature in &self.unreachable_features {
+                println!("  {}", feature);
+            }
+        }
+        if !self.aliases.is_empty() {
+            println!("feature aliases:");
+            for alias in &self.aliases {
+                println!(
+                    "  {} {}: {} is an alias of {}",
+                    alias.package.name, alias.package.version, alias.alias, alias.target
+                );
+            }
+        }
+        if !self.always_enabled_optional_deps.is_empty() {
+            println!("optional dependencies that are always enabled:");
+            for dep in &self.always_enabled_optional_deps {
+                println!(
+                    "  {} {}: {} (in {} builds)",
+                    dep.package.name, dep.package.version, dep.dependency, dep.builds
+                );
+            }
+        }
+        if !self.large_defaults.is_empty() {
+            println!("default features that pull in many packages:");
+            for large in &self.large_defaults {
+                println!(
+                    "  {} {}: {} extra packages",
+                    large.package.name,
+                    large.package.version,
+                    large.extra_packages.len()
+                );
+            }
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "kebab-case")]
+struct PackageJson {
+    name: String,
+    version: String,
+    source: String,
+}
+
+impl PackageJson {
+    fn new(package: PackageMetadata<'_>) -> Self {
+        Self {
+            name: package.name().to_owned(),
+            version: package.version().to_string(),
+            source: package.source().to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "kebab-case")]
+struct FeatureJson {
+    package: PackageJson,
+    feature: String,
+}
+
+impl FeatureJson {
+    fn new(feature: FeatureMetadata<'_>) -> Self {
+        Self {
+            package: PackageJson::new(feature.package()),
+            feature: feature.label().to_string(),
+        }
+    }
+}
+
+impl fmt::Display for FeatureJson {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} {}/{}",
+            self.package.name, self.package.version, self.feature
+        )
+    }
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "kebab-case")]
+struct AliasJson {
+    package: PackageJson,
+    alias: String,
+    target: String,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "kebab-case")]
+struct AlwaysEnabledJson {
+    package: PackageJson,
+    dependency: String,
+    builds: usize,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "kebab-case")]
+struct LargeDefaultJson {
+    package: PackageJson,
+    extra_packages: Vec<PackageJson>,
+}
@@ -24,13 +24,31 @@
 //!
 //! * `select`: query packages and their transitive dependencies
 //! * `resolve-cargo`: query packages and features as would be built by cargo
-//! * `subtree-size`: print dependencies along with their unique subtree size
+//! * `subtree-size`: print dependencies along with their unique subtree size, or with an estimate
+//!   of how much they add to build times
 //! * `dups`: print duplicate packages
+//! * `why`: print the dependency paths from the workspace to a package
+//! * `tree`: print the packages that would be built by cargo as a tree
+//! * `explore`: write a self-contained HTML page for browsing the packages that would be built by
+//!   cargo, with search and highlighting of duplicate versions
 //!
 //! ## Diff commands
 //!
-//! * `diff`: perform a diff of two `cargo metadata` JSON outputs
+//! * `diff`: perform a diff of two `cargo metadata` JSON outputs or git revisions, including changes
+//!   to dependency edges, features and build targets
 //! * `diff-summaries`: perform a diff of two [summaries](https://github.com/facebookincubator/cargo-guppy/tree/main/guppy-summaries)
+//! * `what-if`: simulate enabling features, turning off default features or removing
+//!   dependencies, and show how the build of each workspace member would change
+//!
+//! ## Checks
+//!
+//! * `lint`: check dependencies against a policy file, e.g. for licenses, git dependencies and
+//!   banned packages
+//! * `features-audit`: find feature cycles, features that are never enabled, feature aliases,
+//!   optional dependencies that are always enabled and `default` features that pull in many
+//!   packages
+//! * `feature-hygiene`: find features that workspace packages request on third-party dependencies,
+//!   but that are enabled anyway through other paths
 //!
 //! ## Workspace manipulations
 //!
@@ -38,25 +56,38 @@
 
 mod core;
 mod diff;
+mod explore;
+mod feature_hygiene;
+mod features_audit;
+mod lint;
 mod mv;
-
-pub use crate::{core::*, mv::*};
+mod tree;
+mod weight;
+mod what_if;
+mod why;
+
+pub use crate::{
+    core::*, diff::*, explore::*, feature_hygiene::*, features_audit::*, lint::*, mv::*, tree::*,
+    weight::*, what_if::*, why::*,
+};
 
 use camino::Utf8PathBuf;
 use clap::{ArgEnum, Parser};
 use color_eyre::eyre::{bail, Result, WrapErr};
 use guppy::{
     graph::{
-        cargo::{CargoOptions, CargoSet},
+        cargo::{CargoOptions, CargoResolverVersion, CargoSet},
+        export::ExportFormat,
         feature::{FeatureSet, StandardFeatures},
         summaries::Summary,
         DependencyDirection, DotWrite, PackageDotVisitor, PackageGraph, PackageLink,
-        PackageMetadata,
+        PackageMetadata, PackageSet,
     },
     PackageId,
 };
 use guppy_cmdlib::{
-    string_to_platform_spec, CargoMetadataOptions, CargoResolverOpts, PackagesAndFeatures,
+    package_expr::PackageExpr, string_to_platform_spec, CargoMetadataOptions, CargoResolverOpts,
+    CargoResolverVersionCmd, PackagesAndFeatures,
 };
 use std::{
     borrow::Cow,
@@ -68,27 +99,6 @@ use std::{
     path::PathBuf,
 };
 
-pub fn cmd_diff(json: bool, old: &str, new: &str) -> Result<()> {
-    let old_json = fs::read_to_string(old)?;
-    let new_json = fs::read_to_string(new)?;
-
-    let old_graph = PackageGraph::from_json(&old_json)?;
-    let new_graph = PackageGraph::from_json(&new_json)?;
-
-    let old_packages: Vec<_> = old_graph.packages().collect();
-    let new_packages: Vec<_> = new_graph.packages().collect();
-
-    let diff = diff::DiffOptions::default().diff(&old_packages, &new_packages);
-
-    if json {
-        println!("{}", serde_json::to_string_pretty(&diff).unwrap());
-    } else {
-        print!("{}", diff);
-    }
-
-    Ok(())
-}
-
 #[derive(Debug, Parser)]
 pub struct DiffSummariesOptions {
     /// The old summary
@@ -129,30 +139,25 @@ pub struct DupsOptions {
     #[clap(flatten)]
     filter_opts: FilterOptions,
 
+    #[clap(long)]
+    /// Package expression selecting the packages to check, e.g. "deps(foo)" (default: the
+    /// workspace and its dependencies)
+    expr: Option<String>,
+
     #[clap(flatten)]
     metadata_opts: CargoMetadataOptions,
 }
 
 pub fn cmd_dups(opts: &DupsOptions) -> Result<()> {
-    let command = opts.metadata_opts.make_command();
-    let pkg_graph = command.build_graph()?;
+    let pkg_graph = opts.metadata_opts.build_graph()?;
 
     let resolver = opts.filter_opts.make_resolver(&pkg_graph)?;
-    let selection = pkg_graph.query_workspace();
-
-    let mut dupe_map: HashMap<_, Vec<_>> = HashMap::new();
-    for package in selection
-        .resolve_with_fn(resolver)
-        .packages(DependencyDirection::Forward)
-    {
-        dupe_map.entry(package.name()).or_default().push(package);
-    }
-
-    for (name, dupes) in dupe_map {
-        if dupes.len() <= 1 {
-            continue;
-        }
+    let selection = match &opts.expr {
+        Some(expr) => PackageExpr::parse(expr)?.eval_with_fn(&pkg_graph, resolver)?,
+        None => pkg_graph.query_workspace().resolve_with_fn(resolver),
+    };
 
+    for (name, dupes) in find_dups(selection.packages(DependencyDirection::Forward)) {
         let output = itertools::join(dupes.iter().map(|p| p.version()), ", ");
 
         println!("{} ({})", name, output);
@@ -161,6 +166,18 @@ pub fn cmd_dups(opts: &DupsOptions) -> Result<()> {
     Ok(())
 }
 
+/// Groups packages by name, returning the names that have more than one package.
+pub(crate) fn find_dups<'g>(
+    packages: impl IntoIterator<Item = PackageMetadata<'g>>,
+) -> HashMap<&'g str, Vec<PackageMetadata<'g>>> {
+    let mut dupe_map: HashMap<_, Vec<_>> = HashMap::new();
+    for package in packages {
+        dupe_map.entry(package.name()).or_default().push(package);
+    }
+    dupe_map.retain(|_, dupes| dupes.len() > 1);
+    dupe_map
+}
+
 #[derive(ArgEnum, Copy, Clone, Debug)]
 pub enum BuildKind {
     All,
@@ -204,8 +221,7 @@ pub struct ResolveCargoOptions {
 pub fn cmd_resolve_cargo(opts: &ResolveCargoOptions) -> Result<()> {
     let target_platform = string_to_platform_spec(opts.target_platform.as_deref())?;
     let host_platform = string_to_platform_spec(opts.host_platform.as_deref())?;
-    let command = opts.metadata_opts.make_command();
-    let pkg_graph = command.build_graph()?;
+    let pkg_graph = opts.metadata_opts.build_graph()?;
 
     let mut cargo_opts = CargoOptions::new();
     cargo_opts
@@ -309,6 +325,10 @@ pub struct CmdSelectOptions {
     /// Save selection graph in .dot format
     output_dot: Option<String>,
 
+    #[clap(long, arg_enum, conflicts_with = "output-dot")]
+    /// Print the selection graph in this format instead of listing package IDs
+    output_format: Option<ExportFormatCmd>,
+
     #[clap(flatten)]
     query_opts: QueryOptions,
 
@@ -316,29 +336,68 @@ pub struct CmdSelectOptions {
     metadata_opts: CargoMetadataOptions,
 }
 
+#[derive(ArgEnum, Copy, Clone, Debug)]
+pub enum ExportFormatCmd {
+    Dot,
+    #[clap(name = "graphml")]
+    GraphMl,
+    Json,
+    Mermaid,
+}
+
+impl ExportFormatCmd {
+    /// Converts to guppy's ExportFormat.
+    pub fn to_guppy(self) -> ExportFormat {
+        match self {
+            ExportFormatCmd::Dot => ExportFormat::Dot,
+            ExportFormatCmd::GraphMl => ExportFormat::GraphMl,
+            ExportFormatCmd::Json => ExportFormat::Json,
+            ExportFormatCmd::Mermaid => ExportFormat::Mermaid,
+        }
+    }
+}
+
 pub fn cmd_select(options: &CmdSelectOptions) -> Result<()> {
+    // select always runs `cargo metadata --no-deps`, which the graph cache and offline metadata
+    // don't support.
+    if options.metadata_opts.graph_cache.is_some() {
+        bail!("--graph-cache is not supported by select");
+    }
+    if options.metadata_opts.offline_metadata {
+        bail!("--offline-metadata is not supported by select");
+    }
     let mut command = options.metadata_opts.make_command();
     command.other_options(["--no-deps"]);
     let pkg_graph = command.build_graph()?;
 
-    let query = options.query_opts.apply(&pkg_graph)?;
     let resolver = options.filter_opts.make_resolver(&pkg_graph)?;
-    let package_set = query.resolve_with_fn(resolver);
+    let package_set = options.query_opts.resolve_with_fn(&pkg_graph, resolver)?;
 
-    for package_id in package_set.package_ids(options.output_direction) {
-        let package = pkg_graph.metadata(package_id).unwrap();
+    let kind = options.filter_opts.base_opts.kind;
+    let show_package = |package: PackageMetadata<'_>| {
         let in_workspace = package.in_workspace();
         let direct_dep = package
             .reverse_direct_links()
             .any(|link| link.from().in_workspace() && !link.to().in_workspace());
-        let show_package = match options.filter_opts.base_opts.kind {
+        match kind {
             Kind::All => true,
             Kind::Workspace => in_workspace,
             Kind::DirectThirdParty => direct_dep,
             Kind::ThirdParty => !in_workspace,
-        };
-        if show_package {
-            println!("{}", package_id);
+        }
+    };
+
+    if let Some(output_format) = options.output_format {
+        let export_graph = package_set
+            .filter(DependencyDirection::Forward, show_package)
+            .to_export_graph();
+        print!("{}", export_graph.display(output_format.to_guppy()));
+        return Ok(());
+    }
+
+    for package in package_set.packages(options.output_direction) {
+        if show_package(package) {
+            println!("{}", package.id());
         }
     }
 
@@ -361,13 +420,29 @@ pub struct SubtreeSizeOptions {
     /// The root packages to start the selection from
     root: Option<String>,
 
+    #[clap(long, conflicts_with = "ROOT")]
+    /// Package expression to start the selection from, e.g. "workspace() - foo"
+    expr: Option<String>,
+
+    #[clap(long, arg_enum)]
+    /// Sort by the estimated build weight of each package and its unique dependencies, rather
+    /// than by the number of unique dependencies
+    weight: Option<WeightKind>,
+
+    #[clap(long = "host-platform", requires = "weight")]
+    /// Host platform to simulate builds for with --weight, "current" or "any" (default: any)
+    host_platform: Option<String>,
+
+    #[clap(long = "resolver-version", arg_enum, requires = "weight")]
+    /// Cargo resolver version to simulate builds with for --weight (default: the workspace's)
+    resolver_version: Option<CargoResolverVersionCmd>,
+
     #[clap(flatten)]
     metadata_opts: CargoMetadataOptions,
 }
 
 pub fn cmd_subtree_size(options: &SubtreeSizeOptions) -> Result<()> {
-    let command = options.metadata_opts.make_command();
-    let pkg_graph = command.build_graph()?;
+    let pkg_graph = options.metadata_opts.build_graph()?;
 
     let resolver = options.filter_opts.make_resolver(&pkg_graph)?;
 
@@ -382,17 +457,18 @@ pub fn cmd_subtree_size(options: &SubtreeSizeOptions) -> Result<()> {
                 .find(|metadata| root_name == metadata.name())
         })
         .map(|metadata| metadata.id());
-    let selection = if options.root.is_some() {
-        pkg_graph.query_forward(iter::once(root_id.unwrap()))?
+    let selection = if let Some(expr) = &options.expr {
+        PackageExpr::parse(expr)?.eval_with_fn(&pkg_graph, &resolver)?
+    } else if options.root.is_some() {
+        pkg_graph
+            .query_forward(iter::once(root_id.unwrap()))?
+            .resolve_with_fn(&resolver)
     } else {
-        pkg_graph.query_workspace()
+        pkg_graph.query_workspace().resolve_with_fn(&resolver)
     };
 
     let mut unique_deps: HashMap<&PackageId, HashSet<&PackageId>> = HashMap::new();
-    for package_id in selection
-        .resolve_with_fn(&resolver)
-        .package_ids(DependencyDirection::Forward)
-    {
+    for package_id in selection.package_ids(DependencyDirection::Forward) {
         let subtree_package_set: HashSet<&PackageId> = pkg_graph
             .query_forward(iter::once(package_id))?
             .resolve_with_fn(&resolver)
@@ -435,6 +511,17 @@ pub fn cmd_subtree_size(options: &SubtreeSizeOptions) -> Result<()> {
         }
     }
 
+    if let Some(weight_kind) = options.weight {
+        return print_subtree_weights(
+            options,
+            &pkg_graph,
+            &selection,
+            root_id,
+            unique_deps,
+            weight_kind,
+        );
+    }
+
     let mut sorted_unique_deps = unique_deps.into_iter().collect::<Vec<_>>();
     sorted_unique_deps.sort_by_key(|a| cmp::Reverse(a.1.len()));
 
@@ -449,3 +536,129 @@ pub fn cmd_subtree_size(options: &SubtreeSizeOptions) -> Result<()> {
 
     Ok(())
 }
+
+/// Prints the marginal build weight of each package in the selection: the weight of the package
+/// itself and of the dependencies that are only pulled in through it.
+fn print_subtree_weights<'g>(
+    options: &SubtreeSizeOptions,
+    pkg_graph: &'g PackageGraph,
+    selection: &PackageSet<'g>,
+    root_id: Option<&'g PackageId>,
+    mut unique_deps: HashMap<&'g PackageId, HashSet<&'g PackageId>>,
+    weight_kind: WeightKind,
+) -> Result<()> {
+    // Packages without unique dependencies still have a weight of their own.
+    for package_id in selection.package_ids(DependencyDirection::Forward) {
+        unique_deps.entry(package_id).or_default();
+    }
+
+    let resolver = match options.resolver_version {
+        Some(resolver_version) => resolver_version.to_guppy(),
+        None => workspace_resolver(pkg_graph)?,
+    };
+    let mut cargo_opts = CargoOptions::new();
+    cargo_opts
+        .set_include_dev(options.filter_opts.include_dev)
+        .set_resolver(resolver)
+        .set_target_platform(string_to_platform_spec(
+            options.filter_opts.target.as_deref(),
+        )?)
+        .set_host_platform(string_to_platform_spec(options.host_platform.as_deref())?)
+        .add_omitted_packages(options.filter_opts.base_opts.omitted_package_ids(pkg_graph));
+
+    // Simulate a build of the root, or of the workspace members that were selected.
+    let initials = match root_id {
+        Some(root_id) => pkg_graph.resolve_ids(iter::once(root_id))?,
+        None => {
+            let members = selection.filter(DependencyDirection::Forward, |package| {
+                package.in_workspace()
+            });
+            if members.is_empty() {
+                pkg_graph.resolve_ids(selection.root_ids(DependencyDirection::Forward))?
+            } else {
+                members
+            }
+        }
+    };
+    let cargo_set = CargoSet::new(
+        initials.to_feature_set(StandardFeatures::Default),
+        pkg_graph.feature_graph().resolve_none(),
+        &cargo_opts,
+    )?;
+    let mut model = WeightModel::new(&cargo_set);
+    let mut weigh = |package_id: &PackageId| {
+        let package = pkg_graph.metadata(package_id).expect("valid package ID");
+        model.weight(package).value(weight_kind)
+    };
+
+    let mut weights: Vec<_> = unique_deps
+        .into_iter()
+        .map(|(package_id, deps)| {
+            let mut deps: Vec<_> = deps.into_iter().map(|dep| (weigh(dep), dep)).collect();
+            deps.sort_by_key(|(weight, dep)| (cmp::Reverse(*weight), *dep));
+            let total = weigh(package_id) + deps.iter().map(|(weight, _)| weight).sum::<u64>();
+            (total, package_id, deps)
+        })
+        .collect();
+    weights.sort_by_key(|(total, package_id, _)| (cmp::Reverse(*total), *package_id));
+
+    for (total, package_id, deps) in weights {
+        println!("{} {}", total, package_id);
+        for (weight, dep) in deps {
+            println!("    {} {}", weight, dep);
+        }
+    }
+
+    let missing_sources = model.missing_sources();
+    if !missing_sources.is_empty() {
+        eprintln!(
+            "warning: sources for {} packages couldn't be read, so their size is counted as 0 \
+             (run `cargo fetch` to download them)",
+            missing_sources.len()
+        );
+    }
+
+    Ok(())
+}
+
+/// Returns the feature resolver version Cargo would use for the workspace, based on the `resolver`
+/// field and the edition of the root manifest.
+fn workspace_resolver(pkg_graph: &PackageGraph) -> Result<CargoResolverVersion> {
+    let workspace = pkg_graph.workspace();
+    let manifest_path = workspace.root().join("Cargo.toml");
+    let manifest: toml::Value = toml::from_str(
+        &fs::read_to_string(&manifest_path)
+            .wrap_err_with(|| format!("failed to read {}", manifest_path))?,
+    )
+    .wrap_err_with(|| format!("failed to parse {}", manifest_path))?;
+
+    let resolver = ["workspace", "package"]
+        .iter()
+        .find_map(|section| manifest.get(section)?.get("resolver"));
+    match resolver {
+        Some(resolver) => match resolver.as_str() {
+            Some("1") => Ok(CargoResolverVersion::V1),
+            Some("2") => Ok(CargoResolverVersion::V2),
+            // Version 3 only changes how dependency versions are selected, and resolves features
+            // the same way as version 2.
+            Some("3") => Ok(CargoResolverVersion::V2),
+            _ => bail!(
+                "{}: unsupported resolver version {}",
+                manifest_path,
+                resolver
+            ),
+        },
+        None => {
+            // Packages on edition 2021 and later default to version 2 of the resolver (or version
+            // 3 on edition 2024, which resolves features the same way).
+            let edition_2021 = workspace
+                .member_by_path("")
+                .map_or(false, |package| package.edition() >= "2021");
+            if edition_2021 {
+                Ok(CargoResolverVersion::V2)
+            } else {
+                Ok(CargoResolverVersion::V1)
+            }
+        }
+    }
+}
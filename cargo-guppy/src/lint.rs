@@ -0,0 +1,823 @@
+// Copyright (c) The cargo-guppy Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Implementation for `cargo guppy lint`.
+//!
+//! Lints are driven by a TOML policy file. Each section of the file enables a lint:
+//!
+//! ```toml
+//! # The packages that the licenses and duplicates lints apply to, as a package expression
+//! # (default: "deps(workspace())").
+//! packages = "deps(workspace())"
+//! # Whether to follow dev-dependencies when evaluating package expressions and bans
+//! # (default: false).
+//! include-dev = false
+//!
+//! # Workspace packages that can be published may not have git dependencies.
+//! [git-dependencies]
+//! # Every lint has a level: "deny" (the default), "warn" or "allow".
+//! level = "deny"
+//!
+//! # Workspace packages may not have wildcard (`*`) version requirements on registry dependencies.
+//! [wildcard-requirements]
+//!
+//! # Third-party packages must have a license that's allowed. SPDX expressions are supported.
+//! [licenses]
+//! allow = ["MIT", "Apache-2.0", "BSD-3-Clause"]
+//! # Packages exempt from this lint, by name.
+//! exceptions = ["ring"]
+//!
+//! # No more than this many versions of any package may be present.
+//! [duplicates]
+//! max-versions = 2
+//! skip = ["winapi"]
+//!
+//! # Packages matching `from` may not depend on packages matching `to`, directly or transitively.
+//! [[ban]]
+//! # Package expressions, see `guppy_cmdlib::package_expr` (default for `from`: "workspace()").
+//! from = "guppy"
+//! to = "openssl-sys"
+//! reason = "use rustls instead"
+//! ```
+
+use camino::Utf8PathBuf;
+use clap::Parser;
+use color_eyre::eyre::{Result, WrapErr};
+use guppy::graph::{
+    DependencyDirection, ExternalSource, PackageGraph, PackageLink, PackageMetadata, PackageQuery,
+    PackageSet,
+};
+use guppy_cmdlib::{package_expr::PackageExpr, CargoMetadataOptions};
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, fmt, fmt::Write, fs, iter};
+
+/// The exit code used if lint errors were found. Other failures exit with code 1.
+pub const LINT_FAILURE_EXIT_CODE: i32 = 2;
+
+#[derive(Debug, Parser)]
+pub struct LintOptions {
+    /// Path to the TOML policy file
+    #[clap(long)]
+    policy: Utf8PathBuf,
+
+    /// Output diagnostics in JSON format
+    #[clap(long)]
+    json: bool,
+
+    /// Treat warnings as errors
+    #[clap(long)]
+    deny_warnings: bool,
+
+    #[clap(flatten)]
+    metadata_opts: CargoMetadataOptions,
+}
+
+impl LintOptions {
+    /// Executes this command, returning the exit code.
+    pub fn exec(&self) -> Result<i32> {
+        let policy = fs::read_to_string(&self.policy)
+            .wrap_err_with(|| format!("reading policy {} failed", self.policy))?;
+        let policy: LintPolicy = toml::from_str(&policy)
+            .wrap_err_with(|| format!("parsing policy {} failed", self.policy))?;
+        let pkg_graph = self.metadata_opts.build_graph()?;
+
+        let (output, exit_code) = self.run(&policy, &pkg_graph)?;
+        print!("{}", output);
+        Ok(exit_code)
+    }
+
+    /// Checks the policy against the graph, returning the output and the exit code.
+    fn run(&self, policy: &LintPolicy, graph: &PackageGraph) -> Result<(String, i32)> {
+        let diagnostics = policy.check(graph)?;
+        let errors = diagnostics
+            .iter()
+            .filter(|diagnostic| diagnostic.level == DiagnosticLevel::Error)
+            .count();
+        let warnings = diagnostics.len() - errors;
+
+        let mut output = String::new();
+        if self.json {
+            writeln!(output, "{}", serde_json::to_string_pretty(&diagnostics)?)?;
+        } else {
+            for diagnostic in &diagnostics {
+                writeln!(output, "{}", diagnostic)?;
+            }
+            if !diagnostics.is_empty() {
+                writeln!(output)?;
+            }
+            writeln!(
+                output,
+                "lint: {} {}, {} {}",
+                errors,
+                if errors == 1 { "error" } else { "errors" },
+                warnings,
+                if warnings == 1 { "warning" } else { "warnings" },
+            )?;
+        }
+
+        let exit_code = if errors > 0 || (self.deny_warnings && warnings > 0) {
+            LINT_FAILURE_EXIT_CODE
+        } else {
+            0
+        };
+        Ok((output, exit_code))
+    }
+}
+
+/// A lint policy, read from a TOML file.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+struct LintPolicy {
+    #[serde(default = "default_packages")]
+    packages: String,
+
+    #[serde(default)]
+    include_dev: bool,
+
+    git_dependencies: Option<LevelConfig>,
+
+    wildcard_requirements: Option<LevelConfig>,
+
+    licenses: Option<LicensesConfig>,
+
+    duplicates: Option<DuplicatesConfig>,
+
+    #[serde(default, rename = "ban")]
+    bans: Vec<BanConfig>,
+}
+
+fn default_packages() -> String {
+    "deps(workspace())".to_owned()
+}
+
+fn default_workspace() -> String {
+    "workspace()".to_owned()
+}
+
+fn default_max_versions() -> usize {
+    1
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+enum LintLevel {
+    Deny,
+    Warn,
+    Allow,
+}
+
+impl Default for LintLevel {
+    fn default() -> Self {
+        LintLevel::Deny
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+struct LevelConfig {
+    #[serde(default)]
+    level: LintLevel,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+struct LicensesConfig {
+    #[serde(default)]
+    level: LintLevel,
+    allow: Vec<String>,
+    #[serde(default)]
+    exceptions: Vec<String>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+struct DuplicatesConfig {
+    #[serde(default)]
+    level: LintLevel,
+    #[serde(default = "default_max_versions")]
+    max_versions: usize,
+    #[serde(default)]
+    skip: Vec<String>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+struct BanConfig {
+    #[serde(default)]
+    level: LintLevel,
+    #[serde(default = "default_workspace")]
+    from: String,
+    to: String,
+    reason: Option<String>,
+}
+
+impl LintPolicy {
+    fn check(&self, graph: &PackageGraph) -> Result<Vec<Diagnostic>> {
+        let mut checker = Checker {
+            graph,
+            include_dev: self.include_dev,
+            diagnostics: vec![],
+        };
+        let packages = checker.eval(&self.packages)?;
+
+        if let Some(config) = &self.git_dependencies {
+            checker.check_git_dependencies(config.level);
+        }
+        if let Some(config) = &self.wildcard_requirements {
+            checker.check_wildcard_requirements(config.level);
+        }
+        if let Some(config) = &self.licenses {
+            checker.check_licenses(config, &packages);
+        }
+        if let Some(config) = &self.duplicates {
+            checker.check_duplicates(config, &packages);
+        }
+        for ban in &self.bans {
+            checker.check_ban(ban)?;
+        }
+
+        Ok(checker.diagnostics)
+    }
+}
+
+struct Checker<'g> {
+    graph: &'g PackageGraph,
+    include_dev: bool,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl<'g> Checker<'g> {
+    fn follow_link(&self, link: PackageLink<'g>) -> bool {
+        self.include_dev || !link.dev_only()
+    }
+
+    fn eval(&self, expr: &str) -> Result<PackageSet<'g>> {
+        PackageExpr::parse(expr)?.eval_with_fn(self.graph, |_, link| self.follow_link(link))
+    }
+
+    fn push(
+        &mut self,
+        lint: &'static str,
+        level: LintLevel,
+        packages: Vec<PackageMetadata<'g>>,
+        message: String,
+    ) {
+        let level = match level {
+            LintLevel::Deny => DiagnosticLevel::Error,
+            LintLevel::Warn => DiagnosticLevel::Warning,
+            LintLevel::Allow => return,
+        };
+        self.diagnostics.push(Diagnostic {
+            lint,
+            level,
+            message,
+            packages: packages.into_iter().map(DiagnosticPackage::new).collect(),
+        });
+    }
+
+    fn check_git_dependencies(&mut self, level: LintLevel) {
+        let graph = self.graph;
+        for package in graph.workspace().iter() {
+            if package.publish().is_never() {
+                continue;
+            }
+            for link in package.direct_links() {
+                // Cargo strips dev-dependencies without a version when publishing.
+                if link.dev_only() {
+                    continue;
+                }
+                if let Some(ExternalSource::Git { .. }) = link.to().source().parse_external() {
+                    self.push(
+                        "git-dependencies",
+                        level,
+                        vec![package, link.to()],
+                        format!(
+                            "{} can be published but depends on git package {}",
+                            package_str(package),
+                            package_str(link.to())
+                        ),
+                    );
+                }
+            }
+        }
+    }
+
+    fn check_wildcard_requirements(&mut self, level: LintLevel) {
+        let graph = self.graph;
+        for package in graph.workspace().iter() {
+            for link in package.direct_links() {
+                // Path and git dependencies don't need a version requirement.
+                let source = link.to().source();
+                if source.is_local()
+                    || matches!(source.parse_external(), Some(ExternalSource::Git { .. }))
+                {
+                    continue;
+                }
+                // `*` parses to a requirement without any comparators.
+                if link.version_req().comparators.is_empty() {
+                    self.push(
+                        "wildcard-requirements",
+                        level,
+                        vec![package, link.to()],
+                        format!(
+                            "{} has a wildcard version requirement on {}",
+                            package_str(package),
+                            link.dep_name()
+                        ),
+                    );
+                }
+            }
+        }
+    }
+
+    fn check_licenses(&mut self, config: &LicensesConfig, packages: &PackageSet<'g>) {
+        for package in packages.packages(DependencyDirection::Forward) {
+            if package.in_workspace() || config.exceptions.iter().any(|e| e == package.name()) {
+                continue;
+            }
+            let message = match package.license() {
+                Some(license) => match license_allowed(license, &config.allow) {
+                    Ok(true) => continue,
+                    Ok(false) => format!(
+                        "{} has license '{}', which is not allowed",
+                        package_str(package),
+                        license
+                    ),
+                    Err(err) => format!(
+                        "{} has invalid license expression '{}': {}",
+                        package_str(package),
+                        license,
+                        err
+                    ),
+                },
+                None => format!("{} doesn't specify a license", package_str(package)),
+            };
+            self.push("licenses", config.level, vec![package], message);
+        }
+    }
+
+    fn check_duplicates(&mut self, config: &DuplicatesConfig, packages: &PackageSet<'g>) {
+        let mut by_name: BTreeMap<&str, Vec<PackageMetadata<'g>>> = BTreeMap::new();
+        for package in packages.packages(DependencyDirection::Forward) {
+            by_name.entry(package.name()).or_default().push(package);
+        }
+
+        for (name, mut versions) in by_name {
+            if versions.len() <= config.max_versions || config.skip.iter().any(|s| s == name) {
+                continue;
+            }
+            versions.sort_by(|a, b| a.version().cmp(b.version()));
+            let version_list = versions
+                .iter()
+                .map(|package| package.version().to_string())
+                .collect::<Vec<_>>()
+                .join(", ");
+            let message = format!(
+                "{} versions of {} found, at most {} allowed: {}",
+                versions.len(),
+                name,
+                config.max_versions,
+                version_list
+            );
+            self.push("duplicates", config.level, versions, message);
+        }
+    }
+
+    fn check_ban(&mut self, ban: &BanConfig) -> Result<()> {
+        let graph = self.graph;
+        let from = self.eval(&ban.from)?;
+        let to = self.eval(&ban.to)?;
+
+        for package in from.packages(DependencyDirection::Forward) {
+            let deps = graph
+                .query_forward(iter::once(package.id()))?
+                .resolve_with_fn(|_: &PackageQuery<'g>, link| self.follow_link(link));
+            for banned in deps
+                .intersection(&to)
+                .packages(DependencyDirection::Forward)
+            {
+                if banned.id() == package.id() {
+                    continue;
+                }
+                let mut message = format!(
+                    "{} depends on banned package {}",
+                    package_str(package),
+                    package_str(banned)
+                );
+                if let Some(reason) = &ban.reason {
+                    message.push_str(": ");
+                    message.push_str(reason);
+                }
+                self.push("ban", ban.level, vec![package, banned], message);
+            }
+        }
+        Ok(())
+    }
+}
+
+fn package_str(package: PackageMetadata<'_>) -> String {
+    format!(
+        "{} {} ({})",
+        package.name(),
+        package.version(),
+        package.source()
+    )
+}
+
+/// Returns true if the SPDX license expression is satisfied by the allowed licenses.
+///
+/// `OR` requires one of its operands to be allowed, while `AND` requires both. A license with an
+/// exception (`X WITH Y`) is allowed if either it or the base license is allowed, and likewise for
+/// `X+`. The legacy `/` separator is treated as `OR`.
+fn license_allowed(expr: &str, allow: &[String]) -> Result<bool, String> {
+    if allow.iter().any(|allowed| allowed == expr) {
+        return Ok(true);
+    }
+
+    let expr = expr
+        .replace('/', " OR ")
+        .replace('(', " ( ")
+        .replace(')', " ) ");
+    let tokens: Vec<&str> = expr.split_whitespace().collect();
+    let mut parser = LicenseParser {
+        tokens: &tokens,
+        pos: 0,
+        allow,
+    };
+    let allowed = parser.parse_or()?;
+    match parser.tokens.get(parser.pos) {
+        Some(token) => Err(format!("unexpected '{}'", token)),
+        None => Ok(allowed),
+    }
+}
+
+struct LicenseParser<'a> {
+    tokens: &'a [&'a str],
+    pos: usize,
+    allow: &'a [String],
+}
+
+impl<'a> LicenseParser<'a> {
+    fn next_if(&mut self, expected: &str) -> bool {
+        if self.tokens.get(self.pos) == Some(&expected) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn parse_or(&mut self) -> Result<bool, String> {
+        let mut allowed = self.parse_and()?;
+        while self.next_if("OR") {
+            // Evaluate both sides to catch syntax errors.
+            let rhs = self.parse_and()?;
+            allowed = allowed || rhs;
+        }
+        Ok(allowed)
+    }
+
+    fn parse_and(&mut self) -> Result<bool, String> {
+        let mut allowed = self.parse_primary()?;
+        while self.next_if("AND") {
+            let rhs = self.parse_primary()?;
+            allowed = allowed && rhs;
+        }
+        Ok(allowed)
+    }
+
+    fn parse_primary(&mut self) -> Result<bool, String> {
+        if self.next_if("(") {
+            let allowed = self.parse_or()?;
+            if !self.next_if(")") {
+                return Err("unclosed '('".to_owned());
+            }
+            return Ok(allowed);
+        }
+
+        let license = match self.tokens.get(self.pos) {
+            Some(&token) if !matches!(token, "OR" | "AND" | "WITH" | "(" | ")") => token,
+            Some(token) => return Err(format!("unexpected '{}'", token)),
+            None => return Err("unexpected end of expression".to_owned()),
+        };
+        self.pos += 1;
+        let allow = self.allow;
+        let is_allowed = |license: &str| allow.iter().any(|allowed| allowed == license);
+
+        if self.next_if("WITH") {
+            let exception = match self.tokens.get(self.pos) {
+                Some(&token) if !matches!(token, "OR" | "AND" | "WITH" | "(" | ")") => token,
+                _ => return Err("expected an exception after 'WITH'".to_owned()),
+            };
+            self.pos += 1;
+            let with_exception = format!("{} WITH {}", license, exception);
+            return Ok(is_allowed(&with_exception) || is_allowed(license));
+        }
+        // `X+` means "X or any later version", which is allowed if X is.
+        Ok(is_allowed(license) || license.strip_suffix('+').map_or(false, is_allowed))
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+enum DiagnosticLevel {
+    Error,
+    Warning,
+}
+
+/// A lint diagnostic.
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "kebab-case")]
+struct Diagnostic {
+    lint: &'static str,
+    level: DiagnosticLevel,
+    message: String,
+    /// The packages involved, starting with the one the diagnostic is about.
+    packages: Vec<DiagnosticPackage>,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let level = match self.level {
+            DiagnosticLevel::Error => "error",
+            DiagnosticLevel::Warning => "warning",
+        };
+        write!(f, "{}[{}]: {}", level, self.lint, self.message)
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "kebab-case")]
+struct DiagnosticPackage {
+    name: String,
+    version: String,
+    source: String,
+}
+
+impl DiagnosticPackage {
+    fn new(package: PackageMetadata<'_>) -> Self {
+        Self {
+            name: package.name().to_owned(),
+            version: package.version().to_string(),
+            source: package.source().to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use fixtures::json::JsonFixture;
+    use guppy::JsonValue;
+
+    fn parse_policy(policy: &str) -> LintPolicy {
+        toml::from_str(policy).expect("policy is valid")
+    }
+
+    fn messages(policy: &str, graph: &PackageGraph) -> Vec<String> {
+        parse_policy(policy)
+            .check(graph)
+            .expect("policy checked")
+            .iter()
+            .map(|diagnostic| diagnostic.to_string())
+            .collect()
+    }
+
+    /// Returns metadata1 with testcrate's dependencies on datatest changed to `req`, and datatest
+    /// moved to the git repository `source` if specified.
+    fn edited_metadata1(req: &str, source: Option<&str>) -> PackageGraph {
+        let mut json: JsonValue =
+            serde_json::from_str(JsonFixture::metadata1().json()).expect("valid JSON");
+        for package in json["packages"]
+            .as_array_mut()
+            .expect("packages is an array")
+        {
+            if package["name"] == "testcrate" {
+                for dep in package["dependencies"]
+                    .as_array_mut()
+                    .expect("dependencies is an array")
+                {
+                    dep["req"] = req.into();
+                    if let Some(source) = source {
+                        dep["source"] = format!("git+{}", source).into();
+                    }
+                }
+            } else if let (Some("datatest"), Some(source)) = (package["name"].as_str(), source) {
+                package["source"] = format!("git+{}#0123456789abcdef", source).into();
+            }
+        }
+        PackageGraph::from_json(json.to_string()).expect("graph constructed")
+    }
+
+    #[test]
+    fn license_expressions() {
+        let allow: Vec<_> = ["MIT", "Apache-2.0", "GPL-2.0 WITH Classpath-exception-2.0"]
+            .iter()
+            .map(|license| license.to_string())
+            .collect();
+        let allowed = |expr| license_allowed(expr, &allow);
+
+        assert_eq!(allowed("MIT"), Ok(true));
+        assert_eq!(allowed("BSD-3-Clause"), Ok(false));
+        assert_eq!(allowed("MIT OR BSD-3-Clause"), Ok(true));
+        assert_eq!(allowed("MIT AND BSD-3-Clause"), Ok(false));
+        assert_eq!(allowed("MIT AND Apache-2.0"), Ok(true));
+        // AND binds more tightly than OR.
+        assert_eq!(allowed("BSD-3-Clause OR MIT AND Apache-2.0"), Ok(true));
+        assert_eq!(allowed("(BSD-3-Clause OR MIT) AND Zlib"), Ok(false));
+        assert_eq!(
+            allowed("(BSD-3-Clause OR MIT) AND (Zlib OR Apache-2.0)"),
+            Ok(true)
+        );
+        // Exceptions are allowed if either the exception or the base license is allowed.
+        assert_eq!(allowed("GPL-2.0 WITH Classpath-exception-2.0"), Ok(true));
+        assert_eq!(allowed("GPL-2.0 WITH LLVM-exception"), Ok(false));
+        assert_eq!(allowed("Apache-2.0 WITH LLVM-exception"), Ok(true));
+        // `X+` is allowed if X is.
+        assert_eq!(allowed("Apache-2.0+"), Ok(true));
+        assert_eq!(allowed("GPL-2.0+"), Ok(false));
+        // The legacy `/` separator is treated as OR.
+        assert_eq!(allowed("MIT/Apache-2.0"), Ok(true));
+        assert_eq!(allowed("BSD-3-Clause/Zlib"), Ok(false));
+
+        for invalid in [
+            "",
+            "MIT OR",
+            "(MIT",
+            "MIT)",
+            "AND MIT",
+            "MIT WITH",
+            "MIT Apache-2.0",
+        ] {
+            assert!(
+                allowed(invalid).is_err(),
+                "'{}' is an invalid expression",
+                invalid
+            );
+        }
+    }
+
+    #[test]
+    fn licenses_lint() {
+        let graph = JsonFixture::metadata1().graph();
+        let policy = r#"
+            [licenses]
+            allow = ["MIT", "Apache-2.0", "Unlicense"]
+        "#;
+        assert_eq!(
+            messages(policy, graph),
+            vec![
+                "error[licenses]: mach 0.2.3 (registry+https://github.com/rust-lang/crates.io-index) \
+                 has license 'BSD-2-Clause', which is not allowed"
+            ]
+        );
+
+        let policy = r#"
+            [licenses]
+            level = "warn"
+            allow = ["MIT", "Apache-2.0", "Unlicense"]
+            exceptions = ["mach"]
+        "#;
+        assert_eq!(messages(policy, graph), Vec::<String>::new());
+    }
+
+    #[test]
+    fn duplicates_lint() {
+        let graph = JsonFixture::metadata_dups().graph();
+        // max-versions defaults to 1.
+        let policy = r#"
+            packages = "all()"
+            [duplicates]
+        "#;
+        assert_eq!(
+            messages(policy, graph),
+            vec![
+                "error[duplicates]: 2 versions of bytes found, at most 1 allowed: 0.3.0, 0.5.4",
+                "error[duplicates]: 2 versions of lazy_static found, at most 1 allowed: 0.2.11, \
+                 1.4.0",
+            ]
+        );
+
+        let policy = r#"
+            packages = "all()"
+            [duplicates]
+            skip = ["bytes"]
+        "#;
+        assert_eq!(messages(policy, graph).len(), 1, "bytes is skipped");
+
+        let policy = r#"
+            packages = "all()"
+            [duplicates]
+            max-versions = 2
+        "#;
+        assert_eq!(messages(policy, graph), Vec::<String>::new());
+    }
+
+    #[test]
+    fn git_and_wildcard_lints() {
+        let policy = r#"
+            [git-dependencies]
+            [wildcard-requirements]
+            level = "warn"
+        "#;
+        assert_eq!(
+            messages(policy, JsonFixture::metadata1().graph()),
+            Vec::<String>::new()
+        );
+
+        let graph = edited_metadata1("*", Some("https://github.com/commure/datatest"));
+        // Git dependencies don't need a version requirement.
+        assert_eq!(
+            messages(policy, &graph),
+            vec![
+                "error[git-dependencies]: testcrate 0.1.0 () can be published but depends on \
+                 git package datatest 0.4.2 \
+                 (git+https://github.com/commure/datatest#0123456789abcdef)"
+            ]
+        );
+
+        let graph = edited_metadata1("*", None);
+        assert_eq!(
+            messages(policy, &graph),
+            vec![
+                "warning[wildcard-requirements]: testcrate 0.1.0 () has a wildcard version \
+                  requirement on datatest"
+            ]
+        );
+    }
+
+    #[test]
+    fn bans_lint() {
+        let graph = JsonFixture::metadata1().graph();
+        let policy = r#"
+            [[ban]]
+            to = "dtoa"
+            reason = "use ryu instead"
+
+            [[ban]]
+            level = "allow"
+            to = "serde"
+        "#;
+        assert_eq!(
+            messages(policy, graph),
+            vec![
+                "error[ban]: testcrate 0.1.0 () depends on banned package dtoa 0.4.4 \
+                 (registry+https://github.com/rust-lang/crates.io-index): use ryu instead"
+            ]
+        );
+
+        // Bans are transitive, and a package doesn't ban itself.
+        let policy = r#"
+            [[ban]]
+            from = "serde_yaml"
+            to = "dtoa | serde_yaml"
+        "#;
+        assert_eq!(messages(policy, graph).len(), 1);
+    }
+
+    #[test]
+    fn exit_codes() {
+        let graph = JsonFixture::metadata1().graph();
+        let run = |args: &[&str], policy: &str| {
+            let opts = LintOptions::parse_from(
+                ["lint", "--policy", "lint.toml"]
+                    .iter()
+                    .chain(args)
+                    .copied(),
+            );
+            opts.run(&parse_policy(policy), graph).expect("lint ran")
+        };
+        let error = r#"
+            [[ban]]
+            to = "dtoa"
+        "#;
+        let warning = r#"
+            [[ban]]
+            level = "warn"
+            to = "dtoa"
+        "#;
+
+        let (output, exit_code) = run(&[], error);
+        assert_eq!(exit_code, LINT_FAILURE_EXIT_CODE);
+        assert!(
+            output.ends_with("\nlint: 1 error, 0 warnings\n"),
+            "output ends with a summary:\n{}",
+            output
+        );
+
+        assert_eq!(run(&[], warning).1, 0, "warnings pass by default");
+        assert_eq!(
+            run(&["--deny-warnings"], warning).1,
+            LINT_FAILURE_EXIT_CODE,
+            "warnings fail with --deny-warnings"
+        );
+        assert_eq!(run(&[], "").0, "lint: 0 errors, 0 warnings\n");
+
+        let (output, exit_code) = run(&["--json"], error);
+        assert_eq!(exit_code, LINT_FAILURE_EXIT_CODE);
+        let json: JsonValue = serde_json::from_str(&output).expect("output is JSON");
+        assert_eq!(json[0]["lint"], "ban");
+        assert_eq!(json[0]["level"], "error");
+        assert_eq!(json[0]["packages"][1]["name"], "dtoa");
+    }
+}
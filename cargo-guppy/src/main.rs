@@ -2,8 +2,9 @@
 // SPDX-License-Identifier: MIT OR Apache-2.0
 
 use cargo_guppy::{
-    CmdSelectOptions, DiffSummariesOptions, DupsOptions, MvOptions, ResolveCargoOptions,
-    SubtreeSizeOptions,
+    CmdSelectOptions, DiffOptions, DiffSummariesOptions, DupsOptions, ExploreOptions,
+    FeatureHygieneOptions, FeaturesAuditOptions, LintOptions, MvOptions, ResolveCargoOptions,
+    SubtreeSizeOptions, TreeOptions, WhatIfOptions, WhyOptions,
 };
 use clap::Parser;
 use color_eyre::Result;
@@ -19,16 +20,20 @@ struct Args {
 #[derive(Debug, Parser)]
 enum Command {
     #[structopt(name = "diff")]
-    /// Perform a diff of two cargo metadata JSON files
-    Diff {
-        #[structopt(long)]
-        json: bool,
-        old: String,
-        new: String,
-    },
+    /// Perform a diff of two cargo metadata JSON files or git revisions
+    ///
+    /// With --base, each revision is checked out into a temporary git worktree and its package
+    /// graph is built from there.
+    Diff(DiffOptions),
     #[structopt(name = "diff-summaries")]
     /// Diff two guppy summaries
     DiffSummaries(DiffSummariesOptions),
+    #[structopt(name = "what-if")]
+    /// Show how hypothetical dependency edits would change the build of each workspace member
+    ///
+    /// Edits are simulated in memory: no files are changed. Each workspace member is built on its
+    /// own, as with `cargo build -p <member>`.
+    WhatIf(WhatIfOptions),
     #[structopt(name = "dups")]
     /// Print the number of duplicate packages
     Duplicates(DupsOptions),
@@ -40,7 +45,43 @@ enum Command {
     Select(CmdSelectOptions),
     #[structopt(name = "subtree-size")]
     /// Print a list of dependencies along with their unique subtree size
+    ///
+    /// With --weight, packages are weighed by build targets, source files on disk and the platforms
+    /// they're built on, to estimate how much each dependency adds to build times.
     SubtreeSize(SubtreeSizeOptions),
+    #[structopt(name = "tree")]
+    /// Print the packages that would be built by Cargo as a tree
+    ///
+    /// The tree reflects guppy's simulation of Cargo, including omitted packages, the initials
+    /// platform and custom target and host platforms.
+    Tree(TreeOptions),
+    #[structopt(name = "explore")]
+    /// Write an interactive HTML page for exploring the packages that would be built by Cargo
+    ///
+    /// The page is self-contained and can be opened without a server or network access.
+    Explore(ExploreOptions),
+    #[structopt(name = "why")]
+    /// Print the dependency paths from the workspace to a package
+    ///
+    /// Each step along a path is annotated with the dependency kinds, the platforms it's enabled
+    /// on, and the features through which it's activated.
+    Why(WhyOptions),
+    #[structopt(name = "lint")]
+    /// Check dependencies against a policy file
+    ///
+    /// Exits with code 2 if any lint errors are found.
+    Lint(LintOptions),
+    #[structopt(name = "features-audit")]
+    /// Find feature cycles, unused features, aliases and other redundancies
+    ///
+    /// Use --json for machine-readable output.
+    FeaturesAudit(FeaturesAuditOptions),
+    #[structopt(name = "feature-hygiene")]
+    /// Find features requested on third-party dependencies that are enabled anyway
+    ///
+    /// Each workspace member is built on its own, as with `cargo build -p <member>`, with and
+    /// without each requested feature.
+    FeatureHygiene(FeatureHygieneOptions),
     #[structopt(name = "mv")]
     /// Move packages to another location, fixing up workspace paths
     ///
@@ -81,12 +122,19 @@ fn main() -> Result<()> {
     let args = Args::parse_from(args());
 
     match args.cmd {
-        Command::Diff { json, old, new } => cargo_guppy::cmd_diff(json, &old, &new),
+        Command::Diff(ref options) => options.exec(),
         Command::DiffSummaries(options) => options.exec(),
+        Command::WhatIf(ref options) => options.exec(),
         Command::Duplicates(ref options) => cargo_guppy::cmd_dups(options),
         Command::ResolveCargo(ref options) => cargo_guppy::cmd_resolve_cargo(options),
         Command::Select(ref options) => cargo_guppy::cmd_select(options),
         Command::SubtreeSize(ref options) => cargo_guppy::cmd_subtree_size(options),
+        Command::Tree(ref options) => options.exec(),
+        Command::Explore(ref options) => options.exec(),
+        Command::Why(ref options) => options.exec(),
+        Command::Lint(ref options) => std::process::exit(options.exec()?),
+        Command::FeaturesAudit(ref options) => options.exec(),
+        Command::FeatureHygiene(ref options) => options.exec(),
         Command::Mv(ref options) => options.exec(),
     }
 }
@@ -38,8 +38,7 @@ pub struct MvOptions {
 impl MvOptions {
     pub fn exec(&self) -> Result<()> {
         // Construct a package graph.
-        let command = self.metadata_opts.make_command();
-        let pkg_graph = command.build_graph()?;
+        let pkg_graph = self.metadata_opts.build_graph()?;
         let workspace_root = pkg_graph.workspace().root();
 
         let dest_dir = DestDir::new(&pkg_graph, &self.dest_dir)?;
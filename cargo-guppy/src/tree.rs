@@ -0,0 +1,593 @@
+// Copyright (c) The cargo-guppy Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Implementation for `cargo guppy tree`.
+
+use crate::core::{BaseFilterOptions, Kind};
+use clap::{ArgEnum, Parser};
+use color_eyre::eyre::{bail, Result};
+use guppy::{
+    graph::{
+        cargo::{BuildPlatform, CargoOptions, CargoSet, InitialsPlatform},
+        DependencyDirection, PackageGraph, PackageMetadata,
+    },
+    DependencyKind, PackageId,
+};
+use guppy_cmdlib::{
+    string_to_platform_spec, CargoMetadataOptions, CargoResolverOpts, PackagesAndFeatures,
+};
+use std::{
+    collections::{BTreeMap, BTreeSet, HashSet},
+    fmt::Write,
+};
+
+#[derive(ArgEnum, Clone, Copy, Debug, Eq, Ord, PartialEq, PartialOrd)]
+pub enum EdgeKind {
+    Normal,
+    Build,
+    Dev,
+}
+
+impl EdgeKind {
+    pub(crate) fn as_str(self) -> &'static str {
+        match self {
+            EdgeKind::Normal => "normal",
+            EdgeKind::Build => "build",
+            EdgeKind::Dev => "dev",
+        }
+    }
+
+    pub(crate) fn to_guppy(self) -> DependencyKind {
+        match self {
+            EdgeKind::Normal => DependencyKind::Normal,
+            EdgeKind::Build => DependencyKind::Build,
+            EdgeKind::Dev => DependencyKind::Development,
+        }
+    }
+
+    pub(crate) fn from_guppy(kind: DependencyKind) -> Self {
+        match kind {
+            DependencyKind::Normal => EdgeKind::Normal,
+            DependencyKind::Build => EdgeKind::Build,
+            DependencyKind::Development => EdgeKind::Dev,
+        }
+    }
+}
+
+#[derive(Debug, Parser)]
+pub struct TreeOptions {
+    #[clap(long = "package", short = 'p')]
+    /// Packages to start the query from (default: entire workspace)
+    packages: Vec<String>,
+
+    #[clap(long = "expr", conflicts_with = "packages")]
+    /// Package expression to start the query from, e.g. "workspace() - rdeps(foo)"
+    expr: Option<String>,
+
+    #[clap(long = "features-only")]
+    /// Packages that take part in feature unification but aren't in the result set (default: none)
+    features_only: Vec<String>,
+
+    /// List of features to activate across all packages
+    #[clap(long = "features", short = 'F', use_value_delimiter = true)]
+    features: Vec<String>,
+
+    /// Activate all available features
+    #[clap(long = "all-features")]
+    all_features: bool,
+
+    /// Do not activate the `default` feature
+    #[clap(long = "no-default-features")]
+    no_default_features: bool,
+
+    #[clap(flatten)]
+    resolver_opts: CargoResolverOpts,
+
+    #[clap(flatten)]
+    base_filter_opts: BaseFilterOptions,
+
+    #[clap(long = "target-platform")]
+    /// Evaluate against target platform, "current" or "any" (default: any)
+    target_platform: Option<String>,
+
+    #[clap(long = "host-platform")]
+    /// Evaluate against host platform, "current" or "any" (default: any)
+    host_platform: Option<String>,
+
+    #[clap(long, short = 'i')]
+    /// Invert the tree, showing what depends on the given packages (`name` or `name@version`)
+    invert: Vec<String>,
+
+    #[clap(long)]
+    /// Maximum depth of the tree (default: unlimited)
+    depth: Option<usize>,
+
+    #[clap(long, short = 'e', arg_enum, use_value_delimiter = true)]
+    #[clap(default_values = &["normal", "build", "dev"])]
+    /// Kinds of dependency edges to display
+    edges: Vec<EdgeKind>,
+
+    #[clap(long)]
+    /// Do not display the given packages or their dependencies (`name` or `name@version`)
+    prune: Vec<String>,
+
+    #[clap(long)]
+    /// Only display the dependencies of a package the first time it is seen
+    dedupe: bool,
+
+    #[clap(long)]
+    /// Display the features enabled for each package on the platform it is built on
+    show_features: bool,
+
+    #[clap(flatten)]
+    metadata_opts: CargoMetadataOptions,
+}
+
+impl TreeOptions {
+    pub fn exec(&self) -> Result<()> {
+        let pkg_graph = self.metadata_opts.build_graph()?;
+        print!("{}", self.render(&pkg_graph)?);
+        Ok(())
+    }
+
+    /// Simulates a Cargo build and renders it as a tree.
+    fn render(&self, pkg_graph: &PackageGraph) -> Result<String> {
+        let mut cargo_opts = CargoOptions::new();
+        cargo_opts
+            .set_include_dev(self.resolver_opts.include_dev)
+            .set_resolver(self.resolver_opts.resolver_version.to_guppy())
+            .set_initials_platform(self.resolver_opts.initials_platform.to_guppy())
+            .set_target_platform(string_to_platform_spec(self.target_platform.as_deref())?)
+            .set_host_platform(string_to_platform_spec(self.host_platform.as_deref())?)
+            .add_omitted_packages(self.base_filter_opts.omitted_package_ids(pkg_graph));
+
+        let pf = PackagesAndFeatures {
+            packages: self.packages.clone(),
+            expr: self.expr.clone(),
+            features_only: self.features_only.clone(),
+            features: self.features.clone(),
+            all_features: self.all_features,
+            no_default_features: self.no_default_features,
+        };
+        let (initials, features_only) = pf.make_feature_sets(pkg_graph)?;
+        let cargo_set = CargoSet::new(initials, features_only, &cargo_opts)?;
+
+        let build_graph = BuildGraph::new(
+            &cargo_set,
+            self.resolver_opts.initials_platform.to_guppy(),
+            self.base_filter_opts.kind,
+        );
+
+        let printer = TreePrinter {
+            opts: self,
+            cargo_set: &cargo_set,
+            build_graph: &build_graph,
+        };
+
+        let roots = if self.invert.is_empty() {
+            build_graph.roots.clone()
+        } else {
+            let mut roots = vec![];
+            for spec in &self.invert {
+                let matches: Vec<_> = build_graph
+                    .nodes()
+                    .filter(|node| matches_spec(node.package, spec))
+                    .collect();
+                if matches.is_empty() {
+                    bail!("package '{}' is not built", spec);
+                }
+                roots.extend(matches);
+            }
+            roots
+        };
+
+        let mut out = String::new();
+        let mut seen = HashSet::new();
+        for (idx, root) in roots.into_iter().enumerate() {
+            if idx > 0 {
+                out.push('\n');
+            }
+            printer.print_tree(root, &mut seen, &mut out);
+        }
+        Ok(out)
+    }
+
+    fn is_pruned(&self, package: PackageMetadata<'_>) -> bool {
+        self.prune.iter().any(|spec| matches_spec(package, spec))
+    }
+}
+
+/// Returns true if the package matches `name` or `name@version`.
+fn matches_spec(package: PackageMetadata<'_>, spec: &str) -> bool {
+    match spec.split_once('@') {
+        Some((name, version)) => package.name() == name && package.version().to_string() == version,
+        None => package.name() == spec,
+    }
+}
+
+// ---
+// Build graph
+// ---
+
+/// A package built on a particular platform.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct Node<'g> {
+    pub(crate) package: PackageMetadata<'g>,
+    pub(crate) build_platform: BuildPlatform,
+}
+
+impl<'g> Node<'g> {
+    fn new(package: PackageMetadata<'g>, build_platform: BuildPlatform) -> Self {
+        Self {
+            package,
+            build_platform,
+        }
+    }
+
+    fn key(&self) -> (&'g PackageId, BuildPlatform) {
+        (self.package.id(), self.build_platform)
+    }
+}
+
+/// The packages and dependency edges in a `CargoSet`, with each package split by the platform it is
+/// built on.
+pub(crate) struct BuildGraph<'g> {
+    pub(crate) roots: Vec<Node<'g>>,
+    // Edges are stored as (kind, node) pairs so that they are grouped by kind.
+    forward: BTreeMap<NodeKey<'g>, BTreeSet<(EdgeKind, NodeKey<'g>)>>,
+    reverse: BTreeMap<NodeKey<'g>, BTreeSet<(EdgeKind, NodeKey<'g>)>>,
+    nodes: BTreeMap<NodeKey<'g>, Node<'g>>,
+}
+
+type NodeKey<'g> = (&'g PackageId, BuildPlatform);
+
+impl<'g> BuildGraph<'g> {
+    /// Collects the links followed while computing `cargo_set`.
+    ///
+    /// Links that `kind` doesn't traverse are hidden, along with any packages only reachable
+    /// through them. This doesn't change what's built.
+    pub(crate) fn new(
+        cargo_set: &CargoSet<'g>,
+        initials_platform: InitialsPlatform,
+        kind: Kind,
+    ) -> Self {
+        let mut roots = vec![];
+        for package in cargo_set
+            .initials()
+            .to_package_set()
+            .packages(DependencyDirection::Forward)
+        {
+            let (on_target, on_host) = match initials_platform {
+                InitialsPlatform::Host => (false, true),
+                InitialsPlatform::Standard => (!package.is_proc_macro(), package.is_proc_macro()),
+                InitialsPlatform::ProcMacrosOnTarget => (true, package.is_proc_macro()),
+            };
+            if on_target {
+                roots.push(Node::new(package, BuildPlatform::Target));
+            }
+            if on_host {
+                roots.push(Node::new(package, BuildPlatform::Host));
+            }
+        }
+
+        let mut followed: BTreeMap<NodeKey<'g>, Vec<(EdgeKind, Node<'g>)>> = BTreeMap::new();
+        for build_platform in BuildPlatform::VALUES {
+            for followed_link in cargo_set.followed_links(*build_platform) {
+                let link = followed_link.link();
+                if !kind.should_traverse(&link) {
+                    continue;
+                }
+                followed
+                    .entry((link.from().id(), followed_link.from_build_platform()))
+                    .or_default()
+                    .push((
+                        EdgeKind::from_guppy(followed_link.kind()),
+                        Node::new(link.to(), followed_link.to_build_platform()),
+                    ));
+            }
+        }
+
+        let mut graph = Self {
+            roots: roots.clone(),
+            forward: BTreeMap::new(),
+            reverse: BTreeMap::new(),
+            nodes: roots.iter().map(|node| (node.key(), *node)).collect(),
+        };
+
+        // Only keep the packages reachable from the roots.
+        let mut stack = roots;
+        while let Some(node) = stack.pop() {
+            for (kind, to_node) in followed.get(&node.key()).into_iter().flatten() {
+                graph
+                    .forward
+                    .entry(node.key())
+                    .or_default()
+                    .insert((*kind, to_node.key()));
+                graph
+                    .reverse
+                    .entry(to_node.key())
+                    .or_default()
+                    .insert((*kind, node.key()));
+                if graph.nodes.insert(to_node.key(), *to_node).is_none() {
+                    stack.push(*to_node);
+                }
+            }
+        }
+
+        graph
+    }
+
+    pub(crate) fn nodes(&self) -> impl Iterator<Item = Node<'g>> + '_ {
+        self.nodes.values().copied()
+    }
+
+    pub(crate) fn edges(
+        &self,
+        node: Node<'g>,
+        invert: bool,
+    ) -> impl Iterator<Item = (EdgeKind, Node<'g>)> + '_ {
+        let edges = if invert { &self.reverse } else { &self.forward };
+        edges
+            .get(&node.key())
+            .into_iter()
+            .flatten()
+            .map(move |(kind, key)| (*kind, self.nodes[key]))
+    }
+}
+
+// ---
+// Text output
+// ---
+
+struct TreePrinter<'a, 'g> {
+    opts: &'a TreeOptions,
+    cargo_set: &'a CargoSet<'g>,
+    build_graph: &'a BuildGraph<'g>,
+}
+
+impl<'a, 'g> TreePrinter<'a, 'g> {
+    fn print_tree(&self, root: Node<'g>, seen: &mut HashSet<NodeKey<'g>>, out: &mut String) {
+        let mut path = vec![root.key()];
+        self.print_node(root, false, out);
+        seen.insert(root.key());
+        self.print_children(root, "", 1, &mut path, seen, out);
+    }
+
+    /// Prints the description of a node, without a trailing newline.
+    fn print_node(&self, node: Node<'g>, repeated: bool, out: &mut String) {
+        let package = node.package;
+        write!(out, "{} v{}", package.name(), package.version())
+            .expect("writing to a string is infallible");
+        if package.is_proc_macro() {
+            out.push_str(" (proc-macro)");
+        } else if node.build_platform == BuildPlatform::Host {
+            out.push_str(" (host)");
+        }
+        if self.opts.show_features {
+            if let Some(features) = self
+                .cargo_set
+                .platform_features(node.build_platform)
+                .features_for(package.id())
+                .expect("valid package ID")
+            {
+                write!(out, " (features: {})", features.display_features())
+                    .expect("writing to a string is infallible");
+            }
+        }
+        if repeated {
+            out.push_str(" (*)");
+        }
+    }
+
+    fn print_children(
+        &self,
+        node: Node<'g>,
+        prefix: &str,
+        depth: usize,
+        path: &mut Vec<NodeKey<'g>>,
+        seen: &mut HashSet<NodeKey<'g>>,
+        out: &mut String,
+    ) {
+        out.push('\n');
+        if self.opts.depth.map_or(false, |max_depth| depth > max_depth) {
+            return;
+        }
+
+        let invert = !self.opts.invert.is_empty();
+        let mut children: Vec<_> = self
+            .build_graph
+            .edges(node, invert)
+            .filter(|(kind, child)| {
+                self.opts.edges.contains(kind) && !self.opts.is_pruned(child.package)
+            })
+            .collect();
+        children.sort_by(|(kind_a, a), (kind_b, b)| {
+            (
+                kind_a,
+                a.package.name(),
+                a.package.version(),
+                a.build_platform,
+            )
+                .cmp(&(
+                    kind_b,
+                    b.package.name(),
+                    b.package.version(),
+                    b.build_platform,
+                ))
+        });
+
+        let mut current_kind = EdgeKind::Normal;
+        for (idx, (kind, child)) in children.iter().enumerate() {
+            if *kind != current_kind {
+                // Dependencies are grouped by kind, like in a Cargo.toml.
+                writeln!(out, "{}[{}-dependencies]", prefix, kind.as_str())
+                    .expect("writing to a string is infallible");
+                current_kind = *kind;
+            }
+
+            let last = children
+                .get(idx + 1)
+                .map_or(true, |(next_kind, _)| next_kind != kind);
+            out.push_str(prefix);
+            out.push_str(if last { "└── " } else { "├── " });
+
+            // A node on the current path indicates a cycle (e.g. through dev-dependencies).
+            let repeated = path.contains(&child.key())
+                || (self.opts.dedupe
+                    && seen.contains(&child.key())
+                    && self.build_graph.edges(*child, invert).next().is_some());
+            self.print_node(*child, repeated, out);
+            if repeated {
+                out.push('\n');
+                continue;
+            }
+            seen.insert(child.key());
+
+            let child_prefix = format!("{}{}", prefix, if last { "    " } else { "│   " });
+            path.push(child.key());
+            self.print_children(*child, &child_prefix, depth + 1, path, seen, out);
+            path.pop();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use fixtures::json::JsonFixture;
+    use guppy::graph::feature::StandardFeatures;
+
+    fn parse(args: &[&str]) -> TreeOptions {
+        TreeOptions::parse_from(std::iter::once("tree").chain(args.iter().copied()))
+    }
+
+    fn render(fixture: &JsonFixture, args: &[&str]) -> String {
+        parse(args).render(fixture.graph()).expect("tree rendered")
+    }
+
+    #[test]
+    fn tree_matches_cargo_set() {
+        // Every package in the simulated build is shown, on the platform it is built on.
+        for (name, fixture) in JsonFixture::all_fixtures() {
+            for include_dev in [false, true] {
+                let graph = fixture.graph();
+                let mut cargo_opts = CargoOptions::new();
+                cargo_opts.set_include_dev(include_dev);
+                let cargo_set = graph
+                    .resolve_workspace()
+                    .to_feature_set(StandardFeatures::Default)
+                    .into_cargo_set(&cargo_opts)
+                    .expect("cargo set resolved");
+                let build_graph =
+                    BuildGraph::new(&cargo_set, InitialsPlatform::Standard, Kind::All);
+
+                let actual: BTreeSet<_> = build_graph.nodes().map(|node| node.key()).collect();
+                let expected: BTreeSet<_> = cargo_set
+                    .all_features()
+                    .iter()
+                    .flat_map(|(build_platform, features)| {
+                        features
+                            .to_package_set()
+                            .package_ids(DependencyDirection::Forward)
+                            .map(move |package_id| (package_id, *build_platform))
+                            .collect::<Vec<_>>()
+                    })
+                    .collect();
+                assert_eq!(
+                    actual, expected,
+                    "for fixture {} (include dev: {}), tree nodes match the cargo set",
+                    name, include_dev
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn tree_artifact_deps() {
+        // The build dependency on tool asks for its artifacts on the target, and its library on the
+        // host as usual.
+        let args = [
+            "-p",
+            "consumer",
+            "--resolver-version",
+            "v2",
+            "--target-platform",
+            "wasm32-unknown-unknown",
+            "--host-platform",
+            "x86_64-unknown-linux-gnu",
+        ];
+        assert_eq!(
+            render(JsonFixture::metadata_artifact_deps(), &args),
+            "consumer v0.1.0
+└── tool v0.1.0
+    └── shared v0.1.0
+[build-dependencies]
+├── tool v0.1.0
+│   └── shared v0.1.0
+└── tool v0.1.0 (host)
+    └── shared v0.1.0 (host)
+"
+        );
+
+        let mut inverted = args.to_vec();
+        inverted.extend(["--invert", "shared"]);
+        assert_eq!(
+            render(JsonFixture::metadata_artifact_deps(), &inverted),
+            "shared v0.1.0
+└── tool v0.1.0
+    └── consumer v0.1.0
+    [build-dependencies]
+    └── consumer v0.1.0
+
+shared v0.1.0 (host)
+└── tool v0.1.0 (host)
+    [build-dependencies]
+    └── consumer v0.1.0
+"
+        );
+    }
+
+    #[test]
+    fn tree_proc_macros() {
+        let fixture = JsonFixture::metadata_proc_macro1();
+        assert_eq!(
+            render(
+                fixture,
+                &["-p", "normal-user", "-p", "dev-user", "--include-dev"]
+            ),
+            "dev-user v0.1.0
+[dev-dependencies]
+└── macro v0.1.0 (proc-macro)
+
+normal-user v0.1.0
+└── macro v0.1.0 (proc-macro)
+"
+        );
+        assert_eq!(
+            render(fixture, &["-p", "macro", "--show-features"]),
+            "macro v0.1.0 (proc-macro) (features: [base])\n"
+        );
+    }
+
+    #[test]
+    fn tree_kind_and_features() {
+        let fixture = JsonFixture::metadata1();
+        let full = render(fixture, &[]);
+        let workspace_only = render(fixture, &["--kind", "workspace"]);
+        assert_eq!(workspace_only, "testcrate v0.1.0\n");
+        assert!(
+            full.starts_with("testcrate v0.1.0\n└── datatest"),
+            "full tree includes third-party packages:\n{}",
+            full
+        );
+
+        let features = render(fixture, &["--show-features", "--depth", "0"]);
+        assert_eq!(features, "testcrate v0.1.0 (features: [base])\n");
+        // --features activates features, like in other commands.
+        let opts = parse(&["--features", "foo,bar", "--show-features"]);
+        assert_eq!(opts.features, vec!["foo", "bar"]);
+        assert!(opts.show_features, "--show-features displays features");
+        let opts = parse(&["-F", "foo"]);
+        assert_eq!(opts.features, vec!["foo"]);
+    }
+}
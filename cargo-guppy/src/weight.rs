@@ -0,0 +1,187 @@
+// Copyright (c) The cargo-guppy Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Build weight estimates for `cargo guppy subtree-size --weight`.
+
+use camino::Utf8Path;
+use clap::ArgEnum;
+use guppy::{
+    graph::{cargo::CargoSet, BuildTargetId, PackageMetadata},
+    PackageId,
+};
+use std::{
+    collections::{BTreeSet, HashMap},
+    fs, io,
+};
+
+/// The amount of work a compilation unit costs on top of its source code, in KiB of source.
+///
+/// This accounts for rustc startup, metadata generation and linking, which make many small crates
+/// more expensive to build than one large one.
+const COMPILATION_OVERHEAD: u64 = 16;
+
+/// What to measure packages by.
+#[derive(ArgEnum, Copy, Clone, Debug)]
+pub enum WeightKind {
+    /// Every package weighs 1
+    Packages,
+    /// The number of compilation units: the library once per platform it's built on, plus the
+    /// build script
+    Targets,
+    /// The number of Rust source files on disk
+    Files,
+    /// The size of Rust source files on disk
+    Bytes,
+    /// An estimate of the build cost, combining source size and compilation units
+    Cost,
+}
+
+/// The build weight of a single package.
+#[derive(Clone, Copy, Debug, Default)]
+pub(crate) struct PackageWeight {
+    /// The number of platforms (target and host) the library is built on.
+    builds: u64,
+    /// Whether the package has a build script that is built.
+    build_script: bool,
+    /// The number and size of Rust source files for the library.
+    lib_files: u64,
+    lib_bytes: u64,
+    /// The number and size of Rust source files for the build script.
+    build_script_files: u64,
+    build_script_bytes: u64,
+}
+
+impl PackageWeight {
+    /// Returns the weight of this package as measured by `kind`.
+    pub(crate) fn value(&self, kind: WeightKind) -> u64 {
+        match kind {
+            WeightKind::Packages => 1,
+            WeightKind::Targets => self.targets(),
+            WeightKind::Files => self.lib_files + self.build_script_files,
+            WeightKind::Bytes => self.lib_bytes + self.build_script_bytes,
+            WeightKind::Cost => {
+                // The library is compiled once per platform, while build scripts are always
+                // compiled once, for the host.
+                let mut compiled_bytes = self.builds * self.lib_bytes;
+                if self.build_script {
+                    compiled_bytes += self.build_script_bytes;
+                }
+                compiled_bytes / 1024 + COMPILATION_OVERHEAD * self.targets()
+            }
+        }
+    }
+
+    fn targets(&self) -> u64 {
+        self.builds + u64::from(self.build_script)
+    }
+}
+
+/// Computes and caches the weights of packages built by a `CargoSet`.
+pub(crate) struct WeightModel<'a, 'g> {
+    cargo_set: &'a CargoSet<'g>,
+    cache: HashMap<&'g PackageId, PackageWeight>,
+    missing_sources: BTreeSet<&'g PackageId>,
+}
+
+impl<'a, 'g> WeightModel<'a, 'g> {
+    pub(crate) fn new(cargo_set: &'a CargoSet<'g>) -> Self {
+        Self {
+            cargo_set,
+            cache: HashMap::new(),
+            missing_sources: BTreeSet::new(),
+        }
+    }
+
+    /// Returns the weight of a package.
+    ///
+    /// Packages that aren't built by the `CargoSet`, for example optional dependencies that
+    /// aren't enabled, weigh nothing other than with `WeightKind::Packages`.
+    pub(crate) fn weight(&mut self, package: PackageMetadata<'g>) -> PackageWeight {
+        if let Some(weight) = self.cache.get(package.id()) {
+            return *weight;
+        }
+
+        let builds = [
+            self.cargo_set.target_features(),
+            self.cargo_set.host_features(),
+        ]
+        .iter()
+        .filter(|features| {
+            features
+                .features_for(package.id())
+                .expect("valid package ID")
+                .is_some()
+        })
+        .count() as u64;
+
+        let mut weight = PackageWeight {
+            builds,
+            build_script: builds > 0 && package.has_build_script(),
+            ..PackageWeight::default()
+        };
+        if builds > 0 {
+            let root = package
+                .manifest_path()
+                .parent()
+                .expect("manifest path has a parent");
+            for target in package.build_targets() {
+                let (files, bytes) = match target.id() {
+                    BuildTargetId::Library => (&mut weight.lib_files, &mut weight.lib_bytes),
+                    BuildTargetId::BuildScript => (
+                        &mut weight.build_script_files,
+                        &mut weight.build_script_bytes,
+                    ),
+                    // Other targets aren't built when this package is a dependency.
+                    _ => continue,
+                };
+                match source_size(root, target.path()) {
+                    Ok((target_files, target_bytes)) => {
+                        *files += target_files;
+                        *bytes += target_bytes;
+                    }
+                    Err(_) => {
+                        self.missing_sources.insert(package.id());
+                    }
+                }
+            }
+        }
+
+        self.cache.insert(package.id(), weight);
+        weight
+    }
+
+    /// Returns the packages whose sources couldn't be read, for example because they haven't been
+    /// downloaded yet.
+    pub(crate) fn missing_sources(&self) -> &BTreeSet<&'g PackageId> {
+        &self.missing_sources
+    }
+}
+
+/// Returns the number of Rust source files, and their total size, for a build target.
+///
+/// All `.rs` files in the directory of the target's entry point are counted, except if that's the
+/// package root (as with `build.rs`), in which case only the entry point is counted.
+fn source_size(root: &Utf8Path, path: &Utf8Path) -> io::Result<(u64, u64)> {
+    match path.parent() {
+        Some(dir) if dir != root => dir_source_size(dir.as_std_path()),
+        _ => Ok((1, fs::metadata(path)?.len())),
+    }
+}
+
+fn dir_source_size(dir: &std::path::Path) -> io::Result<(u64, u64)> {
+    let (mut files, mut bytes) = (0, 0);
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let path = entry.path();
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            let (dir_files, dir_bytes) = dir_source_size(&path)?;
+            files += dir_files;
+            bytes += dir_bytes;
+        } else if path.extension().map_or(false, |ext| ext == "rs") {
+            files += 1;
+            bytes += entry.metadata()?.len();
+        }
+    }
+    Ok((files, bytes))
+}
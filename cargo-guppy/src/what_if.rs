@@ -0,0 +1,243 @@
+// Copyright (c) The cargo-guppy Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Implementation for `cargo guppy what-if`.
+
+use crate::tree::EdgeKind;
+use clap::Parser;
+use color_eyre::eyre::{bail, eyre, Result, WrapErr};
+use guppy::{
+    graph::{
+        cargo::CargoOptions, feature::StandardFeatures, PackageGraph, PackageGraphEditor,
+        PackageLink,
+    },
+    DependencyKind, Error,
+};
+use guppy_cmdlib::{string_to_platform_spec, CargoMetadataOptions, CargoResolverOpts};
+
+#[derive(Debug, Parser)]
+pub struct WhatIfOptions {
+    /// Enable a feature on a dependency, e.g. `my-crate:serde/rc`
+    #[clap(long = "enable-feature", value_name = "PACKAGE:DEP/FEATURE")]
+    enable_features: Vec<String>,
+
+    /// Turn off default features of a dependency, e.g. `my-crate:tokio`
+    #[clap(long = "disable-default-features", value_name = "PACKAGE:DEP")]
+    disable_default_features: Vec<String>,
+
+    /// Turn on default features of a dependency
+    #[clap(long = "enable-default-features", value_name = "PACKAGE:DEP")]
+    enable_default_features: Vec<String>,
+
+    /// Remove a dependency
+    #[clap(long = "remove-dep", value_name = "PACKAGE:DEP")]
+    remove_deps: Vec<String>,
+
+    /// Kinds of dependencies that feature edits apply to (default: all kinds present)
+    #[clap(long, arg_enum, use_value_delimiter = true)]
+    kinds: Vec<EdgeKind>,
+
+    /// Build workspace members with all features activated
+    #[clap(long = "all-features")]
+    all_features: bool,
+
+    /// Build workspace members without the `default` feature
+    #[clap(long = "no-default-features", conflicts_with = "all-features")]
+    no_default_features: bool,
+
+    #[clap(flatten)]
+    resolver_opts: CargoResolverOpts,
+
+    #[clap(long = "target-platform")]
+    /// Evaluate against target platform, "current" or "any" (default: any)
+    target_platform: Option<String>,
+
+    #[clap(long = "host-platform")]
+    /// Evaluate against host platform, "current" or "any" (default: any)
+    host_platform: Option<String>,
+
+    #[clap(flatten)]
+    metadata_opts: CargoMetadataOptions,
+}
+
+impl WhatIfOptions {
+    pub fn exec(&self) -> Result<()> {
+        let pkg_graph = self.metadata_opts.build_graph()?;
+        let edits = self.parse_edits(&pkg_graph)?;
+        if edits.is_empty() {
+            bail!("no edits specified, see --help for the available edits");
+        }
+
+        let mut cargo_opts = CargoOptions::new();
+        cargo_opts
+            .set_include_dev(self.resolver_opts.include_dev)
+            .set_resolver(self.resolver_opts.resolver_version.to_guppy())
+            .set_initials_platform(self.resolver_opts.initials_platform.to_guppy())
+            .set_target_platform(string_to_platform_spec(self.target_platform.as_deref())?)
+            .set_host_platform(string_to_platform_spec(self.host_platform.as_deref())?);
+        let features = match (self.all_features, self.no_default_features) {
+            (true, _) => StandardFeatures::All,
+            (false, false) => StandardFeatures::Default,
+            (false, true) => StandardFeatures::None,
+        };
+
+        let report = pkg_graph
+            .what_if(features, &cargo_opts, |editor| {
+                edits.iter().try_for_each(|edit| edit.apply(editor))
+            })
+            .wrap_err("applying edits failed")?;
+
+        if !report.is_changed() {
+            println!("no workspace members would change");
+            return Ok(());
+        }
+        for member in report.changed_members() {
+            println!("{} ({}):", member.name(), member.workspace_path());
+            // The report ends with a newline.
+            print!("{}", member.diff().report());
+        }
+        Ok(())
+    }
+
+    /// Parses the edits specified on the command line, in the order they're applied.
+    fn parse_edits<'g>(&self, pkg_graph: &'g PackageGraph) -> Result<Vec<Edit<'g>>> {
+        let mut edits = vec![];
+        for spec in &self.enable_features {
+            let (link_spec, feature) = spec.rsplit_once('/').ok_or_else(|| {
+                eyre!(
+                    "invalid --enable-feature '{}': expected PACKAGE:DEP/FEATURE",
+                    spec
+                )
+            })?;
+            let link = find_link(pkg_graph, link_spec)?;
+            edits.push(Edit::EnableFeature {
+                link,
+                kinds: self.kinds_for(link)?,
+                feature: feature.to_owned(),
+            });
+        }
+        for (specs, enabled) in [
+            (&self.disable_default_features, false),
+            (&self.enable_default_features, true),
+        ] {
+            for spec in specs {
+                let link = find_link(pkg_graph, spec)?;
+                edits.push(Edit::DefaultFeatures {
+                    link,
+                    kinds: self.kinds_for(link)?,
+                    enabled,
+                });
+            }
+        }
+        // Removals go last so that other edits can refer to the dependencies being removed.
+        for spec in &self.remove_deps {
+            edits.push(Edit::RemoveDep(find_link(pkg_graph, spec)?));
+        }
+        Ok(edits)
+    }
+
+    /// Returns the kinds of this dependency that feature edits apply to.
+    fn kinds_for(&self, link: PackageLink<'_>) -> Result<Vec<DependencyKind>> {
+        let kinds: Vec<_> = DependencyKind::VALUES
+            .iter()
+            .copied()
+            .filter(|kind| {
+                link.req_for_kind(*kind).is_present()
+                    && (self.kinds.is_empty()
+                        || self
+                            .kinds
+                            .iter()
+                            .any(|edge_kind| edge_kind.to_guppy() == *kind))
+            })
+            .collect();
+        if kinds.is_empty() {
+            bail!(
+                "{} doesn't depend on '{}' as any of the selected kinds",
+                link.from().name(),
+                link.dep_name()
+            );
+        }
+        Ok(kinds)
+    }
+}
+
+/// A hypothetical edit to a dependency.
+#[derive(Debug)]
+enum Edit<'g> {
+    EnableFeature {
+        link: PackageLink<'g>,
+        kinds: Vec<DependencyKind>,
+        feature: String,
+    },
+    DefaultFeatures {
+        link: PackageLink<'g>,
+        kinds: Vec<DependencyKind>,
+        enabled: bool,
+    },
+    RemoveDep(PackageLink<'g>),
+}
+
+impl<'g> Edit<'g> {
+    fn apply(&self, editor: &mut PackageGraphEditor<'_>) -> Result<(), Error> {
+        match self {
+            Edit::EnableFeature {
+                link,
+                kinds,
+                feature,
+            } => kinds.iter().try_for_each(|kind| {
+                editor.enable_dependency_features(
+                    link.from().id(),
+                    link.to().id(),
+                    *kind,
+                    [feature.as_str()],
+                )
+            }),
+            Edit::DefaultFeatures {
+                link,
+                kinds,
+                enabled,
+            } => kinds.iter().try_for_each(|kind| {
+                editor.set_default_features(link.from().id(), link.to().id(), *kind, *enabled)
+            }),
+            Edit::RemoveDep(link) => editor.remove_dependency(link.from().id(), link.to().id()),
+        }
+    }
+}
+
+/// Finds the dependency specified as `PACKAGE:DEP`, where `PACKAGE` is `name` or `name@version`
+/// and `DEP` is the name of the dependency in `PACKAGE`'s `Cargo.toml`.
+fn find_link<'g>(pkg_graph: &'g PackageGraph, spec: &str) -> Result<PackageLink<'g>> {
+    let (package_spec, dep_name) = spec
+        .split_once(':')
+        .ok_or_else(|| eyre!("invalid dependency '{}': expected PACKAGE:DEP", spec))?;
+    let (name, version) = match package_spec.split_once('@') {
+        Some((name, version)) => (name, Some(version)),
+        None => (package_spec, None),
+    };
+
+    let mut matches = pkg_graph.packages().filter(|package| {
+        package.name() == name
+            && version.map_or(true, |version| package.version().to_string() == version)
+    });
+    let package = match (matches.next(), matches.next()) {
+        (Some(package), None) => package,
+        (None, _) => bail!("no packages found matching '{}'", package_spec),
+        (Some(_), Some(_)) => bail!(
+            "multiple packages found matching '{}', specify a version with {}@<version>",
+            package_spec,
+            name
+        ),
+    };
+
+    package
+        .direct_links()
+        .find(|link| link.dep_name() == dep_name)
+        .ok_or_else(|| {
+            eyre!(
+                "{} v{} doesn't have a dependency named '{}'",
+                package.name(),
+                package.version(),
+                dep_name
+            )
+        })
+}
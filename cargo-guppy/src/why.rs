@@ -0,0 +1,358 @@
+// Copyright (c) The cargo-guppy Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Implementation for `cargo guppy why`.
+
+use crate::FilterOptions;
+use clap::Parser;
+use color_eyre::eyre::{bail, Result};
+use guppy::{
+    graph::{feature::FeatureGraph, DependencyReq, PackageGraph, PackageLink, PackageMetadata},
+    platform::PlatformStatus,
+    DependencyKind,
+};
+use guppy_cmdlib::CargoMetadataOptions;
+use serde::Serialize;
+use std::{borrow::Cow, fmt::Write};
+
+#[derive(Debug, Parser)]
+pub struct WhyOptions {
+    /// The package to explain, specified as `name` or `name@version`
+    #[clap(name = "PACKAGE")]
+    package: String,
+
+    /// Only show the shortest path from the workspace to the package
+    #[clap(long)]
+    shortest: bool,
+
+    /// The maximum number of paths to show for each matching package
+    #[clap(long, default_value = "64")]
+    limit: usize,
+
+    /// Output paths in JSON format
+    #[clap(long)]
+    json: bool,
+
+    #[clap(flatten)]
+    filter_opts: FilterOptions,
+
+    #[clap(flatten)]
+    metadata_opts: CargoMetadataOptions,
+}
+
+impl WhyOptions {
+    pub fn exec(&self) -> Result<()> {
+        let pkg_graph = self.metadata_opts.build_graph()?;
+        let feature_graph = pkg_graph.feature_graph();
+
+        let targets = self.matching_packages(&pkg_graph)?;
+        let resolver = self.filter_opts.make_resolver(&pkg_graph)?;
+        let query = pkg_graph.query_workspace();
+
+        let mut outputs = Vec::with_capacity(targets.len());
+        for target in targets {
+            let paths = if self.shortest {
+                query
+                    .shortest_path_to_with_fn(target.id(), &resolver)?
+                    .into_iter()
+                    .collect()
+            } else {
+                query.all_paths_to_with_fn(target.id(), Some(self.limit), &resolver)?
+            };
+            outputs.push((target, paths));
+        }
+
+        if self.json {
+            let json: Vec<_> = outputs
+                .iter()
+                .map(|(target, paths)| WhyJson::new(feature_graph, *target, paths))
+                .collect();
+            println!("{}", serde_json::to_string_pretty(&json)?);
+            return Ok(());
+        }
+
+        for (idx, (target, paths)) in outputs.iter().enumerate() {
+            if idx > 0 {
+                println!();
+            }
+            print!("{}", render_tree(feature_graph, *target, paths));
+            if !self.shortest && paths.len() >= self.limit {
+                eprintln!(
+                    "note: only showing the first {} paths to {} v{}, use --limit to show more",
+                    self.limit,
+                    target.name(),
+                    target.version()
+                );
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Returns the packages matching `name` or `name@version`.
+    fn matching_packages<'g>(
+        &self,
+        pkg_graph: &'g PackageGraph,
+    ) -> Result<Vec<PackageMetadata<'g>>> {
+        let (name, version) = match self.package.split_once('@') {
+            Some((name, version)) => (name, Some(version)),
+            None => (self.package.as_str(), None),
+        };
+
+        let mut matches: Vec<_> = pkg_graph
+            .packages()
+            .filter(|package| {
+                package.name() == name
+                    && version.map_or(true, |version| package.version().to_string() == version)
+            })
+            .collect();
+        if matches.is_empty() {
+            bail!("no packages found matching '{}'", self.package);
+        }
+        matches.sort_by(|a, b| a.version().cmp(b.version()));
+        Ok(matches)
+    }
+}
+
+// ---
+// Text output
+// ---
+
+/// Renders paths as an inverted tree rooted at the target package.
+fn render_tree(
+    feature_graph: FeatureGraph<'_>,
+    target: PackageMetadata<'_>,
+    paths: &[Vec<PackageLink<'_>>],
+) -> String {
+    let mut out = format!("{} v{}", target.name(), target.version());
+    if paths.is_empty() {
+        out.push_str(" (not reachable from the workspace)\n");
+        return out;
+    }
+    out.push('\n');
+
+    // Paths go from the workspace to the target: walk them backwards.
+    let tails: Vec<_> = paths
+        .iter()
+        .filter(|path| !path.is_empty())
+        .map(|path| path.iter().rev().copied().collect::<Vec<_>>())
+        .collect();
+    render_children(feature_graph, &tails, "", &mut out);
+    out
+}
+
+fn render_children(
+    feature_graph: FeatureGraph<'_>,
+    tails: &[Vec<PackageLink<'_>>],
+    prefix: &str,
+    out: &mut String,
+) {
+    // Group tails by their first link, preserving the order in which they were seen (shorter
+    // paths come first).
+    let mut groups: Vec<(PackageLink<'_>, Vec<Vec<PackageLink<'_>>>)> = vec![];
+    for tail in tails {
+        let (first, rest) = tail.split_first().expect("tails are non-empty");
+        let rest = rest.to_vec();
+        match groups
+            .iter_mut()
+            .find(|(link, _)| link.from().id() == first.from().id())
+        {
+            Some((_, rests)) => rests.push(rest),
+            None => groups.push((*first, vec![rest])),
+        }
+    }
+
+    let group_count = groups.len();
+    for (idx, (link, rests)) in groups.into_iter().enumerate() {
+        let last = idx + 1 == group_count;
+        let from = link.from();
+        writeln!(
+            out,
+            "{}{} {} v{} ({})",
+            prefix,
+            if last { "└──" } else { "├──" },
+            from.name(),
+            from.version(),
+            describe_link(feature_graph, link),
+        )
+        .expect("writing to a string is infallible");
+
+        let rests: Vec<_> = rests.into_iter().filter(|rest| !rest.is_empty()).collect();
+        let child_prefix = format!("{}{}", prefix, if last { "    " } else { "│   " });
+        render_children(feature_graph, &rests, &child_prefix, out);
+    }
+}
+
+/// Describes the kinds, platforms and features through which `link` is followed.
+fn describe_link(feature_graph: FeatureGraph<'_>, link: PackageLink<'_>) -> String {
+    let mut parts: Vec<String> = DependencyKind::VALUES
+        .iter()
+        .filter_map(|kind| describe_req(*kind, link.req_for_kind(*kind)))
+        .collect();
+
+    let features: Vec<_> = feature_graph
+        .conditional_links_for(link)
+        .map(|conditional_link| {
+            format!(
+                "{} -> {}",
+                conditional_link.from().label(),
+                conditional_link.to().label()
+            )
+        })
+        .collect();
+    if !features.is_empty() {
+        parts.push(format!("features: {}", features.join(", ")));
+    }
+
+    parts.join("; ")
+}
+
+fn describe_req(kind: DependencyKind, req: DependencyReq<'_>) -> Option<String> {
+    if !req.is_present() {
+        return None;
+    }
+    let status = req.status();
+    let kind = kind_str(kind);
+
+    let mut descs = vec![];
+    let required = status.required_status();
+    if let Some(platforms) = describe_platforms(required) {
+        descs.push(format!("{}{}", kind, platforms));
+    }
+    let optional = status.optional_status();
+    if !required.is_always() {
+        if let Some(platforms) = describe_platforms(optional) {
+            descs.push(format!("optional {}{}", kind, platforms));
+        }
+    }
+    Some(descs.join(", "))
+}
+
+fn describe_platforms(status: PlatformStatus<'_>) -> Option<String> {
+    match status {
+        PlatformStatus::Never => None,
+        PlatformStatus::Always => Some(String::new()),
+        PlatformStatus::PlatformDependent { eval } => Some(format!(
+            " on {}",
+            eval.target_spec_strs().collect::<Vec<_>>().join(" or ")
+        )),
+    }
+}
+
+fn kind_str(kind: DependencyKind) -> &'static str {
+    match kind {
+        DependencyKind::Normal => "normal",
+        DependencyKind::Build => "build",
+        DependencyKind::Development => "dev",
+    }
+}
+
+// ---
+// JSON output
+// ---
+
+#[derive(Serialize)]
+struct WhyJson<'g> {
+    id: String,
+    name: &'g str,
+    version: String,
+    paths: Vec<Vec<HopJson<'g>>>,
+}
+
+impl<'g> WhyJson<'g> {
+    fn new(
+        feature_graph: FeatureGraph<'g>,
+        target: PackageMetadata<'g>,
+        paths: &[Vec<PackageLink<'g>>],
+    ) -> Self {
+        Self {
+            id: target.id().to_string(),
+            name: target.name(),
+            version: target.version().to_string(),
+            paths: paths
+                .iter()
+                .map(|path| {
+                    path.iter()
+                        .map(|link| HopJson::new(feature_graph, *link))
+                        .collect()
+                })
+                .collect(),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct HopJson<'g> {
+    from: String,
+    to: String,
+    dep_name: &'g str,
+    kinds: Vec<KindJson<'g>>,
+    features: Vec<FeatureEdgeJson>,
+}
+
+impl<'g> HopJson<'g> {
+    fn new(feature_graph: FeatureGraph<'g>, link: PackageLink<'g>) -> Self {
+        let kinds = DependencyKind::VALUES
+            .iter()
+            .filter_map(|kind| {
+                let req = link.req_for_kind(*kind);
+                req.is_present().then(|| {
+                    let status = req.status();
+                    KindJson {
+                        kind: kind_str(*kind),
+                        required: PlatformJson::new(status.required_status()),
+                        optional: PlatformJson::new(status.optional_status()),
+                    }
+                })
+            })
+            .collect();
+        let features = feature_graph
+            .conditional_links_for(link)
+            .map(|conditional_link| FeatureEdgeJson {
+                from: conditional_link.from().label().to_string(),
+                to: conditional_link.to().label().to_string(),
+            })
+            .collect();
+
+        Self {
+            from: link.from().id().to_string(),
+            to: link.to().id().to_string(),
+            dep_name: link.dep_name(),
+            kinds,
+            features,
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct KindJson<'g> {
+    kind: &'static str,
+    required: PlatformJson<'g>,
+    optional: PlatformJson<'g>,
+}
+
+/// Either `"always"`, `"never"`, or a list of platform specifications.
+#[derive(Serialize)]
+#[serde(untagged)]
+enum PlatformJson<'g> {
+    Simple(&'static str),
+    Specs(Vec<Cow<'g, str>>),
+}
+
+impl<'g> PlatformJson<'g> {
+    fn new(status: PlatformStatus<'g>) -> Self {
+        match status {
+            PlatformStatus::Never => PlatformJson::Simple("never"),
+            PlatformStatus::Always => PlatformJson::Simple("always"),
+            PlatformStatus::PlatformDependent { eval } => {
+                PlatformJson::Specs(eval.target_spec_strs().collect())
+            }
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct FeatureEdgeJson {
+    from: String,
+    to: String,
+}
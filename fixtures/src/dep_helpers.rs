@@ -843,6 +843,157 @@ pub fn assert_link_order<'g>(
     }
 }
 
+/// Assert that two package graphs have the same packages, links and features.
+///
+/// Links are compared regardless of order. Used to check graphs produced in different ways, for
+/// example from `cargo metadata` and from a cache.
+pub fn assert_graphs_equal(expected: &PackageGraph, actual: &PackageGraph, msg: &str) {
+    fn sorted_links(package: PackageMetadata<'_>) -> Vec<PackageLink<'_>> {
+        let mut links: Vec<_> = package
+            .direct_links_directed(DependencyDirection::Forward)
+            .collect();
+        links.sort_by_key(|link| link.to().id());
+        links
+    }
+
+    let sorted_ids = |graph: &PackageGraph| {
+        let mut ids: Vec<_> = graph.package_ids().cloned().collect();
+        ids.sort();
+        ids
+    };
+    assert_eq!(
+        sorted_ids(expected),
+        sorted_ids(actual),
+        "{}: package IDs match",
+        msg
+    );
+    assert_eq!(
+        expected.link_count(),
+        actual.link_count(),
+        "{}: link counts match",
+        msg
+    );
+    assert_eq!(
+        expected.workspace().root(),
+        actual.workspace().root(),
+        "{}: workspace roots match",
+        msg
+    );
+    assert_eq!(
+        expected
+            .workspace()
+            .iter_by_path()
+            .map(|(path, package)| (path, package.id()))
+            .collect::<Vec<_>>(),
+        actual
+            .workspace()
+            .iter_by_path()
+            .map(|(path, package)| (path, package.id()))
+            .collect::<Vec<_>>(),
+        "{}: workspace members match",
+        msg
+    );
+
+    for expected_package in expected.packages() {
+        let id = expected_package.id();
+        let actual_package = actual.metadata(id).expect("package exists in actual graph");
+        let msg = format!("{}: for package {}", msg, id);
+
+        assert_eq!(
+            expected_package.version(),
+            actual_package.version(),
+            "{}",
+            msg
+        );
+        assert_eq!(
+            expected_package.source(),
+            actual_package.source(),
+            "{}",
+            msg
+        );
+        assert_eq!(
+            expected_package.edition(),
+            actual_package.edition(),
+            "{}",
+            msg
+        );
+        assert_eq!(
+            expected_package.metadata_table(),
+            actual_package.metadata_table(),
+            "{}",
+            msg
+        );
+        assert_eq!(
+            expected_package
+                .build_targets()
+                .map(|target| (target.id(), target.kind(), target.path()))
+                .collect::<Vec<_>>(),
+            actual_package
+                .build_targets()
+                .map(|target| (target.id(), target.kind(), target.path()))
+                .collect::<Vec<_>>(),
+            "{}: build targets match",
+            msg
+        );
+        let sorted_features = |package: PackageMetadata<'_>| {
+            let mut features: Vec<_> = package.named_features().map(|f| f.to_owned()).collect();
+            features.sort();
+            features
+        };
+        assert_eq!(
+            sorted_features(expected_package),
+            sorted_features(actual_package),
+            "{}: named features match",
+            msg
+        );
+
+        let expected_links = sorted_links(expected_package);
+        let actual_links = sorted_links(actual_package);
+        assert_eq!(
+            expected_links.len(),
+            actual_links.len(),
+            "{}: link counts match",
+            msg
+        );
+        for (expected_link, actual_link) in expected_links.into_iter().zip(actual_links) {
+            assert_eq!(expected_link.to().id(), actual_link.to().id(), "{}", msg);
+            assert_eq!(expected_link.dep_name(), actual_link.dep_name(), "{}", msg);
+            assert_eq!(
+                expected_link.version_req(),
+                actual_link.version_req(),
+                "{}",
+                msg
+            );
+            for kind in DependencyKind::VALUES {
+                assert_eq!(
+                    format!("{:?}", expected_link.req_for_kind(*kind).status()),
+                    format!("{:?}", actual_link.req_for_kind(*kind).status()),
+                    "{}: {} -> {} ({:?}) statuses match",
+                    msg,
+                    expected_link.from().name(),
+                    expected_link.to().name(),
+                    kind,
+                );
+            }
+        }
+    }
+
+    let expected_features = expected.feature_graph();
+    let actual_features = actual.feature_graph();
+    assert_eq!(
+        expected_features.feature_count(),
+        actual_features.feature_count(),
+        "{}: feature counts match",
+        msg
+    );
+    assert_eq!(
+        expected_features.link_count(),
+        actual_features.link_count(),
+        "{}: feature link counts match",
+        msg
+    );
+}
+
 fn dep_link_ptrs<'g>(dep_links: impl IntoIterator<Item = PackageLink<'g>>) -> Vec<PackageLinkPtrs> {
     let mut triples: Vec<_> = dep_links
         .into_iter()
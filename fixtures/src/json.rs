@@ -112,6 +112,14 @@ pub static METADATA_WEAK_NAMESPACED_ARRAYVEC: &str =
 pub static METADATA_WEAK_NAMESPACED_TINYVEC: &str =
     "tinyvec 1.5.1 (registry+https://github.com/rust-lang/crates.io-index)";
 
+pub static METADATA_ARTIFACT_DEPS_PATH: &str = "../small/metadata_artifact_deps.json";
+pub static METADATA_ARTIFACT_DEPS_CONSUMER: &str =
+    "path+file:///Users/fakeuser/local/testcrates/artifact-deps/consumer#0.1.0";
+pub static METADATA_ARTIFACT_DEPS_TOOL: &str =
+    "path+file:///Users/fakeuser/local/testcrates/artifact-deps/tool#0.1.0";
+pub static METADATA_ARTIFACT_DEPS_SHARED: &str =
+    "path+file:///Users/fakeuser/local/testcrates/artifact-deps/shared#0.1.0";
+
 pub static METADATA_LIBRA_PATH: &str = "../large/metadata_libra.json";
 pub static METADATA_LIBRA_ADMISSION_CONTROL_SERVICE: &str =
     "admission-control-service 0.1.0 (path+file:///Users/fakeuser/local/libra/admission_control/admission-control-service)";
@@ -213,6 +221,7 @@ define_fixtures! {
     metadata_proc_macro1 => METADATA_PROC_MACRO1_PATH,
     metadata_alternate_registries => METADATA_ALTERNATE_REGISTRIES_PATH,
     metadata_weak_namespaced_features => METADATA_WEAK_NAMESPACED_FEATURES_PATH,
+    metadata_artifact_deps => METADATA_ARTIFACT_DEPS_PATH,
     metadata_libra => METADATA_LIBRA_PATH,
     metadata_libra_f0091a4 => METADATA_LIBRA_F0091A4_PATH,
     metadata_libra_9ffd93b => METADATA_LIBRA_9FFD93B_PATH,
@@ -1159,6 +1168,11 @@ impl FixtureDetails {
         Self::new(details)
     }
 
+    pub(crate) fn metadata_artifact_deps() -> Self {
+        let details = HashMap::new();
+        Self::new(details)
+    }
+
     pub(crate) fn metadata_libra() -> Self {
         let mut details = HashMap::new();
 
@@ -0,0 +1 @@
+// Included as inline::nested.
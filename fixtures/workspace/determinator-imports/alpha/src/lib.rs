@@ -0,0 +1,8 @@
+mod foo;
+#[path = "other/bar_impl.rs"]
+mod bar;
+mod inline {
+    mod nested;
+}
+
+pub static DATA: &str = include_str!("../data/alpha.txt");
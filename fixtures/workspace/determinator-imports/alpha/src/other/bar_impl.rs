@@ -0,0 +1 @@
+// Included as bar.
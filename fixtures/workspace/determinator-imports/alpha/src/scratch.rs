@@ -0,0 +1 @@
+// Not part of any target.
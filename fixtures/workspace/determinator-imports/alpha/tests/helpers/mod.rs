@@ -0,0 +1 @@
+// Included by the integration test.
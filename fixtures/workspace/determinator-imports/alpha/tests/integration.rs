@@ -0,0 +1,3 @@
+mod helpers;
+
+static INPUT: &str = include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/data/input.txt"));
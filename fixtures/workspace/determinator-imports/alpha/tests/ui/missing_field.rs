@@ -0,0 +1,8 @@
+// Compiled at runtime by a UI test, not part of any target.
+struct Foo {
+    a: u32,
+}
+
+fn main() {
+    let _ = Foo {};
+}
@@ -0,0 +1 @@
+pub static CONFIG: &str = include_str!("../../shared/config.txt");
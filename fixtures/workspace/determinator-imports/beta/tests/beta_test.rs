@@ -0,0 +1 @@
+static ALPHA_INPUT: &[u8] = include_bytes!("../../alpha/tests/data/input.txt");
@@ -0,0 +1,7 @@
+macro_rules! data_path {
+    () => {
+        "../data.txt"
+    };
+}
+
+pub static DATA: &str = include_str!(data_path!());
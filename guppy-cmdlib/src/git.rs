@@ -0,0 +1,180 @@
+// Copyright (c) The cargo-guppy Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Helpers for looking at other revisions of a Git repository.
+
+use camino::{Utf8Path, Utf8PathBuf};
+use color_eyre::eyre::{bail, Result, WrapErr};
+use std::{convert::TryFrom, process::Command};
+
+/// Returns the root of the Git repository containing `dir`.
+pub fn repo_root(dir: &Utf8Path) -> Result<Utf8PathBuf> {
+    Ok(Utf8PathBuf::from(git(
+        dir,
+        &["rev-parse", "--show-toplevel"],
+    )?))
+}
+
+/// Runs a git command in the given directory, returning its trimmed standard output.
+pub fn git(dir: &Utf8Path, args: &[&str]) -> Result<String> {
+    Ok(String::from_utf8(git_bytes(dir, args)?)?.trim().to_owned())
+}
+
+/// Runs a git command in the given directory, returning its standard output as is.
+///
+/// This is useful for commands that produce null-separated output with `-z`.
+pub fn git_bytes(dir: &Utf8Path, args: &[&str]) -> Result<Vec<u8>> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(args)
+        .output()
+        .wrap_err("running git failed")?;
+    if !output.status.success() {
+        bail!(
+            "`git {}` failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(output.stdout)
+}
+
+/// A temporary Git worktree with a revision checked out, removed on drop.
+#[derive(Debug)]
+pub struct Worktree {
+    repo_root: Utf8PathBuf,
+    dir: Utf8PathBuf,
+}
+
+impl Worktree {
+    /// Checks out `rev` into a new temporary worktree of the repository at `repo_root`.
+    pub fn add(repo_root: &Utf8Path, rev: &str) -> Result<Self> {
+        let commit = git(
+            repo_root,
+            &["rev-parse", "--verify", &format!("{}^{{commit}}", rev)],
+        )
+        .wrap_err_with(|| format!("revision {} not found", rev))?;
+
+        let dir =
+            std::env::temp_dir().join(format!("guppy-worktree-{}-{}", std::process::id(), commit));
+        let dir = Utf8PathBuf::try_from(dir)?;
+        git(
+            repo_root,
+            &[
+                "worktree",
+                "add",
+                "--detach",
+                "--quiet",
+                dir.as_str(),
+                &commit,
+            ],
+        )
+        .wrap_err_with(|| format!("checking out revision {} failed", rev))?;
+
+        Ok(Self {
+            repo_root: repo_root.to_path_buf(),
+            dir,
+        })
+    }
+
+    /// Returns the directory the worktree is checked out in.
+    pub fn dir(&self) -> &Utf8Path {
+        &self.dir
+    }
+}
+
+impl Drop for Worktree {
+    fn drop(&mut self) {
+        if let Err(err) = git(
+            &self.repo_root,
+            &["worktree", "remove", "--force", self.dir.as_str()],
+        ) {
+            eprintln!(
+                "warning: failed to remove temporary worktree {}: {}",
+                self.dir, err
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn worktree_add_and_drop() {
+        let temp_dir = tempfile::tempdir().expect("created temp dir");
+        let repo_root = Utf8Path::from_path(temp_dir.path()).expect("temp dir is valid UTF-8");
+        make_repo(repo_root);
+
+        let worktree_dir = {
+            let worktree = Worktree::add(repo_root, "HEAD~1").expect("worktree added");
+            let worktree_dir = worktree.dir().to_path_buf();
+            assert_eq!(
+                fs::read_to_string(worktree_dir.join("file.txt")).expect("file checked out"),
+                "old\n",
+                "worktree has the old revision checked out"
+            );
+            assert!(
+                git(repo_root, &["worktree", "list"])
+                    .expect("worktrees listed")
+                    .contains(worktree_dir.as_str()),
+                "worktree is registered with the repository"
+            );
+            worktree_dir
+        };
+
+        assert!(!worktree_dir.exists(), "worktree directory removed on drop");
+        assert!(
+            !git(repo_root, &["worktree", "list"])
+                .expect("worktrees listed")
+                .contains(worktree_dir.as_str()),
+            "worktree is unregistered on drop"
+        );
+        assert_eq!(
+            fs::read_to_string(repo_root.join("file.txt")).expect("file exists"),
+            "new\n",
+            "main checkout is untouched"
+        );
+    }
+
+    #[test]
+    fn worktree_missing_rev() {
+        let temp_dir = tempfile::tempdir().expect("created temp dir");
+        let repo_root = Utf8Path::from_path(temp_dir.path()).expect("temp dir is valid UTF-8");
+        make_repo(repo_root);
+
+        let err = Worktree::add(repo_root, "does-not-exist").expect_err("revision is missing");
+        assert!(
+            err.to_string()
+                .contains("revision does-not-exist not found"),
+            "unexpected error: {}",
+            err
+        );
+    }
+
+    /// Creates a repository with two commits to `file.txt`.
+    fn make_repo(dir: &Utf8Path) {
+        git(dir, &["init", "--quiet"]).expect("git init succeeded");
+        for contents in ["old\n", "new\n"] {
+            fs::write(dir.join("file.txt"), contents).expect("file written");
+            git(dir, &["add", "file.txt"]).expect("git add succeeded");
+            git(
+                dir,
+                &[
+                    "-c",
+                    "user.name=guppy",
+                    "-c",
+                    "user.email=guppy@example.com",
+                    "commit",
+                    "--quiet",
+                    "--message",
+                    contents.trim(),
+                ],
+            )
+            .expect("git commit succeeded");
+        }
+    }
+}
@@ -5,11 +5,15 @@
 //!
 //! This library allows translating command-line arguments into guppy's data structures.
 
+pub mod git;
+pub mod package_expr;
 #[cfg(feature = "proptest1")]
 pub mod proptest;
 
+use crate::{git::Worktree, package_expr::PackageExpr};
+use camino::Utf8PathBuf;
 use clap::{ArgEnum, Parser};
-use color_eyre::eyre::Result;
+use color_eyre::eyre::{eyre, Result, WrapErr};
 use guppy::{
     graph::{
         cargo::{CargoResolverVersion, InitialsPlatform},
@@ -17,9 +21,9 @@ use guppy::{
         PackageGraph,
     },
     platform::{Platform, PlatformSpec, TargetFeatures},
-    MetadataCommand,
+    MetadataCommand, OfflineMetadataCommand,
 };
-use std::{env, path::PathBuf};
+use std::{convert::TryFrom, env, path::PathBuf};
 
 /// Support for packages and features.
 ///
@@ -30,6 +34,10 @@ pub struct PackagesAndFeatures {
     /// Packages to start the query from (default: entire workspace)
     pub packages: Vec<String>,
 
+    #[clap(long = "expr", conflicts_with = "packages")]
+    /// Package expression to start the query from, e.g. "workspace() - rdeps(foo)"
+    pub expr: Option<String>,
+
     #[clap(long = "features-only")]
     /// Packages that take part in feature unification but aren't in the result set (default: none)
     pub features_only: Vec<String>,
@@ -55,7 +63,9 @@ impl PackagesAndFeatures {
         &self,
         graph: &'g PackageGraph,
     ) -> Result<(FeatureSet<'g>, FeatureSet<'g>)> {
-        let package_set = if self.packages.is_empty() {
+        let package_set = if let Some(expr) = &self.expr {
+            PackageExpr::parse(expr)?.eval(graph)?
+        } else if self.packages.is_empty() {
             graph.resolve_workspace()
         } else {
             graph.resolve_workspace_names(self.packages.iter())?
@@ -147,6 +157,17 @@ pub struct CargoMetadataOptions {
     /// Path to Cargo.toml
     #[clap(long)]
     pub manifest_path: Option<PathBuf>,
+
+    /// Path to a cache of the package graph, reused while Cargo.lock and Cargo.toml files are
+    /// unchanged
+    #[clap(long)]
+    pub graph_cache: Option<Utf8PathBuf>,
+
+    /// Read Cargo.toml and Cargo.lock files directly instead of running `cargo metadata`
+    ///
+    /// Third-party manifests are looked up in vendor directories and Cargo's local caches.
+    #[clap(long)]
+    pub offline_metadata: bool,
 }
 
 impl CargoMetadataOptions {
@@ -173,6 +194,90 @@ impl CargoMetadataOptions {
         }
         command
     }
+
+    /// Evaluates this struct and creates an `OfflineMetadataCommand`.
+    pub fn make_offline_command(&self) -> OfflineMetadataCommand {
+        let mut command = OfflineMetadataCommand::new();
+        if let Some(manifest_path) = &self.manifest_path {
+            command.manifest_path(manifest_path);
+        }
+        command
+    }
+
+    /// Builds the package graph, going through the graph cache if one was specified.
+    ///
+    /// A cache that is missing, out of date, unreadable or built for a different manifest path or
+    /// with different options is rebuilt from `cargo metadata`. Failures to read or write the
+    /// cache are printed as warnings.
+    pub fn build_graph(&self) -> Result<PackageGraph> {
+        match &self.graph_cache {
+            Some(cache_path) => {
+                let context = format!(
+                    "manifest-path={} offline-metadata={}",
+                    self.abs_manifest_path()?.display(),
+                    self.offline_metadata
+                );
+                PackageGraph::load_or_build_cached(
+                    cache_path,
+                    &context,
+                    || self.build_graph_uncached(),
+                    |err| eprintln!("warning: {}", err),
+                )
+            }
+            None => self.build_graph_uncached(),
+        }
+    }
+
+    /// Builds the package graph for the workspace as of the given Git revision.
+    ///
+    /// The revision is checked out into a temporary worktree, and the manifest path is looked up
+    /// at the same location relative to the repository root. The graph cache is not used.
+    pub fn build_graph_at(&self, rev: &str) -> Result<PackageGraph> {
+        let abs_manifest_path = Utf8PathBuf::try_from(self.abs_manifest_path()?)?;
+        let manifest_dir = abs_manifest_path
+            .parent()
+            .ok_or_else(|| eyre!("manifest path {} has no parent", abs_manifest_path))?;
+        let repo_root = git::repo_root(manifest_dir)?;
+        let rel_manifest_path = abs_manifest_path
+            .strip_prefix(&repo_root)
+            .wrap_err_with(|| {
+                format!(
+                    "manifest path {} is not within repository {}",
+                    abs_manifest_path, repo_root
+                )
+            })?;
+
+        let worktree = Worktree::add(&repo_root, rev)?;
+        let manifest_path = worktree.dir().join(rel_manifest_path);
+        let workspace_dir = manifest_path
+            .parent()
+            .expect("manifest path has a parent")
+            .to_path_buf();
+        // Cargo reads its configuration relative to the current directory, so run it from within
+        // the worktree.
+        let graph = if self.offline_metadata {
+            let mut command = self.make_offline_command();
+            command
+                .manifest_path(&manifest_path)
+                .current_dir(&workspace_dir);
+            command.build_graph()
+        } else {
+            let mut command = self.make_command();
+            command
+                .manifest_path(&manifest_path)
+                .current_dir(&workspace_dir);
+            command.build_graph()
+        };
+        graph.wrap_err_with(|| format!("building package graph at revision {} failed", rev))
+    }
+
+    fn build_graph_uncached(&self) -> Result<PackageGraph> {
+        if self.offline_metadata {
+            Ok(self.make_offline_command().build_graph()?)
+        } else {
+            Ok(self.make_command().build_graph()?)
+        }
+    }
 }
 
 /// Parse a given triple, the string "current", or "any", into a platform.
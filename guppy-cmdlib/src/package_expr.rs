@@ -0,0 +1,683 @@
+// Copyright (c) The cargo-guppy Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! A small expression language for selecting packages.
+//!
+//! Expressions are built out of functions that each select a set of packages, combined with set
+//! operators:
+//!
+//! * `all()`, `none()`: all packages or no packages.
+//! * `workspace()`: workspace members.
+//! * `foo` or `foo@1.2.3`: packages with this name (and version). It is an error for no package
+//!   to match.
+//! * `deps(expr)`: packages matched by `expr` along with their transitive dependencies.
+//! * `rdeps(expr)`: packages matched by `expr` along with their transitive reverse dependencies.
+//! * `kind(proc-macro)`: packages with this kind of build target. Kinds are `lib`, `bin`,
+//!   `proc-macro` and `build-script`.
+//! * `source(git)`: packages from this source. Sources are `workspace`, `path`, `git`,
+//!   `registry` and `crates-io`.
+//! * `name(/regex/)`: packages whose names match this regex. `name(foo)` matches the name exactly,
+//!   and like `foo` is an error if no package matches.
+//! * `platform(cfg(windows))`: packages depended on through a platform-specific dependency, i.e.
+//!   from a `[target.'cfg(windows)'.dependencies]` section. The platform may also be a target
+//!   triple.
+//!
+//!   This matches the spec as written in `Cargo.toml`, ignoring whitespace: it is not evaluated
+//!   against a platform. For example, `platform(x86_64-pc-windows-msvc)` does not match
+//!   dependencies declared under `cfg(windows)`, and `platform(cfg(any(unix, windows)))` does
+//!   not match `cfg(any(windows, unix))`.
+//!
+//! The set operators are `|` or `+` for union, `&` for intersection, and `-` for difference. `not`
+//! or `!` takes the complement of an expression. `&` and `not` bind more tightly than the other
+//! operators, and parentheses can be used for grouping.
+//!
+//! Package names can contain `-`, so the difference operator must be preceded by whitespace or a
+//! closing parenthesis. `+` is always the union operator, so `foo+bar` is the union of `foo` and
+//! `bar`.
+//!
+//! # Examples
+//!
+//! * `deps(foo) - rdeps(bar)`: dependencies of `foo` that don't depend on `bar`.
+//! * `deps(workspace()) & kind(proc-macro)`: all proc macros used by the workspace.
+//! * `source(git) | name(/^windows/)`: git dependencies, and packages whose names start with
+//!   `windows`.
+
+use color_eyre::eyre::{bail, eyre, Result, WrapErr};
+use guppy::{
+    graph::{
+        BuildTargetId, DependencyDirection, ExternalSource, PackageGraph, PackageLink,
+        PackageMetadata, PackageQuery, PackageSet,
+    },
+    platform::PlatformStatus,
+    DependencyKind,
+};
+use regex::Regex;
+use std::fmt;
+
+/// A parsed package expression.
+///
+/// For the syntax, see the [module documentation](self).
+#[derive(Clone, Debug)]
+pub struct PackageExpr {
+    input: String,
+    node: ExprNode,
+}
+
+impl PackageExpr {
+    /// Parses a package expression.
+    pub fn parse(input: &str) -> Result<Self> {
+        let mut parser = ExprParser { input, pos: 0 };
+        let node = parser
+            .parse_all()
+            .wrap_err_with(|| format!("error parsing package expression '{}'", input))?;
+        Ok(Self {
+            input: input.to_owned(),
+            node,
+        })
+    }
+
+    /// Evaluates this expression against a package graph, following all dependency links for
+    /// `deps()` and `rdeps()`.
+    pub fn eval<'g>(&self, graph: &'g PackageGraph) -> Result<PackageSet<'g>> {
+        self.eval_with_fn(graph, |_, _| true)
+    }
+
+    /// Evaluates this expression against a package graph, following only the links for which
+    /// `resolver_fn` returns true for `deps()` and `rdeps()`.
+    pub fn eval_with_fn<'g>(
+        &self,
+        graph: &'g PackageGraph,
+        mut resolver_fn: impl FnMut(&PackageQuery<'g>, PackageLink<'g>) -> bool,
+    ) -> Result<PackageSet<'g>> {
+        self.node
+            .eval(graph, &mut resolver_fn)
+            .wrap_err_with(|| format!("error evaluating package expression '{}'", self.input))
+    }
+}
+
+impl fmt::Display for PackageExpr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.input)
+    }
+}
+
+// ---
+// Expression tree
+// ---
+
+#[derive(Clone, Debug)]
+enum ExprNode {
+    All,
+    None,
+    Workspace,
+    Name(NameMatcher),
+    Deps(Box<ExprNode>),
+    Rdeps(Box<ExprNode>),
+    Kind(PackageKind),
+    Source(SourceKind),
+    /// A platform spec, matched textually against the specs in `[target]` sections.
+    Platform(String),
+    Not(Box<ExprNode>),
+    Union(Box<ExprNode>, Box<ExprNode>),
+    Intersection(Box<ExprNode>, Box<ExprNode>),
+    Difference(Box<ExprNode>, Box<ExprNode>),
+}
+
+impl ExprNode {
+    fn eval<'g, F>(&self, graph: &'g PackageGraph, resolver_fn: &mut F) -> Result<PackageSet<'g>>
+    where
+        F: FnMut(&PackageQuery<'g>, PackageLink<'g>) -> bool,
+    {
+        let filter = |pred: &dyn Fn(PackageMetadata<'g>) -> bool| {
+            graph
+                .resolve_all()
+                .filter(DependencyDirection::Forward, pred)
+        };
+
+        let set = match self {
+            ExprNode::All => graph.resolve_all(),
+            ExprNode::None => graph.resolve_none(),
+            ExprNode::Workspace => graph.resolve_workspace(),
+            ExprNode::Name(matcher) => {
+                let set = filter(&|package| matcher.matches(package));
+                if let NameMatcher::Exact { name, version } = matcher {
+                    if set.is_empty() {
+                        match version {
+                            Some(version) => bail!("unknown package: {}@{}", name, version),
+                            None => bail!("unknown package: {}", name),
+                        }
+                    }
+                }
+                set
+            }
+            ExprNode::Deps(inner) => inner
+                .eval(graph, resolver_fn)?
+                .to_package_query(DependencyDirection::Forward)
+                .resolve_with_fn(|query, link| resolver_fn(query, link)),
+            ExprNode::Rdeps(inner) => inner
+                .eval(graph, resolver_fn)?
+                .to_package_query(DependencyDirection::Reverse)
+                .resolve_with_fn(|query, link| resolver_fn(query, link)),
+            ExprNode::Kind(kind) => filter(&|package| kind.matches(package)),
+            ExprNode::Source(source) => filter(&|package| source.matches(package)),
+            ExprNode::Platform(spec) => {
+                let spec = normalize_spec(spec);
+                filter(&|package| {
+                    package
+                        .reverse_direct_links()
+                        .any(|link| link_has_platform(link, &spec))
+                })
+            }
+            ExprNode::Not(inner) => graph
+                .resolve_all()
+                .difference(&inner.eval(graph, resolver_fn)?),
+            ExprNode::Union(a, b) => a
+                .eval(graph, resolver_fn)?
+                .union(&b.eval(graph, resolver_fn)?),
+            ExprNode::Intersection(a, b) => a
+                .eval(graph, resolver_fn)?
+                .intersection(&b.eval(graph, resolver_fn)?),
+            ExprNode::Difference(a, b) => a
+                .eval(graph, resolver_fn)?
+                .difference(&b.eval(graph, resolver_fn)?),
+        };
+        Ok(set)
+    }
+}
+
+#[derive(Clone, Debug)]
+enum NameMatcher {
+    Exact {
+        name: String,
+        version: Option<String>,
+    },
+    Regex(Regex),
+}
+
+impl NameMatcher {
+    fn matches(&self, package: PackageMetadata<'_>) -> bool {
+        match self {
+            NameMatcher::Exact { name, version } => {
+                package.name() == name
+                    && version
+                        .as_ref()
+                        .map_or(true, |version| package.version().to_string() == *version)
+            }
+            NameMatcher::Regex(regex) => regex.is_match(package.name()),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+enum PackageKind {
+    Lib,
+    Bin,
+    ProcMacro,
+    BuildScript,
+}
+
+impl PackageKind {
+    fn matches(self, package: PackageMetadata<'_>) -> bool {
+        match self {
+            PackageKind::Lib => {
+                !package.is_proc_macro() && package.build_target(&BuildTargetId::Library).is_some()
+            }
+            PackageKind::Bin => package
+                .build_targets()
+                .any(|target| matches!(target.id(), BuildTargetId::Binary(_))),
+            PackageKind::ProcMacro => package.is_proc_macro(),
+            PackageKind::BuildScript => package.has_build_script(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+enum SourceKind {
+    Workspace,
+    Path,
+    Git,
+    Registry,
+    CratesIo,
+}
+
+impl SourceKind {
+    fn matches(self, package: PackageMetadata<'_>) -> bool {
+        let source = package.source();
+        match self {
+            SourceKind::Workspace => source.is_workspace(),
+            SourceKind::Path => source.is_path(),
+            SourceKind::Git => matches!(source.parse_external(), Some(ExternalSource::Git { .. })),
+            SourceKind::Registry => {
+                matches!(source.parse_external(), Some(ExternalSource::Registry(_)))
+            }
+            SourceKind::CratesIo => source.is_crates_io(),
+        }
+    }
+}
+
+/// Returns true if this link is declared under a `[target]` section matching `spec`.
+fn link_has_platform(link: PackageLink<'_>, spec: &str) -> bool {
+    DependencyKind::VALUES.iter().any(|kind| {
+        let status = link.req_for_kind(*kind).status();
+        [status.required_status(), status.optional_status()]
+            .iter()
+            .any(|status| match status {
+                PlatformStatus::PlatformDependent { eval } => eval
+                    .target_spec_strs()
+                    .any(|target_spec| normalize_spec(&target_spec) == spec),
+                PlatformStatus::Never | PlatformStatus::Always => false,
+            })
+    })
+}
+
+/// Removes whitespace from a platform spec so that e.g. `cfg(any(unix, windows))` and
+/// `cfg(any(unix,windows))` compare equal.
+fn normalize_spec(spec: &str) -> String {
+    spec.chars().filter(|c| !c.is_whitespace()).collect()
+}
+
+// ---
+// Parser
+// ---
+
+struct ExprParser<'a> {
+    input: &'a str,
+    pos: usize,
+}
+
+impl<'a> ExprParser<'a> {
+    fn parse_all(&mut self) -> Result<ExprNode> {
+        let node = self.parse_union()?;
+        self.skip_whitespace();
+        if let Some(c) = self.peek() {
+            bail!("unexpected '{}' at position {}", c, self.pos);
+        }
+        Ok(node)
+    }
+
+    fn parse_union(&mut self) -> Result<ExprNode> {
+        let mut lhs = self.parse_intersection()?;
+        loop {
+            self.skip_whitespace();
+            let op = match self.peek() {
+                Some(op @ ('|' | '+' | '-')) => op,
+                _ => return Ok(lhs),
+            };
+            self.bump();
+            let rhs = Box::new(self.parse_intersection()?);
+            lhs = match op {
+                '-' => ExprNode::Difference(Box::new(lhs), rhs),
+                _ => ExprNode::Union(Box::new(lhs), rhs),
+            };
+        }
+    }
+
+    fn parse_intersection(&mut self) -> Result<ExprNode> {
+        let mut lhs = self.parse_unary()?;
+        loop {
+            self.skip_whitespace();
+            if self.peek() != Some('&') {
+                return Ok(lhs);
+            }
+            self.bump();
+            let rhs = self.parse_unary()?;
+            lhs = ExprNode::Intersection(Box::new(lhs), Box::new(rhs));
+        }
+    }
+
+    fn parse_unary(&mut self) -> Result<ExprNode> {
+        self.skip_whitespace();
+        if self.peek() == Some('!') {
+            self.bump();
+            return Ok(ExprNode::Not(Box::new(self.parse_unary()?)));
+        }
+
+        let start = self.pos;
+        let ident = self.parse_ident();
+        if ident == "not" && self.peek().map_or(false, |c| c.is_whitespace() || c == '(') {
+            return Ok(ExprNode::Not(Box::new(self.parse_unary()?)));
+        }
+        self.pos = start;
+        self.parse_atom()
+    }
+
+    fn parse_atom(&mut self) -> Result<ExprNode> {
+        self.skip_whitespace();
+        if self.peek() == Some('(') {
+            self.bump();
+            let node = self.parse_union()?;
+            self.expect(')')?;
+            return Ok(node);
+        }
+
+        let start = self.pos;
+        let ident = self.parse_ident();
+        if ident.is_empty() {
+            return Err(match self.peek() {
+                Some(c) => eyre!("expected expression at position {}, found '{}'", start, c),
+                None => eyre!(
+                    "expected expression at position {}, found end of input",
+                    start
+                ),
+            });
+        }
+
+        self.skip_whitespace();
+        if self.peek() != Some('(') {
+            // This is a bare package name.
+            let matcher = match ident.split_once('@') {
+                Some((name, version)) => NameMatcher::Exact {
+                    name: name.to_owned(),
+                    version: Some(version.to_owned()),
+                },
+                None => NameMatcher::Exact {
+                    name: ident.to_owned(),
+                    version: None,
+                },
+            };
+            return Ok(ExprNode::Name(matcher));
+        }
+        self.bump();
+
+        let node = match ident {
+            "all" => ExprNode::All,
+            "none" => ExprNode::None,
+            "workspace" => ExprNode::Workspace,
+            "deps" | "rdeps" => {
+                let inner = Box::new(self.parse_union()?);
+                if ident == "deps" {
+                    ExprNode::Deps(inner)
+                } else {
+                    ExprNode::Rdeps(inner)
+                }
+            }
+            "kind" => {
+                let arg_pos = self.pos;
+                let kind = match self.parse_raw_arg()? {
+                    "lib" => PackageKind::Lib,
+                    "bin" => PackageKind::Bin,
+                    "proc-macro" => PackageKind::ProcMacro,
+                    "build-script" => PackageKind::BuildScript,
+                    other => bail!(
+                        "unknown kind '{}' at position {} (expected lib, bin, proc-macro or \
+                         build-script)",
+                        other,
+                        arg_pos
+                    ),
+                };
+                ExprNode::Kind(kind)
+            }
+            "source" => {
+                let arg_pos = self.pos;
+                let source = match self.parse_raw_arg()? {
+                    "workspace" => SourceKind::Workspace,
+                    "path" => SourceKind::Path,
+                    "git" => SourceKind::Git,
+                    "registry" => SourceKind::Registry,
+                    "crates-io" => SourceKind::CratesIo,
+                    other => bail!(
+                        "unknown source '{}' at position {} (expected workspace, path, git, \
+                         registry or crates-io)",
+                        other,
+                        arg_pos
+                    ),
+                };
+                ExprNode::Source(source)
+            }
+            "name" => {
+                self.skip_whitespace();
+                let matcher = if self.peek() == Some('/') {
+                    let regex = self.parse_regex()?;
+                    NameMatcher::Regex(regex)
+                } else {
+                    NameMatcher::Exact {
+                        name: self.parse_raw_arg()?.to_owned(),
+                        version: None,
+                    }
+                };
+                ExprNode::Name(matcher)
+            }
+            "platform" => {
+                let arg_pos = self.pos;
+                let spec = self.parse_raw_arg()?;
+                if spec.is_empty() {
+                    bail!("expected platform at position {}", arg_pos);
+                }
+                ExprNode::Platform(spec.to_owned())
+            }
+            other => bail!("unknown function '{}' at position {}", other, start),
+        };
+
+        // Functions that take their arguments as raw strings consume the closing parenthesis.
+        if matches!(
+            node,
+            ExprNode::All
+                | ExprNode::None
+                | ExprNode::Workspace
+                | ExprNode::Deps(_)
+                | ExprNode::Rdeps(_)
+        ) {
+            self.expect(')')?;
+        }
+        Ok(node)
+    }
+
+    /// Parses an identifier: a package name, optionally followed by `@version`.
+    fn parse_ident(&mut self) -> &'a str {
+        let start = self.pos;
+        if self
+            .peek()
+            .map_or(false, |c| c.is_ascii_alphanumeric() || c == '_')
+        {
+            while self.peek().map_or(false, |c| {
+                c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.' | '@')
+            }) {
+                self.bump();
+            }
+        }
+        &self.input[start..self.pos]
+    }
+
+    /// Parses the rest of a function call as a raw string, up to the matching closing parenthesis.
+    fn parse_raw_arg(&mut self) -> Result<&'a str> {
+        let start = self.pos;
+        let mut depth = 0;
+        while let Some(c) = self.peek() {
+            match c {
+                '(' => depth += 1,
+                ')' if depth == 0 => {
+                    let arg = self.input[start..self.pos].trim();
+                    self.bump();
+                    return Ok(arg);
+                }
+                ')' => depth -= 1,
+                _ => {}
+            }
+            self.bump();
+        }
+        bail!("expected ')' at end of input");
+    }
+
+    /// Parses `/regex/)`, including the closing parenthesis.
+    fn parse_regex(&mut self) -> Result<Regex> {
+        let start = self.pos;
+        self.expect('/')?;
+        let mut regex = String::new();
+        loop {
+            match self.peek() {
+                Some('/') => break,
+                Some('\\') => {
+                    self.bump();
+                    match self.peek() {
+                        // An escaped slash is a literal slash.
+                        Some('/') => regex.push('/'),
+                        Some(c) => {
+                            regex.push('\\');
+                            regex.push(c);
+                        }
+                        None => bail!("unterminated regex starting at position {}", start),
+                    }
+                }
+                Some(c) => regex.push(c),
+                None => bail!("unterminated regex starting at position {}", start),
+            }
+            self.bump();
+        }
+        self.bump();
+        self.expect(')')?;
+        Regex::new(&regex).wrap_err_with(|| format!("invalid regex at position {}", start))
+    }
+
+    fn expect(&mut self, expected: char) -> Result<()> {
+        self.skip_whitespace();
+        match self.peek() {
+            Some(c) if c == expected => {
+                self.bump();
+                Ok(())
+            }
+            Some(c) => bail!(
+                "expected '{}' at position {}, found '{}'",
+                expected,
+                self.pos,
+                c
+            ),
+            None => bail!("expected '{}' at end of input", expected),
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.peek().map_or(false, char::is_whitespace) {
+            self.bump();
+        }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.input[self.pos..].chars().next()
+    }
+
+    fn bump(&mut self) {
+        if let Some(c) = self.peek() {
+            self.pos += c.len_utf8();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use fixtures::json::JsonFixture;
+
+    fn eval_names(expr: &str) -> Vec<&'static str> {
+        let graph = JsonFixture::metadata1().graph();
+        let set = PackageExpr::parse(expr)
+            .expect("expression is valid")
+            .eval(graph)
+            .expect("expression evaluates");
+        let mut names: Vec<_> = set
+            .packages(DependencyDirection::Forward)
+            .map(|package| package.name())
+            .collect();
+        names.sort_unstable();
+        names.dedup();
+        names
+    }
+
+    #[test]
+    fn parse_errors() {
+        for expr in [
+            "",
+            "deps(",
+            "deps(foo",
+            "foo bar",
+            "unknown(foo)",
+            "kind(dylib)",
+            "source(svn)",
+            "name(/[/)",
+            "name(/foo",
+            "workspace() -",
+            "platform()",
+        ] {
+            assert!(PackageExpr::parse(expr).is_err(), "'{}' should fail", expr);
+        }
+    }
+
+    #[test]
+    fn unknown_names() {
+        let graph = JsonFixture::metadata1().graph();
+        for expr in [
+            "no-such-package",
+            "dtoa@0.0.1",
+            "name(no-such-package)",
+            "deps(no-such-package) | dtoa",
+        ] {
+            let expr = PackageExpr::parse(expr).expect("expression is valid");
+            assert!(
+                expr.eval(graph).is_err(),
+                "'{}' should fail to evaluate",
+                expr
+            );
+        }
+        // Regexes are allowed to match nothing.
+        assert_eq!(eval_names("name(/^no-such-package$/)"), Vec::<&str>::new());
+    }
+
+    #[test]
+    fn metadata1_eval() {
+        assert_eq!(eval_names("workspace()"), vec!["testcrate"]);
+        assert_eq!(eval_names("none()"), Vec::<&str>::new());
+        assert_eq!(eval_names("all()").len(), eval_names("deps(all())").len());
+        assert_eq!(eval_names("dtoa"), vec!["dtoa"]);
+        assert_eq!(eval_names("dtoa@0.4.4"), vec!["dtoa"]);
+        assert_eq!(
+            eval_names("deps(serde_yaml)"),
+            vec![
+                "dtoa",
+                "linked-hash-map",
+                "serde",
+                "serde_yaml",
+                "yaml-rust"
+            ]
+        );
+        assert_eq!(
+            eval_names("rdeps(dtoa)"),
+            vec!["datatest", "dtoa", "serde_yaml", "testcrate"]
+        );
+        assert_eq!(
+            eval_names("deps(serde_yaml) - rdeps(dtoa)"),
+            vec!["linked-hash-map", "serde", "yaml-rust"]
+        );
+        assert_eq!(
+            eval_names("deps(serde_yaml) & (rdeps(dtoa) | linked-hash-map)"),
+            vec!["dtoa", "linked-hash-map", "serde_yaml"]
+        );
+        assert_eq!(eval_names("name(/^yaml/)"), vec!["yaml-rust"]);
+        assert_eq!(
+            eval_names("name(dtoa) + name(serde)"),
+            vec!["dtoa", "serde"]
+        );
+        assert_eq!(eval_names("dtoa+serde"), vec!["dtoa", "serde"]);
+        assert_eq!(eval_names("source(workspace)"), vec!["testcrate"]);
+        assert_eq!(
+            eval_names("not source(crates-io)"),
+            eval_names("source(workspace) | source(path) | source(git)")
+        );
+        assert_eq!(eval_names("!all()"), Vec::<&str>::new());
+        assert_eq!(
+            eval_names("kind(proc-macro)"),
+            vec!["ctor", "datatest-derive"]
+        );
+        assert_eq!(
+            eval_names("platform(cfg(windows))"),
+            eval_names("platform( cfg( windows ) )")
+        );
+        assert!(
+            eval_names("platform(cfg(windows))").contains(&"winapi"),
+            "winapi is a windows-only dependency"
+        );
+        // Specs are matched as written, not evaluated.
+        assert!(
+            !eval_names("platform(x86_64-pc-windows-msvc)").contains(&"winapi"),
+            "triples don't match cfg() specs"
+        );
+    }
+}
@@ -31,6 +31,7 @@ impl PackagesAndFeatures {
                             .into_iter()
                             .map(|package| package.to_string())
                             .collect(),
+                        expr: None,
                         features_only: features_only
                             .into_iter()
                             .map(|package| package.to_string())
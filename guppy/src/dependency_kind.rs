@@ -6,7 +6,7 @@ use std::fmt;
 /// A descriptor for the kind of dependency.
 ///
 /// Cargo dependencies may be one of three kinds.
-#[derive(Copy, Clone, Debug, Eq, Hash, PartialEq)]
+#[derive(Copy, Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
 pub enum DependencyKind {
     /// Normal dependencies.
     ///
@@ -21,6 +21,24 @@ pub enum Error {
     MetadataSerializeError(serde_json::Error),
     /// An error occurred while constructing a `PackageGraph` from parsed metadata.
     PackageGraphConstructError(String),
+    /// An error occurred while editing a `PackageGraph` in memory.
+    PackageGraphEditError(String),
+    /// An error occurred while reading or writing a `PackageGraph` cache at the given path.
+    ///
+    /// This is present if the `cache` feature is enabled.
+    #[cfg(feature = "cache")]
+    GraphCacheError(Utf8PathBuf, Box<dyn error::Error + Send + Sync>),
+    /// An error occurred while reading `[patch]` and `[replace]` overrides from the manifest at the
+    /// given path.
+    ///
+    /// This is present if the `overrides` feature is enabled.
+    #[cfg(feature = "overrides")]
+    OverridesError(Utf8PathBuf, String),
+    /// An error occurred while reading a workspace without running Cargo.
+    ///
+    /// This is present if the `offline` feature is enabled.
+    #[cfg(feature = "offline")]
+    OfflineMetadataError(String),
     /// A package ID was unknown to this `PackageGraph`.
     UnknownPackageId(PackageId),
     /// A feature ID was unknown to this `FeatureGraph`.
@@ -93,6 +111,19 @@ impl fmt::Display for Error {
             MetadataParseError(_) => write!(f, "`cargo metadata` returned invalid JSON output"),
             MetadataSerializeError(_) => write!(f, "failed to serialize `cargo metadata` to JSON"),
             PackageGraphConstructError(s) => write!(f, "failed to construct package graph: {}", s),
+            PackageGraphEditError(s) => write!(f, "failed to edit package graph: {}", s),
+            #[cfg(feature = "cache")]
+            GraphCacheError(path, _) => {
+                write!(f, "failed to access package graph cache at {}", path)
+            }
+            #[cfg(feature = "overrides")]
+            OverridesError(path, msg) => {
+                write!(f, "failed to read overrides from {}: {}", path, msg)
+            }
+            #[cfg(feature = "offline")]
+            OfflineMetadataError(msg) => {
+                write!(f, "failed to read workspace without running cargo: {}", msg)
+            }
             UnknownPackageId(id) => write!(f, "unknown package ID: {}", id),
             UnknownFeatureId(package_id, feature) => {
                 write!(f, "unknown feature ID: '{}/{}'", package_id, feature)
@@ -157,6 +188,13 @@ impl error::Error for Error {
             MetadataSerializeError(err) => Some(err),
             CommandError(err) => Some(err.as_ref()),
             PackageGraphConstructError(_) => None,
+            PackageGraphEditError(_) => None,
+            #[cfg(feature = "cache")]
+            GraphCacheError(_, err) => Some(err.as_ref()),
+            #[cfg(feature = "overrides")]
+            OverridesError(_, _) => None,
+            #[cfg(feature = "offline")]
+            OfflineMetadataError(_) => None,
             UnknownPackageId(_) => None,
             UnknownFeatureId(_, _) => None,
             UnknownWorkspacePath(_) => None,
@@ -0,0 +1,284 @@
+// Copyright (c) The cargo-guppy Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Support for artifact dependencies (`-Z bindeps`).
+
+use crate::JsonValue;
+use serde::{Deserialize, Deserializer, Serialize};
+use std::collections::HashMap;
+
+/// Information about an [artifact
+/// dependency](https://doc.rust-lang.org/nightly/cargo/reference/unstable.html#artifact-dependencies),
+/// i.e. a dependency that has the `artifact` key set.
+///
+/// Artifact dependencies make binaries, cdylibs or staticlibs of a package available to the
+/// dependent package, optionally built for a specific platform.
+///
+/// Returned by [`DependencyReq::artifact`](crate::graph::DependencyReq::artifact).
+#[derive(Copy, Clone, Debug)]
+pub struct ArtifactDep<'g> {
+    inner: &'g ArtifactImpl,
+}
+
+assert_covariant!(ArtifactDep);
+
+impl<'g> ArtifactDep<'g> {
+    pub(super) fn new(inner: &'g ArtifactImpl) -> Self {
+        Self { inner }
+    }
+
+    /// Returns the kinds of artifacts requested, as specified in the `artifact` key.
+    pub fn kinds(&self) -> impl Iterator<Item = ArtifactKind<'g>> + ExactSizeIterator + 'g {
+        self.inner.kinds.iter().map(|kind| ArtifactKind::new(kind))
+    }
+
+    /// Returns true if the library target of the dependency is also made available, i.e. if
+    /// `lib = true` is specified.
+    pub fn lib(&self) -> bool {
+        self.inner.lib
+    }
+
+    /// Returns the platform the artifacts are built for, as specified in the `target` key.
+    pub fn target(&self) -> ArtifactTarget<'g> {
+        match self.inner.target.as_deref() {
+            None => ArtifactTarget::Default,
+            Some(ArtifactImpl::TARGET_KEYWORD) => ArtifactTarget::Target,
+            Some(triple) => ArtifactTarget::Triple(triple),
+        }
+    }
+}
+
+/// A kind of artifact requested by an [`ArtifactDep`].
+#[derive(Copy, Clone, Debug, Eq, Hash, PartialEq)]
+#[non_exhaustive]
+pub enum ArtifactKind<'g> {
+    /// A binary target: either all binaries of the dependency (`"bin"`), or a specific one
+    /// (`"bin:<name>"`).
+    Bin {
+        /// The name of the binary, if specified.
+        name: Option<&'g str>,
+    },
+
+    /// The `cdylib` of the dependency.
+    Cdylib,
+
+    /// The `staticlib` of the dependency.
+    Staticlib,
+
+    /// An artifact kind not known to guppy.
+    Unknown(&'g str),
+}
+
+impl<'g> ArtifactKind<'g> {
+    fn new(kind: &'g str) -> Self {
+        match kind {
+            "bin" => ArtifactKind::Bin { name: None },
+            "cdylib" => ArtifactKind::Cdylib,
+            "staticlib" => ArtifactKind::Staticlib,
+            other => match other.strip_prefix("bin:") {
+                Some(name) => ArtifactKind::Bin { name: Some(name) },
+                None => ArtifactKind::Unknown(other),
+            },
+        }
+    }
+}
+
+/// The platform an [`ArtifactDep`] is built for.
+#[derive(Copy, Clone, Debug, Eq, Hash, PartialEq)]
+pub enum ArtifactTarget<'g> {
+    /// No `target` was specified: artifacts are built for the same platform as the dependency
+    /// would otherwise be, i.e. the host for build dependencies and the target for everything
+    /// else.
+    Default,
+
+    /// `target = "target"`: artifacts are built for the target platform. This is used with build
+    /// dependencies, which would otherwise be built for the host.
+    Target,
+
+    /// Artifacts are built for the platform with this target triple.
+    Triple(&'g str),
+}
+
+// ---
+// Implementation
+// ---
+
+/// Artifact information for a single dependency, in the format `cargo metadata` uses.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub(crate) struct ArtifactImpl {
+    pub(super) kinds: Vec<Box<str>>,
+    pub(super) lib: bool,
+    pub(super) target: Option<Box<str>>,
+}
+
+impl ArtifactImpl {
+    /// The value of `target` that means "build for the target platform".
+    const TARGET_KEYWORD: &'static str = "target";
+
+    /// Merges in another instance of the same dependency, e.g. a platform-specific one.
+    ///
+    /// Artifact kinds and `lib` are unioned. Cargo requires instances within a section to agree on
+    /// the target, so the first one seen is kept.
+    pub(super) fn merge(&mut self, other: &ArtifactImpl) {
+        for kind in &other.kinds {
+            if !self.kinds.contains(kind) {
+                self.kinds.push(kind.clone());
+            }
+        }
+        self.lib |= other.lib;
+        if self.target.is_none() {
+            self.target = other.target.clone();
+        }
+    }
+}
+
+/// Artifact information extracted from `cargo metadata` output.
+///
+/// `cargo_metadata` doesn't know about the `artifact` key on dependencies, so it's extracted from
+/// the JSON separately. This maps package IDs to the artifact information for each of their
+/// dependencies, in the order they're listed.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct MetadataArtifacts {
+    packages: HashMap<String, Vec<Option<ArtifactImpl>>>,
+}
+
+impl MetadataArtifacts {
+    /// Extracts artifact information from `cargo metadata` JSON.
+    ///
+    /// Only the `id` and `dependencies[].artifact` keys of packages are looked at. Everything else
+    /// is skipped without being parsed into a value.
+    pub(crate) fn extract<'de, D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        #[derive(Deserialize)]
+        struct MetadataRepr {
+            #[serde(default)]
+            packages: Vec<PackageRepr>,
+        }
+
+        #[derive(Deserialize)]
+        struct PackageRepr {
+            // Let cargo_metadata report errors for malformed packages.
+            id: Option<String>,
+            #[serde(default)]
+            dependencies: Vec<DependencyRepr>,
+        }
+
+        #[derive(Deserialize)]
+        struct DependencyRepr {
+            #[serde(default)]
+            artifact: Option<ArtifactImpl>,
+        }
+
+        let metadata = MetadataRepr::deserialize(deserializer)?;
+        let packages = metadata
+            .packages
+            .into_iter()
+            .filter_map(|package| {
+                let artifacts: Vec<_> = package
+                    .dependencies
+                    .into_iter()
+                    .map(|dep| dep.artifact)
+                    .collect();
+                match package.id {
+                    Some(id) if artifacts.iter().any(Option::is_some) => Some((id, artifacts)),
+                    _ => None,
+                }
+            })
+            .collect();
+        Ok(Self { packages })
+    }
+
+    /// Adds artifact information back into `cargo metadata` JSON produced by `cargo_metadata`.
+    pub(crate) fn insert_into(&self, metadata: &mut JsonValue) -> Result<(), serde_json::Error> {
+        if self.packages.is_empty() {
+            return Ok(());
+        }
+        let package_values = match metadata
+            .get_mut("packages")
+            .and_then(JsonValue::as_array_mut)
+        {
+            Some(packages) => packages,
+            None => return Ok(()),
+        };
+        for package in package_values {
+            let artifacts = match package
+                .get("id")
+                .and_then(JsonValue::as_str)
+                .and_then(|id| self.packages.get(id))
+            {
+                Some(artifacts) => artifacts,
+                None => continue,
+            };
+            let deps = match package
+                .get_mut("dependencies")
+                .and_then(JsonValue::as_array_mut)
+            {
+                Some(deps) => deps,
+                None => continue,
+            };
+            for (dep, artifact) in deps.iter_mut().zip(artifacts) {
+                if let (Some(dep), Some(artifact)) = (dep.as_object_mut(), artifact) {
+                    dep.insert("artifact".to_owned(), serde_json::to_value(artifact)?);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Removes and returns the artifact information for the dependencies of this package.
+    pub(crate) fn take(&mut self, package_id: &str) -> Vec<Option<ArtifactImpl>> {
+        self.packages.remove(package_id).unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn artifact_kinds() {
+        assert_eq!(ArtifactKind::new("bin"), ArtifactKind::Bin { name: None });
+        assert_eq!(
+            ArtifactKind::new("bin:tool-cli"),
+            ArtifactKind::Bin {
+                name: Some("tool-cli")
+            }
+        );
+        assert_eq!(ArtifactKind::new("cdylib"), ArtifactKind::Cdylib);
+        assert_eq!(ArtifactKind::new("staticlib"), ArtifactKind::Staticlib);
+        assert_eq!(ArtifactKind::new("dylib"), ArtifactKind::Unknown("dylib"));
+    }
+
+    #[test]
+    fn metadata_round_trip() {
+        let json = serde_json::json!({
+            "packages": [{
+                "id": "a 0.1.0",
+                "dependencies": [
+                    { "name": "b" },
+                    { "name": "c", "artifact": { "kinds": ["bin"], "lib": false, "target": "target" } },
+                ],
+            }],
+        });
+        let mut artifacts = MetadataArtifacts::extract(&json).expect("artifacts extracted");
+
+        let mut stripped = json.clone();
+        stripped["packages"][0]["dependencies"][1]
+            .as_object_mut()
+            .unwrap()
+            .remove("artifact");
+        artifacts
+            .insert_into(&mut stripped)
+            .expect("artifacts inserted");
+        assert_eq!(stripped, json, "artifacts round-tripped");
+
+        let deps = artifacts.take("a 0.1.0");
+        assert_eq!(deps.len(), 2);
+        assert!(deps[0].is_none());
+        let artifact = ArtifactDep::new(deps[1].as_ref().expect("c is an artifact dep"));
+        assert_eq!(artifact.target(), ArtifactTarget::Target);
+        assert!(!artifact.lib());
+    }
+}
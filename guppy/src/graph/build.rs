@@ -3,10 +3,10 @@
 
 use crate::{
     graph::{
-        cargo_version_matches, BuildTargetImpl, BuildTargetKindImpl, DepRequiredOrOptional,
-        DependencyReqImpl, NamedFeatureDep, OwnedBuildTargetId, PackageGraph, PackageGraphData,
-        PackageIx, PackageLinkImpl, PackageMetadataImpl, PackagePublishImpl, PackageSourceImpl,
-        WorkspaceImpl,
+        cargo_version_matches, ArtifactImpl, BuildTargetImpl, BuildTargetKindImpl,
+        DepRequiredOrOptional, DependencyReqImpl, MetadataArtifacts, NamedFeatureDep,
+        OwnedBuildTargetId, PackageGraph, PackageGraphData, PackageIx, PackageLinkImpl,
+        PackageMetadataImpl, PackagePublishImpl, PackageSourceImpl, WorkspaceImpl,
     },
     sorted_set::SortedSet,
     Error, PackageId,
@@ -27,7 +27,10 @@ use target_spec::TargetSpec;
 
 impl PackageGraph {
     /// Constructs a new `PackageGraph` instances from the given metadata.
-    pub(crate) fn build(metadata: Metadata) -> Result<Self, Error> {
+    pub(crate) fn build(
+        metadata: Metadata,
+        mut artifacts: MetadataArtifacts,
+    ) -> Result<Self, Error> {
         // resolve_nodes is missing if the metadata was generated with --no-deps.
         let resolve_nodes = metadata.resolve.map(|r| r.nodes).unwrap_or_default();
 
@@ -49,7 +52,10 @@ impl PackageGraph {
         let packages: HashMap<_, _> = metadata
             .packages
             .into_iter()
-            .map(|package| build_state.process_package(package))
+            .map(|package| {
+                let package_artifacts = artifacts.take(&package.id.repr);
+                build_state.process_package(package, package_artifacts)
+            })
             .collect::<Result<_, _>>()?;
 
         let dep_graph = build_state.finish();
@@ -76,7 +82,7 @@ impl PackageGraph {
 
 impl WorkspaceImpl {
     /// Indexes and creates a new workspace.
-    fn new(
+    pub(super) fn new(
         workspace_root: impl Into<Utf8PathBuf>,
         target_directory: impl Into<Utf8PathBuf>,
         metadata_table: serde_json::Value,
@@ -128,6 +134,8 @@ impl WorkspaceImpl {
             metadata_table,
             members_by_path,
             members_by_name,
+            // Overrides aren't part of `cargo metadata` output, and are loaded separately.
+            overrides: vec![],
             #[cfg(feature = "proptest1")]
             name_list: OnceCell::new(),
         })
@@ -191,6 +199,7 @@ impl<'a> GraphBuildState<'a> {
     fn process_package(
         &mut self,
         package: Package,
+        artifacts: Vec<Option<ArtifactImpl>>,
     ) -> Result<(PackageId, PackageMetadataImpl), Error> {
         let package_id = PackageId::from_metadata(package.id);
         let (package_ix, _, _) = self.package_data(&package_id)?;
@@ -226,8 +235,13 @@ impl<'a> GraphBuildState<'a> {
         // resolved_deps is missing if the metadata was generated with --no-deps.
         let resolved_deps = self.resolve_data.remove(&package_id).unwrap_or_default();
 
+        // artifacts is either empty or has one entry per dependency.
+        let deps_with_artifacts = package.dependencies.iter().enumerate().map(|(idx, dep)| {
+            let artifact = artifacts.get(idx).and_then(Option::as_ref);
+            (dep, artifact)
+        });
         let dep_resolver =
-            DependencyResolver::new(&package_id, &self.package_data, &package.dependencies);
+            DependencyResolver::new(&package_id, &self.package_data, deps_with_artifacts);
 
         for NodeDep {
             name: resolved_name,
@@ -398,7 +412,7 @@ impl PackageSourceImpl {
 }
 
 impl NamedFeatureDep {
-    fn from_cargo_string(input: impl Into<String>) -> Self {
+    pub(super) fn from_cargo_string(input: impl Into<String>) -> Self {
         let input = input.into();
         match input.split_once('/') {
             Some((dep_name, feature)) => {
@@ -557,12 +571,13 @@ impl<'g> DependencyResolver<'g> {
     fn new(
         from_id: &'g PackageId,
         package_data: &'g HashMap<PackageId, (NodeIndex<PackageIx>, String, Version)>,
-        package_deps: impl IntoIterator<Item = &'g Dependency>,
+        package_deps: impl IntoIterator<Item = DependencyInstance<'g>>,
     ) -> Self {
         let mut renamed_map = HashMap::new();
         let mut original_map: HashMap<_, DependencyReqs<'g>> = HashMap::new();
 
-        for dep in package_deps {
+        for instance in package_deps {
+            let (dep, _) = instance;
             match &dep.rename {
                 // The rename != dep.name check is because of Cargo.toml instances like this:
                 //
@@ -580,11 +595,11 @@ impl<'g> DependencyResolver<'g> {
                     let (_, deps) = renamed_map
                         .entry(resolved_name.into())
                         .or_insert_with(|| (rename.as_str(), DependencyReqs::default()));
-                    deps.push(dep);
+                    deps.push(instance);
                 }
                 Some(_) | None => {
                     let deps = original_map.entry(dep.name.as_str()).or_default();
-                    deps.push(dep);
+                    deps.push(instance);
                 }
             }
         }
@@ -603,7 +618,7 @@ impl<'g> DependencyResolver<'g> {
         &'a self,
         resolved_name: &str,
         package_id: &PackageId,
-    ) -> Result<(&'g str, impl Iterator<Item = &'g Dependency> + 'a), Error> {
+    ) -> Result<(&'g str, impl Iterator<Item = DependencyInstance<'g>> + 'a), Error> {
         // This method needs to reconcile three separate sources of data:
         // 1. The metadata for each package, which is basically a parsed version of the Cargo.toml
         //    for that package.
@@ -665,24 +680,27 @@ impl<'g> DependencyResolver<'g> {
     }
 }
 
+/// A dependency listed in `Cargo.toml`, along with its artifact information if any.
+type DependencyInstance<'g> = (&'g Dependency, Option<&'g ArtifactImpl>);
+
 /// Maintains a list of dependency requirements to match up to for a given package name.
 #[derive(Clone, Debug, Default)]
 struct DependencyReqs<'g> {
-    reqs: Vec<&'g Dependency>,
+    reqs: Vec<DependencyInstance<'g>>,
 }
 
 impl<'g> DependencyReqs<'g> {
-    fn push(&mut self, dependency: &'g Dependency) {
-        self.reqs.push(dependency);
+    fn push(&mut self, instance: DependencyInstance<'g>) {
+        self.reqs.push(instance);
     }
 
     fn matches_for<'a>(
         &'a self,
         version: &'a Version,
-    ) -> impl Iterator<Item = &'g Dependency> + 'a {
-        self.reqs.iter().filter_map(move |dep| {
-            if cargo_version_matches(&dep.req, version) {
-                Some(*dep)
+    ) -> impl Iterator<Item = DependencyInstance<'g>> + 'a {
+        self.reqs.iter().filter_map(move |instance| {
+            if cargo_version_matches(&instance.0.req, version) {
+                Some(*instance)
             } else {
                 None
             }
@@ -695,13 +713,13 @@ impl PackageLinkImpl {
         from_id: &PackageId,
         name: &str,
         resolved_name: &str,
-        deps: impl IntoIterator<Item = &'a Dependency>,
+        deps: impl IntoIterator<Item = DependencyInstance<'a>>,
     ) -> Result<Self, Error> {
         let mut version_req = None;
         let mut normal = DependencyReqImpl::default();
         let mut build = DependencyReqImpl::default();
         let mut dev = DependencyReqImpl::default();
-        for dep in deps {
+        for (dep, artifact) in deps {
             // Dev dependencies cannot be optional.
             if dep.kind == DependencyKind::Development && dep.optional {
                 return Err(Error::PackageGraphConstructError(format!(
@@ -716,9 +734,9 @@ impl PackageLinkImpl {
             }
 
             match dep.kind {
-                DependencyKind::Normal => normal.add_instance(from_id, dep)?,
-                DependencyKind::Build => build.add_instance(from_id, dep)?,
-                DependencyKind::Development => dev.add_instance(from_id, dep)?,
+                DependencyKind::Normal => normal.add_instance(from_id, dep, artifact)?,
+                DependencyKind::Build => build.add_instance(from_id, dep, artifact)?,
+                DependencyKind::Development => dev.add_instance(from_id, dep, artifact)?,
                 _ => {
                     // unknown dependency kind -- can't do much with this!
                     continue;
@@ -764,7 +782,18 @@ impl PackageLinkImpl {
 /// causes this union-ing to *not* happen, so that's why we store all the features enabled by
 /// each target separately.
 impl DependencyReqImpl {
-    fn add_instance(&mut self, from_id: &PackageId, dep: &Dependency) -> Result<(), Error> {
+    fn add_instance(
+        &mut self,
+        from_id: &PackageId,
+        dep: &Dependency,
+        artifact: Option<&ArtifactImpl>,
+    ) -> Result<(), Error> {
+        if let Some(artifact) = artifact {
+            match &mut self.artifact {
+                Some(existing) => existing.merge(artifact),
+                None => self.artifact = Some(artifact.clone()),
+            }
+        }
         if dep.optional {
             self.optional.add_instance(from_id, dep)
         } else {
@@ -791,20 +820,35 @@ impl DepRequiredOrOptional {
             None => None,
         };
 
-        self.build_if.add_spec(target_spec.as_ref());
-        if dep.uses_default_features {
-            self.default_features_if.add_spec(target_spec.as_ref());
+        self.add_spec_instance(
+            target_spec.as_ref(),
+            dep.uses_default_features,
+            &dep.features,
+        );
+        Ok(())
+    }
+
+    /// Adds an instance of a dependency, enabled on platforms matching `target_spec` (or all
+    /// platforms if `None`).
+    pub(super) fn add_spec_instance(
+        &mut self,
+        target_spec: Option<&TargetSpec>,
+        uses_default_features: bool,
+        features: &[String],
+    ) {
+        self.build_if.add_spec(target_spec);
+        if uses_default_features {
+            self.default_features_if.add_spec(target_spec);
         } else {
-            self.no_default_features_if.add_spec(target_spec.as_ref());
+            self.no_default_features_if.add_spec(target_spec);
         }
 
-        for feature in &dep.features {
+        for feature in features {
             self.feature_targets
                 .entry(feature.clone())
                 .or_default()
-                .add_spec(target_spec.as_ref());
+                .add_spec(target_spec);
         }
-        Ok(())
     }
 }
 
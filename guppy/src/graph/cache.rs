@@ -0,0 +1,907 @@
+// Copyright (c) The cargo-guppy Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Binary on-disk caches for `PackageGraph` instances.
+//!
+//! The cache stores the data `PackageGraph::build` produces from `cargo metadata` output in a
+//! compact form, so that it can be loaded back without invoking Cargo. The feature graph and other
+//! derived data are not stored: they are computed lazily from the package data as usual.
+//!
+//! The file layout is:
+//!
+//! * the magic bytes `CACHE_MAGIC`
+//! * the format version, a little-endian `u32`
+//! * a bincode-encoded `CacheHeader`, used to check whether the cache is up-to-date and was built
+//!   the same way
+//! * a bincode-encoded `GraphRepr`
+
+use crate::{
+    graph::{
+        ArtifactImpl, BuildTargetImpl, BuildTargetKindImpl, DepRequiredOrOptional,
+        DependencyReqImpl, GitReqImpl, NamedFeatureDep, OverrideImpl, OverrideKind,
+        OverrideSourceImpl, OwnedBuildTargetId, PackageGraph, PackageGraphData, PackageIx,
+        PackageLinkImpl, PackageMetadataImpl, PackagePublishImpl, PackageSourceImpl, WorkspaceImpl,
+    },
+    platform::{target_spec_str, PlatformStatusImpl},
+    sorted_set::SortedSet,
+    Error, PackageId,
+};
+use camino::{Utf8Path, Utf8PathBuf};
+use indexmap::{IndexMap, IndexSet};
+use once_cell::sync::OnceCell;
+use petgraph::prelude::*;
+use semver::{Version, VersionReq};
+use serde::{Deserialize, Serialize};
+use smallvec::SmallVec;
+use std::{
+    collections::{hash_map::DefaultHasher, BTreeMap, HashMap},
+    error, fs,
+    hash::Hasher,
+    io::{self, BufReader, BufWriter, Read, Write},
+};
+use target_spec::TargetSpec;
+
+const CACHE_MAGIC: &[u8; 8] = b"guppygc\0";
+
+/// The version of the cache format. Bump this whenever the representation below changes.
+const CACHE_FORMAT_VERSION: u32 = 4;
+
+type BoxError = Box<dyn error::Error + Send + Sync>;
+
+impl PackageGraph {
+    /// Loads a `PackageGraph` previously written with [`save_cache`](Self::save_cache).
+    ///
+    /// Requires the `cache` feature.
+    ///
+    /// `context` describes how the graph was built, for example the absolute path to the
+    /// workspace's `Cargo.toml` and the options `cargo metadata` was invoked with. It must match
+    /// the `context` the cache was saved with.
+    ///
+    /// Returns `Ok(None)` if there's no cache at `path`, if the cache was written by an
+    /// incompatible version of guppy, if it was saved with a different `context`, or if it is out
+    /// of date. A cache is out of date if the workspace's `Cargo.lock` or the `Cargo.toml` of any
+    /// workspace or path package has changed since it was written.
+    pub fn load_cached(path: impl AsRef<Utf8Path>, context: &str) -> Result<Option<Self>, Error> {
+        let path = path.as_ref();
+        let cache_err = |err: BoxError| Error::GraphCacheError(path.to_path_buf(), err);
+
+        let file = match fs::File::open(path) {
+            Ok(file) => file,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(cache_err(err.into())),
+        };
+        let mut reader = BufReader::new(file);
+
+        let mut magic = [0; 8];
+        reader
+            .read_exact(&mut magic)
+            .map_err(|err| cache_err(err.into()))?;
+        if &magic != CACHE_MAGIC {
+            return Err(cache_err("not a package graph cache".into()));
+        }
+        let mut version = [0; 4];
+        reader
+            .read_exact(&mut version)
+            .map_err(|err| cache_err(err.into()))?;
+        if u32::from_le_bytes(version) != CACHE_FORMAT_VERSION {
+            return Ok(None);
+        }
+
+        let header: CacheHeader =
+            bincode::deserialize_from(&mut reader).map_err(|err| cache_err(err))?;
+        if header.context != context {
+            return Ok(None);
+        }
+        if hash_inputs(&header.inputs).map_err(cache_err)? != header.key {
+            return Ok(None);
+        }
+
+        let repr: GraphRepr =
+            bincode::deserialize_from(&mut reader).map_err(|err| cache_err(err))?;
+        let graph = repr.into_graph().map_err(cache_err)?;
+        Ok(Some(graph))
+    }
+
+    /// Loads a `PackageGraph` from the cache at `path` if it's up-to-date and was saved with the
+    /// same `context`, and otherwise builds it with `build_fn` and writes it to the cache.
+    ///
+    /// Failures to read or write the cache are not fatal: they're passed to `warn_fn`, and the
+    /// graph is built or returned as usual. Errors returned by `build_fn` are passed through.
+    ///
+    /// Requires the `cache` feature.
+    pub fn load_or_build_cached<E>(
+        path: impl AsRef<Utf8Path>,
+        context: &str,
+        build_fn: impl FnOnce() -> Result<Self, E>,
+        mut warn_fn: impl FnMut(&Error),
+    ) -> Result<Self, E> {
+        let path = path.as_ref();
+        match Self::load_cached(path, context) {
+            Ok(Some(graph)) => return Ok(graph),
+            Ok(None) => {}
+            Err(err) => warn_fn(&err),
+        }
+        let graph = build_fn()?;
+        if let Err(err) = graph.save_cache(path, context) {
+            warn_fn(&err);
+        }
+        Ok(graph)
+    }
+
+    /// Writes this `PackageGraph` to a binary cache at `path`, which can later be read back with
+    /// [`load_cached`](Self::load_cached) with the same `context`.
+    ///
+    /// The cache is keyed on `context` and on the contents of the workspace's `Cargo.lock` and the `Cargo.toml` of
+    /// every workspace and path package. It is written to a temporary file first and then moved
+    /// into place, so readers never observe a partially written cache.
+    ///
+    /// Requires the `cache` feature.
+    pub fn save_cache(&self, path: impl AsRef<Utf8Path>, context: &str) -> Result<(), Error> {
+        let path = path.as_ref();
+        let temp_path = Utf8PathBuf::from(format!("{}.tmp", path));
+
+        let write = || -> Result<(), BoxError> {
+            let inputs = self.cache_inputs();
+            let header = CacheHeader {
+                context: context.to_owned(),
+                key: hash_inputs(&inputs)?,
+                inputs,
+            };
+            let repr = GraphRepr::new(self);
+
+            let mut writer = BufWriter::new(fs::File::create(&temp_path)?);
+            writer.write_all(CACHE_MAGIC)?;
+            writer.write_all(&CACHE_FORMAT_VERSION.to_le_bytes())?;
+            bincode::serialize_into(&mut writer, &header)?;
+            bincode::serialize_into(&mut writer, &repr)?;
+            writer.flush()?;
+            drop(writer);
+            fs::rename(&temp_path, path)?;
+            Ok(())
+        };
+
+        write().map_err(|err| {
+            // Don't leave a partially written file behind. Errors here are irrelevant: the file
+            // may not even have been created.
+            let _ = fs::remove_file(&temp_path);
+            Error::GraphCacheError(path.to_path_buf(), err)
+        })
+    }
+
+    /// Returns the files the cache for this graph is keyed on, in sorted order.
+    fn cache_inputs(&self) -> Vec<String> {
+        let root = &self.data.workspace.root;
+        let mut inputs: Vec<String> = self
+            .data
+            .packages
+            .values()
+            .filter(|package| {
+                matches!(
+                    package.source,
+                    PackageSourceImpl::Workspace(_) | PackageSourceImpl::Path(_)
+                )
+            })
+            .map(|package| package.manifest_path.to_string())
+            .chain([
+                root.join("Cargo.toml").into_string(),
+                root.join("Cargo.lock").into_string(),
+            ])
+            .collect();
+        inputs.sort_unstable();
+        inputs.dedup();
+        inputs
+    }
+}
+
+/// Hashes the paths and contents of the given files. Missing files are hashed as such, so that
+/// creating them later invalidates the cache.
+fn hash_inputs(inputs: &[String]) -> Result<u64, BoxError> {
+    let mut hasher = DefaultHasher::new();
+    for input in inputs {
+        hasher.write(input.as_bytes());
+        hasher.write_u8(0);
+        match fs::read(input) {
+            Ok(contents) => {
+                hasher.write_u8(1);
+                hasher.write_usize(contents.len());
+                hasher.write(&contents);
+            }
+            Err(err) if err.kind() == io::ErrorKind::NotFound => hasher.write_u8(0),
+            Err(err) => return Err(format!("failed to read {}: {}", input, err).into()),
+        }
+    }
+    Ok(hasher.finish())
+}
+
+#[derive(Deserialize, Serialize)]
+struct CacheHeader {
+    context: String,
+    key: u64,
+    inputs: Vec<String>,
+}
+
+// ---
+// Graph representation
+// ---
+
+// Paths, versions, version requirements and target specs are stored as strings and parsed again on
+// load. JSON metadata tables are stored as serialized JSON, since bincode can't represent
+// self-describing data.
+
+#[derive(Deserialize, Serialize)]
+struct GraphRepr {
+    workspace: WorkspaceRepr,
+    // Packages are stored in node index order, and links in edge index order, so that indexes are
+    // preserved across a save and load.
+    packages: Vec<PackageRepr>,
+    links: Vec<LinkRepr>,
+}
+
+impl GraphRepr {
+    fn new(graph: &PackageGraph) -> Self {
+        let workspace = &graph.data.workspace;
+        let workspace = WorkspaceRepr {
+            root: workspace.root.to_string(),
+            target_directory: workspace.target_directory.to_string(),
+            metadata_table: workspace.metadata_table.to_string(),
+            members: workspace
+                .members_by_path
+                .values()
+                .map(|id| id.repr().to_owned())
+                .collect(),
+            overrides: workspace.overrides.iter().map(OverrideRepr::new).collect(),
+        };
+
+        let packages = graph
+            .dep_graph
+            .node_weights()
+            .map(|package_id| {
+                let package = graph
+                    .data
+                    .metadata_impl(package_id)
+                    .expect("every node has associated metadata");
+                PackageRepr::new(package_id, package)
+            })
+            .collect();
+        let links = graph
+            .dep_graph
+            .edge_references()
+            .map(|edge| LinkRepr::new(edge.source(), edge.target(), edge.weight()))
+            .collect();
+
+        Self {
+            workspace,
+            packages,
+            links,
+        }
+    }
+
+    fn into_graph(self) -> Result<PackageGraph, BoxError> {
+        let mut dep_graph = Graph::with_capacity(self.packages.len(), self.links.len());
+        let mut packages = HashMap::with_capacity(self.packages.len());
+        for package in self.packages {
+            let package_id = PackageId::new(package.id.as_str());
+            let package_ix = dep_graph.add_node(package_id.clone());
+            packages.insert(package_id, package.into_impl(package_ix)?);
+        }
+        for link in self.links {
+            let from = NodeIndex::<PackageIx>::new(link.from as usize);
+            let to = NodeIndex::<PackageIx>::new(link.to as usize);
+            if from.index() >= dep_graph.node_count() || to.index() >= dep_graph.node_count() {
+                return Err("link refers to unknown package".into());
+            }
+            dep_graph.add_edge(from, to, link.into_impl()?);
+        }
+
+        let workspace = self.workspace;
+        let mut workspace_impl = WorkspaceImpl::new(
+            workspace.root,
+            workspace.target_directory,
+            serde_json::from_str(&workspace.metadata_table)?,
+            &packages,
+            workspace.members.into_iter().map(PackageId::new),
+        )?;
+        workspace_impl.overrides = workspace
+            .overrides
+            .into_iter()
+            .map(OverrideRepr::into_impl)
+            .collect::<Result<_, _>>()?;
+
+        Ok(PackageGraph {
+            dep_graph,
+            sccs: OnceCell::new(),
+            feature_graph: OnceCell::new(),
+            data: PackageGraphData {
+                packages,
+                workspace: workspace_impl,
+            },
+        })
+    }
+}
+
+#[derive(Deserialize, Serialize)]
+struct WorkspaceRepr {
+    root: String,
+    target_directory: String,
+    metadata_table: String,
+    members: Vec<String>,
+    overrides: Vec<OverrideRepr>,
+}
+
+#[derive(Deserialize, Serialize)]
+struct OverrideRepr {
+    is_replace: bool,
+    name: String,
+    original_source: String,
+    replaced_version: Option<String>,
+    replacement: OverrideSourceRepr,
+    package_ids: Vec<String>,
+}
+
+impl OverrideRepr {
+    fn new(override_: &OverrideImpl) -> Self {
+        Self {
+            is_replace: override_.kind == OverrideKind::Replace,
+            name: override_.name.to_string(),
+            original_source: override_.original_source.to_string(),
+            replaced_version: override_
+                .replaced_version
+                .as_ref()
+                .map(|version| version.to_string()),
+            replacement: OverrideSourceRepr::new(&override_.replacement),
+            package_ids: override_
+                .package_ids
+                .iter()
+                .map(|id| id.repr().to_owned())
+                .collect(),
+        }
+    }
+
+    fn into_impl(self) -> Result<OverrideImpl, BoxError> {
+        Ok(OverrideImpl {
+            kind: if self.is_replace {
+                OverrideKind::Replace
+            } else {
+                OverrideKind::Patch
+            },
+            name: self.name.into_boxed_str(),
+            original_source: self.original_source.into_boxed_str(),
+            replaced_version: self
+                .replaced_version
+                .map(|version| Version::parse(&version))
+                .transpose()?,
+            replacement: self.replacement.into_impl(),
+            package_ids: self.package_ids.into_iter().map(PackageId::new).collect(),
+        })
+    }
+}
+
+#[derive(Deserialize, Serialize)]
+enum OverrideSourceRepr {
+    Path(String),
+    Branch(String, String),
+    Tag(String, String),
+    Rev(String, String),
+    DefaultBranch(String),
+    Registry {
+        registry: Option<String>,
+        version: Option<String>,
+    },
+}
+
+impl OverrideSourceRepr {
+    fn new(source: &OverrideSourceImpl) -> Self {
+        match source {
+            OverrideSourceImpl::Path(path) => Self::Path(path.to_string()),
+            OverrideSourceImpl::Git { repository, req } => {
+                let repository = repository.to_string();
+                match req {
+                    GitReqImpl::Branch(branch) => Self::Branch(repository, branch.to_string()),
+                    GitReqImpl::Tag(tag) => Self::Tag(repository, tag.to_string()),
+                    GitReqImpl::Rev(rev) => Self::Rev(repository, rev.to_string()),
+                    GitReqImpl::Default => Self::DefaultBranch(repository),
+                }
+            }
+            OverrideSourceImpl::Registry { registry, version } => Self::Registry {
+                registry: registry.as_ref().map(|registry| registry.to_string()),
+                version: version.as_ref().map(|version| version.to_string()),
+            },
+        }
+    }
+
+    fn into_impl(self) -> OverrideSourceImpl {
+        let git = |repository: String, req| OverrideSourceImpl::Git {
+            repository: repository.into_boxed_str(),
+            req,
+        };
+        match self {
+            Self::Path(path) => OverrideSourceImpl::Path(Utf8PathBuf::from(path).into_boxed_path()),
+            Self::Branch(repository, branch) => {
+                git(repository, GitReqImpl::Branch(branch.into_boxed_str()))
+            }
+            Self::Tag(repository, tag) => git(repository, GitReqImpl::Tag(tag.into_boxed_str())),
+            Self::Rev(repository, rev) => git(repository, GitReqImpl::Rev(rev.into_boxed_str())),
+            Self::DefaultBranch(repository) => git(repository, GitReqImpl::Default),
+            Self::Registry { registry, version } => OverrideSourceImpl::Registry {
+                registry: registry.map(String::into_boxed_str),
+                version: version.map(String::into_boxed_str),
+            },
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize)]
+struct PackageRepr {
+    id: String,
+    name: String,
+    version: String,
+    authors: Vec<String>,
+    description: Option<String>,
+    license: Option<String>,
+    license_file: Option<String>,
+    manifest_path: String,
+    categories: Vec<String>,
+    keywords: Vec<String>,
+    readme: Option<String>,
+    repository: Option<String>,
+    homepage: Option<String>,
+    documentation: Option<String>,
+    edition: String,
+    metadata_table: String,
+    links: Option<String>,
+    // None means that publishing is unrestricted.
+    publish: Option<Vec<String>>,
+    default_run: Option<String>,
+    rust_version: Option<String>,
+    named_features: Vec<(String, Vec<NamedFeatureDepRepr>)>,
+    optional_deps: Vec<String>,
+    source: PackageSourceRepr,
+    build_targets: Vec<(BuildTargetIdRepr, BuildTargetRepr)>,
+    has_default_feature: bool,
+}
+
+impl PackageRepr {
+    fn new(package_id: &PackageId, package: &PackageMetadataImpl) -> Self {
+        let to_string = |s: &Option<Box<str>>| s.as_ref().map(|s| s.to_string());
+        let path_to_string = |s: &Option<Box<Utf8Path>>| s.as_ref().map(|s| s.to_string());
+
+        Self {
+            id: package_id.repr().to_owned(),
+            name: package.name.clone(),
+            version: package.version.to_string(),
+            authors: package.authors.clone(),
+            description: to_string(&package.description),
+            license: to_string(&package.license),
+            license_file: path_to_string(&package.license_file),
+            manifest_path: package.manifest_path.to_string(),
+            categories: package.categories.clone(),
+            keywords: package.keywords.clone(),
+            readme: path_to_string(&package.readme),
+            repository: to_string(&package.repository),
+            homepage: to_string(&package.homepage),
+            documentation: to_string(&package.documentation),
+            edition: package.edition.to_string(),
+            metadata_table: package.metadata_table.to_string(),
+            links: to_string(&package.links),
+            publish: match &package.publish {
+                PackagePublishImpl::Unrestricted => None,
+                PackagePublishImpl::Registries(registries) => Some(registries.to_vec()),
+            },
+            default_run: to_string(&package.default_run),
+            rust_version: package.rust_version.as_ref().map(|req| req.to_string()),
+            named_features: package
+                .named_features
+                .iter()
+                .map(|(name, deps)| {
+                    let deps = deps.iter().map(NamedFeatureDepRepr::new).collect();
+                    (name.to_string(), deps)
+                })
+                .collect(),
+            optional_deps: package
+                .optional_deps
+                .iter()
+                .map(|dep| dep.to_string())
+                .collect(),
+            source: PackageSourceRepr::new(&package.source),
+            build_targets: package
+                .build_targets
+                .iter()
+                .map(|(id, target)| (BuildTargetIdRepr::new(id), BuildTargetRepr::new(target)))
+                .collect(),
+            has_default_feature: package.has_default_feature,
+        }
+    }
+
+    fn into_impl(self, package_ix: NodeIndex<PackageIx>) -> Result<PackageMetadataImpl, BoxError> {
+        let into_box = |s: Option<String>| s.map(String::into_boxed_str);
+        let into_path = |s: Option<String>| s.map(|s| Utf8PathBuf::from(s).into_boxed_path());
+
+        Ok(PackageMetadataImpl {
+            name: self.name,
+            version: Version::parse(&self.version)?,
+            authors: self.authors,
+            description: into_box(self.description),
+            license: into_box(self.license),
+            license_file: into_path(self.license_file),
+            manifest_path: Utf8PathBuf::from(self.manifest_path).into_boxed_path(),
+            categories: self.categories,
+            keywords: self.keywords,
+            readme: into_path(self.readme),
+            repository: into_box(self.repository),
+            homepage: into_box(self.homepage),
+            documentation: into_box(self.documentation),
+            edition: self.edition.into_boxed_str(),
+            metadata_table: serde_json::from_str(&self.metadata_table)?,
+            links: into_box(self.links),
+            publish: match self.publish {
+                None => PackagePublishImpl::Unrestricted,
+                Some(registries) => PackagePublishImpl::Registries(registries.into_boxed_slice()),
+            },
+            default_run: into_box(self.default_run),
+            rust_version: self
+                .rust_version
+                .map(|req| VersionReq::parse(&req))
+                .transpose()?,
+            named_features: self
+                .named_features
+                .into_iter()
+                .map(|(name, deps)| {
+                    let deps: SmallVec<_> = deps
+                        .into_iter()
+                        .map(NamedFeatureDepRepr::into_impl)
+                        .collect();
+                    (name.into_boxed_str(), deps)
+                })
+                .collect::<IndexMap<_, _>>(),
+            optional_deps: self
+                .optional_deps
+                .into_iter()
+                .map(String::into_boxed_str)
+                .collect::<IndexSet<_>>(),
+            package_ix,
+            source: self.source.into_impl(),
+            build_targets: self
+                .build_targets
+                .into_iter()
+                .map(|(id, target)| (id.into_impl(), target.into_impl()))
+                .collect::<BTreeMap<_, _>>(),
+            has_default_feature: self.has_default_feature,
+        })
+    }
+}
+
+#[derive(Deserialize, Serialize)]
+enum NamedFeatureDepRepr {
+    NamedFeature(String),
+    OptionalDependency(String),
+    DependencyNamedFeature {
+        dep_name: String,
+        feature: String,
+        weak: bool,
+    },
+}
+
+impl NamedFeatureDepRepr {
+    fn new(dep: &NamedFeatureDep) -> Self {
+        match dep {
+            NamedFeatureDep::NamedFeature(name) => Self::NamedFeature(name.to_string()),
+            NamedFeatureDep::OptionalDependency(name) => Self::OptionalDependency(name.to_string()),
+            NamedFeatureDep::DependencyNamedFeature {
+                dep_name,
+                feature,
+                weak,
+            } => Self::DependencyNamedFeature {
+                dep_name: dep_name.to_string(),
+                feature: feature.to_string(),
+                weak: *weak,
+            },
+        }
+    }
+
+    fn into_impl(self) -> NamedFeatureDep {
+        match self {
+            Self::NamedFeature(name) => NamedFeatureDep::NamedFeature(name.into_boxed_str()),
+            Self::OptionalDependency(name) => {
+                NamedFeatureDep::OptionalDependency(name.into_boxed_str())
+            }
+            Self::DependencyNamedFeature {
+                dep_name,
+                feature,
+                weak,
+            } => NamedFeatureDep::DependencyNamedFeature {
+                dep_name: dep_name.into_boxed_str(),
+                feature: feature.into_boxed_str(),
+                weak,
+            },
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize)]
+enum PackageSourceRepr {
+    Workspace(String),
+    Path(String),
+    CratesIo,
+    External(String),
+}
+
+impl PackageSourceRepr {
+    fn new(source: &PackageSourceImpl) -> Self {
+        match source {
+            PackageSourceImpl::Workspace(path) => Self::Workspace(path.to_string()),
+            PackageSourceImpl::Path(path) => Self::Path(path.to_string()),
+            PackageSourceImpl::CratesIo => Self::CratesIo,
+            PackageSourceImpl::External(source) => Self::External(source.to_string()),
+        }
+    }
+
+    fn into_impl(self) -> PackageSourceImpl {
+        match self {
+            Self::Workspace(path) => {
+                PackageSourceImpl::Workspace(Utf8PathBuf::from(path).into_boxed_path())
+            }
+            Self::Path(path) => PackageSourceImpl::Path(Utf8PathBuf::from(path).into_boxed_path()),
+            Self::CratesIo => PackageSourceImpl::CratesIo,
+            Self::External(source) => PackageSourceImpl::External(source.into_boxed_str()),
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize)]
+enum BuildTargetIdRepr {
+    Library,
+    BuildScript,
+    Binary(String),
+    Example(String),
+    Test(String),
+    Benchmark(String),
+}
+
+impl BuildTargetIdRepr {
+    fn new(id: &OwnedBuildTargetId) -> Self {
+        match id {
+            OwnedBuildTargetId::Library => Self::Library,
+            OwnedBuildTargetId::BuildScript => Self::BuildScript,
+            OwnedBuildTargetId::Binary(name) => Self::Binary(name.to_string()),
+            OwnedBuildTargetId::Example(name) => Self::Example(name.to_string()),
+            OwnedBuildTargetId::Test(name) => Self::Test(name.to_string()),
+            OwnedBuildTargetId::Benchmark(name) => Self::Benchmark(name.to_string()),
+        }
+    }
+
+    fn into_impl(self) -> OwnedBuildTargetId {
+        match self {
+            Self::Library => OwnedBuildTargetId::Library,
+            Self::BuildScript => OwnedBuildTargetId::BuildScript,
+            Self::Binary(name) => OwnedBuildTargetId::Binary(name.into_boxed_str()),
+            Self::Example(name) => OwnedBuildTargetId::Example(name.into_boxed_str()),
+            Self::Test(name) => OwnedBuildTargetId::Test(name.into_boxed_str()),
+            Self::Benchmark(name) => OwnedBuildTargetId::Benchmark(name.into_boxed_str()),
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize)]
+struct BuildTargetRepr {
+    kind: BuildTargetKindRepr,
+    lib_name: Option<String>,
+    required_features: Vec<String>,
+    path: String,
+    edition: String,
+    doc_tests: bool,
+}
+
+impl BuildTargetRepr {
+    fn new(target: &BuildTargetImpl) -> Self {
+        Self {
+            kind: match &target.kind {
+                BuildTargetKindImpl::LibraryOrExample(crate_types) => {
+                    BuildTargetKindRepr::LibraryOrExample(crate_types.as_slice().to_vec())
+                }
+                BuildTargetKindImpl::ProcMacro => BuildTargetKindRepr::ProcMacro,
+                BuildTargetKindImpl::Binary => BuildTargetKindRepr::Binary,
+            },
+            lib_name: target.lib_name.as_ref().map(|name| name.to_string()),
+            required_features: target.required_features.clone(),
+            path: target.path.to_string(),
+            edition: target.edition.to_string(),
+            doc_tests: target.doc_tests,
+        }
+    }
+
+    fn into_impl(self) -> BuildTargetImpl {
+        BuildTargetImpl {
+            kind: match self.kind {
+                BuildTargetKindRepr::LibraryOrExample(crate_types) => {
+                    BuildTargetKindImpl::LibraryOrExample(SortedSet::new(crate_types))
+                }
+                BuildTargetKindRepr::ProcMacro => BuildTargetKindImpl::ProcMacro,
+                BuildTargetKindRepr::Binary => BuildTargetKindImpl::Binary,
+            },
+            lib_name: self.lib_name.map(String::into_boxed_str),
+            required_features: self.required_features,
+            path: Utf8PathBuf::from(self.path).into_boxed_path(),
+            edition: self.edition.into_boxed_str(),
+            doc_tests: self.doc_tests,
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize)]
+enum BuildTargetKindRepr {
+    LibraryOrExample(Vec<String>),
+    ProcMacro,
+    Binary,
+}
+
+#[derive(Deserialize, Serialize)]
+struct LinkRepr {
+    from: u32,
+    to: u32,
+    dep_name: String,
+    resolved_name: String,
+    version_req: String,
+    normal: DependencyReqRepr,
+    build: DependencyReqRepr,
+    dev: DependencyReqRepr,
+}
+
+impl LinkRepr {
+    fn new(from: NodeIndex<PackageIx>, to: NodeIndex<PackageIx>, link: &PackageLinkImpl) -> Self {
+        Self {
+            from: from.index() as u32,
+            to: to.index() as u32,
+            dep_name: link.dep_name.clone(),
+            resolved_name: link.resolved_name.clone(),
+            version_req: link.version_req.to_string(),
+            normal: DependencyReqRepr::new(&link.normal),
+            build: DependencyReqRepr::new(&link.build),
+            dev: DependencyReqRepr::new(&link.dev),
+        }
+    }
+
+    fn into_impl(self) -> Result<PackageLinkImpl, BoxError> {
+        Ok(PackageLinkImpl {
+            dep_name: self.dep_name,
+            resolved_name: self.resolved_name,
+            version_req: VersionReq::parse(&self.version_req)?,
+            normal: self.normal.into_impl()?,
+            build: self.build.into_impl()?,
+            dev: self.dev.into_impl()?,
+        })
+    }
+}
+
+#[derive(Deserialize, Serialize)]
+struct DependencyReqRepr {
+    required: DepRequiredOrOptionalRepr,
+    optional: DepRequiredOrOptionalRepr,
+    artifact: Option<ArtifactImpl>,
+}
+
+impl DependencyReqRepr {
+    fn new(req: &DependencyReqImpl) -> Self {
+        Self {
+            required: DepRequiredOrOptionalRepr::new(&req.required),
+            optional: DepRequiredOrOptionalRepr::new(&req.optional),
+            artifact: req.artifact.clone(),
+        }
+    }
+
+    fn into_impl(self) -> Result<DependencyReqImpl, BoxError> {
+        Ok(DependencyReqImpl {
+            required: self.required.into_impl()?,
+            optional: self.optional.into_impl()?,
+            artifact: self.artifact,
+        })
+    }
+}
+
+#[derive(Deserialize, Serialize)]
+struct DepRequiredOrOptionalRepr {
+    build_if: PlatformStatusRepr,
+    default_features_if: PlatformStatusRepr,
+    no_default_features_if: PlatformStatusRepr,
+    feature_targets: Vec<(String, PlatformStatusRepr)>,
+}
+
+impl DepRequiredOrOptionalRepr {
+    fn new(req: &DepRequiredOrOptional) -> Self {
+        Self {
+            build_if: PlatformStatusRepr::new(&req.build_if),
+            default_features_if: PlatformStatusRepr::new(&req.default_features_if),
+            no_default_features_if: PlatformStatusRepr::new(&req.no_default_features_if),
+            feature_targets: req
+                .feature_targets
+                .iter()
+                .map(|(feature, status)| (feature.clone(), PlatformStatusRepr::new(status)))
+                .collect(),
+        }
+    }
+
+    fn into_impl(self) -> Result<DepRequiredOrOptional, BoxError> {
+        Ok(DepRequiredOrOptional {
+            build_if: self.build_if.into_impl()?,
+            default_features_if: self.default_features_if.into_impl()?,
+            no_default_features_if: self.no_default_features_if.into_impl()?,
+            feature_targets: self
+                .feature_targets
+                .into_iter()
+                .map(|(feature, status)| Ok((feature, status.into_impl()?)))
+                .collect::<Result<_, BoxError>>()?,
+        })
+    }
+}
+
+#[derive(Deserialize, Serialize)]
+enum PlatformStatusRepr {
+    Always,
+    Specs(Vec<String>),
+}
+
+impl PlatformStatusRepr {
+    fn new(status: &PlatformStatusImpl) -> Self {
+        match status {
+            PlatformStatusImpl::Always => Self::Always,
+            PlatformStatusImpl::Specs(specs) => Self::Specs(
+                specs
+                    .iter()
+                    .map(|spec| target_spec_str(spec).into_owned())
+                    .collect(),
+            ),
+        }
+    }
+
+    fn into_impl(self) -> Result<PlatformStatusImpl, BoxError> {
+        match self {
+            Self::Always => Ok(PlatformStatusImpl::Always),
+            Self::Specs(specs) => Ok(PlatformStatusImpl::Specs(
+                specs
+                    .into_iter()
+                    .map(TargetSpec::new)
+                    .collect::<Result<_, _>>()?,
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stale_or_invalid_cache() {
+        let temp_dir = tempfile::tempdir().expect("created temp dir");
+        let dir = Utf8Path::from_path(temp_dir.path()).expect("temp dir is UTF-8");
+
+        let path = dir.join("missing.cache");
+        assert!(PackageGraph::load_cached(&path, "test")
+            .expect("missing cache is not an error")
+            .is_none());
+
+        // A header whose key doesn't match its inputs.
+        let header = CacheHeader {
+            context: "test".to_owned(),
+            key: hash_inputs(&[]).expect("hashed no inputs").wrapping_add(1),
+            inputs: vec![],
+        };
+        let mut contents = CACHE_MAGIC.to_vec();
+        contents.extend_from_slice(&CACHE_FORMAT_VERSION.to_le_bytes());
+        contents.extend(bincode::serialize(&header).expect("serialized header"));
+        fs::write(&path, &contents).expect("wrote cache");
+        assert!(PackageGraph::load_cached(&path, "test")
+            .expect("stale cache is not an error")
+            .is_none());
+
+        // A cache from a different format version.
+        let mut contents = CACHE_MAGIC.to_vec();
+        contents.extend_from_slice(&(CACHE_FORMAT_VERSION + 1).to_le_bytes());
+        fs::write(&path, &contents).expect("wrote cache");
+        assert!(PackageGraph::load_cached(&path, "test")
+            .expect("cache from another version is not an error")
+            .is_none());
+
+        fs::write(&path, b"not a cache").expect("wrote cache");
+        PackageGraph::load_cached(&path, "test").expect_err("invalid cache is an error");
+    }
+}
@@ -4,10 +4,15 @@
 use crate::{
     graph::{
         cargo::{
-            CargoIntermediateSet, CargoOptions, CargoResolverVersion, CargoSet, InitialsPlatform,
+            BuildPlatform, CargoIntermediateSet, CargoOptions, CargoResolverVersion, CargoSet,
+            InitialsPlatform,
         },
-        feature::{ConditionalLink, FeatureLabel, FeatureQuery, FeatureSet, StandardFeatures},
-        DependencyDirection, PackageGraph, PackageIx, PackageLink, PackageSet,
+        feature::{
+            ClosureCache, ConditionalLink, FeatureGraph, FeatureLabel, FeatureQuery, FeatureSet,
+            StandardFeatures,
+        },
+        ArtifactTarget, DependencyDirection, FeatureIx, PackageGraph, PackageIx, PackageLink,
+        PackageSet,
     },
     platform::{EnabledTernary, PlatformSpec},
     sorted_set::SortedSet,
@@ -15,10 +20,14 @@ use crate::{
 };
 use fixedbitset::FixedBitSet;
 use petgraph::{prelude::*, visit::VisitMap};
+use std::{cell::RefCell, collections::HashMap, hash::Hash};
 
 pub(super) struct CargoSetBuildState<'a> {
     opts: &'a CargoOptions<'a>,
     omitted_packages: SortedSet<NodeIndex<PackageIx>>,
+    // Only used if several builds are performed with this state.
+    enabled_cache: Option<EnabledCache>,
+    closure_caches: Option<ClosureCaches>,
 }
 
 impl<'a> CargoSetBuildState<'a> {
@@ -32,11 +41,22 @@ impl<'a> CargoSetBuildState<'a> {
         Ok(Self {
             opts,
             omitted_packages,
+            enabled_cache: None,
+            closure_caches: None,
         })
     }
 
+    /// Prepares this build state for several builds with the same options, sharing platform
+    /// evaluations of links and feature closures across them.
+    pub(super) fn with_shared_cache(mut self) -> Self {
+        self.enabled_cache = Some(EnabledCache::default());
+        self.closure_caches = Some(ClosureCaches::default());
+        self
+    }
+
+    /// Builds a `CargoSet`.
     pub(super) fn build<'g>(
-        self,
+        &self,
         initials: FeatureSet<'g>,
         features_only: FeatureSet<'g>,
     ) -> CargoSet<'g> {
@@ -50,31 +70,36 @@ impl<'a> CargoSetBuildState<'a> {
         }
     }
 
-    pub(super) fn build_intermediate(self, query: FeatureQuery) -> CargoIntermediateSet {
+    pub(super) fn build_intermediate<'g>(
+        &self,
+        query: FeatureQuery<'g>,
+    ) -> CargoIntermediateSet<'g> {
+        // Provenance isn't exposed for intermediate sets.
+        let mut predecessors = FeaturePredecessors::default();
         match self.opts.resolver {
-            CargoResolverVersion::V1 => self.new_v1_intermediate(query, false),
+            CargoResolverVersion::V1 => self.new_v1_intermediate(query, false, &mut predecessors),
             CargoResolverVersion::V1Install => {
                 let avoid_dev_deps = !self.opts.include_dev;
-                self.new_v1_intermediate(query, avoid_dev_deps)
+                self.new_v1_intermediate(query, avoid_dev_deps, &mut predecessors)
             }
-            CargoResolverVersion::V2 => self.new_v2_intermediate(query),
+            CargoResolverVersion::V2 => self.new_v2_intermediate(query, &mut predecessors),
         }
     }
 
     fn new_v1<'g>(
-        self,
+        &self,
         initials: FeatureSet<'g>,
         features_only: FeatureSet<'g>,
         avoid_dev_deps: bool,
     ) -> CargoSet<'g> {
-        self.build_set(initials, features_only, |query| {
-            self.new_v1_intermediate(query, avoid_dev_deps)
+        self.build_set(initials, features_only, |query, predecessors| {
+            self.new_v1_intermediate(query, avoid_dev_deps, predecessors)
         })
     }
 
-    fn new_v2<'g>(self, initials: FeatureSet<'g>, features_only: FeatureSet<'g>) -> CargoSet<'g> {
-        self.build_set(initials, features_only, |query| {
-            self.new_v2_intermediate(query)
+    fn new_v2<'g>(&self, initials: FeatureSet<'g>, features_only: FeatureSet<'g>) -> CargoSet<'g> {
+        self.build_set(initials, features_only, |query, predecessors| {
+            self.new_v2_intermediate(query, predecessors)
         })
     }
 
@@ -86,11 +111,127 @@ impl<'a> CargoSetBuildState<'a> {
         self.omitted_packages.contains(&package_ix)
     }
 
+    fn platform_spec(&self, build_platform: BuildPlatform) -> &PlatformSpec {
+        match build_platform {
+            BuildPlatform::Target => &self.opts.target_platform,
+            BuildPlatform::Host => &self.opts.host_platform,
+        }
+    }
+
+    /// Returns true if the dependency of this kind is enabled on the platform, considering
+    /// optional dependencies if `consider_optional` is true.
+    fn is_link_enabled(
+        &self,
+        link: &PackageLink<'_>,
+        kind: DependencyKind,
+        build_platform: BuildPlatform,
+        consider_optional: bool,
+    ) -> bool {
+        let compute = || {
+            let req_status = link.req_for_kind(kind).status();
+            let platform_spec = self.platform_spec(build_platform);
+            if consider_optional {
+                req_status.enabled_on(platform_spec) != EnabledTernary::Disabled
+            } else {
+                req_status.required_on(platform_spec) != EnabledTernary::Disabled
+            }
+        };
+        match &self.enabled_cache {
+            Some(cache) => {
+                let key = (link.edge_ix(), kind, build_platform, consider_optional);
+                EnabledCache::get_or_insert(&cache.package_links, key, compute)
+            }
+            None => compute(),
+        }
+    }
+
+    /// Returns the feature that enables this link if it's optional.
+    fn optional_feature_ix(
+        &self,
+        graph: FeatureGraph<'_>,
+        link: &PackageLink<'_>,
+    ) -> Option<NodeIndex<FeatureIx>> {
+        let compute = || {
+            let feature_id = (
+                link.from().id(),
+                FeatureLabel::OptionalDependency(link.dep_name()),
+            );
+            graph
+                .metadata(feature_id)
+                .ok()
+                .map(|metadata| metadata.feature_ix())
+        };
+        match &self.enabled_cache {
+            Some(cache) => {
+                EnabledCache::get_or_insert(&cache.optional_features, link.edge_ix(), compute)
+            }
+            None => compute(),
+        }
+    }
+
+    /// Returns true if the feature dependency of this kind is enabled on the platform.
+    fn is_conditional_link_enabled(
+        &self,
+        link: &ConditionalLink<'_>,
+        kind: DependencyKind,
+        build_platform: BuildPlatform,
+    ) -> bool {
+        let compute = || {
+            link.status_for_kind(kind)
+                .enabled_on(self.platform_spec(build_platform))
+                != EnabledTernary::Disabled
+        };
+        match &self.enabled_cache {
+            Some(cache) => {
+                let key = (link.edge_ix(), kind, build_platform);
+                EnabledCache::get_or_insert(&cache.feature_links, key, compute)
+            }
+            None => compute(),
+        }
+    }
+
+    /// If the dependency of this kind is an artifact dependency built on the other platform from
+    /// the one it would usually be built on, returns whether its library is also requested.
+    ///
+    /// Normal and dev dependencies are usually built on the target, and build dependencies on the
+    /// host. `CargoSet` only models those two platforms, so artifacts requested for some third
+    /// platform are treated as being built on the target.
+    fn artifact_redirect(&self, link: &PackageLink<'_>, kind: DependencyKind) -> Option<bool> {
+        let artifact = link.req_for_kind(kind).artifact()?;
+        let triple_matches = |platform_spec: &PlatformSpec, triple: &str| match platform_spec {
+            PlatformSpec::Platform(platform) => platform.triple_str() == triple,
+            PlatformSpec::Always | PlatformSpec::Any => false,
+        };
+        let build_platform = match artifact.target() {
+            ArtifactTarget::Default => return None,
+            ArtifactTarget::Target => BuildPlatform::Target,
+            ArtifactTarget::Triple(triple) => {
+                let on_host = triple_matches(&self.opts.host_platform, triple);
+                let on_target = triple_matches(&self.opts.target_platform, triple);
+                match (on_host, on_target) {
+                    (true, false) => BuildPlatform::Host,
+                    (false, true) => BuildPlatform::Target,
+                    // The host and target platforms are the same.
+                    (true, true) => return None,
+                    (false, false) => BuildPlatform::Target,
+                }
+            }
+        };
+        let usual_platform = match kind {
+            DependencyKind::Build => BuildPlatform::Host,
+            DependencyKind::Normal | DependencyKind::Development => BuildPlatform::Target,
+        };
+        (build_platform != usual_platform).then(|| artifact.lib())
+    }
+
     fn build_set<'g>(
         &self,
         initials: FeatureSet<'g>,
         features_only: FeatureSet<'g>,
-        intermediate_fn: impl FnOnce(FeatureQuery<'g>) -> CargoIntermediateSet<'g>,
+        intermediate_fn: impl FnOnce(
+            FeatureQuery<'g>,
+            &mut FeaturePredecessors,
+        ) -> CargoIntermediateSet<'g>,
     ) -> CargoSet<'g> {
         // Prepare a package query for step 2.
         let graph = *initials.graph();
@@ -135,8 +276,10 @@ impl<'a> CargoSetBuildState<'a> {
         // 1. Build the intermediate set containing the features for any possible package that can
         // be built, including features-only packages.
         let initials_plus_features_only = initials.union(&features_only);
+        let mut feature_predecessors = FeaturePredecessors::default();
         let intermediate_set = intermediate_fn(
             initials_plus_features_only.to_feature_query(DependencyDirection::Forward),
+            &mut feature_predecessors,
         );
         let (target_set, host_set) = intermediate_set.target_host_sets();
 
@@ -147,35 +290,38 @@ impl<'a> CargoSetBuildState<'a> {
         let mut proc_macro_edge_ixs = Vec::new();
         // This list will contain build dep edges out of target packages.
         let mut build_dep_edge_ixs = Vec::new();
+        // These lists will contain every link followed out of target and host packages, along with
+        // the dependency kind and the platform the dependency is built on.
+        let mut followed_links = Vec::new();
+        let mut host_followed_links = Vec::new();
 
         let is_enabled = |feature_set: &FeatureSet<'_>,
                           link: &PackageLink<'_>,
                           kind: DependencyKind,
-                          platform_spec: &PlatformSpec| {
+                          build_platform: BuildPlatform| {
             let (from, to) = link.endpoints();
-            let req_status = link.req_for_kind(kind).status();
             // Check the complete set to figure out whether we look at required_on or
             // enabled_on.
-            let consider_optional = feature_set
-                .contains((from.id(), FeatureLabel::OptionalDependency(link.dep_name())))
-                .unwrap_or_else(|_| {
+            let consider_optional = match self.optional_feature_ix(graph, link) {
+                Some(feature_ix) => feature_set.contains_ix(feature_ix),
+                None => {
                     // If the feature ID isn't present, it means the dependency wasn't declared
                     // as optional. In that case the value doesn't matter.
                     debug_assert!(
-                        req_status.optional_status().is_never(),
+                        link.req_for_kind(kind)
+                            .status()
+                            .optional_status()
+                            .is_never(),
                         "for {} -> {}, dep '{}' not declared as optional",
                         from.name(),
                         to.name(),
                         link.dep_name()
                     );
                     false
-                });
+                }
+            };
 
-            if consider_optional {
-                req_status.enabled_on(platform_spec) != EnabledTernary::Disabled
-            } else {
-                req_status.required_on(platform_spec) != EnabledTernary::Disabled
-            }
+            self.is_link_enabled(link, kind, build_platform, consider_optional)
         };
 
         // Record workspace + direct third-party deps in these sets.
@@ -185,8 +331,6 @@ impl<'a> CargoSetBuildState<'a> {
 
         // 2. Figure out what packages will be included on the target platform, i.e. normal + dev
         // (if requested).
-        let target_platform = &self.opts.target_platform;
-        let host_platform = &self.opts.host_platform;
 
         let target_packages = target_query.resolve_with_fn(|query, link| {
             let (from, to) = link.endpoints();
@@ -206,40 +350,93 @@ impl<'a> CargoSetBuildState<'a> {
             // Build dependencies are only considered if there's a build script.
             let consider_build = from.has_build_script();
 
-            let mut follow_target =
-                is_enabled(target_set, &link, DependencyKind::Normal, target_platform)
-                    || (consider_dev
-                        && is_enabled(
-                            target_set,
-                            &link,
-                            DependencyKind::Development,
-                            target_platform,
-                        ));
+            let normal = is_enabled(
+                target_set,
+                &link,
+                DependencyKind::Normal,
+                BuildPlatform::Target,
+            );
+            let dev = consider_dev
+                && is_enabled(
+                    target_set,
+                    &link,
+                    DependencyKind::Development,
+                    BuildPlatform::Target,
+                );
+            // Build dependencies are evaluated against the host platform.
+            let build = consider_build
+                && is_enabled(
+                    target_set,
+                    &link,
+                    DependencyKind::Build,
+                    BuildPlatform::Host,
+                );
+
+            // The platforms that normal and dev dependencies, and build dependencies, are built on.
+            let (mut normal_dev_target, mut normal_dev_host) = (normal || dev, false);
+            let (mut build_target, mut build_host) = (false, build);
 
             // Proc macros build on the host, so for normal/dev dependencies redirect it to the host
             // instead.
-            let proc_macro_redirect = follow_target && to.is_proc_macro();
+            let proc_macro_redirect = normal_dev_target && to.is_proc_macro();
+            if proc_macro_redirect {
+                normal_dev_target = false;
+                normal_dev_host = true;
+            } else if normal_dev_target {
+                // Artifact dependencies may ask to be built on the other platform.
+                if let Some(lib) = self.artifact_redirect(&link, DependencyKind::Normal) {
+                    // The artifacts are built on the host, and the library (if requested) on the
+                    // target.
+                    normal_dev_target = lib;
+                    normal_dev_host = true;
+                }
+            }
+            if build {
+                if let Some(lib) = self.artifact_redirect(&link, DependencyKind::Build) {
+                    // The artifacts are built on the target, and the library (if requested) on
+                    // the host.
+                    build_target = !proc_macro_redirect;
+                    build_host = lib;
+                }
+            }
 
-            // Build dependencies are evaluated against the host platform.
-            let build_dep_redirect = consider_build
-                && is_enabled(target_set, &link, DependencyKind::Build, host_platform);
+            // Record the links followed, by kind and platform.
+            for (kind, enabled) in [
+                (DependencyKind::Normal, normal),
+                (DependencyKind::Development, dev),
+            ] {
+                if enabled {
+                    if normal_dev_target {
+                        followed_links.push((link.edge_ix(), kind, BuildPlatform::Target));
+                    }
+                    if normal_dev_host {
+                        followed_links.push((link.edge_ix(), kind, BuildPlatform::Host));
+                    }
+                }
+            }
+            if build_target {
+                followed_links.push((link.edge_ix(), DependencyKind::Build, BuildPlatform::Target));
+            }
+            if build_host {
+                followed_links.push((link.edge_ix(), DependencyKind::Build, BuildPlatform::Host));
+            }
 
             // Finally, process what needs to be done.
-            if build_dep_redirect || proc_macro_redirect {
+            if normal_dev_host || build_host {
                 if from.in_workspace() {
                     // The 'to' node is either in the workspace or a direct dependency [a].
                     host_direct_deps.visit(to.package_ix());
                 }
                 host_ixs.push(to.package_ix());
             }
-            if build_dep_redirect {
+            if build_host {
                 build_dep_edge_ixs.push(link.edge_ix());
             }
             if proc_macro_redirect {
                 proc_macro_edge_ixs.push(link.edge_ix());
-                follow_target = false;
             }
 
+            let follow_target = normal_dev_target || build_target;
             if from.in_workspace() && follow_target {
                 // The 'to' node is either in the workspace or a direct dependency.
                 target_direct_deps.visit(to.package_ix());
@@ -266,11 +463,26 @@ impl<'a> CargoSetBuildState<'a> {
 
                 // Only normal and build dependencies are considered, regardless of whether this is
                 // an initial. (Dev-dependencies of initials would have been considered in step 2).
-                let res = is_enabled(host_set, &link, DependencyKind::Normal, host_platform)
-                    || (consider_build
-                        && is_enabled(host_set, &link, DependencyKind::Build, host_platform));
+                let normal =
+                    is_enabled(host_set, &link, DependencyKind::Normal, BuildPlatform::Host);
+                let build = consider_build
+                    && is_enabled(host_set, &link, DependencyKind::Build, BuildPlatform::Host);
+                if normal {
+                    host_followed_links.push((
+                        link.edge_ix(),
+                        DependencyKind::Normal,
+                        BuildPlatform::Host,
+                    ));
+                }
+                if build {
+                    host_followed_links.push((
+                        link.edge_ix(),
+                        DependencyKind::Build,
+                        BuildPlatform::Host,
+                    ));
+                }
 
-                if res {
+                if normal || build {
                     if from.in_workspace() {
                         // The 'to' node is either in the workspace or a direct dependency.
                         host_direct_deps.visit(to.package_ix());
@@ -304,6 +516,9 @@ impl<'a> CargoSetBuildState<'a> {
             host_direct_deps,
             proc_macro_edge_ixs: SortedSet::new(proc_macro_edge_ixs),
             build_dep_edge_ixs: SortedSet::new(build_dep_edge_ixs),
+            target_followed_links: SortedSet::new(followed_links),
+            host_followed_links: SortedSet::new(host_followed_links),
+            feature_predecessors,
         }
     }
 
@@ -311,30 +526,54 @@ impl<'a> CargoSetBuildState<'a> {
         &self,
         query: FeatureQuery<'g>,
         avoid_dev_deps: bool,
+        predecessors: &mut FeaturePredecessors,
     ) -> CargoIntermediateSet<'g> {
         // Perform a "complete" feature query. This will provide more packages than will be
         // included in the final build, but for each package it will have the correct feature set.
-        let complete_set = query.resolve_with_fn(|query, link| {
+        let accept = |link: ConditionalLink<'g>, from_initial: bool| {
             if self.is_omitted(link.to().package_ix()) {
                 // Pretend that the omitted set doesn't exist.
                 false
-            } else if !avoid_dev_deps
-                && query
-                    .starts_from(link.from().feature_id())
-                    .expect("valid ID")
-            {
+            } else if !avoid_dev_deps && from_initial {
                 // Follow everything for initials.
                 true
             } else {
                 // Follow normal and build edges for everything else.
                 !link.dev_only()
             }
-        });
+        };
+        let complete_set = match &self.closure_caches {
+            Some(caches) => {
+                let (complete_set, _) = caches.unified.resolve(
+                    *query.graph(),
+                    query.params.initials().iter().copied(),
+                    |feature_ix| query.params.has_initial(feature_ix),
+                    |link, from_initial, _| accept(link, from_initial),
+                    &mut predecessors.target,
+                );
+                complete_set
+            }
+            None => query.resolve_with_fn_tracking(
+                |query, link| {
+                    let from_initial = query
+                        .starts_from(link.from().feature_id())
+                        .expect("valid ID");
+                    accept(link, from_initial)
+                },
+                &mut predecessors.target,
+            ),
+        };
+        // Features are unified across the target and the host.
+        predecessors.host = predecessors.target.clone();
 
         CargoIntermediateSet::Unified(complete_set)
     }
 
-    fn new_v2_intermediate<'g>(&self, query: FeatureQuery<'g>) -> CargoIntermediateSet<'g> {
+    fn new_v2_intermediate<'g>(
+        &self,
+        query: FeatureQuery<'g>,
+        predecessors: &mut FeaturePredecessors,
+    ) -> CargoIntermediateSet<'g> {
         let graph = *query.graph();
         // Note that proc macros specified in initials take part in feature resolution
         // for both target and host ixs. If they didn't, then the query would be partitioned into
@@ -359,9 +598,8 @@ impl<'a> CargoSetBuildState<'a> {
             .collect();
 
         let is_enabled =
-            |link: &ConditionalLink<'_>, kind: DependencyKind, platform_spec: &PlatformSpec| {
-                let platform_status = link.status_for_kind(kind);
-                platform_status.enabled_on(platform_spec) != EnabledTernary::Disabled
+            |link: &ConditionalLink<'_>, kind: DependencyKind, build_platform: BuildPlatform| {
+                self.is_conditional_link_enabled(link, kind, build_platform)
             };
 
         let target_query = if self.opts.initials_platform == InitialsPlatform::Host {
@@ -375,64 +613,212 @@ impl<'a> CargoSetBuildState<'a> {
         let target_query_2 = target_query.clone();
 
         // 1. Perform a feature query for the target.
-        let target_platform = &self.opts.target_platform;
-        let host_platform = &self.opts.host_platform;
-        let target = target_query.resolve_with_fn(|query, link| {
-            let (from, to) = link.endpoints();
+        let mut host_seeds = Vec::new();
+        let target = match &self.closure_caches {
+            Some(caches) => {
+                let (target, notes) = caches.target.resolve(
+                    graph,
+                    target_query.params.initials().iter().copied(),
+                    |feature_ix| target_query.params.has_initial(feature_ix),
+                    |link, from_initial, notes| self.accept_v2_target(link, from_initial, notes),
+                    &mut predecessors.target,
+                );
+                host_seeds = notes;
+                target
+            }
+            None => target_query.resolve_with_fn_tracking(
+                |query, link| {
+                    let from_initial = query
+                        .starts_from(link.from().feature_id())
+                        .expect("valid ID");
+                    self.accept_v2_target(link, from_initial, &mut host_seeds)
+                },
+                &mut predecessors.target,
+            ),
+        };
+        for (feature_ix, edge_ix) in host_seeds {
+            host_ixs.push(feature_ix);
+            predecessors
+                .host_from_target
+                .entry(feature_ix)
+                .or_insert(edge_ix);
+        }
 
-            if self.is_omitted(to.package_ix()) {
+        // 2. Perform a feature query for the host.
+        let accept = |link: ConditionalLink<'g>, from_initial: bool| {
+            if self.is_omitted(link.to().package_ix()) {
                 // Pretend that the omitted set doesn't exist.
                 return false;
             }
+            // During feature resolution, the v2 resolver doesn't check for whether this package
+            // has a build script. It also unifies dev dependencies of initials, even on the host
+            // platform.
+            let consider_dev = self.opts.include_dev && from_initial;
 
-            let consider_dev =
-                self.opts.include_dev && query.starts_from(from.feature_id()).expect("valid ID");
-            // This resolver doesn't check for whether this package has a build script.
-            let mut follow_target = is_enabled(&link, DependencyKind::Normal, target_platform)
+            is_enabled(&link, DependencyKind::Normal, BuildPlatform::Host)
+                || is_enabled(&link, DependencyKind::Build, BuildPlatform::Host)
                 || (consider_dev
-                    && is_enabled(&link, DependencyKind::Development, target_platform));
+                    && is_enabled(&link, DependencyKind::Development, BuildPlatform::Host))
+        };
+        let host_ixs = SortedSet::new(host_ixs);
+        let host = match &self.closure_caches {
+            Some(caches) => {
+                let (host, _) = caches.host.resolve(
+                    graph,
+                    host_ixs.iter().copied(),
+                    |feature_ix| target_query_2.params.has_initial(feature_ix),
+                    |link, from_initial, _| accept(link, from_initial),
+                    &mut predecessors.host,
+                );
+                host
+            }
+            None => graph
+                .query_from_parts(host_ixs, DependencyDirection::Forward)
+                .resolve_with_fn_tracking(
+                    |_, link| {
+                        let from_initial = target_query_2
+                            .starts_from(link.from().feature_id())
+                            .expect("valid ID");
+                        accept(link, from_initial)
+                    },
+                    &mut predecessors.host,
+                ),
+        };
 
-            // Proc macros build on the host, so for normal/dev dependencies redirect it to the host
-            // instead.
-            let proc_macro_redirect = follow_target && to.package().is_proc_macro();
+        CargoIntermediateSet::TargetHost { target, host }
+    }
 
-            // Build dependencies are evaluated against the host platform.
-            let build_dep_redirect = is_enabled(&link, DependencyKind::Build, host_platform);
+    /// Returns true if this link is followed on the target during v2 feature resolution.
+    ///
+    /// Features that are built on the host because of this link are recorded in `host_seeds`,
+    /// along with the link's edge.
+    fn accept_v2_target(
+        &self,
+        link: ConditionalLink<'_>,
+        from_initial: bool,
+        host_seeds: &mut Vec<HostSeed>,
+    ) -> bool {
+        let to = link.to();
+
+        if self.is_omitted(to.package_ix()) {
+            // Pretend that the omitted set doesn't exist.
+            return false;
+        }
 
-            // Finally, process what needs to be done.
-            if build_dep_redirect || proc_macro_redirect {
-                host_ixs.push(to.feature_ix());
+        let is_enabled =
+            |link: &ConditionalLink<'_>, kind: DependencyKind, build_platform: BuildPlatform| {
+                self.is_conditional_link_enabled(link, kind, build_platform)
+            };
+
+        let consider_dev = self.opts.include_dev && from_initial;
+        // This resolver doesn't check for whether this package has a build script.
+        let mut follow_target = is_enabled(&link, DependencyKind::Normal, BuildPlatform::Target)
+            || (consider_dev
+                && is_enabled(&link, DependencyKind::Development, BuildPlatform::Target));
+
+        // Proc macros build on the host, so for normal/dev dependencies redirect it to the host
+        // instead.
+        let proc_macro_redirect = follow_target && to.package().is_proc_macro();
+
+        // Build dependencies are evaluated against the host platform.
+        let mut build_dep_redirect = is_enabled(&link, DependencyKind::Build, BuildPlatform::Host);
+
+        // Artifact dependencies may ask to be built on the other platform. This mirrors the
+        // logic in build_set.
+        let mut artifact_redirect = false;
+        if follow_target {
+            if let Some(lib) = self.artifact_redirect(&link.package_link(), DependencyKind::Normal)
+            {
+                artifact_redirect = true;
+                follow_target = lib;
             }
-            if proc_macro_redirect {
-                follow_target = false;
+        }
+        if build_dep_redirect {
+            if let Some(lib) = self.artifact_redirect(&link.package_link(), DependencyKind::Build) {
+                follow_target = true;
+                build_dep_redirect = lib;
             }
+        }
 
-            follow_target
-        });
+        // Finally, process what needs to be done.
+        if build_dep_redirect || proc_macro_redirect || artifact_redirect {
+            host_seeds.push((to.feature_ix(), link.edge_ix()));
+        }
+        if proc_macro_redirect {
+            follow_target = false;
+        }
 
-        // 2. Perform a feature query for the host.
-        let host = graph
-            .query_from_parts(SortedSet::new(host_ixs), DependencyDirection::Forward)
-            .resolve_with_fn(|_, link| {
-                let (from, to) = link.endpoints();
-                if self.is_omitted(to.package_ix()) {
-                    // Pretend that the omitted set doesn't exist.
-                    return false;
-                }
-                // During feature resolution, the v2 resolver doesn't check for whether this package
-                // has a build script. It also unifies dev dependencies of initials, even on the
-                // host platform.
-                let consider_dev = self.opts.include_dev
-                    && target_query_2
-                        .starts_from(from.feature_id())
-                        .expect("valid ID");
+        follow_target
+    }
+}
 
-                is_enabled(&link, DependencyKind::Normal, host_platform)
-                    || is_enabled(&link, DependencyKind::Build, host_platform)
-                    || (consider_dev
-                        && is_enabled(&link, DependencyKind::Development, host_platform))
-            });
+/// Records how each feature in a `CargoSet` was first reached during feature resolution.
+///
+/// Each map goes from a feature to the edge through which it was first reached. Initials are
+/// not present in the maps.
+#[derive(Clone, Debug, Default)]
+pub(super) struct FeaturePredecessors {
+    pub(super) target: HashMap<NodeIndex<FeatureIx>, EdgeIndex<FeatureIx>>,
+    pub(super) host: HashMap<NodeIndex<FeatureIx>, EdgeIndex<FeatureIx>>,
+    /// Host features that were pulled in by a build dependency or proc macro edge out of a target
+    /// feature. These are initials for the host query.
+    pub(super) host_from_target: HashMap<NodeIndex<FeatureIx>, EdgeIndex<FeatureIx>>,
+}
 
-        CargoIntermediateSet::TargetHost { target, host }
+/// Caches whether links are enabled on the target and host platforms.
+///
+/// Evaluating a link against a platform may involve evaluating `cfg()` expressions. The same links
+/// are visited again by each `CargoSet` built with the same options, so the results are shared
+/// across builds. A single build visits each link only a few times, and doesn't use a cache.
+#[derive(Debug, Default)]
+struct EnabledCache {
+    package_links: RefCell<HashMap<PackageLinkKey, bool>>,
+    feature_links: RefCell<HashMap<FeatureLinkKey, bool>>,
+    // The feature that enables each optional package link.
+    optional_features: RefCell<HashMap<EdgeIndex<PackageIx>, Option<NodeIndex<FeatureIx>>>>,
+}
+
+/// A package link and dependency kind evaluated on a platform, along with whether optional
+/// dependencies are considered.
+type PackageLinkKey = (EdgeIndex<PackageIx>, DependencyKind, BuildPlatform, bool);
+
+/// A feature link and dependency kind evaluated on a platform.
+type FeatureLinkKey = (EdgeIndex<FeatureIx>, DependencyKind, BuildPlatform);
+
+impl EnabledCache {
+    fn get_or_insert<K: Eq + Hash, V: Copy>(
+        map: &RefCell<HashMap<K, V>>,
+        key: K,
+        compute: impl FnOnce() -> V,
+    ) -> V {
+        if let Some(value) = map.borrow().get(&key) {
+            return *value;
+        }
+        let value = compute();
+        map.borrow_mut().insert(key, value);
+        value
+    }
+}
+
+/// Feature closures shared across builds, for each kind of feature resolution.
+#[derive(Debug)]
+struct ClosureCaches {
+    // Used by the v1 resolver, which unifies features across the target and the host.
+    unified: ClosureCache<()>,
+    target: ClosureCache<HostSeed>,
+    host: ClosureCache<()>,
+}
+
+impl Default for ClosureCaches {
+    fn default() -> Self {
+        Self {
+            unified: ClosureCache::new(),
+            target: ClosureCache::new(),
+            host: ClosureCache::new(),
+        }
     }
 }
+
+/// A feature built on the host because of a link out of a target feature, along with the link's
+/// edge.
+type HostSeed = (NodeIndex<FeatureIx>, EdgeIndex<FeatureIx>);
@@ -3,17 +3,24 @@
 
 use crate::{
     graph::{
-        cargo::build::CargoSetBuildState,
-        feature::{FeatureGraph, FeatureSet},
+        cargo::{
+            build::{CargoSetBuildState, FeaturePredecessors},
+            PerPackageCargoSets,
+        },
+        feature::{ConditionalLink, FeatureGraph, FeatureId, FeatureMetadata, FeatureSet},
         DependencyDirection, PackageGraph, PackageIx, PackageLink, PackageSet,
     },
     platform::PlatformSpec,
     sorted_set::SortedSet,
-    Error, PackageId,
+    DependencyKind, Error, PackageId,
 };
+use fixedbitset::FixedBitSet;
 use petgraph::prelude::*;
 use serde::{Deserialize, Serialize};
-use std::{collections::HashSet, fmt};
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+};
 
 /// Options for queries which simulate what Cargo does.
 ///
@@ -206,8 +213,15 @@ pub struct CargoSet<'g> {
     pub(super) host_direct_deps: PackageSet<'g>,
     pub(super) proc_macro_edge_ixs: SortedSet<EdgeIndex<PackageIx>>,
     pub(super) build_dep_edge_ixs: SortedSet<EdgeIndex<PackageIx>>,
+    pub(super) target_followed_links: SortedSet<FollowedLinkIx>,
+    pub(super) host_followed_links: SortedSet<FollowedLinkIx>,
+    pub(super) feature_predecessors: FeaturePredecessors,
 }
 
+/// A link followed out of a package, along with the kind of dependency and the platform the
+/// dependency is built on.
+pub(super) type FollowedLinkIx = (EdgeIndex<PackageIx>, DependencyKind, BuildPlatform);
+
 assert_covariant!(CargoSet);
 
 impl<'g> CargoSet<'g> {
@@ -230,6 +244,13 @@ impl<'g> CargoSet<'g> {
     /// `FeatureSet::into_cargo_set()`, and it may be more convenient to use that if the code is
     /// written in a "fluent" style.
     ///
+    /// [Artifact dependencies](crate::graph::ArtifactDep) out of target packages are placed on the
+    /// platform they request: a build dependency with `target = "target"` is built on the target,
+    /// and a dependency whose `target` is the host platform's triple is built on the host. If
+    /// `lib = true` is also specified, the dependency is additionally built on the platform it
+    /// would otherwise be built on. Artifacts requested for a triple that is neither the host nor
+    /// the target are placed on the target.
+    ///
     ///
     pub fn new(
         initials: FeatureSet<'g>,
@@ -240,6 +261,60 @@ impl<'g> CargoSet<'g> {
         Ok(build_state.build(initials, features_only))
     }
 
+    /// Simulates a separate Cargo build for each package in `initials`, with the given options.
+    ///
+    /// This answers the question "what does `cargo build -p <package>` build?" for several packages
+    /// at once. For each package with features in `initials`, those features are used as the
+    /// initials of its build. `features_only` takes part in feature unification for every build.
+    ///
+    /// The features and packages for each package are the same as those of `CargoSet::new`. This is
+    /// faster than calling `CargoSet::new` for each package, because work is shared across builds:
+    /// * Feature resolution starts from a different set of initials in each build, but features
+    ///   outside the initials are resolved the same way every time. The features reachable from
+    ///   each of them are computed once and reused by later builds.
+    /// * Whether each dependency is enabled on the target and host platforms is evaluated once.
+    ///
+    /// The paths returned by [`explain_feature`](Self::explain_feature) may differ from those of a
+    /// separate build, but they still start from a feature in the initials or `features_only`.
+    ///
+    /// For a summary of which third-party features are built by which packages, see
+    /// [`PerPackageCargoSets::sharing_matrix`].
+    pub fn new_per_package(
+        initials: &FeatureSet<'g>,
+        features_only: &FeatureSet<'g>,
+        opts: &CargoOptions<'_>,
+    ) -> Result<PerPackageCargoSets<'g>, Error> {
+        let feature_graph = *initials.graph();
+        let build_state =
+            CargoSetBuildState::new(feature_graph.package_graph, opts)?.with_shared_cache();
+
+        // Split up the initials by package.
+        let mut package_initials: HashMap<_, FixedBitSet> = HashMap::new();
+        for feature_ix in initials.ixs_unordered() {
+            package_initials
+                .entry(feature_graph.package_ix_for_feature_ix(feature_ix))
+                .or_insert_with(|| FixedBitSet::with_capacity(feature_graph.feature_count()))
+                .insert(feature_ix.index());
+        }
+
+        // Build dependencies before their dependents, so that the feature closures of
+        // dependencies are available to later builds.
+        let mut sets: Vec<_> = initials
+            .to_package_set()
+            .packages(DependencyDirection::Reverse)
+            .map(|package| {
+                let included = package_initials
+                    .remove(&package.package_ix())
+                    .expect("package has initial features");
+                let package_initials = FeatureSet::from_included(feature_graph, included);
+                let cargo_set = build_state.build(package_initials, features_only.clone());
+                (package, cargo_set)
+            })
+            .collect();
+        sets.reverse();
+        Ok(PerPackageCargoSets::new(feature_graph, sets))
+    }
+
     /// Creates a new `CargoIntermediateSet` based on the given query and options.
     ///
     /// This set contains an over-estimate of targets and features.
@@ -383,6 +458,247 @@ impl<'g> CargoSet<'g> {
             .iter()
             .map(move |edge_ix| package_graph.edge_ix_to_link(*edge_ix))
     }
+
+    /// Returns the dependency links followed out of packages built on the specified platform.
+    ///
+    /// These are the edges of the build graph that this `CargoSet` was computed from: each
+    /// package in `platform_features(build_platform)`, other than the initials, is reachable
+    /// through these links. A link is returned once for each dependency kind it was followed
+    /// for, and once for each platform the dependency is built on.
+    ///
+    /// Links are returned in an unspecified order.
+    pub fn followed_links<'a>(
+        &'a self,
+        build_platform: BuildPlatform,
+    ) -> impl ExactSizeIterator<Item = FollowedLink<'g>> + 'a {
+        let package_graph = self.target_features.graph().package_graph;
+        let followed_links = match build_platform {
+            BuildPlatform::Target => &self.target_followed_links,
+            BuildPlatform::Host => &self.host_followed_links,
+        };
+        followed_links
+            .iter()
+            .map(move |(edge_ix, kind, to_build_platform)| FollowedLink {
+                link: package_graph.edge_ix_to_link(*edge_ix),
+                kind: *kind,
+                from_build_platform: build_platform,
+                to_build_platform: *to_build_platform,
+            })
+    }
+
+    /// Explains why a feature is enabled in this build.
+    ///
+    /// The feature is looked up on the target platform first, and then on the host platform. For
+    /// more control, use `explain_platform_feature`.
+    ///
+    /// Returns `None` if the feature isn't built on either platform, and an error if the feature
+    /// ID is unknown.
+    pub fn explain_feature<'a>(
+        &self,
+        feature_id: impl Into<FeatureId<'a>>,
+    ) -> Result<Option<FeatureExplanation<'g>>, Error> {
+        let feature_id = feature_id.into();
+        for build_platform in BuildPlatform::VALUES {
+            if let Some(explanation) = self.explain_platform_feature(feature_id, *build_platform)? {
+                return Ok(Some(explanation));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Explains why a feature is enabled on the specified build platform.
+    ///
+    /// Returns `None` if the feature isn't built on this platform, and an error if the feature ID
+    /// is unknown.
+    pub fn explain_platform_feature<'a>(
+        &self,
+        feature_id: impl Into<FeatureId<'a>>,
+        build_platform: BuildPlatform,
+    ) -> Result<Option<FeatureExplanation<'g>>, Error> {
+        let feature_id = feature_id.into();
+        if !self
+            .platform_features(build_platform)
+            .contains(feature_id)?
+        {
+            return Ok(None);
+        }
+        let feature_graph = *self.feature_graph();
+        let feature_ix = self.feature_graph().metadata(feature_id)?.feature_ix();
+        let feature = feature_graph.metadata_for_ix(feature_ix);
+
+        // Walk the predecessors back to the initial this feature was first reached from.
+        let predecessors = &self.feature_predecessors;
+        let mut steps = vec![];
+        let mut current_ix = feature.feature_ix();
+        let mut current_platform = build_platform;
+        loop {
+            let platform_predecessors = match current_platform {
+                BuildPlatform::Target => &predecessors.target,
+                BuildPlatform::Host => &predecessors.host,
+            };
+            let (edge_ix, from_platform) = match platform_predecessors.get(&current_ix) {
+                Some(edge_ix) => (*edge_ix, current_platform),
+                None => match current_platform {
+                    // Host features may have been pulled in by a build dependency or a proc macro
+                    // on the target.
+                    BuildPlatform::Host => match predecessors.host_from_target.get(&current_ix) {
+                        Some(edge_ix) => (*edge_ix, BuildPlatform::Target),
+                        None => break,
+                    },
+                    BuildPlatform::Target => break,
+                },
+            };
+
+            let (from, to, conditional_link) = feature_graph.edge_ix_to_parts(edge_ix);
+            current_ix = from.feature_ix();
+            steps.push(FeatureStep {
+                from,
+                from_build_platform: from_platform,
+                to,
+                to_build_platform: current_platform,
+                conditional_link,
+            });
+            current_platform = from_platform;
+        }
+        steps.reverse();
+
+        Ok(Some(FeatureExplanation {
+            root: feature_graph.metadata_for_ix(current_ix),
+            root_build_platform: current_platform,
+            feature,
+            build_platform,
+            steps,
+        }))
+    }
+}
+
+/// An explanation for why a feature is enabled in a `CargoSet`, returned by
+/// `CargoSet::explain_feature`.
+///
+/// This is the chain of feature dependencies through which the feature was first reached during
+/// feature resolution, starting from a feature in the initials or `features_only`.
+///
+/// ## Notes
+///
+/// Cargo unifies features across packages that aren't necessarily built: for example, with the
+/// version 1 resolver, dev-dependencies of initials contribute features. The chain may pass through
+/// such packages.
+#[derive(Clone, Debug)]
+pub struct FeatureExplanation<'g> {
+    root: FeatureMetadata<'g>,
+    root_build_platform: BuildPlatform,
+    feature: FeatureMetadata<'g>,
+    build_platform: BuildPlatform,
+    steps: Vec<FeatureStep<'g>>,
+}
+
+impl<'g> FeatureExplanation<'g> {
+    /// Returns the initial feature that the explained feature was first reached from.
+    ///
+    /// If the explained feature is itself an initial, this is the same as `feature`.
+    pub fn root(&self) -> FeatureMetadata<'g> {
+        self.root
+    }
+
+    /// Returns the build platform the root feature is built on.
+    pub fn root_build_platform(&self) -> BuildPlatform {
+        self.root_build_platform
+    }
+
+    /// Returns the feature being explained.
+    pub fn feature(&self) -> FeatureMetadata<'g> {
+        self.feature
+    }
+
+    /// Returns the build platform the explained feature is built on.
+    pub fn build_platform(&self) -> BuildPlatform {
+        self.build_platform
+    }
+
+    /// Returns the steps from the root to the explained feature, in order.
+    ///
+    /// This is empty if the explained feature is an initial.
+    pub fn steps(&self) -> &[FeatureStep<'g>] {
+        &self.steps
+    }
+}
+
+/// A single step in a `FeatureExplanation`.
+#[derive(Copy, Clone, Debug)]
+pub struct FeatureStep<'g> {
+    from: FeatureMetadata<'g>,
+    from_build_platform: BuildPlatform,
+    to: FeatureMetadata<'g>,
+    to_build_platform: BuildPlatform,
+    conditional_link: Option<ConditionalLink<'g>>,
+}
+
+impl<'g> FeatureStep<'g> {
+    /// Returns the feature this step starts from.
+    pub fn from(&self) -> FeatureMetadata<'g> {
+        self.from
+    }
+
+    /// Returns the build platform the `from` feature is built on.
+    pub fn from_build_platform(&self) -> BuildPlatform {
+        self.from_build_platform
+    }
+
+    /// Returns the feature this step leads to.
+    pub fn to(&self) -> FeatureMetadata<'g> {
+        self.to
+    }
+
+    /// Returns the build platform the `to` feature is built on.
+    ///
+    /// This is different from `from_build_platform` if this step is a build dependency or a
+    /// procedural macro.
+    pub fn to_build_platform(&self) -> BuildPlatform {
+        self.to_build_platform
+    }
+
+    /// Returns the conditional link for this step, if it crosses into a dependency.
+    ///
+    /// Returns `None` for steps within a single package, such as a named feature enabling another.
+    pub fn conditional_link(&self) -> Option<ConditionalLink<'g>> {
+        self.conditional_link
+    }
+}
+
+/// A dependency link followed while simulating a Cargo build, returned by
+/// `CargoSet::followed_links`.
+#[derive(Copy, Clone, Debug)]
+pub struct FollowedLink<'g> {
+    link: PackageLink<'g>,
+    kind: DependencyKind,
+    from_build_platform: BuildPlatform,
+    to_build_platform: BuildPlatform,
+}
+
+impl<'g> FollowedLink<'g> {
+    /// Returns the package link that was followed.
+    pub fn link(&self) -> PackageLink<'g> {
+        self.link
+    }
+
+    /// Returns the kind of dependency the link was followed for.
+    pub fn kind(&self) -> DependencyKind {
+        self.kind
+    }
+
+    /// Returns the platform the dependent package is built on.
+    pub fn from_build_platform(&self) -> BuildPlatform {
+        self.from_build_platform
+    }
+
+    /// Returns the platform the dependency is built on.
+    ///
+    /// This is different from `from_build_platform` for build dependencies and procedural macros
+    /// out of target packages, and for [artifact dependencies](crate::graph::ArtifactDep) that
+    /// request the other platform.
+    pub fn to_build_platform(&self) -> BuildPlatform {
+        self.to_build_platform
+    }
 }
 
 /// Either the target or the host platform.
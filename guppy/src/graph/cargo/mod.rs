@@ -8,5 +8,7 @@
 
 pub(super) mod build;
 mod cargo_api;
+mod per_package;
 
 pub use cargo_api::*;
+pub use per_package::*;
@@ -0,0 +1,232 @@
+// Copyright (c) The cargo-guppy Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use crate::{
+    debug_ignore::DebugIgnore,
+    graph::{
+        cargo::{BuildPlatform, CargoSet},
+        feature::{FeatureGraph, FeatureId, FeatureMetadata},
+        DependencyDirection, FeatureIx, PackageMetadata,
+    },
+    Error, PackageId,
+};
+use fixedbitset::FixedBitSet;
+use petgraph::prelude::*;
+use std::collections::HashMap;
+
+/// A separate `CargoSet` for each initial package, as would be built by `cargo build -p <package>`.
+///
+/// Created by [`CargoSet::new_per_package`](CargoSet::new_per_package).
+#[derive(Clone, Debug)]
+pub struct PerPackageCargoSets<'g> {
+    graph: DebugIgnore<FeatureGraph<'g>>,
+    sets: Vec<(PackageMetadata<'g>, CargoSet<'g>)>,
+    indexes: HashMap<&'g PackageId, usize>,
+}
+
+assert_covariant!(PerPackageCargoSets);
+
+impl<'g> PerPackageCargoSets<'g> {
+    pub(super) fn new(
+        graph: FeatureGraph<'g>,
+        sets: Vec<(PackageMetadata<'g>, CargoSet<'g>)>,
+    ) -> Self {
+        let indexes = sets
+            .iter()
+            .enumerate()
+            .map(|(idx, (package, _))| (package.id(), idx))
+            .collect();
+        Self {
+            graph: DebugIgnore(graph),
+            sets,
+            indexes,
+        }
+    }
+
+    /// Returns the number of initial packages.
+    pub fn len(&self) -> usize {
+        self.sets.len()
+    }
+
+    /// Returns true if there are no initial packages.
+    pub fn is_empty(&self) -> bool {
+        self.sets.is_empty()
+    }
+
+    /// Returns the `CargoSet` for this package, or `None` if it isn't one of the initial packages.
+    pub fn get(&self, package_id: &PackageId) -> Option<&CargoSet<'g>> {
+        self.indexes.get(package_id).map(|&idx| &self.sets[idx].1)
+    }
+
+    /// Iterates over the initial packages and their `CargoSet`s, in topological order.
+    pub fn iter<'a>(
+        &'a self,
+    ) -> impl Iterator<Item = (PackageMetadata<'g>, &'a CargoSet<'g>)> + ExactSizeIterator + 'a
+    {
+        self.sets
+            .iter()
+            .map(|(package, cargo_set)| (*package, cargo_set))
+    }
+
+    /// Returns a matrix of which initial packages build which third-party (non-workspace)
+    /// features, on each platform.
+    pub fn sharing_matrix(&self) -> FeatureSharingMatrix<'g> {
+        let graph = *self.graph;
+        let mut all_features = graph.resolve_none();
+        for (_, cargo_set) in &self.sets {
+            for (_, features) in cargo_set.all_features() {
+                all_features = all_features.union(features);
+            }
+        }
+
+        let mut rows = vec![];
+        for feature in all_features.features(DependencyDirection::Forward) {
+            if feature.package().in_workspace() {
+                continue;
+            }
+            for build_platform in BuildPlatform::VALUES {
+                let mut packages = FixedBitSet::with_capacity(self.sets.len());
+                for (idx, (_, cargo_set)) in self.sets.iter().enumerate() {
+                    if cargo_set
+                        .platform_features(*build_platform)
+                        .contains_ix(feature.feature_ix())
+                    {
+                        packages.insert(idx);
+                    }
+                }
+                if packages.count_ones(..) > 0 {
+                    rows.push(SharingRowImpl {
+                        feature_ix: feature.feature_ix(),
+                        build_platform: *build_platform,
+                        packages,
+                    });
+                }
+            }
+        }
+
+        let row_indexes = rows
+            .iter()
+            .enumerate()
+            .map(|(idx, row)| ((row.feature_ix, row.build_platform), idx))
+            .collect();
+        FeatureSharingMatrix {
+            graph: DebugIgnore(graph),
+            packages: self.sets.iter().map(|(package, _)| *package).collect(),
+            package_indexes: self.indexes.clone(),
+            rows,
+            row_indexes,
+        }
+    }
+}
+
+/// A matrix of which packages build which third-party features.
+///
+/// Each column is an initial package of a [`PerPackageCargoSets`], and each row is a third-party
+/// feature built on a particular platform. Features that are built by many packages are shared
+/// among them, while features built by just one package are only pulled in by that package.
+///
+/// Created by [`PerPackageCargoSets::sharing_matrix`].
+#[derive(Clone, Debug)]
+pub struct FeatureSharingMatrix<'g> {
+    graph: DebugIgnore<FeatureGraph<'g>>,
+    packages: Vec<PackageMetadata<'g>>,
+    package_indexes: HashMap<&'g PackageId, usize>,
+    rows: Vec<SharingRowImpl>,
+    row_indexes: HashMap<(NodeIndex<FeatureIx>, BuildPlatform), usize>,
+}
+
+assert_covariant!(FeatureSharingMatrix);
+
+impl<'g> FeatureSharingMatrix<'g> {
+    /// Iterates over the packages in the columns of this matrix, in topological order.
+    pub fn packages<'a>(
+        &'a self,
+    ) -> impl Iterator<Item = PackageMetadata<'g>> + ExactSizeIterator + 'a {
+        self.packages.iter().copied()
+    }
+
+    /// Iterates over the rows of this matrix, in topological order of features.
+    ///
+    /// Only features built by at least one package are present.
+    pub fn rows<'a>(
+        &'a self,
+    ) -> impl Iterator<Item = FeatureSharingRow<'g, 'a>> + ExactSizeIterator + 'a {
+        self.rows.iter().map(move |inner| FeatureSharingRow {
+            matrix: self,
+            inner,
+        })
+    }
+
+    /// Returns the row for this feature on the given platform, or `None` if no package builds it.
+    ///
+    /// Returns an error if the feature ID is unknown.
+    pub fn row<'a, 'b>(
+        &'a self,
+        feature_id: impl Into<FeatureId<'b>>,
+        build_platform: BuildPlatform,
+    ) -> Result<Option<FeatureSharingRow<'g, 'a>>, Error> {
+        let feature_ix = self.graph.metadata(feature_id.into())?.feature_ix();
+        Ok(self
+            .row_indexes
+            .get(&(feature_ix, build_platform))
+            .map(|&idx| FeatureSharingRow {
+                matrix: self,
+                inner: &self.rows[idx],
+            }))
+    }
+}
+
+/// A row of a [`FeatureSharingMatrix`]: a third-party feature, and the packages that build it.
+#[derive(Clone, Copy, Debug)]
+pub struct FeatureSharingRow<'g, 'a> {
+    matrix: &'a FeatureSharingMatrix<'g>,
+    inner: &'a SharingRowImpl,
+}
+
+impl<'g, 'a> FeatureSharingRow<'g, 'a> {
+    /// Returns the feature for this row.
+    pub fn feature(&self) -> FeatureMetadata<'g> {
+        self.matrix.graph.metadata_for_ix(self.inner.feature_ix)
+    }
+
+    /// Returns the platform the feature is built on.
+    pub fn build_platform(&self) -> BuildPlatform {
+        self.inner.build_platform
+    }
+
+    /// Returns the number of packages that build this feature.
+    pub fn len(&self) -> usize {
+        self.inner.packages.count_ones(..)
+    }
+
+    /// Returns true if no packages build this feature.
+    ///
+    /// This is always false for rows returned by a `FeatureSharingMatrix`.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Iterates over the packages that build this feature, in topological order.
+    pub fn packages(&self) -> impl Iterator<Item = PackageMetadata<'g>> + 'a {
+        let matrix = self.matrix;
+        self.inner
+            .packages
+            .ones()
+            .map(move |idx| matrix.packages[idx])
+    }
+
+    /// Returns true if the given package builds this feature.
+    pub fn contains(&self, package_id: &PackageId) -> bool {
+        self.matrix
+            .package_indexes
+            .get(package_id)
+            .map_or(false, |&idx| self.inner.packages.contains(idx))
+    }
+}
+
+#[derive(Clone, Debug)]
+struct SharingRowImpl {
+    feature_ix: NodeIndex<FeatureIx>,
+    build_platform: BuildPlatform,
+    packages: FixedBitSet,
+}
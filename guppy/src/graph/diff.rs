@@ -0,0 +1,1005 @@
+// Copyright (c) The cargo-guppy Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Compare and diff package graphs.
+//!
+//! A diff of two package graphs lists packages that were added, removed or changed between them,
+//! along with changes to individual dependency edges, named features and build targets.
+//!
+//! The main entry point is [`GraphDiff`], which can be created through
+//! [`PackageGraph::diff`](crate::graph::PackageGraph::diff) or [`GraphDiff::new`].
+
+use crate::{
+    graph::{
+        ArtifactDep, ArtifactKind, ArtifactTarget, BuildTarget, BuildTargetId, BuildTargetKind,
+        DependencyReq, EnabledStatus, PackageGraph, PackageLink, PackageMetadata, PackageSource,
+    },
+    platform::PlatformStatus,
+    DependencyKind,
+};
+use camino::Utf8PathBuf;
+use semver::Version;
+use serde::Serialize;
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt,
+};
+
+impl PackageGraph {
+    /// Computes a diff between this package graph and a newer one.
+    pub fn diff(&self, new: &PackageGraph) -> GraphDiff {
+        GraphDiff::new(self, new)
+    }
+}
+
+/// A structured diff of two package graphs.
+///
+/// Packages are matched up across the two graphs by name and source, so graphs built from
+/// different checkouts of a workspace can be compared. Workspace and path packages are identified
+/// by their paths relative to the workspace root.
+///
+/// ## Human-readable reports
+///
+/// The [`report`](GraphDiff::report) method can be used with `fmt::Display` to generate a
+/// friendly, human-readable report.
+///
+/// ## Machine-readable serialization
+///
+/// A `GraphDiff` can be serialized through `serde`. The output format is part of the API.
+#[derive(Clone, Debug, Default, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct GraphDiff {
+    /// Packages that are only present in the new graph.
+    pub added: Vec<DiffPackage>,
+
+    /// Packages that are only present in the old graph.
+    pub removed: Vec<DiffPackage>,
+
+    /// Packages whose version or source changed.
+    pub version_changes: Vec<VersionChange>,
+
+    /// Packages present in both graphs whose dependencies, features or build targets changed.
+    ///
+    /// This includes packages listed in `version_changes`.
+    pub changed: Vec<PackageChanges>,
+}
+
+impl GraphDiff {
+    /// Computes a diff between two package graphs.
+    pub fn new(old: &PackageGraph, new: &PackageGraph) -> Self {
+        let mut diff = GraphDiff::default();
+
+        for (old_package, new_package) in match_packages(old, new, &mut diff) {
+            let is_version_change = old_package.version() != new_package.version()
+                || source_str(old_package) != source_str(new_package);
+            if is_version_change {
+                diff.version_changes.push(VersionChange {
+                    name: new_package.name().to_owned(),
+                    old_version: old_package.version().clone(),
+                    new_version: new_package.version().clone(),
+                    old_source: source_str(old_package),
+                    new_source: source_str(new_package),
+                });
+            }
+
+            let changes = PackageChanges::new(old_package, new_package);
+            if !changes.is_unchanged() {
+                diff.changed.push(changes);
+            }
+        }
+
+        diff.added.sort();
+        diff.removed.sort();
+        diff.version_changes
+            .sort_by(|a, b| (&a.name, &a.new_version).cmp(&(&b.name, &b.new_version)));
+        diff.changed
+            .sort_by(|a, b| (&a.name, &a.version).cmp(&(&b.name, &b.version)));
+        diff
+    }
+
+    /// Returns true if there are any changes in this diff.
+    pub fn is_changed(&self) -> bool {
+        !self.is_unchanged()
+    }
+
+    /// Returns true if there are no changes in this diff.
+    pub fn is_unchanged(&self) -> bool {
+        self.added.is_empty()
+            && self.removed.is_empty()
+            && self.version_changes.is_empty()
+            && self.changed.is_empty()
+    }
+
+    /// Returns a report for this diff.
+    ///
+    /// This report can be used with `fmt::Display`.
+    pub fn report(&self) -> GraphDiffReport<'_> {
+        GraphDiffReport { diff: self }
+    }
+}
+
+/// Matches up packages across the two graphs, recording unmatched packages in `diff`.
+///
+/// Packages with the same name, version and source are matched first. Among the rest, packages
+/// with the same name are matched if there's exactly one of them on either side, or else if they
+/// share a source.
+fn match_packages<'g1, 'g2>(
+    old: &'g1 PackageGraph,
+    new: &'g2 PackageGraph,
+    diff: &mut GraphDiff,
+) -> Vec<(PackageMetadata<'g1>, PackageMetadata<'g2>)> {
+    let mut new_by_key: HashMap<_, _> = new
+        .packages()
+        .map(|package| (package_key(package), package))
+        .collect();
+
+    let mut matched = vec![];
+    let mut old_unmatched: BTreeMap<&str, Vec<PackageMetadata<'g1>>> = BTreeMap::new();
+    for old_package in old.packages() {
+        match new_by_key.remove(&package_key(old_package)) {
+            Some(new_package) => matched.push((old_package, new_package)),
+            None => old_unmatched
+                .entry(old_package.name())
+                .or_default()
+                .push(old_package),
+        }
+    }
+
+    let mut new_unmatched: BTreeMap<&str, Vec<PackageMetadata<'g2>>> = BTreeMap::new();
+    for new_package in new_by_key.into_values() {
+        new_unmatched
+            .entry(new_package.name())
+            .or_default()
+            .push(new_package);
+    }
+
+    for (name, mut old_packages) in old_unmatched {
+        let mut new_packages = new_unmatched.remove(name).unwrap_or_default();
+        if old_packages.len() == 1 && new_packages.len() == 1 {
+            matched.push((old_packages.remove(0), new_packages.remove(0)));
+        } else {
+            old_packages.retain(|old_package| {
+                let old_source = source_str(*old_package);
+                let mut same_source = new_packages
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, new_package)| source_str(**new_package) == old_source);
+                match (same_source.next(), same_source.next()) {
+                    (Some((idx, _)), None) => {
+                        matched.push((*old_package, new_packages.remove(idx)));
+                        false
+                    }
+                    _ => true,
+                }
+            });
+        }
+        diff.removed
+            .extend(old_packages.into_iter().map(DiffPackage::new));
+        diff.added
+            .extend(new_packages.into_iter().map(DiffPackage::new));
+    }
+    for new_packages in new_unmatched.into_values() {
+        diff.added
+            .extend(new_packages.into_iter().map(DiffPackage::new));
+    }
+
+    matched
+}
+
+fn package_key(package: PackageMetadata<'_>) -> (&str, &Version, String) {
+    (package.name(), package.version(), source_str(package))
+}
+
+/// Returns the source of a package as a string, with the workspace root represented as `"."`.
+fn source_str(package: PackageMetadata<'_>) -> String {
+    match package.source() {
+        PackageSource::Workspace(path) if path.as_str().is_empty() => ".".to_owned(),
+        source => source.to_string(),
+    }
+}
+
+/// A package in a [`GraphDiff`].
+#[derive(Clone, Debug, Eq, Ord, PartialEq, PartialOrd, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct DiffPackage {
+    /// The name of the package.
+    pub name: String,
+
+    /// The version of the package.
+    pub version: Version,
+
+    /// The source of the package: a path relative to the workspace root for workspace and path
+    /// packages (`"."` for the workspace root itself), or a string like `"registry+https://github.com/rust-lang/crates.io-index"` for
+    /// external packages.
+    pub source: String,
+
+    /// Whether this package is in the workspace.
+    pub in_workspace: bool,
+}
+
+impl DiffPackage {
+    fn new(package: PackageMetadata<'_>) -> Self {
+        Self {
+            name: package.name().to_owned(),
+            version: package.version().clone(),
+            source: source_str(package),
+            in_workspace: package.in_workspace(),
+        }
+    }
+}
+
+impl fmt::Display for DiffPackage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {} ({})", self.name, self.version, self.source)
+    }
+}
+
+/// A package whose version or source changed between the two graphs.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct VersionChange {
+    /// The name of the package.
+    pub name: String,
+
+    /// The version in the old graph.
+    pub old_version: Version,
+
+    /// The version in the new graph.
+    pub new_version: Version,
+
+    /// The source in the old graph.
+    pub old_source: String,
+
+    /// The source in the new graph.
+    pub new_source: String,
+}
+
+impl VersionChange {
+    /// Returns true if the version was upgraded.
+    pub fn is_upgrade(&self) -> bool {
+        self.new_version > self.old_version
+    }
+
+    /// Returns true if the version was downgraded.
+    pub fn is_downgrade(&self) -> bool {
+        self.new_version < self.old_version
+    }
+}
+
+/// Changes to the dependencies, named features and build targets of a package present in both
+/// graphs.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct PackageChanges {
+    /// The name of the package.
+    pub name: String,
+
+    /// The version of the package in the new graph.
+    pub version: Version,
+
+    /// The source of the package in the new graph.
+    pub source: String,
+
+    /// Dependency edges that were added.
+    pub added_links: Vec<DiffLink>,
+
+    /// Dependency edges that were removed.
+    pub removed_links: Vec<DiffLink>,
+
+    /// Dependency edges that were changed.
+    pub changed_links: Vec<LinkChange>,
+
+    /// Named features that were added, along with what they enable.
+    pub added_features: BTreeMap<String, Vec<String>>,
+
+    /// Named features that were removed, along with what they used to enable.
+    pub removed_features: BTreeMap<String, Vec<String>>,
+
+    /// Named features whose definitions changed.
+    pub changed_features: BTreeMap<String, FeatureChange>,
+
+    /// Build targets that were added.
+    pub added_targets: Vec<DiffTarget>,
+
+    /// Build targets that were removed.
+    pub removed_targets: Vec<DiffTarget>,
+
+    /// Build targets that were changed.
+    pub changed_targets: Vec<TargetChange>,
+}
+
+impl PackageChanges {
+    fn new(old: PackageMetadata<'_>, new: PackageMetadata<'_>) -> Self {
+        let mut changes = Self {
+            name: new.name().to_owned(),
+            version: new.version().clone(),
+            source: source_str(new),
+            added_links: vec![],
+            removed_links: vec![],
+            changed_links: vec![],
+            added_features: BTreeMap::new(),
+            removed_features: BTreeMap::new(),
+            changed_features: BTreeMap::new(),
+            added_targets: vec![],
+            removed_targets: vec![],
+            changed_targets: vec![],
+        };
+
+        let (changed_links, removed_links, added_links) = match_links(
+            old.direct_links().map(DiffLink::new).collect(),
+            new.direct_links().map(DiffLink::new).collect(),
+        );
+        changes.changed_links = changed_links;
+        changes.removed_links = removed_links;
+        changes.added_links = added_links;
+        changes.added_links.sort();
+        changes.removed_links.sort();
+        changes.changed_links.sort();
+
+        let mut new_features = named_features(new);
+        for (feature, old_deps) in named_features(old) {
+            match new_features.remove(&feature) {
+                Some(new_deps) => {
+                    if old_deps != new_deps {
+                        changes.changed_features.insert(
+                            feature,
+                            FeatureChange {
+                                old: old_deps,
+                                new: new_deps,
+                            },
+                        );
+                    }
+                }
+                None => {
+                    changes.removed_features.insert(feature, old_deps);
+                }
+            }
+        }
+        changes.added_features = new_features;
+
+        let mut new_targets: BTreeMap<_, _> = new
+            .build_targets()
+            .map(|target| (target.id(), DiffTarget::new(new, target)))
+            .collect();
+        for target in old.build_targets() {
+            let id = target.id();
+            let old_target = DiffTarget::new(old, target);
+            match new_targets.remove(&id) {
+                Some(new_target) => {
+                    if old_target != new_target {
+                        changes.changed_targets.push(TargetChange {
+                            old: old_target,
+                            new: new_target,
+                        });
+                    }
+                }
+                None => changes.removed_targets.push(old_target),
+            }
+        }
+        changes.added_targets.extend(new_targets.into_values());
+        changes.removed_targets.sort();
+        changes.changed_targets.sort();
+
+        changes
+    }
+
+    /// Returns true if nothing about this package changed.
+    pub fn is_unchanged(&self) -> bool {
+        self.added_links.is_empty()
+            && self.removed_links.is_empty()
+            && self.changed_links.is_empty()
+            && self.added_features.is_empty()
+            && self.removed_features.is_empty()
+            && self.changed_features.is_empty()
+            && self.added_targets.is_empty()
+            && self.removed_targets.is_empty()
+            && self.changed_targets.is_empty()
+    }
+}
+
+/// Matches up the direct links of a package across the two graphs, returning changed, removed and
+/// added links.
+///
+/// Links to the same package are matched first. A package may depend on several versions of the
+/// same crate under one name (e.g. one as a normal and another as a dev-dependency), so among the
+/// rest, links are matched on the name they're imported under and the name of the package they
+/// point to only if that's unambiguous.
+fn match_links(
+    old_links: Vec<DiffLink>,
+    mut new_links: Vec<DiffLink>,
+) -> (Vec<LinkChange>, Vec<DiffLink>, Vec<DiffLink>) {
+    let mut changed = vec![];
+    let mut old_unmatched = vec![];
+    for old_link in old_links {
+        let same_package = new_links.iter().position(|new_link| {
+            new_link.dep_name == old_link.dep_name && new_link.to == old_link.to
+        });
+        match same_package {
+            Some(idx) => {
+                let new_link = new_links.remove(idx);
+                if old_link != new_link {
+                    changed.push(LinkChange {
+                        old: old_link,
+                        new: new_link,
+                    });
+                }
+            }
+            None => old_unmatched.push(old_link),
+        }
+    }
+
+    let key = |link: &DiffLink| (link.dep_name.clone(), link.to.name.clone());
+    let mut old_counts = HashMap::new();
+    for link in &old_unmatched {
+        *old_counts.entry(key(link)).or_insert(0) += 1;
+    }
+    let mut removed = vec![];
+    for old_link in old_unmatched {
+        let mut same_name = new_links
+            .iter()
+            .enumerate()
+            .filter(|(_, new_link)| key(new_link) == key(&old_link));
+        let new_idx = match (same_name.next(), same_name.next()) {
+            (Some((idx, _)), None) if old_counts[&key(&old_link)] == 1 => Some(idx),
+            _ => None,
+        };
+        match new_idx {
+            Some(idx) => changed.push(LinkChange {
+                old: old_link,
+                new: new_links.remove(idx),
+            }),
+            None => removed.push(old_link),
+        }
+    }
+
+    (changed, removed, new_links)
+}
+
+fn named_features(package: PackageMetadata<'_>) -> BTreeMap<String, Vec<String>> {
+    package
+        .named_features_full()
+        .map(|(_, feature, deps)| {
+            let mut deps: Vec<_> = deps.iter().map(|dep| dep.to_string()).collect();
+            deps.sort();
+            (feature.to_owned(), deps)
+        })
+        .collect()
+}
+
+/// A dependency edge in a [`GraphDiff`].
+#[derive(Clone, Debug, Eq, Ord, PartialEq, PartialOrd, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct DiffLink {
+    /// The name the dependency is specified under in `Cargo.toml`.
+    pub dep_name: String,
+
+    /// The package this edge points to.
+    pub to: DiffPackage,
+
+    /// The version requirement specified for this dependency.
+    pub version_req: String,
+
+    /// Details about this dependency as a normal dependency, if it is one.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub normal: Option<DiffReq>,
+
+    /// Details about this dependency as a build dependency, if it is one.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub build: Option<DiffReq>,
+
+    /// Details about this dependency as a dev-dependency, if it is one.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dev: Option<DiffReq>,
+}
+
+impl DiffLink {
+    fn new(link: PackageLink<'_>) -> Self {
+        let req = |kind| {
+            let req = link.req_for_kind(kind);
+            req.is_present().then(|| DiffReq::new(req))
+        };
+        Self {
+            dep_name: link.dep_name().to_owned(),
+            to: DiffPackage::new(link.to()),
+            version_req: link.version_req().to_string(),
+            normal: req(DependencyKind::Normal),
+            build: req(DependencyKind::Build),
+            dev: req(DependencyKind::Development),
+        }
+    }
+
+    /// Returns the details for the given dependency kind, if this is a dependency of that kind.
+    pub fn req_for_kind(&self, kind: DependencyKind) -> Option<&DiffReq> {
+        match kind {
+            DependencyKind::Normal => self.normal.as_ref(),
+            DependencyKind::Build => self.build.as_ref(),
+            DependencyKind::Development => self.dev.as_ref(),
+        }
+    }
+}
+
+impl fmt::Display for DiffLink {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} -> {} (req {})",
+            self.dep_name, self.to, self.version_req
+        )?;
+        for kind in DependencyKind::VALUES {
+            if let Some(req) = self.req_for_kind(*kind) {
+                write!(f, "\n        {}: {}", kind.to_str(), req)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A dependency edge that changed between the two graphs.
+#[derive(Clone, Debug, Eq, Ord, PartialEq, PartialOrd, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct LinkChange {
+    /// The edge in the old graph.
+    pub old: DiffLink,
+
+    /// The edge in the new graph.
+    pub new: DiffLink,
+}
+
+/// The state of a dependency of a particular kind: when it is enabled, and which features it turns
+/// on.
+///
+/// This is an owned, serializable version of [`DependencyReq`].
+#[derive(Clone, Debug, Eq, Ord, PartialEq, PartialOrd, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct DiffReq {
+    /// When this dependency is enabled.
+    pub status: DiffEnabledStatus,
+
+    /// When the default features of this dependency are enabled.
+    pub default_features: DiffEnabledStatus,
+
+    /// Features enabled on this dependency, along with when they're enabled.
+    pub features: BTreeMap<String, DiffEnabledStatus>,
+
+    /// Artifact information, if this is an artifact dependency.
+    pub artifact: Option<DiffArtifact>,
+}
+
+impl DiffReq {
+    fn new(req: DependencyReq<'_>) -> Self {
+        Self {
+            status: DiffEnabledStatus::new(req.status()),
+            default_features: DiffEnabledStatus::new(req.default_features()),
+            features: req
+                .features()
+                .map(|feature| {
+                    (
+                        feature.to_owned(),
+                        DiffEnabledStatus::new(req.feature_status(feature)),
+                    )
+                })
+                .collect(),
+            artifact: req.artifact().map(DiffArtifact::new),
+        }
+    }
+}
+
+impl fmt::Display for DiffReq {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}, default features {}",
+            self.status, self.default_features
+        )?;
+        if !self.features.is_empty() {
+            write!(f, ", features: ")?;
+            for (idx, (feature, status)) in self.features.iter().enumerate() {
+                if idx > 0 {
+                    write!(f, ", ")?;
+                }
+                write!(f, "{} ({})", feature, status)?;
+            }
+        }
+        if let Some(artifact) = &self.artifact {
+            write!(f, ", artifact: {}", artifact)?;
+        }
+        Ok(())
+    }
+}
+
+/// An owned, serializable version of [`ArtifactDep`].
+#[derive(Clone, Debug, Eq, Ord, PartialEq, PartialOrd, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct DiffArtifact {
+    /// The kinds of artifacts requested, as specified in `Cargo.toml`.
+    pub kinds: Vec<String>,
+
+    /// Whether the library target is also requested.
+    pub lib: bool,
+
+    /// The platform the artifacts are built for, as specified in `Cargo.toml`.
+    pub target: Option<String>,
+}
+
+impl DiffArtifact {
+    fn new(artifact: ArtifactDep<'_>) -> Self {
+        Self {
+            kinds: artifact
+                .kinds()
+                .map(|kind| match kind {
+                    ArtifactKind::Bin { name: None } => "bin".to_owned(),
+                    ArtifactKind::Bin { name: Some(name) } => format!("bin:{}", name),
+                    ArtifactKind::Cdylib => "cdylib".to_owned(),
+                    ArtifactKind::Staticlib => "staticlib".to_owned(),
+                    ArtifactKind::Unknown(kind) => kind.to_owned(),
+                })
+                .collect(),
+            lib: artifact.lib(),
+            target: match artifact.target() {
+                ArtifactTarget::Default => None,
+                ArtifactTarget::Target => Some("target".to_owned()),
+                ArtifactTarget::Triple(triple) => Some(triple.to_owned()),
+            },
+        }
+    }
+}
+
+impl fmt::Display for DiffArtifact {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.kinds.join(" + "))?;
+        if self.lib {
+            write!(f, " + lib")?;
+        }
+        if let Some(target) = &self.target {
+            write!(f, " for {}", target)?;
+        }
+        Ok(())
+    }
+}
+
+/// An owned, serializable version of [`EnabledStatus`].
+#[derive(Clone, Debug, Eq, Ord, PartialEq, PartialOrd, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct DiffEnabledStatus {
+    /// The platforms on which this is required.
+    pub required: DiffPlatformStatus,
+
+    /// The platforms on which this is optional.
+    pub optional: DiffPlatformStatus,
+}
+
+impl DiffEnabledStatus {
+    fn new(status: EnabledStatus<'_>) -> Self {
+        Self {
+            required: DiffPlatformStatus::new(status.required_status()),
+            optional: DiffPlatformStatus::new(status.optional_status()),
+        }
+    }
+}
+
+impl fmt::Display for DiffEnabledStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (&self.required, &self.optional) {
+            (DiffPlatformStatus::Never, DiffPlatformStatus::Never) => write!(f, "never"),
+            (required, DiffPlatformStatus::Never) => write!(f, "required {}", required),
+            (DiffPlatformStatus::Never, optional) => write!(f, "optional {}", optional),
+            (DiffPlatformStatus::Always, _) => write!(f, "required always"),
+            (required, optional) => write!(f, "required {}, optional {}", required, optional),
+        }
+    }
+}
+
+/// An owned, serializable version of [`PlatformStatus`].
+#[derive(Clone, Debug, Eq, Ord, PartialEq, PartialOrd, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum DiffPlatformStatus {
+    /// Never enabled.
+    Never,
+
+    /// Always enabled.
+    Always,
+
+    /// Enabled on platforms matching any of these target specifications.
+    PlatformDependent(Vec<String>),
+}
+
+impl DiffPlatformStatus {
+    fn new(status: PlatformStatus<'_>) -> Self {
+        match status {
+            PlatformStatus::Never => DiffPlatformStatus::Never,
+            PlatformStatus::Always => DiffPlatformStatus::Always,
+            PlatformStatus::PlatformDependent { eval } => {
+                let mut specs: Vec<_> = eval
+                    .target_spec_strs()
+                    .map(|spec| spec.into_owned())
+                    .collect();
+                specs.sort();
+                DiffPlatformStatus::PlatformDependent(specs)
+            }
+        }
+    }
+}
+
+impl fmt::Display for DiffPlatformStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DiffPlatformStatus::Never => write!(f, "never"),
+            DiffPlatformStatus::Always => write!(f, "always"),
+            DiffPlatformStatus::PlatformDependent(specs) => write!(f, "on {}", specs.join(" or ")),
+        }
+    }
+}
+
+/// A named feature whose definition changed.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct FeatureChange {
+    /// What the feature enabled in the old graph.
+    pub old: Vec<String>,
+
+    /// What the feature enables in the new graph.
+    pub new: Vec<String>,
+}
+
+/// A build target in a [`GraphDiff`].
+#[derive(Clone, Debug, Eq, Ord, PartialEq, PartialOrd, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct DiffTarget {
+    /// An identifier for the target, e.g. `"lib"` or `"bin:foo"`.
+    pub id: String,
+
+    /// The kind of target.
+    pub kind: DiffTargetKind,
+
+    /// The path to the target's entry point, relative to the package directory if possible.
+    pub path: Utf8PathBuf,
+
+    /// Features required to build this target.
+    pub required_features: Vec<String>,
+
+    /// The Rust edition this target is built with.
+    pub edition: String,
+
+    /// Whether doctests are run for this target.
+    pub doc_tests: bool,
+}
+
+impl DiffTarget {
+    fn new(package: PackageMetadata<'_>, target: BuildTarget<'_>) -> Self {
+        let id = match target.id() {
+            BuildTargetId::Library => "lib".to_owned(),
+            BuildTargetId::BuildScript => "build-script".to_owned(),
+            BuildTargetId::Binary(name) => format!("bin:{}", name),
+            BuildTargetId::Example(name) => format!("example:{}", name),
+            BuildTargetId::Test(name) => format!("test:{}", name),
+            BuildTargetId::Benchmark(name) => format!("bench:{}", name),
+        };
+        let kind = match target.kind() {
+            BuildTargetKind::LibraryOrExample(crate_types) => {
+                DiffTargetKind::LibraryOrExample(crate_types.to_vec())
+            }
+            BuildTargetKind::ProcMacro => DiffTargetKind::ProcMacro,
+            BuildTargetKind::Binary => DiffTargetKind::Binary,
+        };
+        let path = match package.manifest_path().parent() {
+            Some(dir) => target.path().strip_prefix(dir).unwrap_or(target.path()),
+            None => target.path(),
+        };
+
+        Self {
+            id,
+            kind,
+            path: path.to_path_buf(),
+            required_features: target.required_features().to_vec(),
+            edition: target.edition().to_owned(),
+            doc_tests: target.doc_tests(),
+        }
+    }
+}
+
+/// An owned, serializable version of [`BuildTargetKind`].
+#[derive(Clone, Debug, Eq, Ord, PartialEq, PartialOrd, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum DiffTargetKind {
+    /// A library or example, with the specified crate types.
+    LibraryOrExample(Vec<String>),
+
+    /// A procedural macro.
+    ProcMacro,
+
+    /// A binary.
+    Binary,
+}
+
+impl fmt::Display for DiffTargetKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DiffTargetKind::LibraryOrExample(crate_types) => {
+                write!(f, "{}", crate_types.join(", "))
+            }
+            DiffTargetKind::ProcMacro => write!(f, "proc-macro"),
+            DiffTargetKind::Binary => write!(f, "bin"),
+        }
+    }
+}
+
+/// A build target that changed between the two graphs.
+#[derive(Clone, Debug, Eq, Ord, PartialEq, PartialOrd, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct TargetChange {
+    /// The target in the old graph.
+    pub old: DiffTarget,
+
+    /// The target in the new graph.
+    pub new: DiffTarget,
+}
+
+/// A report of a diff between two package graphs.
+///
+/// This report can be generated or written to a file through `fmt::Display`.
+#[derive(Clone, Debug)]
+pub struct GraphDiffReport<'a> {
+    diff: &'a GraphDiff,
+}
+
+impl<'a> fmt::Display for GraphDiffReport<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let diff = self.diff;
+
+        if !diff.added.is_empty() {
+            writeln!(f, "added packages:")?;
+            for package in &diff.added {
+                writeln!(f, "  + {}", package)?;
+            }
+            writeln!(f)?;
+        }
+
+        if !diff.removed.is_empty() {
+            writeln!(f, "removed packages:")?;
+            for package in &diff.removed {
+                writeln!(f, "  - {}", package)?;
+            }
+            writeln!(f)?;
+        }
+
+        if !diff.version_changes.is_empty() {
+            writeln!(f, "version changes:")?;
+            for change in &diff.version_changes {
+                let marker = if change.is_upgrade() {
+                    "^"
+                } else if change.is_downgrade() {
+                    "v"
+                } else {
+                    "*"
+                };
+                write!(
+                    f,
+                    "  {} {} {} -> {}",
+                    marker, change.name, change.old_version, change.new_version
+                )?;
+                if change.old_source != change.new_source {
+                    write!(f, " ({} -> {})", change.old_source, change.new_source)?;
+                }
+                writeln!(f)?;
+            }
+            writeln!(f)?;
+        }
+
+        if !diff.changed.is_empty() {
+            writeln!(f, "changed packages:")?;
+            for changes in &diff.changed {
+                writeln!(
+                    f,
+                    "  {} {} ({}):",
+                    changes.name, changes.version, changes.source
+                )?;
+                write_package_changes(f, changes)?;
+            }
+            writeln!(f)?;
+        }
+
+        Ok(())
+    }
+}
+
+fn write_package_changes(f: &mut fmt::Formatter<'_>, changes: &PackageChanges) -> fmt::Result {
+    for link in &changes.added_links {
+        writeln!(f, "    + dependency {}", link)?;
+    }
+    for link in &changes.removed_links {
+        writeln!(f, "    - dependency {}", link)?;
+    }
+    for LinkChange { old, new } in &changes.changed_links {
+        writeln!(f, "    * dependency {} -> {}", new.dep_name, new.to.name)?;
+        if old.to.version != new.to.version || old.to.source != new.to.source {
+            writeln!(f, "        package: {} -> {}", old.to, new.to)?;
+        }
+        if old.version_req != new.version_req {
+            writeln!(
+                f,
+                "        version req: {} -> {}",
+                old.version_req, new.version_req
+            )?;
+        }
+        for kind in DependencyKind::VALUES {
+            match (old.req_for_kind(*kind), new.req_for_kind(*kind)) {
+                (Some(old_req), Some(new_req)) if old_req != new_req => {
+                    writeln!(f, "        {}: {}", kind.to_str(), old_req)?;
+                    writeln!(
+                        f,
+                        "        {}  -> {}",
+                        " ".repeat(kind.to_str().len()),
+                        new_req
+                    )?;
+                }
+                (Some(old_req), None) => {
+                    writeln!(f, "        {}: {} -> (none)", kind.to_str(), old_req)?;
+                }
+                (None, Some(new_req)) => {
+                    writeln!(f, "        {}: (none) -> {}", kind.to_str(), new_req)?;
+                }
+                _ => {}
+            }
+        }
+    }
+
+    for (feature, deps) in &changes.added_features {
+        writeln!(f, "    + feature {} = [{}]", feature, deps.join(", "))?;
+    }
+    for (feature, deps) in &changes.removed_features {
+        writeln!(f, "    - feature {} = [{}]", feature, deps.join(", "))?;
+    }
+    for (feature, change) in &changes.changed_features {
+        writeln!(
+            f,
+            "    * feature {}: [{}] -> [{}]",
+            feature,
+            change.old.join(", "),
+            change.new.join(", ")
+        )?;
+    }
+
+    for target in &changes.added_targets {
+        writeln!(
+            f,
+            "    + target {} ({}, {})",
+            target.id, target.kind, target.path
+        )?;
+    }
+    for target in &changes.removed_targets {
+        writeln!(
+            f,
+            "    - target {} ({}, {})",
+            target.id, target.kind, target.path
+        )?;
+    }
+    for TargetChange { old, new } in &changes.changed_targets {
+        writeln!(f, "    * target {}", new.id)?;
+        if old.kind != new.kind {
+            writeln!(f, "        kind: {} -> {}", old.kind, new.kind)?;
+        }
+        if old.path != new.path {
+            writeln!(f, "        path: {} -> {}", old.path, new.path)?;
+        }
+        if old.required_features != new.required_features {
+            writeln!(
+                f,
+                "        required features: [{}] -> [{}]",
+                old.required_features.join(", "),
+                new.required_features.join(", ")
+            )?;
+        }
+        if old.edition != new.edition {
+            writeln!(f, "        edition: {} -> {}", old.edition, new.edition)?;
+        }
+        if old.doc_tests != new.doc_tests {
+            writeln!(
+                f,
+                "        doctests: {} -> {}",
+                old.doc_tests, new.doc_tests
+            )?;
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,870 @@
+// Copyright (c) The cargo-guppy Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! In-memory edits to a `PackageGraph`.
+
+use crate::{
+    graph::{
+        cargo_version_matches, normalize_path, BuildTargetImpl, BuildTargetKindImpl,
+        DepRequiredOrOptional, DependencyReqImpl, NamedFeatureDep, OverrideImpl,
+        OwnedBuildTargetId, PackageGraph, PackageIx, PackageLinkImpl, PackageMetadataImpl,
+        PackagePublishImpl, PackageSourceImpl,
+    },
+    platform::PlatformStatusImpl,
+    sorted_set::SortedSet,
+    DependencyKind, Error, JsonValue, PackageId,
+};
+use camino::{Utf8Path, Utf8PathBuf};
+use fixedbitset::FixedBitSet;
+use indexmap::{IndexMap, IndexSet};
+use petgraph::{prelude::*, visit::Dfs};
+use semver::{Version, VersionReq};
+use smallvec::SmallVec;
+use std::collections::BTreeMap;
+use target_spec::TargetSpec;
+
+impl PackageGraph {
+    /// Returns an editor that makes in-memory changes to this graph.
+    ///
+    /// For more, see the documentation for [`PackageGraphEditor`].
+    pub fn edit(&mut self) -> PackageGraphEditor<'_> {
+        PackageGraphEditor { graph: self }
+    }
+}
+
+/// Makes in-memory changes to a `PackageGraph`, as if manifests had been edited and
+/// `cargo metadata` re-run.
+///
+/// Tools that edit manifests can use this to look at the effect of an edit without a round trip
+/// through Cargo. Cargo's dependency resolution isn't simulated, so edits are limited to packages
+/// already present in the graph: new dependencies must point to an existing package, and version
+/// requirements must match the version of the package they point to.
+///
+/// Each edit invalidates the graph's caches, including its feature graph. If an edit returns an
+/// error, the graph is left unchanged.
+///
+/// Created by [`PackageGraph::edit`].
+///
+/// # Examples
+///
+/// ```
+/// use guppy::{
+///     graph::{DependencyDirection, DependencySpec},
+///     DependencyKind, MetadataCommand,
+/// };
+///
+/// let mut graph = MetadataCommand::new().build_graph().expect("guppy graph constructed");
+/// let guppy_id = graph.workspace().member_by_path("guppy").expect("guppy found").id().clone();
+/// let semver_id = graph
+///     .resolve_package_name("semver")
+///     .package_ids(DependencyDirection::Forward)
+///     .next()
+///     .expect("semver found")
+///     .clone();
+///
+/// // What if guppy didn't depend on semver any more?
+/// graph.edit().remove_dependency(&guppy_id, &semver_id).expect("dependency removed");
+/// assert!(!graph.directly_depends_on(&guppy_id, &semver_id).expect("valid package IDs"));
+///
+/// // Add it back as an optional dependency.
+/// let mut spec = DependencySpec::new(DependencyKind::Normal);
+/// spec.set_optional(true);
+/// graph.edit().add_dependency(&guppy_id, &semver_id, &spec).expect("dependency added");
+/// let guppy = graph.metadata(&guppy_id).expect("valid package ID");
+/// assert!(guppy.named_features().any(|feature| feature == "semver"));
+/// ```
+#[derive(Debug)]
+pub struct PackageGraphEditor<'a> {
+    graph: &'a mut PackageGraph,
+}
+
+impl<'a> PackageGraphEditor<'a> {
+    /// Adds a dependency from one package to another.
+    ///
+    /// If `from` already depends on `to`, the dependency is added as another instance of the
+    /// existing link: for example, a build dependency can be added to a package that's already a
+    /// normal dependency. The name of the dependency must match the one already in use.
+    ///
+    /// If the dependency is optional, an implicit feature is added for it unless a named feature
+    /// already has the same name.
+    pub fn add_dependency(
+        &mut self,
+        from: &PackageId,
+        to: &PackageId,
+        spec: &DependencySpec,
+    ) -> Result<(), Error> {
+        let from_ix = self.graph.package_ix(from)?;
+        let to_ix = self.graph.package_ix(to)?;
+        let to_metadata = &self.graph.data.packages[to];
+
+        if spec.kind == DependencyKind::Development && spec.optional {
+            return Err(Error::PackageGraphEditError(format!(
+                "{} -> {}: dev-dependencies cannot be optional",
+                from, to
+            )));
+        }
+        let version_req = match &spec.version_req {
+            Some(version_req) => version_req.clone(),
+            None => VersionReq::parse(&format!("^{}", to_metadata.version))
+                .expect("caret requirement for a valid version is valid"),
+        };
+        if !cargo_version_matches(&version_req, &to_metadata.version) {
+            return Err(Error::PackageGraphEditError(format!(
+                "{} -> {}: version ({}) doesn't match requirement ({})",
+                from, to, to_metadata.version, version_req
+            )));
+        }
+        let dep_name = spec
+            .rename
+            .clone()
+            .unwrap_or_else(|| to_metadata.name.clone());
+        let target_spec = spec
+            .target
+            .as_deref()
+            .map(|target| {
+                target.parse::<TargetSpec>().map_err(|err| {
+                    Error::TargetSpecError(
+                        format!(
+                            "parsing target '{}' for dependency '{}' of '{}'",
+                            target, dep_name, from
+                        ),
+                        err,
+                    )
+                })
+            })
+            .transpose()?;
+
+        match self.graph.dep_graph.find_edge(from_ix, to_ix) {
+            Some(edge_ix) => {
+                let link = &mut self.graph.dep_graph[edge_ix];
+                if link.dep_name != dep_name {
+                    return Err(Error::PackageGraphEditError(format!(
+                        "{} -> {}: already depended on as '{}', not '{}'",
+                        from, to, link.dep_name, dep_name
+                    )));
+                }
+                link.req_mut(spec.kind)
+                    .add_spec_instance(spec, target_spec.as_ref());
+            }
+            None => {
+                let name_in_use =
+                    self.graph
+                        .dep_graph
+                        .edges_directed(from_ix, Outgoing)
+                        .any(|edge| {
+                            let link = edge.weight();
+                            link.dep_name == dep_name && !link.req(spec.kind).enabled().is_never()
+                        });
+                if name_in_use {
+                    return Err(Error::PackageGraphEditError(format!(
+                        "{}: a {} dependency named '{}' already exists",
+                        from, spec.kind, dep_name
+                    )));
+                }
+
+                let mut link = PackageLinkImpl {
+                    resolved_name: dep_name.replace('-', "_"),
+                    dep_name: dep_name.clone(),
+                    version_req,
+                    normal: DependencyReqImpl::default(),
+                    build: DependencyReqImpl::default(),
+                    dev: DependencyReqImpl::default(),
+                };
+                link.req_mut(spec.kind)
+                    .add_spec_instance(spec, target_spec.as_ref());
+                self.graph.dep_graph.add_edge(from_ix, to_ix, link);
+            }
+        }
+
+        if spec.optional {
+            let from_metadata = self
+                .graph
+                .data
+                .packages
+                .get_mut(from)
+                .expect("package ID is valid");
+            let dep_name = dep_name.into_boxed_str();
+            if from_metadata.optional_deps.insert(dep_name.clone()) {
+                let explicit = from_metadata.named_features.values().any(|deps| {
+                    deps.iter().any(|dep| {
+                        matches!(dep, NamedFeatureDep::OptionalDependency(name) if *name == dep_name)
+                    })
+                });
+                if !explicit && !from_metadata.named_features.contains_key(&dep_name) {
+                    from_metadata.named_features.insert(
+                        dep_name.clone(),
+                        std::iter::once(NamedFeatureDep::OptionalDependency(dep_name)).collect(),
+                    );
+                }
+            }
+        }
+
+        self.finish_edit();
+        Ok(())
+    }
+
+    /// Removes the dependency from one package to another, across all dependency kinds.
+    ///
+    /// As with `cargo remove`, if no other dependency by the same name remains, references to it
+    /// are removed from the features of `from`.
+    ///
+    /// Packages outside the workspace that are no longer depended on are removed from the graph,
+    /// as Cargo would remove them from the lockfile.
+    pub fn remove_dependency(&mut self, from: &PackageId, to: &PackageId) -> Result<(), Error> {
+        let from_ix = self.graph.package_ix(from)?;
+        let edge_ix = self.edge_ix(from, to)?;
+        let reachable_before = self.reachable();
+        let link = self
+            .graph
+            .dep_graph
+            .remove_edge(edge_ix)
+            .expect("edge index is valid");
+
+        let name_remains = self
+            .graph
+            .dep_graph
+            .edges_directed(from_ix, Outgoing)
+            .any(|edge| edge.weight().dep_name == link.dep_name);
+        if !name_remains {
+            self.graph
+                .data
+                .packages
+                .get_mut(from)
+                .expect("package ID is valid")
+                .remove_dependency_name(&link.dep_name);
+        }
+
+        self.remove_unreachable(&reachable_before);
+        self.finish_edit();
+        Ok(())
+    }
+
+    /// Sets the features enabled on a dependency of the given kind, replacing any features that
+    /// were enabled before.
+    ///
+    /// The features are enabled on every platform the dependency is enabled on.
+    pub fn set_dependency_features<'f>(
+        &mut self,
+        from: &PackageId,
+        to: &PackageId,
+        kind: DependencyKind,
+        default_features: bool,
+        features: impl IntoIterator<Item = &'f str>,
+    ) -> Result<(), Error> {
+        let features: Vec<_> = features.into_iter().collect();
+        self.edit_dependency_parts(from, to, kind, |part| {
+            part.set_default_features(default_features);
+            part.feature_targets.clear();
+            part.enable_features(&features);
+        })
+    }
+
+    /// Enables features on a dependency of the given kind, in addition to any features that were
+    /// enabled before.
+    ///
+    /// The features are enabled on every platform the dependency is enabled on.
+    pub fn enable_dependency_features<'f>(
+        &mut self,
+        from: &PackageId,
+        to: &PackageId,
+        kind: DependencyKind,
+        features: impl IntoIterator<Item = &'f str>,
+    ) -> Result<(), Error> {
+        let features: Vec<_> = features.into_iter().collect();
+        self.edit_dependency_parts(from, to, kind, |part| part.enable_features(&features))
+    }
+
+    /// Disables features on a dependency of the given kind, leaving other features unchanged.
+    ///
+    /// Features that weren't enabled on the dependency are ignored.
+    pub fn disable_dependency_features<'f>(
+        &mut self,
+        from: &PackageId,
+        to: &PackageId,
+        kind: DependencyKind,
+        features: impl IntoIterator<Item = &'f str>,
+    ) -> Result<(), Error> {
+        let features: Vec<_> = features.into_iter().collect();
+        self.edit_dependency_parts(from, to, kind, |part| {
+            part.feature_targets
+                .retain(|feature, _| !features.contains(&feature.as_str()))
+        })
+    }
+
+    /// Sets whether default features are enabled on a dependency of the given kind, leaving other
+    /// features unchanged.
+    ///
+    /// This applies to every platform the dependency is enabled on.
+    pub fn set_default_features(
+        &mut self,
+        from: &PackageId,
+        to: &PackageId,
+        kind: DependencyKind,
+        default_features: bool,
+    ) -> Result<(), Error> {
+        self.edit_dependency_parts(from, to, kind, |part| {
+            part.set_default_features(default_features)
+        })
+    }
+
+    /// Sets the version requirement for a dependency.
+    ///
+    /// The requirement must match the version of `to`, since changing the package a dependency
+    /// resolves to requires Cargo.
+    pub fn set_version_req(
+        &mut self,
+        from: &PackageId,
+        to: &PackageId,
+        version_req: VersionReq,
+    ) -> Result<(), Error> {
+        let edge_ix = self.edge_ix(from, to)?;
+        let to_version = &self.graph.data.packages[to].version;
+        if !cargo_version_matches(&version_req, to_version) {
+            return Err(Error::PackageGraphEditError(format!(
+                "{} -> {}: version ({}) doesn't match requirement ({})",
+                from, to, to_version, version_req
+            )));
+        }
+        self.graph.dep_graph[edge_ix].version_req = version_req;
+
+        self.finish_edit();
+        Ok(())
+    }
+
+    /// Adds a member to the workspace, returning its package ID.
+    ///
+    /// If a path dependency is already present at the same path and with the same name, it is
+    /// turned into a workspace member. Otherwise, a new package with a library target and no
+    /// dependencies is created.
+    pub fn add_workspace_member(
+        &mut self,
+        member: &NewWorkspaceMember,
+    ) -> Result<PackageId, Error> {
+        let path = normalize_path(&member.path);
+        if path.is_absolute() || path.starts_with("..") {
+            return Err(Error::PackageGraphEditError(format!(
+                "workspace member path '{}' must be within the workspace",
+                member.path
+            )));
+        }
+        let workspace = &self.graph.data.workspace;
+        if workspace.members_by_path.contains_key(&path) {
+            return Err(Error::PackageGraphEditError(format!(
+                "workspace member already exists at path '{}'",
+                path
+            )));
+        }
+        if workspace.members_by_name.contains_key(member.name.as_str()) {
+            return Err(Error::PackageGraphEditError(format!(
+                "workspace member named '{}' already exists",
+                member.name
+            )));
+        }
+
+        let existing = self
+            .graph
+            .data
+            .packages
+            .iter()
+            .find(|(_, package)| matches!(&package.source, PackageSourceImpl::Path(existing) if **existing == path))
+            .map(|(id, package)| (id.clone(), package.name == member.name));
+        let package_id = match existing {
+            Some((package_id, true)) => {
+                let package = self
+                    .graph
+                    .data
+                    .packages
+                    .get_mut(&package_id)
+                    .expect("package ID is valid");
+                package.source = PackageSourceImpl::Workspace(path.clone().into_boxed_path());
+                package_id
+            }
+            Some((package_id, false)) => {
+                return Err(Error::PackageGraphEditError(format!(
+                    "path dependency '{}' already exists at path '{}'",
+                    package_id, path
+                )));
+            }
+            None => {
+                let mut metadata_impl = member.to_metadata_impl(&workspace.root, &path)?;
+                let package_id = PackageId::new(format!(
+                    "path+file://{}#{}@{}",
+                    workspace.root.join(&path),
+                    member.name,
+                    member.version
+                ));
+                metadata_impl.package_ix = self.graph.dep_graph.add_node(package_id.clone());
+                self.graph
+                    .data
+                    .packages
+                    .insert(package_id.clone(), metadata_impl);
+                package_id
+            }
+        };
+
+        let workspace = &mut self.graph.data.workspace;
+        workspace.members_by_path.insert(path, package_id.clone());
+        workspace
+            .members_by_name
+            .insert(member.name.clone().into_boxed_str(), package_id.clone());
+
+        self.finish_edit();
+        Ok(package_id)
+    }
+
+    /// Removes a member from the workspace.
+    ///
+    /// If other packages depend on the member, it is kept as a path dependency. Otherwise, it is
+    /// removed from the graph, along with any packages outside the workspace that are no longer
+    /// depended on.
+    pub fn remove_workspace_member(&mut self, package_id: &PackageId) -> Result<(), Error> {
+        let package_ix = self.graph.package_ix(package_id)?;
+        let path = match &self.graph.data.packages[package_id].source {
+            PackageSourceImpl::Workspace(path) => path.clone(),
+            _ => {
+                return Err(Error::PackageGraphEditError(format!(
+                    "{} is not a workspace member",
+                    package_id
+                )));
+            }
+        };
+
+        let reachable_before = self.reachable();
+        let workspace = &mut self.graph.data.workspace;
+        workspace.members_by_path.remove(&*path);
+        workspace
+            .members_by_name
+            .remove(self.graph.data.packages[package_id].name.as_str());
+
+        let has_dependents = self
+            .graph
+            .dep_graph
+            .neighbors_directed(package_ix, Incoming)
+            .any(|ix| ix != package_ix);
+        if has_dependents {
+            self.graph
+                .data
+                .packages
+                .get_mut(package_id)
+                .expect("package ID is valid")
+                .source = PackageSourceImpl::Path(path);
+        }
+        // If nothing depends on the package, it is no longer reachable from the workspace.
+        self.remove_unreachable(&reachable_before);
+
+        self.finish_edit();
+        Ok(())
+    }
+
+    // ---
+    // Helper methods
+    // ---
+
+    fn edge_ix(&self, from: &PackageId, to: &PackageId) -> Result<EdgeIndex<PackageIx>, Error> {
+        let from_ix = self.graph.package_ix(from)?;
+        let to_ix = self.graph.package_ix(to)?;
+        self.graph
+            .dep_graph
+            .find_edge(from_ix, to_ix)
+            .ok_or_else(|| {
+                Error::PackageGraphEditError(format!("{} doesn't depend on {}", from, to))
+            })
+    }
+
+    /// Returns a copy of the requirements for a dependency of the given kind, so that they can be
+    /// put back with `restore_dependency_req` after a trial edit.
+    ///
+    /// Returns `None` if `from` doesn't depend on `to`.
+    pub(super) fn dependency_req(
+        &self,
+        from: &PackageId,
+        to: &PackageId,
+        kind: DependencyKind,
+    ) -> Option<DependencyReqImpl> {
+        let edge_ix = self.edge_ix(from, to).ok()?;
+        Some(self.graph.dep_graph[edge_ix].req(kind).clone())
+    }
+
+    /// Replaces the requirements for a dependency of the given kind with ones previously returned
+    /// by `dependency_req`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `from` doesn't depend on `to`.
+    pub(super) fn restore_dependency_req(
+        &mut self,
+        from: &PackageId,
+        to: &PackageId,
+        kind: DependencyKind,
+        req: DependencyReqImpl,
+    ) {
+        let edge_ix = self
+            .edge_ix(from, to)
+            .expect("dependency_req was called for this dependency");
+        *self.graph.dep_graph[edge_ix].req_mut(kind) = req;
+
+        self.finish_edit();
+    }
+
+    /// Calls `edit` on the required and optional parts of a dependency that are present.
+    fn edit_dependency_parts(
+        &mut self,
+        from: &PackageId,
+        to: &PackageId,
+        kind: DependencyKind,
+        mut edit: impl FnMut(&mut DepRequiredOrOptional),
+    ) -> Result<(), Error> {
+        let edge_ix = self.edge_ix(from, to)?;
+        let req = self.graph.dep_graph[edge_ix].req_mut(kind);
+        if req.enabled().is_never() {
+            return Err(Error::PackageGraphEditError(format!(
+                "{} -> {}: no {} dependency found",
+                from, to, kind
+            )));
+        }
+        for part in [&mut req.required, &mut req.optional] {
+            if !part.build_if.is_never() {
+                edit(part);
+            }
+        }
+
+        self.finish_edit();
+        Ok(())
+    }
+
+    /// Returns the set of packages reachable from workspace members.
+    fn reachable(&self) -> FixedBitSet {
+        let dep_graph = &self.graph.dep_graph;
+        let mut dfs = Dfs::empty(dep_graph);
+        for member_id in self.graph.data.workspace.members_by_path.values() {
+            dfs.move_to(self.graph.data.packages[member_id].package_ix);
+            while dfs.next(dep_graph).is_some() {}
+        }
+        dfs.discovered
+    }
+
+    /// Removes packages that were reachable from workspace members before an edit, but no longer
+    /// are.
+    ///
+    /// Packages that were already unreachable (for example, ones only present in a fixture) are
+    /// left alone. Node indexes must not have changed since `reachable_before` was computed.
+    fn remove_unreachable(&mut self, reachable_before: &FixedBitSet) {
+        let reachable = self.reachable();
+
+        // Remove nodes in reverse order. petgraph moves the last node into the slot of a removed
+        // node, and going in reverse order ensures that the moved node is never one that's yet to
+        // be removed.
+        let mut unreachable: Vec<_> = self
+            .graph
+            .dep_graph
+            .node_indices()
+            .filter(|ix| reachable_before.contains(ix.index()) && !reachable.contains(ix.index()))
+            .collect();
+        unreachable.reverse();
+        for package_ix in unreachable {
+            let package_id = self
+                .graph
+                .dep_graph
+                .remove_node(package_ix)
+                .expect("package index is valid");
+            self.graph.data.packages.remove(&package_id);
+            if let Some(moved_id) = self.graph.dep_graph.node_weight(package_ix) {
+                self.graph
+                    .data
+                    .packages
+                    .get_mut(moved_id)
+                    .expect("moved package ID is valid")
+                    .package_ix = package_ix;
+            }
+        }
+    }
+
+    fn finish_edit(&mut self) {
+        let data = &mut self.graph.data;
+        OverrideImpl::resolve(
+            &mut data.workspace.overrides,
+            &data.workspace.root,
+            &data.packages,
+        );
+        #[cfg(feature = "proptest1")]
+        data.workspace.name_list.take();
+        self.graph.invalidate_caches();
+    }
+}
+
+/// The specification for a dependency added through [`PackageGraphEditor::add_dependency`].
+///
+/// This corresponds to a dependency entry in `Cargo.toml`.
+#[derive(Clone, Debug)]
+pub struct DependencySpec {
+    kind: DependencyKind,
+    version_req: Option<VersionReq>,
+    rename: Option<String>,
+    optional: bool,
+    default_features: bool,
+    features: Vec<String>,
+    target: Option<String>,
+}
+
+impl DependencySpec {
+    /// Creates a new `DependencySpec` for a dependency of the given kind.
+    ///
+    /// By default, the dependency is required, has default features enabled, and is enabled on all
+    /// platforms. Its version requirement is a caret requirement for the version of the package
+    /// depended on, as `cargo add` would write.
+    pub fn new(kind: DependencyKind) -> Self {
+        Self {
+            kind,
+            version_req: None,
+            rename: None,
+            optional: false,
+            default_features: true,
+            features: vec![],
+            target: None,
+        }
+    }
+
+    /// Sets the version requirement for this dependency.
+    pub fn set_version_req(&mut self, version_req: VersionReq) -> &mut Self {
+        self.version_req = Some(version_req);
+        self
+    }
+
+    /// Sets the name this dependency is renamed to, as with `package = "..."` in `Cargo.toml`.
+    pub fn set_rename(&mut self, rename: impl Into<String>) -> &mut Self {
+        self.rename = Some(rename.into());
+        self
+    }
+
+    /// Sets whether this dependency is optional.
+    pub fn set_optional(&mut self, optional: bool) -> &mut Self {
+        self.optional = optional;
+        self
+    }
+
+    /// Sets whether default features are enabled for this dependency.
+    pub fn set_default_features(&mut self, default_features: bool) -> &mut Self {
+        self.default_features = default_features;
+        self
+    }
+
+    /// Adds features to enable for this dependency.
+    pub fn add_features(
+        &mut self,
+        features: impl IntoIterator<Item = impl Into<String>>,
+    ) -> &mut Self {
+        self.features.extend(features.into_iter().map(|f| f.into()));
+        self
+    }
+
+    /// Makes this dependency platform-specific, as with `[target.'cfg(...)'.dependencies]`.
+    ///
+    /// The target may be a `cfg()` expression or a target triple.
+    pub fn set_target(&mut self, target: impl Into<String>) -> &mut Self {
+        self.target = Some(target.into());
+        self
+    }
+}
+
+/// A new workspace member added through [`PackageGraphEditor::add_workspace_member`].
+#[derive(Clone, Debug)]
+pub struct NewWorkspaceMember {
+    name: String,
+    version: Version,
+    path: Utf8PathBuf,
+    edition: String,
+    features: IndexMap<String, Vec<String>>,
+}
+
+impl NewWorkspaceMember {
+    /// Creates a new `NewWorkspaceMember` with the given name and version, at the given path
+    /// relative to the workspace root.
+    ///
+    /// By default, the member uses the 2021 edition and has no features.
+    pub fn new(name: impl Into<String>, version: Version, path: impl Into<Utf8PathBuf>) -> Self {
+        Self {
+            name: name.into(),
+            version,
+            path: path.into(),
+            edition: "2021".to_owned(),
+            features: IndexMap::new(),
+        }
+    }
+
+    /// Sets the edition for this member.
+    pub fn set_edition(&mut self, edition: impl Into<String>) -> &mut Self {
+        self.edition = edition.into();
+        self
+    }
+
+    /// Adds a named feature to this member, as in the `[features]` section of `Cargo.toml`.
+    ///
+    /// Since the member starts off without dependencies, features may only refer to other named
+    /// features.
+    pub fn add_feature(
+        &mut self,
+        name: impl Into<String>,
+        deps: impl IntoIterator<Item = impl Into<String>>,
+    ) -> &mut Self {
+        self.features.insert(
+            name.into(),
+            deps.into_iter().map(|dep| dep.into()).collect(),
+        );
+        self
+    }
+
+    fn to_metadata_impl(
+        &self,
+        workspace_root: &Utf8Path,
+        path: &Utf8Path,
+    ) -> Result<PackageMetadataImpl, Error> {
+        let named_features = self
+            .features
+            .iter()
+            .map(|(feature_name, deps)| {
+                let deps = deps
+                    .iter()
+                    .map(
+                        |dep| match NamedFeatureDep::from_cargo_string(dep.as_str()) {
+                            NamedFeatureDep::NamedFeature(name) => {
+                                Ok(NamedFeatureDep::NamedFeature(name))
+                            }
+                            _ => Err(Error::PackageGraphEditError(format!(
+                            "new workspace member '{}': feature '{}' refers to a dependency ('{}')",
+                            self.name, feature_name, dep
+                        ))),
+                        },
+                    )
+                    .collect::<Result<SmallVec<_>, _>>()?;
+                Ok((feature_name.clone().into_boxed_str(), deps))
+            })
+            .collect::<Result<IndexMap<_, _>, Error>>()?;
+
+        let package_dir = workspace_root.join(path);
+        let mut build_targets = BTreeMap::new();
+        build_targets.insert(
+            OwnedBuildTargetId::Library,
+            BuildTargetImpl {
+                kind: BuildTargetKindImpl::LibraryOrExample(SortedSet::new(vec!["lib".to_owned()])),
+                lib_name: Some(self.name.replace('-', "_").into_boxed_str()),
+                required_features: vec![],
+                path: package_dir.join("src/lib.rs").into_boxed_path(),
+                edition: self.edition.clone().into_boxed_str(),
+                doc_tests: true,
+            },
+        );
+
+        Ok(PackageMetadataImpl {
+            name: self.name.clone(),
+            version: self.version.clone(),
+            authors: vec![],
+            description: None,
+            license: None,
+            license_file: None,
+            manifest_path: package_dir.join("Cargo.toml").into_boxed_path(),
+            categories: vec![],
+            keywords: vec![],
+            readme: None,
+            repository: None,
+            homepage: None,
+            documentation: None,
+            edition: self.edition.clone().into_boxed_str(),
+            metadata_table: JsonValue::Null,
+            links: None,
+            publish: PackagePublishImpl::Unrestricted,
+            default_run: None,
+            rust_version: None,
+            has_default_feature: named_features.contains_key("default"),
+            named_features,
+            optional_deps: IndexSet::new(),
+
+            // This is set by the caller once the package has been added to the graph.
+            package_ix: NodeIndex::end(),
+            source: PackageSourceImpl::Workspace(path.to_path_buf().into_boxed_path()),
+            build_targets,
+        })
+    }
+}
+
+// ---
+// Helper implementations
+// ---
+
+impl PackageLinkImpl {
+    fn req(&self, kind: DependencyKind) -> &DependencyReqImpl {
+        match kind {
+            DependencyKind::Normal => &self.normal,
+            DependencyKind::Build => &self.build,
+            DependencyKind::Development => &self.dev,
+        }
+    }
+
+    fn req_mut(&mut self, kind: DependencyKind) -> &mut DependencyReqImpl {
+        match kind {
+            DependencyKind::Normal => &mut self.normal,
+            DependencyKind::Build => &mut self.build,
+            DependencyKind::Development => &mut self.dev,
+        }
+    }
+}
+
+impl DependencyReqImpl {
+    fn add_spec_instance(&mut self, spec: &DependencySpec, target_spec: Option<&TargetSpec>) {
+        let part = if spec.optional {
+            &mut self.optional
+        } else {
+            &mut self.required
+        };
+        part.add_spec_instance(target_spec, spec.default_features, &spec.features);
+    }
+}
+
+impl DepRequiredOrOptional {
+    fn set_default_features(&mut self, default_features: bool) {
+        let enabled_on = self.build_if.clone();
+        if default_features {
+            self.default_features_if = enabled_on;
+            self.no_default_features_if = PlatformStatusImpl::default();
+        } else {
+            self.default_features_if = PlatformStatusImpl::default();
+            self.no_default_features_if = enabled_on;
+        }
+    }
+
+    fn enable_features(&mut self, features: &[&str]) {
+        for feature in features {
+            self.feature_targets
+                .insert(feature.to_string(), self.build_if.clone());
+        }
+    }
+}
+
+impl PackageMetadataImpl {
+    /// Removes references to a dependency that no longer exists from this package's features.
+    fn remove_dependency_name(&mut self, dep_name: &str) {
+        let was_optional = self.optional_deps.shift_remove(dep_name);
+        if was_optional {
+            // Remove the implicit feature for this dependency, if any.
+            let is_implicit = matches!(
+                self.named_features.get(dep_name).map(|deps| deps.as_slice()),
+                Some([NamedFeatureDep::OptionalDependency(name)]) if &**name == dep_name
+            );
+            if is_implicit {
+                self.named_features.shift_remove(dep_name);
+            }
+        }
+
+        // Old-style references to the implicit feature for an optional dependency are dangling if
+        // the implicit feature was removed.
+        let dangling_feature = was_optional && !self.named_features.contains_key(dep_name);
+        for deps in self.named_features.values_mut() {
+            deps.retain(|dep| match dep {
+                NamedFeatureDep::OptionalDependency(name) => &**name != dep_name,
+                NamedFeatureDep::DependencyNamedFeature { dep_name: name, .. } => {
+                    &**name != dep_name
+                }
+                NamedFeatureDep::NamedFeature(name) => !dangling_feature || &**name != dep_name,
+            });
+        }
+    }
+}
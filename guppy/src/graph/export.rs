@@ -0,0 +1,649 @@
+// Copyright (c) The cargo-guppy Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Export package and feature graphs to other graph formats.
+//!
+//! A [`PackageSet`] or [`FeatureSet`] can be converted into an [`ExportGraph`], a format-neutral
+//! list of nodes and links annotated with attributes. An `ExportGraph` can then be written out by
+//! any implementation of [`GraphExporter`]. Exporters for the following formats are provided:
+//!
+//! * [`DotExporter`]: the [Graphviz](https://graphviz.org/) `dot` format. Nodes are colored by
+//!   package source, and links are styled by dependency kind.
+//! * [`GraphMlExporter`]: [GraphML](http://graphml.graphdrawing.org/), an XML format supported by
+//!   tools like Gephi, yEd and Cytoscape.
+//! * [`JsonExporter`]: a JSON node-link format, documented on [`ExportGraph`].
+//! * [`MermaidExporter`]: a [Mermaid](https://mermaid.js.org/) flowchart.
+//!
+//! [`ExportFormat`] can be used to pick an exporter at runtime.
+//!
+//! # Examples
+//!
+//! ```
+//! use guppy::graph::export::ExportFormat;
+//! use guppy::MetadataCommand;
+//!
+//! let package_graph = MetadataCommand::new().build_graph().unwrap();
+//! let export_graph = package_graph.resolve_workspace().to_export_graph();
+//!
+//! // Print out the workspace as a Mermaid flowchart.
+//! println!("{}", export_graph.display(ExportFormat::Mermaid));
+//! ```
+
+use crate::{
+    graph::{
+        feature::{FeatureMetadata, FeatureSet},
+        DependencyDirection, DotWrite, ExternalSource, PackageLink, PackageMetadata, PackageSet,
+    },
+    DependencyKind,
+};
+use serde::Serialize;
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    fmt::{self, Write},
+};
+
+impl<'g> PackageSet<'g> {
+    /// Converts this package set into a format-neutral graph for export.
+    ///
+    /// Packages are returned in forward topological order. For the attributes set on nodes and
+    /// links, see the documentation for [`ExportGraph`].
+    pub fn to_export_graph(&self) -> ExportGraph {
+        let nodes = self
+            .packages(DependencyDirection::Forward)
+            .map(|package| ExportNode {
+                id: package.id().to_string(),
+                label: package_label(package),
+                attributes: package_attributes(package),
+            })
+            .collect();
+        let links = self
+            .links(DependencyDirection::Forward)
+            .map(|link| ExportLink {
+                source: link.from().id().to_string(),
+                target: link.to().id().to_string(),
+                label: link.dep_name().to_owned(),
+                attributes: link_attributes(link, |kind| link.req_for_kind(kind).is_present()),
+            })
+            .collect();
+        ExportGraph { nodes, links }
+    }
+}
+
+impl<'g> FeatureSet<'g> {
+    /// Converts this feature set into a format-neutral graph for export.
+    ///
+    /// Features are returned in forward topological order. For the attributes set on nodes and
+    /// links, see the documentation for [`ExportGraph`].
+    pub fn to_export_graph(&self) -> ExportGraph {
+        let nodes = self
+            .features(DependencyDirection::Forward)
+            .map(|feature| {
+                let mut attributes = package_attributes(feature.package());
+                attributes.insert("feature".to_owned(), feature.label().to_string());
+                ExportNode {
+                    id: feature.feature_id().to_string(),
+                    label: feature_label(feature),
+                    attributes,
+                }
+            })
+            .collect();
+        let links = self
+            .link_parts(DependencyDirection::Forward)
+            .map(|(from, to, conditional_link)| {
+                let (label, attributes) = match conditional_link {
+                    Some(conditional_link) => {
+                        let link = conditional_link.package_link();
+                        (
+                            link.dep_name().to_owned(),
+                            link_attributes(link, |kind| {
+                                !conditional_link.status_for_kind(kind).is_never()
+                            }),
+                        )
+                    }
+                    None => (String::new(), BTreeMap::new()),
+                };
+                ExportLink {
+                    source: from.feature_id().to_string(),
+                    target: to.feature_id().to_string(),
+                    label,
+                    attributes,
+                }
+            })
+            .collect();
+        ExportGraph { nodes, links }
+    }
+}
+
+/// A format-neutral representation of a graph, ready to be exported.
+///
+/// Created by [`PackageSet::to_export_graph`] or [`FeatureSet::to_export_graph`], or manually.
+///
+/// ## Attributes
+///
+/// Graphs created by guppy set the following attributes on nodes:
+///
+/// * `name`, `version` and `source`: the name, version and source of the package.
+/// * `source-kind`: one of `workspace`, `path`, `registry`, `git` or `external` (for other
+///   external sources).
+/// * `feature`: for feature graphs only, the feature label, e.g. `default` or `[base]`.
+///
+/// Links between packages, and links between features of different packages, have these
+/// attributes:
+///
+/// * `dep-name`: the name of the dependency as seen by the dependent package, taking renames into
+///   account.
+/// * `kinds`: the dependency kinds this link is present for, separated by commas, e.g.
+///   `normal,build`. The names are as returned by [`DependencyKind::to_str`].
+///
+/// Links between features of the same package have no attributes.
+///
+/// ## JSON format
+///
+/// An `ExportGraph` serializes (through `serde` or [`JsonExporter`]) to a node-link format similar
+/// to the one used by [NetworkX](https://networkx.org/) and [D3](https://d3js.org/). The output
+/// format is part of the API.
+///
+/// ```json
+/// {
+///   "nodes": [
+///     {
+///       "id": "foo 0.1.0 (path+file:///path/to/foo)",
+///       "label": "foo v0.1.0",
+///       "attributes": { "name": "foo", "source-kind": "workspace", ... }
+///     },
+///     ...
+///   ],
+///   "links": [
+///     {
+///       "source": "foo 0.1.0 (path+file:///path/to/foo)",
+///       "target": "bar 1.0.0 (registry+https://github.com/rust-lang/crates.io-index)",
+///       "label": "bar",
+///       "attributes": { "dep-name": "bar", "kinds": "normal" }
+///     },
+///     ...
+///   ]
+/// }
+/// ```
+#[derive(Clone, Debug, Default, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct ExportGraph {
+    /// The nodes in this graph.
+    pub nodes: Vec<ExportNode>,
+
+    /// The links in this graph. The source and target of each link must be the ID of a node in
+    /// `nodes`.
+    pub links: Vec<ExportLink>,
+}
+
+impl ExportGraph {
+    /// Returns a value that, when displayed, writes out this graph using the given exporter.
+    ///
+    /// Displaying the value fails with `fmt::Error` if a link refers to a node that isn't part of
+    /// the graph.
+    pub fn display<'a, E: GraphExporter + 'a>(&'a self, exporter: E) -> impl fmt::Display + 'a {
+        ExportDisplay {
+            graph: self,
+            exporter,
+        }
+    }
+
+    /// Returns a map of node IDs to their positions in `nodes`.
+    fn node_positions(&self) -> HashMap<&str, usize> {
+        self.nodes
+            .iter()
+            .enumerate()
+            .map(|(position, node)| (node.id.as_str(), position))
+            .collect()
+    }
+
+    /// Returns the positions of the source and target of each link, failing if a link refers to a
+    /// node not in the graph.
+    fn link_positions(&self) -> Result<Vec<(usize, usize, &ExportLink)>, fmt::Error> {
+        let positions = self.node_positions();
+        self.links
+            .iter()
+            .map(|link| {
+                let source = positions.get(link.source.as_str()).ok_or(fmt::Error)?;
+                let target = positions.get(link.target.as_str()).ok_or(fmt::Error)?;
+                Ok((*source, *target, link))
+            })
+            .collect()
+    }
+}
+
+/// A node in an [`ExportGraph`].
+#[derive(Clone, Debug, Default, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct ExportNode {
+    /// A unique identifier for this node.
+    pub id: String,
+
+    /// A human-readable label for this node.
+    pub label: String,
+
+    /// Additional attributes for this node.
+    pub attributes: BTreeMap<String, String>,
+}
+
+/// A directed link in an [`ExportGraph`].
+#[derive(Clone, Debug, Default, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct ExportLink {
+    /// The ID of the node this link starts from.
+    pub source: String,
+
+    /// The ID of the node this link points to.
+    pub target: String,
+
+    /// A human-readable label for this link. May be empty.
+    pub label: String,
+
+    /// Additional attributes for this link.
+    pub attributes: BTreeMap<String, String>,
+}
+
+/// Writes out an [`ExportGraph`] in some format.
+///
+/// Implement this trait to add support for other formats.
+pub trait GraphExporter {
+    /// Writes out the graph to the given formatter.
+    fn export(&self, graph: &ExportGraph, f: &mut fmt::Formatter<'_>) -> fmt::Result;
+}
+
+impl<T> GraphExporter for &T
+where
+    T: GraphExporter + ?Sized,
+{
+    fn export(&self, graph: &ExportGraph, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        (**self).export(graph, f)
+    }
+}
+
+/// The graph formats supported by guppy.
+///
+/// `ExportFormat` implements [`GraphExporter`] by forwarding to the exporter for the format.
+#[derive(Copy, Clone, Debug, Eq, Hash, PartialEq)]
+pub enum ExportFormat {
+    /// The Graphviz `dot` format. See [`DotExporter`].
+    Dot,
+
+    /// GraphML. See [`GraphMlExporter`].
+    GraphMl,
+
+    /// The JSON node-link format. See [`JsonExporter`].
+    Json,
+
+    /// Mermaid flowcharts. See [`MermaidExporter`].
+    Mermaid,
+}
+
+impl ExportFormat {
+    /// A list of all the possible values of `ExportFormat`.
+    pub const VALUES: &'static [Self; 4] = &[
+        ExportFormat::Dot,
+        ExportFormat::GraphMl,
+        ExportFormat::Json,
+        ExportFormat::Mermaid,
+    ];
+
+    /// Returns a string representing this format.
+    pub fn to_str(self) -> &'static str {
+        match self {
+            ExportFormat::Dot => "dot",
+            ExportFormat::GraphMl => "graphml",
+            ExportFormat::Json => "json",
+            ExportFormat::Mermaid => "mermaid",
+        }
+    }
+}
+
+impl fmt::Display for ExportFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.to_str())
+    }
+}
+
+impl GraphExporter for ExportFormat {
+    fn export(&self, graph: &ExportGraph, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExportFormat::Dot => DotExporter.export(graph, f),
+            ExportFormat::GraphMl => GraphMlExporter.export(graph, f),
+            ExportFormat::Json => JsonExporter.export(graph, f),
+            ExportFormat::Mermaid => MermaidExporter.export(graph, f),
+        }
+    }
+}
+
+/// Exports graphs in the Graphviz `dot` format.
+///
+/// Nodes are identified by their position in the graph, and are colored by the `source-kind`
+/// attribute: blue for workspace packages, dark green for other path dependencies, dark orange
+/// for Git dependencies and black for registry dependencies. Links are styled by the strongest
+/// kind in the `kinds` attribute: solid for normal dependencies, dashed for build dependencies and
+/// dotted for dev-dependencies.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct DotExporter;
+
+impl GraphExporter for DotExporter {
+    fn export(&self, graph: &ExportGraph, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let links = graph.link_positions()?;
+
+        writeln!(f, "digraph {{")?;
+        for (position, node) in graph.nodes.iter().enumerate() {
+            write!(f, "{}{} [label=\"", INDENT, position)?;
+            write!(DotWrite::new(f), "{}", node.label)?;
+            write!(f, "\"")?;
+            if let Some(color) = source_color(node) {
+                write!(f, ", color=\"{}\"", color)?;
+            }
+            writeln!(f, "]")?;
+        }
+        for (source, target, link) in links {
+            write!(f, "{}{} -> {} [label=\"", INDENT, source, target)?;
+            write!(DotWrite::new(f), "{}", link.label)?;
+            write!(f, "\"")?;
+            if let Some(kind) = strongest_kind(link) {
+                let style = match kind {
+                    DependencyKind::Normal => "solid",
+                    DependencyKind::Build => "dashed",
+                    DependencyKind::Development => "dotted",
+                };
+                write!(f, ", style=\"{}\"", style)?;
+            }
+            writeln!(f, "]")?;
+        }
+        writeln!(f, "}}")
+    }
+}
+
+/// Exports graphs in the [GraphML](http://graphml.graphdrawing.org/) format.
+///
+/// Labels are stored in the `label` key, and every node and link attribute is declared as a
+/// string-valued key of the same name.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct GraphMlExporter;
+
+impl GraphExporter for GraphMlExporter {
+    fn export(&self, graph: &ExportGraph, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let links = graph.link_positions()?;
+        let node_keys: BTreeSet<_> = graph
+            .nodes
+            .iter()
+            .flat_map(|node| node.attributes.keys())
+            .collect();
+        let edge_keys: BTreeSet<_> = graph
+            .links
+            .iter()
+            .flat_map(|link| link.attributes.keys())
+            .collect();
+
+        writeln!(f, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+        writeln!(
+            f,
+            r#"<graphml xmlns="http://graphml.graphdrawing.org/xmlns">"#
+        )?;
+        writeln!(
+            f,
+            r#"{}<key id="label" for="all" attr.name="label" attr.type="string"/>"#,
+            INDENT
+        )?;
+        for (prefix, keys) in [("node", &node_keys), ("edge", &edge_keys)] {
+            for key in keys {
+                writeln!(
+                    f,
+                    r#"{}<key id="{}-{}" for="{}" attr.name="{}" attr.type="string"/>"#,
+                    INDENT,
+                    prefix,
+                    XmlEscape(key),
+                    prefix,
+                    XmlEscape(key)
+                )?;
+            }
+        }
+
+        writeln!(f, r#"{}<graph id="G" edgedefault="directed">"#, INDENT)?;
+        for (position, node) in graph.nodes.iter().enumerate() {
+            writeln!(f, r#"{0}{0}<node id="n{1}">"#, INDENT, position)?;
+            write_graphml_data(f, "node", &node.label, &node.attributes)?;
+            writeln!(f, "{0}{0}</node>", INDENT)?;
+        }
+        for (edge_position, (source, target, link)) in links.into_iter().enumerate() {
+            writeln!(
+                f,
+                r#"{0}{0}<edge id="e{1}" source="n{2}" target="n{3}">"#,
+                INDENT, edge_position, source, target
+            )?;
+            write_graphml_data(f, "edge", &link.label, &link.attributes)?;
+            writeln!(f, "{0}{0}</edge>", INDENT)?;
+        }
+        writeln!(f, "{}</graph>", INDENT)?;
+        writeln!(f, "</graphml>")
+    }
+}
+
+/// Exports graphs in the JSON node-link format documented on [`ExportGraph`].
+#[derive(Copy, Clone, Debug, Default)]
+pub struct JsonExporter;
+
+impl GraphExporter for JsonExporter {
+    fn export(&self, graph: &ExportGraph, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Check links the same way other exporters do.
+        graph.link_positions()?;
+        let json = serde_json::to_string_pretty(graph).map_err(|_| fmt::Error)?;
+        writeln!(f, "{}", json)
+    }
+}
+
+/// Exports graphs as [Mermaid](https://mermaid.js.org/) flowcharts.
+///
+/// Nodes are styled by the `source-kind` attribute, with the same colors as [`DotExporter`].
+/// Links are drawn by the strongest kind in the `kinds` attribute: normal arrows for normal
+/// dependencies, thick arrows for build dependencies and dotted arrows for dev-dependencies.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct MermaidExporter;
+
+impl GraphExporter for MermaidExporter {
+    fn export(&self, graph: &ExportGraph, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let links = graph.link_positions()?;
+
+        writeln!(f, "flowchart TD")?;
+        let mut classes: BTreeMap<&str, Vec<usize>> = BTreeMap::new();
+        for (position, node) in graph.nodes.iter().enumerate() {
+            writeln!(
+                f,
+                "{}n{}[\"{}\"]",
+                INDENT,
+                position,
+                MermaidEscape(&node.label)
+            )?;
+            if let Some(source_kind) = node.attributes.get(SOURCE_KIND) {
+                classes.entry(source_kind).or_default().push(position);
+            }
+        }
+        for (source, target, link) in links {
+            let arrow = match strongest_kind(link) {
+                Some(DependencyKind::Build) => "==>",
+                Some(DependencyKind::Development) => "-.->",
+                Some(DependencyKind::Normal) | None => "-->",
+            };
+            write!(f, "{}n{} {}", INDENT, source, arrow)?;
+            if !link.label.is_empty() {
+                write!(f, "|\"{}\"|", MermaidEscape(&link.label))?;
+            }
+            writeln!(f, " n{}", target)?;
+        }
+        for (source_kind, positions) in classes {
+            let color = match color_for_source_kind(source_kind) {
+                Some(color) => color,
+                None => continue,
+            };
+            writeln!(f, "{}classDef {} stroke:{}", INDENT, source_kind, color)?;
+            let nodes: Vec<_> = positions
+                .iter()
+                .map(|position| format!("n{}", position))
+                .collect();
+            writeln!(f, "{}class {} {}", INDENT, nodes.join(","), source_kind)?;
+        }
+        Ok(())
+    }
+}
+
+// ---
+// Helper methods
+// ---
+
+static INDENT: &str = "    ";
+static SOURCE_KIND: &str = "source-kind";
+static KINDS: &str = "kinds";
+
+struct ExportDisplay<'a, E> {
+    graph: &'a ExportGraph,
+    exporter: E,
+}
+
+impl<'a, E: GraphExporter> fmt::Display for ExportDisplay<'a, E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.exporter.export(self.graph, f)
+    }
+}
+
+fn package_label(package: PackageMetadata<'_>) -> String {
+    format!("{} v{}", package.name(), package.version())
+}
+
+fn feature_label(feature: FeatureMetadata<'_>) -> String {
+    format!("{}/{}", package_label(feature.package()), feature.label())
+}
+
+fn package_attributes(package: PackageMetadata<'_>) -> BTreeMap<String, String> {
+    let source = package.source();
+    let source_kind = if source.is_workspace() {
+        "workspace"
+    } else if source.is_path() {
+        "path"
+    } else {
+        match source.parse_external() {
+            Some(ExternalSource::Registry(_)) => "registry",
+            Some(ExternalSource::Git { .. }) => "git",
+            _ => "external",
+        }
+    };
+
+    let mut attributes = BTreeMap::new();
+    attributes.insert("name".to_owned(), package.name().to_owned());
+    attributes.insert("version".to_owned(), package.version().to_string());
+    attributes.insert("source".to_owned(), source.to_string());
+    attributes.insert(SOURCE_KIND.to_owned(), source_kind.to_owned());
+    attributes
+}
+
+fn link_attributes(
+    link: PackageLink<'_>,
+    is_present: impl Fn(DependencyKind) -> bool,
+) -> BTreeMap<String, String> {
+    let kinds: Vec<_> = [
+        DependencyKind::Normal,
+        DependencyKind::Build,
+        DependencyKind::Development,
+    ]
+    .into_iter()
+    .filter(|kind| is_present(*kind))
+    .map(|kind| kind.to_str())
+    .collect();
+
+    let mut attributes = BTreeMap::new();
+    attributes.insert("dep-name".to_owned(), link.dep_name().to_owned());
+    attributes.insert(KINDS.to_owned(), kinds.join(","));
+    attributes
+}
+
+fn source_color(node: &ExportNode) -> Option<&'static str> {
+    node.attributes
+        .get(SOURCE_KIND)
+        .and_then(|source_kind| color_for_source_kind(source_kind))
+}
+
+fn color_for_source_kind(source_kind: &str) -> Option<&'static str> {
+    match source_kind {
+        "workspace" => Some("blue"),
+        "path" => Some("darkgreen"),
+        "git" => Some("darkorange"),
+        "registry" => Some("black"),
+        _ => None,
+    }
+}
+
+/// Returns the strongest dependency kind listed in the `kinds` attribute of this link, with normal
+/// dependencies being the strongest and dev-dependencies the weakest.
+fn strongest_kind(link: &ExportLink) -> Option<DependencyKind> {
+    let kinds = link.attributes.get(KINDS)?;
+    let kinds = kinds.split(',');
+    [
+        DependencyKind::Normal,
+        DependencyKind::Build,
+        DependencyKind::Development,
+    ]
+    .into_iter()
+    .find(|kind| kinds.clone().any(|s| s == kind.to_str()))
+}
+
+fn write_graphml_data(
+    f: &mut fmt::Formatter<'_>,
+    prefix: &str,
+    label: &str,
+    attributes: &BTreeMap<String, String>,
+) -> fmt::Result {
+    writeln!(
+        f,
+        r#"{0}{0}{0}<data key="label">{1}</data>"#,
+        INDENT,
+        XmlEscape(label)
+    )?;
+    for (key, value) in attributes {
+        writeln!(
+            f,
+            r#"{0}{0}{0}<data key="{1}-{2}">{3}</data>"#,
+            INDENT,
+            prefix,
+            XmlEscape(key),
+            XmlEscape(value)
+        )?;
+    }
+    Ok(())
+}
+
+/// Escapes special characters in XML text and attribute values.
+struct XmlEscape<'a>(&'a str);
+
+impl<'a> fmt::Display for XmlEscape<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for c in self.0.chars() {
+            match c {
+                '&' => f.write_str("&amp;")?,
+                '<' => f.write_str("&lt;")?,
+                '>' => f.write_str("&gt;")?,
+                '"' => f.write_str("&quot;")?,
+                '\'' => f.write_str("&apos;")?,
+                c => f.write_char(c)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Escapes special characters in quoted Mermaid labels.
+struct MermaidEscape<'a>(&'a str);
+
+impl<'a> fmt::Display for MermaidEscape<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for c in self.0.chars() {
+            match c {
+                '"' => f.write_str("#quot;")?,
+                '|' => f.write_str("#124;")?,
+                '\n' => f.write_str("<br>")?,
+                c => f.write_char(c)?,
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,408 @@
+// Copyright (c) The cargo-guppy Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Audits of features for cycles and redundancies.
+
+use crate::{
+    graph::{
+        cargo::{BuildPlatform, CargoOptions, CargoSet},
+        feature::{FeatureGraph, FeatureId, FeatureLabel, FeatureMetadata, StandardFeatures},
+        DependencyDirection, FeatureIx, PackageMetadata,
+    },
+    Error,
+};
+use petgraph::prelude::*;
+use std::{
+    cmp::Reverse,
+    collections::{BTreeSet, HashMap},
+};
+
+impl<'g> FeatureGraph<'g> {
+    /// Audits the features in this graph, looking for cycles and redundancies.
+    ///
+    /// For more information, see the documentation for [`FeatureAudit`].
+    pub fn audit(&self, opts: &FeatureAuditOptions<'_>) -> Result<FeatureAudit<'g>, Error> {
+        FeatureAudit::new(*self, opts)
+    }
+}
+
+/// Options for [`FeatureGraph::audit`].
+#[derive(Clone, Debug)]
+pub struct FeatureAuditOptions<'a> {
+    cargo_opts: CargoOptions<'a>,
+    large_default_threshold: usize,
+}
+
+impl<'a> FeatureAuditOptions<'a> {
+    /// The default value for [`set_large_default_threshold`](Self::set_large_default_threshold).
+    pub const DEFAULT_LARGE_DEFAULT_THRESHOLD: usize = 10;
+
+    /// Creates a new `FeatureAuditOptions` with default settings.
+    pub fn new() -> Self {
+        Self {
+            cargo_opts: CargoOptions::new(),
+            large_default_threshold: Self::DEFAULT_LARGE_DEFAULT_THRESHOLD,
+        }
+    }
+
+    /// Sets the Cargo options used to simulate builds of each workspace member.
+    ///
+    /// These builds are used to find optional dependencies that are always enabled.
+    pub fn set_cargo_options(&mut self, cargo_opts: CargoOptions<'a>) -> &mut Self {
+        self.cargo_opts = cargo_opts;
+        self
+    }
+
+    /// Sets the minimum number of extra packages that the `default` feature of a package must pull
+    /// in to be reported.
+    pub fn set_large_default_threshold(&mut self, threshold: usize) -> &mut Self {
+        self.large_default_threshold = threshold;
+        self
+    }
+}
+
+impl<'a> Default for FeatureAuditOptions<'a> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The results of auditing the features in a [`FeatureGraph`].
+///
+/// A `FeatureAudit` reports:
+/// * cycles between features, as returned by [`FeatureGraph::cycles`].
+/// * unreachable features: named features of packages in the graph that aren't enabled when
+///   building the workspace with all features, even taking dev-dependencies and all platforms into
+///   account.
+/// * aliases: named features that enable exactly the same features as another named feature in
+///   the same package, or only that other feature.
+/// * optional dependencies that are always enabled in practice: across simulated builds of each
+///   workspace member with default features, whenever the package is built, the optional
+///   dependency is enabled.
+/// * large defaults: `default` features that pull in many more packages than the package would
+///   without them.
+///
+/// Only packages reachable from the workspace are audited.
+///
+/// Created by [`FeatureGraph::audit`].
+#[derive(Clone, Debug)]
+pub struct FeatureAudit<'g> {
+    cycles: Vec<Vec<FeatureMetadata<'g>>>,
+    unreachable: Vec<FeatureMetadata<'g>>,
+    aliases: Vec<FeatureAlias<'g>>,
+    always_enabled: Vec<AlwaysEnabledDep<'g>>,
+    large_defaults: Vec<LargeDefault<'g>>,
+}
+
+impl<'g> FeatureAudit<'g> {
+    fn new(graph: FeatureGraph<'g>, opts: &FeatureAuditOptions<'_>) -> Result<Self, Error> {
+        let reachable = graph.query_workspace(StandardFeatures::All).resolve();
+        let packages: Vec<_> = reachable
+            .to_package_set()
+            .packages(DependencyDirection::Forward)
+            .collect();
+
+        let mut unreachable = vec![];
+        let mut aliases = vec![];
+        for package in &packages {
+            let mut named: Vec<_> = graph
+                .feature_ixs_for_package_ix(package.package_ix())
+                .map(|feature_ix| graph.metadata_for_ix(feature_ix))
+                .filter(|feature| matches!(feature.label(), FeatureLabel::Named(_)))
+                .collect();
+            // Feature nodes aren't stored in any particular order, so sort them for stable output.
+            named.sort_by_key(|feature| feature.label());
+            unreachable.extend(
+                named
+                    .iter()
+                    .filter(|feature| !reachable.contains_ix(feature.feature_ix())),
+            );
+            aliases.extend(Self::find_aliases(graph, &named));
+        }
+
+        Ok(Self {
+            cycles: graph
+                .cycles()
+                .all_cycles()
+                .map(|cycle| {
+                    cycle
+                        .into_iter()
+                        .map(|feature_id| {
+                            graph
+                                .metadata(feature_id)
+                                .expect("features in cycles are in the graph")
+                        })
+                        .collect()
+                })
+                .collect(),
+            unreachable,
+            aliases,
+            always_enabled: Self::find_always_enabled(graph, opts)?,
+            large_defaults: Self::find_large_defaults(graph, &packages, opts)?,
+        })
+    }
+
+    /// Returns all the cycles of 2 or more features in the graph, as returned by
+    /// [`Cycles::all_cycles`](crate::graph::feature::Cycles::all_cycles).
+    pub fn cycles(&self) -> &[Vec<FeatureMetadata<'g>>] {
+        &self.cycles
+    }
+
+    /// Returns named features that aren't enabled by a build of the workspace with all features.
+    ///
+    /// Packages are returned in topological order, and features within a package are sorted by
+    /// name.
+    pub fn unreachable_features<'a>(
+        &'a self,
+    ) -> impl Iterator<Item = FeatureMetadata<'g>> + ExactSizeIterator + 'a {
+        self.unreachable.iter().copied()
+    }
+
+    /// Returns named features that are aliases of other named features.
+    pub fn aliases<'a>(
+        &'a self,
+    ) -> impl Iterator<Item = &'a FeatureAlias<'g>> + ExactSizeIterator + 'a {
+        self.aliases.iter()
+    }
+
+    /// Returns optional dependencies that are enabled in every simulated build their package is a
+    /// part of, sorted by feature ID.
+    pub fn always_enabled_optional_deps<'a>(
+        &'a self,
+    ) -> impl Iterator<Item = &'a AlwaysEnabledDep<'g>> + ExactSizeIterator + 'a {
+        self.always_enabled.iter()
+    }
+
+    /// Returns `default` features that pull in at least as many extra packages as the threshold
+    /// set in the options, largest first.
+    pub fn large_defaults<'a>(
+        &'a self,
+    ) -> impl Iterator<Item = &'a LargeDefault<'g>> + ExactSizeIterator + 'a {
+        self.large_defaults.iter()
+    }
+
+    /// Returns true if nothing was found.
+    pub fn is_empty(&self) -> bool {
+        self.cycles.is_empty()
+            && self.unreachable.is_empty()
+            && self.aliases.is_empty()
+            && self.always_enabled.is_empty()
+            && self.large_defaults.is_empty()
+    }
+
+    // ---
+    // Helper methods
+    // ---
+
+    fn find_aliases(
+        graph: FeatureGraph<'g>,
+        named: &[FeatureMetadata<'g>],
+    ) -> Vec<FeatureAlias<'g>> {
+        let dep_graph = graph.dep_graph();
+        let base_ix = match named.first() {
+            // The base feature always comes first.
+            Some(feature) => graph
+                .feature_ixs_for_package_ix(feature.package_ix())
+                .next(),
+            None => return vec![],
+        };
+        // The direct dependencies of each named feature, other than the base feature of the
+        // package, which every feature depends on.
+        let direct_deps: Vec<BTreeSet<NodeIndex<FeatureIx>>> = named
+            .iter()
+            .map(|feature| {
+                dep_graph
+                    .neighbors_directed(feature.feature_ix(), Outgoing)
+                    .filter(|ix| Some(*ix) != base_ix)
+                    .collect()
+            })
+            .collect();
+        let mut dep_set_counts: HashMap<&BTreeSet<_>, usize> = HashMap::new();
+        for deps in &direct_deps {
+            *dep_set_counts.entry(deps).or_default() += 1;
+        }
+
+        let mut aliases = vec![];
+        for (idx, feature) in named.iter().enumerate() {
+            // The default feature is special, and features without dependencies are usually
+            // marker features used through `cfg(feature = "...")`.
+            if feature.label() == FeatureLabel::Named("default") || direct_deps[idx].is_empty() {
+                continue;
+            }
+            // If more than two features have the same dependencies, they're likely part of a
+            // hierarchy of features (as in `web-sys` or `windows-sys`) rather than duplicates of
+            // each other.
+            let in_hierarchy = dep_set_counts[&direct_deps[idx]] > 2;
+
+            let target = named.iter().enumerate().find(|(other_idx, other)| {
+                if *other_idx == idx {
+                    return false;
+                }
+                let is_only_dep =
+                    direct_deps[idx].len() == 1 && direct_deps[idx].contains(&other.feature_ix());
+                // For features with the same dependencies, report the later one as an alias of the
+                // earlier one.
+                let is_duplicate = !in_hierarchy
+                    && *other_idx < idx
+                    && direct_deps[idx] == direct_deps[*other_idx];
+                is_only_dep || is_duplicate
+            });
+            if let Some((_, target)) = target {
+                aliases.push(FeatureAlias {
+                    alias: *feature,
+                    target: *target,
+                });
+            }
+        }
+        aliases
+    }
+
+    fn find_always_enabled(
+        graph: FeatureGraph<'g>,
+        opts: &FeatureAuditOptions<'_>,
+    ) -> Result<Vec<AlwaysEnabledDep<'g>>, Error> {
+        let initials = graph
+            .package_graph
+            .resolve_workspace()
+            .to_feature_set(StandardFeatures::Default);
+        let per_package =
+            CargoSet::new_per_package(&initials, &graph.resolve_none(), &opts.cargo_opts)?;
+
+        // For each optional dependency, the number of builds its package is a part of, and the
+        // number of builds it's enabled in.
+        let mut counts: HashMap<NodeIndex<FeatureIx>, (usize, usize)> = HashMap::new();
+        for (_, cargo_set) in per_package.iter() {
+            for build_platform in BuildPlatform::VALUES {
+                let features = cargo_set.platform_features(*build_platform);
+                for package in features
+                    .to_package_set()
+                    .packages(DependencyDirection::Forward)
+                {
+                    for feature_ix in graph.feature_ixs_for_package_ix(package.package_ix()) {
+                        if !matches!(
+                            graph.metadata_for_ix(feature_ix).label(),
+                            FeatureLabel::OptionalDependency(_)
+                        ) {
+                            continue;
+                        }
+                        let (built, enabled) = counts.entry(feature_ix).or_default();
+                        *built += 1;
+                        if features.contains_ix(feature_ix) {
+                            *enabled += 1;
+                        }
+                    }
+                }
+            }
+        }
+
+        let mut always_enabled: Vec<_> = counts
+            .into_iter()
+            .filter(|(_, (built, enabled))| built == enabled)
+            .map(|(feature_ix, (built, _))| AlwaysEnabledDep {
+                feature: graph.metadata_for_ix(feature_ix),
+                builds: built,
+            })
+            .collect();
+        always_enabled.sort_by_key(|dep| dep.feature.feature_id());
+        Ok(always_enabled)
+    }
+
+    fn find_large_defaults(
+        graph: FeatureGraph<'g>,
+        packages: &[PackageMetadata<'g>],
+        opts: &FeatureAuditOptions<'_>,
+    ) -> Result<Vec<LargeDefault<'g>>, Error> {
+        let mut large_defaults = vec![];
+        for package in packages {
+            let default_id = FeatureId::named(package.id(), "default");
+            if !graph.contains(default_id) {
+                continue;
+            }
+            let with_default = graph
+                .query_forward([default_id])?
+                .resolve()
+                .to_package_set();
+            let without_default = graph
+                .query_forward([FeatureId::base(package.id())])?
+                .resolve()
+                .to_package_set();
+            let extra = with_default.difference(&without_default);
+            if extra.len() >= opts.large_default_threshold && !extra.is_empty() {
+                large_defaults.push(LargeDefault {
+                    package: *package,
+                    extra_packages: extra.packages(DependencyDirection::Forward).collect(),
+                });
+            }
+        }
+        // Sort by size, largest first. The sort is stable so ties stay in topological order.
+        large_defaults.sort_by_key(|large| Reverse(large.extra_packages.len()));
+        Ok(large_defaults)
+    }
+}
+
+/// A named feature that is an alias of another named feature in the same package.
+///
+/// Returned by [`FeatureAudit::aliases`].
+#[derive(Clone, Copy, Debug)]
+pub struct FeatureAlias<'g> {
+    alias: FeatureMetadata<'g>,
+    target: FeatureMetadata<'g>,
+}
+
+impl<'g> FeatureAlias<'g> {
+    /// Returns the feature that is an alias.
+    pub fn alias(&self) -> FeatureMetadata<'g> {
+        self.alias
+    }
+
+    /// Returns the feature that `alias` is an alias of.
+    pub fn target(&self) -> FeatureMetadata<'g> {
+        self.target
+    }
+}
+
+/// An optional dependency that is enabled in every build its package is a part of.
+///
+/// Returned by [`FeatureAudit::always_enabled_optional_deps`].
+#[derive(Clone, Copy, Debug)]
+pub struct AlwaysEnabledDep<'g> {
+    feature: FeatureMetadata<'g>,
+    builds: usize,
+}
+
+impl<'g> AlwaysEnabledDep<'g> {
+    /// Returns the feature corresponding to the optional dependency.
+    pub fn feature(&self) -> FeatureMetadata<'g> {
+        self.feature
+    }
+
+    /// Returns the number of builds the package is a part of.
+    ///
+    /// Each workspace member is built separately, and packages built on both the target and the
+    /// host platforms are counted twice.
+    pub fn builds(&self) -> usize {
+        self.builds
+    }
+}
+
+/// A `default` feature that pulls in a large number of extra packages.
+///
+/// Returned by [`FeatureAudit::large_defaults`].
+#[derive(Clone, Debug)]
+pub struct LargeDefault<'g> {
+    package: PackageMetadata<'g>,
+    extra_packages: Vec<PackageMetadata<'g>>,
+}
+
+impl<'g> LargeDefault<'g> {
+    /// Returns the package whose `default` feature this is.
+    pub fn package(&self) -> PackageMetadata<'g> {
+        self.package
+    }
+
+    /// Returns the packages that are only pulled in because of the `default` feature, in
+    /// topological order.
+    pub fn extra_packages(&self) -> &[PackageMetadata<'g>] {
+        &self.extra_packages
+    }
+}
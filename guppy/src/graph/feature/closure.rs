@@ -0,0 +1,335 @@
+// Copyright (c) The cargo-guppy Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Feature resolution with memoized closures, shared across several resolves.
+
+use crate::graph::{
+    feature::{ConditionalLink, FeatureGraph, FeatureSet, WeakIndex},
+    FeatureIx,
+};
+use fixedbitset::FixedBitSet;
+use indexmap::IndexSet;
+use petgraph::{prelude::*, visit::VisitMap};
+use std::{
+    cell::RefCell,
+    collections::{BTreeMap, HashMap, HashSet},
+    hash::Hash,
+    rc::Rc,
+};
+
+/// Caches the closures of features reached while resolving, for reuse by later resolves.
+///
+/// A resolve follows links according to an `accept` function, which is told whether the link
+/// starts from an initial feature. Links out of non-initial features are followed the same way in
+/// every resolve, so the set of features reachable from a non-initial feature doesn't depend on the
+/// initials. This cache stores those sets.
+///
+/// Every resolve performed with a cache must use an `accept` function that behaves the same way for
+/// links out of non-initial features. Accepting a link out of an initial feature must be at least
+/// as permissive as accepting it out of a non-initial one.
+///
+/// `N` is a note that `accept` may record for a link, for example a feature to use as an initial
+/// in a later resolve. Notes are stored along with each closure.
+#[derive(Debug)]
+pub(in crate::graph) struct ClosureCache<N> {
+    closures: RefCell<HashMap<NodeIndex<FeatureIx>, Rc<FeatureClosure<N>>>>,
+}
+
+impl<N: Copy + Eq + Hash> ClosureCache<N> {
+    pub(in crate::graph) fn new() -> Self {
+        Self {
+            closures: RefCell::new(HashMap::new()),
+        }
+    }
+
+    /// Resolves the features reachable from `seeds`.
+    ///
+    /// Features for which `is_initial` returns true have their links accepted as initials. Records
+    /// in `predecessors` the edge through which each feature not in `seeds` was first reached, and
+    /// returns the resolved set along with the notes recorded by `accept`.
+    ///
+    /// The result is the same as that of a `FeatureQuery` resolved with the same `accept` function,
+    /// though the recorded predecessors may differ.
+    pub(in crate::graph) fn resolve<'g>(
+        &self,
+        graph: FeatureGraph<'g>,
+        seeds: impl IntoIterator<Item = NodeIndex<FeatureIx>>,
+        is_initial: impl Fn(NodeIndex<FeatureIx>) -> bool,
+        accept: impl Fn(ConditionalLink<'g>, bool, &mut Vec<N>) -> bool,
+        predecessors: &mut HashMap<NodeIndex<FeatureIx>, EdgeIndex<FeatureIx>>,
+    ) -> (FeatureSet<'g>, Vec<N>) {
+        let mut resolve = Resolve::new(graph, self, &is_initial, &accept, true);
+        for seed in seeds {
+            resolve.add_seed(seed);
+        }
+        resolve.run();
+
+        predecessors.extend(
+            resolve
+                .features
+                .iter()
+                .filter_map(|(feature_ix, edge_ix)| Some((*feature_ix, (*edge_ix)?))),
+        );
+        let included = resolve.included;
+        let notes = resolve.notes.into_iter().collect();
+        (FeatureSet::from_included(graph, included), notes)
+    }
+
+    fn get(&self, feature_ix: NodeIndex<FeatureIx>) -> Option<Rc<FeatureClosure<N>>> {
+        self.closures.borrow().get(&feature_ix).cloned()
+    }
+
+    /// Returns the closure of a non-initial feature, computing it if necessary.
+    fn get_or_compute<'g>(
+        &self,
+        graph: FeatureGraph<'g>,
+        feature_ix: NodeIndex<FeatureIx>,
+        accept: &dyn Fn(ConditionalLink<'g>, bool, &mut Vec<N>) -> bool,
+    ) -> Rc<FeatureClosure<N>> {
+        if let Some(closure) = self.get(feature_ix) {
+            return closure;
+        }
+
+        // Closures are computed without creating further closures. Closures computed earlier are
+        // reused.
+        let mut resolve = Resolve::new(graph, self, &|_| false, accept, false);
+        resolve.add_seed(feature_ix);
+        resolve.run();
+
+        let activated = resolve.activated.ones().map(WeakIndex).collect();
+        let pending = resolve
+            .pending
+            .into_iter()
+            .flat_map(|(weak_index, edges)| {
+                edges
+                    .into_iter()
+                    .map(move |(edge_ix, _)| (edge_ix, WeakIndex(weak_index)))
+            })
+            .collect();
+        let closure = Rc::new(FeatureClosure {
+            features: resolve.features,
+            activated,
+            pending,
+            notes: resolve.notes.into_iter().collect(),
+        });
+        self.closures
+            .borrow_mut()
+            .insert(feature_ix, closure.clone());
+        closure
+    }
+}
+
+/// The features reachable from a non-initial feature.
+#[derive(Debug)]
+struct FeatureClosure<N> {
+    /// The features in this closure, along with the edge through which each was first reached. The
+    /// first feature is the one this closure was computed for.
+    features: Vec<(NodeIndex<FeatureIx>, Option<EdgeIndex<FeatureIx>>)>,
+    /// Weak dependencies activated within this closure.
+    activated: Vec<WeakIndex>,
+    /// Weak links out of this closure, whose dependencies weren't activated within it.
+    pending: Vec<(EdgeIndex<FeatureIx>, WeakIndex)>,
+    /// Notes recorded for links out of this closure.
+    notes: Vec<N>,
+}
+
+enum Work {
+    /// Visit a feature that was just included.
+    Visit(NodeIndex<FeatureIx>),
+    /// Evaluate a weak link whose dependency was activated, along with whether it starts from an
+    /// initial.
+    Evaluate(EdgeIndex<FeatureIx>, bool),
+}
+
+struct Resolve<'c, 'g, N> {
+    graph: FeatureGraph<'g>,
+    cache: &'c ClosureCache<N>,
+    is_initial: &'c dyn Fn(NodeIndex<FeatureIx>) -> bool,
+    accept: &'c dyn Fn(ConditionalLink<'g>, bool, &mut Vec<N>) -> bool,
+    // Whether to compute closures for features followed out of initials.
+    compute_closures: bool,
+    included: FixedBitSet,
+    features: Vec<(NodeIndex<FeatureIx>, Option<EdgeIndex<FeatureIx>>)>,
+    activated: FixedBitSet,
+    // Weak links seen so far, along with whether they start from an initial.
+    weak_links: HashSet<(EdgeIndex<FeatureIx>, bool)>,
+    // Weak links whose dependencies haven't been activated yet, by weak index.
+    pending: BTreeMap<usize, Vec<(EdgeIndex<FeatureIx>, bool)>>,
+    // Closures share many notes, so they're deduplicated.
+    notes: IndexSet<N>,
+    note_buffer: Vec<N>,
+    work: Vec<Work>,
+}
+
+impl<'c, 'g, N: Copy + Eq + Hash> Resolve<'c, 'g, N> {
+    fn new(
+        graph: FeatureGraph<'g>,
+        cache: &'c ClosureCache<N>,
+        is_initial: &'c dyn Fn(NodeIndex<FeatureIx>) -> bool,
+        accept: &'c dyn Fn(ConditionalLink<'g>, bool, &mut Vec<N>) -> bool,
+        compute_closures: bool,
+    ) -> Self {
+        Self {
+            graph,
+            cache,
+            is_initial,
+            accept,
+            compute_closures,
+            included: FixedBitSet::with_capacity(graph.feature_count()),
+            features: vec![],
+            activated: FixedBitSet::with_capacity(graph.inner.weak.len()),
+            weak_links: HashSet::new(),
+            pending: BTreeMap::new(),
+            notes: IndexSet::new(),
+            note_buffer: vec![],
+            work: vec![],
+        }
+    }
+
+    fn add_seed(&mut self, feature_ix: NodeIndex<FeatureIx>) {
+        if self.include(feature_ix, None) {
+            self.work.push(Work::Visit(feature_ix));
+        }
+    }
+
+    fn run(&mut self) {
+        while let Some(work) = self.work.pop() {
+            match work {
+                Work::Visit(feature_ix) => self.visit(feature_ix),
+                Work::Evaluate(edge_ix, from_initial) => {
+                    let (source_ix, target_ix) = self
+                        .graph
+                        .dep_graph()
+                        .edge_endpoints(edge_ix)
+                        .expect("valid edge ix");
+                    let (link, _) = self
+                        .graph
+                        .edge_to_conditional_link(source_ix, target_ix, edge_ix, None)
+                        .expect("weak links are conditional");
+                    if self.accept(link, from_initial) {
+                        self.follow(target_ix, edge_ix);
+                    }
+                }
+            }
+        }
+    }
+
+    fn accept(&mut self, link: ConditionalLink<'g>, from_initial: bool) -> bool {
+        let accepted = (self.accept)(link, from_initial, &mut self.note_buffer);
+        self.notes.extend(self.note_buffer.drain(..));
+        accepted
+    }
+
+    fn include(
+        &mut self,
+        feature_ix: NodeIndex<FeatureIx>,
+        edge_ix: Option<EdgeIndex<FeatureIx>>,
+    ) -> bool {
+        if self.included.visit(feature_ix) {
+            self.features.push((feature_ix, edge_ix));
+            true
+        } else {
+            false
+        }
+    }
+
+    fn follow(&mut self, feature_ix: NodeIndex<FeatureIx>, edge_ix: EdgeIndex<FeatureIx>) {
+        if self.include(feature_ix, Some(edge_ix)) {
+            self.work.push(Work::Visit(feature_ix));
+        }
+    }
+
+    fn visit(&mut self, feature_ix: NodeIndex<FeatureIx>) {
+        let from_initial = (self.is_initial)(feature_ix);
+        if !from_initial {
+            let closure = if self.compute_closures {
+                Some(
+                    self.cache
+                        .get_or_compute(self.graph, feature_ix, self.accept),
+                )
+            } else {
+                self.cache.get(feature_ix)
+            };
+            if let Some(closure) = closure {
+                self.merge(&closure);
+                return;
+            }
+        }
+
+        for edge_ref in self.graph.dep_graph().edges(feature_ix) {
+            let target_ix = edge_ref.target();
+            match self.graph.edge_to_conditional_link(
+                feature_ix,
+                target_ix,
+                edge_ref.id(),
+                Some(edge_ref.weight()),
+            ) {
+                Some((_, Some(weak_index))) => {
+                    self.add_weak(edge_ref.id(), weak_index, from_initial);
+                }
+                Some((link, None)) => {
+                    if self.accept(link, from_initial) {
+                        // Following a non-weak link activates weak links to the same dependency.
+                        if let Some(weak_index) = self.graph.inner.weak.get(link.package_edge_ix())
+                        {
+                            self.activate(weak_index);
+                        }
+                        self.follow(target_ix, edge_ref.id());
+                    }
+                }
+                None => {
+                    // Feature links within the same package are always followed.
+                    self.follow(target_ix, edge_ref.id());
+                }
+            }
+        }
+    }
+
+    fn merge(&mut self, closure: &FeatureClosure<N>) {
+        for (feature_ix, edge_ix) in &closure.features {
+            // Initials in the closure were visited as non-initials, so also visit them as initials.
+            if self.include(*feature_ix, *edge_ix) && (self.is_initial)(*feature_ix) {
+                self.work.push(Work::Visit(*feature_ix));
+            }
+        }
+        self.notes.extend(closure.notes.iter().copied());
+        for weak_index in &closure.activated {
+            self.activate(*weak_index);
+        }
+        for (edge_ix, weak_index) in &closure.pending {
+            self.add_weak(*edge_ix, *weak_index, false);
+        }
+    }
+
+    fn activate(&mut self, weak_index: WeakIndex) {
+        if self.activated.put(weak_index.0) {
+            return;
+        }
+        if let Some(edges) = self.pending.remove(&weak_index.0) {
+            self.work.extend(
+                edges
+                    .into_iter()
+                    .map(|(edge_ix, from_initial)| Work::Evaluate(edge_ix, from_initial)),
+            );
+        }
+    }
+
+    fn add_weak(
+        &mut self,
+        edge_ix: EdgeIndex<FeatureIx>,
+        weak_index: WeakIndex,
+        from_initial: bool,
+    ) {
+        if !self.weak_links.insert((edge_ix, from_initial)) {
+            return;
+        }
+        if self.activated.contains(weak_index.0) {
+            self.work.push(Work::Evaluate(edge_ix, from_initial));
+        } else {
+            self.pending
+                .entry(weak_index.0)
+                .or_default()
+                .push((edge_ix, from_initial));
+        }
+    }
+}
@@ -6,13 +6,17 @@ use crate::{
     errors::FeatureGraphWarning,
     graph::{
         feature::{
-            build::{FeatureGraphBuildState, FeaturePetgraph},
+            build::{FeatureEdgeReference, FeatureGraphBuildState, FeaturePetgraph},
             Cycles, FeatureFilter, FeatureList, WeakDependencies, WeakIndex,
         },
-        DependencyDirection, FeatureIndexInPackage, FeatureIx, PackageGraph, PackageIx,
-        PackageLink, PackageMetadata,
+        ArtifactDep, DependencyDirection, FeatureIndexInPackage, FeatureIx, PackageGraph,
+        PackageIx, PackageLink, PackageMetadata,
+    },
+    petgraph_support::{
+        paths::{all_simple_paths_filter_map, shortest_path},
+        scc::Sccs,
+        topo::TopoWithCycles,
     },
-    petgraph_support::{scc::Sccs, topo::TopoWithCycles},
     platform::{PlatformStatus, PlatformStatusImpl},
     DependencyKind, Error, PackageId,
 };
@@ -22,7 +26,11 @@ use petgraph::{
     prelude::*,
     visit::{EdgeFiltered, IntoNodeReferences},
 };
-use std::{collections::HashMap, fmt, iter, iter::FromIterator};
+use std::{
+    collections::{HashMap, HashSet},
+    fmt, iter,
+    iter::FromIterator,
+};
 
 // Some general notes about feature graphs:
 //
@@ -191,6 +199,74 @@ impl<'g> FeatureGraph<'g> {
         Ok(self.dep_graph().contains_edge(a_ix, b_ix))
     }
 
+    /// Returns the shortest path from `feature_a` to `feature_b`, if `feature_a` depends on
+    /// `feature_b`.
+    ///
+    /// The path is returned as the list of conditional links crossed along the way, in order.
+    /// Edges within a single package, such as a named feature enabling another named feature, are
+    /// not represented in this list: two consecutive links always end and start in the same
+    /// package.
+    ///
+    /// Returns `None` if there's no path from `feature_a` to `feature_b`, and an empty list if
+    /// the path doesn't leave the package (including if the features are the same). Returns an
+    /// error if either feature ID is unknown.
+    pub fn shortest_path<'a>(
+        &self,
+        feature_a: impl Into<FeatureId<'a>>,
+        feature_b: impl Into<FeatureId<'a>>,
+    ) -> Result<Option<Vec<ConditionalLink<'g>>>, Error> {
+        let a_ix = self.feature_ix(feature_a.into())?;
+        let b_ix = self.feature_ix(feature_b.into())?;
+        Ok(self.shortest_path_ixs(a_ix, b_ix, |_| true))
+    }
+
+    /// Returns all distinct paths from `feature_a` to `feature_b` that don't visit a feature more
+    /// than once.
+    ///
+    /// Each path is returned as the list of conditional links crossed along the way, in the same
+    /// manner as [`shortest_path`](Self::shortest_path). Paths are sorted by length.
+    ///
+    /// The number of paths can grow exponentially with the size of the graph. If `limit` is
+    /// specified, at most that many paths are returned.
+    ///
+    /// Returns an error if either feature ID is unknown.
+    pub fn all_paths<'a>(
+        &self,
+        feature_a: impl Into<FeatureId<'a>>,
+        feature_b: impl Into<FeatureId<'a>>,
+        limit: Option<usize>,
+    ) -> Result<Vec<Vec<ConditionalLink<'g>>>, Error> {
+        let a_ix = self.feature_ix(feature_a.into())?;
+        let b_ix = self.feature_ix(feature_b.into())?;
+        Ok(self.all_paths_ixs(a_ix, b_ix, limit, |_| true))
+    }
+
+    /// Returns the conditional links derived from the given `PackageLink`.
+    ///
+    /// Each conditional link represents a feature of the `from` package that enables a feature of
+    /// the `to` package through this dependency. This can be used to figure out which features
+    /// cause a dependency to be pulled in.
+    ///
+    /// The link must be from the same package graph as this feature graph.
+    pub fn conditional_links_for(
+        &self,
+        link: PackageLink<'g>,
+    ) -> impl Iterator<Item = ConditionalLink<'g>> + 'g {
+        let this = *self;
+        let package_edge_ix = link.edge_ix();
+        self.feature_ixs_for_package_ix(link.from().package_ix())
+            .flat_map(move |feature_ix| this.dep_graph().edges(feature_ix))
+            .filter_map(move |edge| {
+                let (conditional_link, _) = this.edge_to_conditional_link(
+                    edge.source(),
+                    edge.target(),
+                    edge.id(),
+                    Some(edge.weight()),
+                )?;
+                (conditional_link.package_edge_ix() == package_edge_ix).then(|| conditional_link)
+            })
+    }
+
     /// Returns information about dependency cycles.
     ///
     /// For more information, see the documentation for `Cycles`.
@@ -300,6 +376,30 @@ impl<'g> FeatureGraph<'g> {
         }
     }
 
+    /// Returns the endpoints of this edge, along with the conditional link if this is a
+    /// conditional edge.
+    pub(in crate::graph) fn edge_ix_to_parts(
+        &self,
+        edge_ix: EdgeIndex<FeatureIx>,
+    ) -> (
+        FeatureMetadata<'g>,
+        FeatureMetadata<'g>,
+        Option<ConditionalLink<'g>>,
+    ) {
+        let (source_ix, target_ix) = self
+            .dep_graph()
+            .edge_endpoints(edge_ix)
+            .expect("valid edge ix");
+        let conditional_link = self
+            .edge_to_conditional_link(source_ix, target_ix, edge_ix, None)
+            .map(|(link, _)| link);
+        (
+            self.metadata_for_ix(source_ix),
+            self.metadata_for_ix(target_ix),
+            conditional_link,
+        )
+    }
+
     fn feature_ix_depends_on(
         &self,
         a_ix: NodeIndex<FeatureIx>,
@@ -324,6 +424,65 @@ impl<'g> FeatureGraph<'g> {
         has_path_connecting(&edge_filtered, a_ix, b_ix, None)
     }
 
+    /// Returns the shortest path from `a_ix` to `b_ix` going only through features for which
+    /// `include` returns true.
+    pub(super) fn shortest_path_ixs(
+        &self,
+        a_ix: NodeIndex<FeatureIx>,
+        b_ix: NodeIndex<FeatureIx>,
+        mut include: impl FnMut(NodeIndex<FeatureIx>) -> bool,
+    ) -> Option<Vec<ConditionalLink<'g>>> {
+        let path = shortest_path(self.dep_graph(), iter::once(a_ix), b_ix, |edge| {
+            include(edge.target())
+        })?;
+        Some(self.edges_to_conditional_links(path))
+    }
+
+    /// Returns all distinct simple paths from `a_ix` to `b_ix` going only through features for
+    /// which `include` returns true.
+    pub(super) fn all_paths_ixs(
+        &self,
+        a_ix: NodeIndex<FeatureIx>,
+        b_ix: NodeIndex<FeatureIx>,
+        limit: Option<usize>,
+        mut include: impl FnMut(NodeIndex<FeatureIx>) -> bool,
+    ) -> Vec<Vec<ConditionalLink<'g>>> {
+        // Several paths through the feature graph may cross the same conditional links, differing
+        // only in edges within a package. Only return one of each, deduplicating while searching
+        // so that the limit applies to distinct paths.
+        let mut seen = HashSet::new();
+        all_simple_paths_filter_map(
+            self.dep_graph(),
+            iter::once(a_ix),
+            b_ix,
+            limit,
+            |edge| include(edge.target()),
+            |path| {
+                let links = self.edges_to_conditional_links(path.iter().copied());
+                seen.insert(links.iter().map(|link| link.edge_ix()).collect::<Vec<_>>())
+                    .then(|| links)
+            },
+        )
+    }
+
+    fn edges_to_conditional_links(
+        &self,
+        edges: impl IntoIterator<Item = FeatureEdgeReference<'g>>,
+    ) -> Vec<ConditionalLink<'g>> {
+        edges
+            .into_iter()
+            .filter_map(|edge| {
+                let (link, _) = self.edge_to_conditional_link(
+                    edge.source(),
+                    edge.target(),
+                    edge.id(),
+                    Some(edge.weight()),
+                )?;
+                Some(link)
+            })
+            .collect()
+    }
+
     pub(super) fn feature_ixs_for_package_ix(
         &self,
         package_ix: NodeIndex<PackageIx>,
@@ -809,6 +968,14 @@ impl<'g> ConditionalLink<'g> {
         self.inner.dev_only()
     }
 
+    /// Returns artifact information if the dependency in the section specified by the given
+    /// dependency kind is an artifact dependency.
+    ///
+    /// This is a shortcut for `self.package_link().req_for_kind(kind).artifact()`.
+    pub fn artifact_for_kind(&self, kind: DependencyKind) -> Option<ArtifactDep<'g>> {
+        self.package_link().req_for_kind(kind).artifact()
+    }
+
     /// Returns the `PackageLink` from which this `ConditionalLink` was derived.
     pub fn package_link(&self) -> PackageLink<'g> {
         self.graph
@@ -820,8 +987,7 @@ impl<'g> ConditionalLink<'g> {
     // Helper methods
     // ---
 
-    #[allow(dead_code)]
-    pub(super) fn edge_ix(&self) -> EdgeIndex<FeatureIx> {
+    pub(in crate::graph) fn edge_ix(&self) -> EdgeIndex<FeatureIx> {
         self.edge_ix
     }
 
@@ -0,0 +1,311 @@
+// Copyright (c) The cargo-guppy Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Suggestions for features requested by workspace packages that have no effect.
+
+use crate::{
+    graph::{
+        cargo::{BuildPlatform, CargoOptions, CargoSet, PerPackageCargoSets},
+        feature::{FeatureGraph, FeatureId, StandardFeatures},
+        DependencyDirection, PackageGraph, PackageGraphEditor, PackageLink,
+    },
+    DependencyKind, Error, PackageId,
+};
+
+impl<'g> FeatureGraph<'g> {
+    /// Looks for features that workspace packages request on third-party dependencies, but that
+    /// would be enabled anyway through other paths.
+    ///
+    /// For more information, see the documentation for [`FeatureHygiene`].
+    pub fn hygiene(
+        &self,
+        features: StandardFeatures,
+        opts: &CargoOptions<'_>,
+    ) -> Result<FeatureHygiene<'g>, Error> {
+        FeatureHygiene::new(*self, features, opts)
+    }
+}
+
+/// A report of features requested on dependencies of workspace packages that could be removed
+/// without changing what's built.
+///
+/// For every dependency of a workspace package on a package outside the workspace, each feature
+/// it enables is checked by simulating Cargo builds without that feature: every workspace member
+/// whose build includes the package is built on its own, as with `cargo build -p <member>`, using
+/// the given features and Cargo options. If the feature is still enabled on the same build
+/// platforms in each of those builds, it is redundant. `default-features = true` (the default) is
+/// checked the same way, using the `default` feature of the dependency.
+///
+/// Features are checked one at a time, and each one found to be redundant is treated as removed
+/// while checking the rest. This means that if two dependencies request the same feature, only
+/// one of them is reported, and removing every reported feature together doesn't change what's
+/// built.
+///
+/// Features that aren't enabled in any of these builds at all, for example on dependencies
+/// specific to a platform that isn't being built for, aren't reported. Neither are features
+/// requested by optional dependencies that aren't enabled.
+/// Dev-dependencies are only checked if [`CargoOptions::set_include_dev`] is true.
+///
+/// Created by [`FeatureGraph::hygiene`].
+#[derive(Clone, Debug)]
+pub struct FeatureHygiene<'g> {
+    links: Vec<LinkHygiene<'g>>,
+}
+
+impl<'g> FeatureHygiene<'g> {
+    fn new(
+        graph: FeatureGraph<'g>,
+        features: StandardFeatures,
+        opts: &CargoOptions<'_>,
+    ) -> Result<Self, Error> {
+        let package_graph = graph.package_graph;
+        let mut checker = RedundancyChecker::new(package_graph, features, opts)?;
+        let mut links = vec![];
+
+        for member in package_graph.workspace().iter() {
+            // Workspace members whose builds may include this member.
+            let builders: Vec<_> = package_graph
+                .query_reverse([member.id()])?
+                .resolve()
+                .packages(DependencyDirection::Forward)
+                .filter(|package| package.in_workspace())
+                .map(|package| package.id())
+                .collect();
+
+            for link in member.direct_links() {
+                if link.to().in_workspace() {
+                    continue;
+                }
+                for kind in DependencyKind::VALUES {
+                    if *kind == DependencyKind::Development && !opts.include_dev {
+                        continue;
+                    }
+                    if let Some(hygiene) = LinkHygiene::new(&mut checker, &builders, link, *kind)? {
+                        links.push(hygiene);
+                    }
+                }
+            }
+        }
+
+        Ok(Self { links })
+    }
+
+    /// Iterates over dependencies that request features that are redundant.
+    ///
+    /// Dependencies are grouped by workspace package, in order of workspace path. A dependency
+    /// listed as more than one kind is returned once for each kind.
+    pub fn links<'a>(
+        &'a self,
+    ) -> impl Iterator<Item = &'a LinkHygiene<'g>> + ExactSizeIterator + 'a {
+        self.links.iter()
+    }
+
+    /// Returns true if no redundant features were found.
+    pub fn is_empty(&self) -> bool {
+        self.links.is_empty()
+    }
+}
+
+/// Redundant features requested by a single dependency of a workspace package.
+///
+/// Returned by [`FeatureHygiene::links`].
+#[derive(Clone, Debug)]
+pub struct LinkHygiene<'g> {
+    link: PackageLink<'g>,
+    kind: DependencyKind,
+    redundant_features: Vec<&'g str>,
+    redundant_default: bool,
+}
+
+impl<'g> LinkHygiene<'g> {
+    fn new(
+        checker: &mut RedundancyChecker<'g, '_>,
+        builders: &[&'g PackageId],
+        link: PackageLink<'g>,
+        kind: DependencyKind,
+    ) -> Result<Option<Self>, Error> {
+        let req = link.req_for_kind(kind);
+        if !req.is_present() {
+            return Ok(None);
+        }
+        let (from, to) = (link.from().id(), link.to().id());
+        // Features requested by an optional dependency only take effect if it's enabled.
+        if req.status().required_status().is_never()
+            && !checker.is_enabled(
+                builders,
+                FeatureId::optional_dependency(from, link.dep_name()),
+            )
+        {
+            return Ok(None);
+        }
+
+        let mut redundant_features = vec![];
+        for feature in req.features() {
+            let is_redundant = checker.remove_if_redundant(
+                builders,
+                link,
+                kind,
+                FeatureId::named(to, feature),
+                |editor| editor.disable_dependency_features(from, to, kind, [feature]),
+            )?;
+            if is_redundant {
+                redundant_features.push(feature);
+            }
+        }
+        let redundant_default = !req.default_features().is_never()
+            && checker.remove_if_redundant(
+                builders,
+                link,
+                kind,
+                FeatureId::named(to, "default"),
+                |editor| editor.set_default_features(from, to, kind, false),
+            )?;
+
+        if redundant_features.is_empty() && !redundant_default {
+            return Ok(None);
+        }
+        Ok(Some(Self {
+            link,
+            kind,
+            redundant_features,
+            redundant_default,
+        }))
+    }
+
+    /// Returns the dependency.
+    pub fn link(&self) -> PackageLink<'g> {
+        self.link
+    }
+
+    /// Returns the kind of dependency, e.g. normal or build.
+    pub fn kind(&self) -> DependencyKind {
+        self.kind
+    }
+
+    /// Returns the features listed in `features = [...]` for this dependency that are enabled
+    /// anyway through other paths.
+    pub fn redundant_features(&self) -> &[&'g str] {
+        &self.redundant_features
+    }
+
+    /// Returns true if this dependency uses default features, but they're enabled anyway through
+    /// other paths, so `default-features = false` would make no difference.
+    pub fn is_default_redundant(&self) -> bool {
+        self.redundant_default
+    }
+}
+
+/// Checks whether features are redundant in the builds of workspace members.
+///
+/// Redundant features are removed from a working copy of the package graph as they're found, so
+/// that each feature is checked with every earlier removal applied.
+struct RedundancyChecker<'g, 'a> {
+    working_graph: PackageGraph,
+    features: StandardFeatures,
+    opts: &'a CargoOptions<'a>,
+    original: PerPackageCargoSets<'g>,
+}
+
+impl<'g, 'a> RedundancyChecker<'g, 'a> {
+    fn new(
+        package_graph: &'g PackageGraph,
+        features: StandardFeatures,
+        opts: &'a CargoOptions<'a>,
+    ) -> Result<Self, Error> {
+        let original = member_builds(
+            package_graph,
+            package_graph.workspace().member_ids(),
+            features,
+            opts,
+        )?;
+        Ok(Self {
+            working_graph: package_graph.clone(),
+            features,
+            opts,
+            original,
+        })
+    }
+
+    /// Returns true if `feature_id` is enabled in the original build of some member in `builders`.
+    fn is_enabled(&self, builders: &[&PackageId], feature_id: FeatureId<'_>) -> bool {
+        enabled_platforms(&self.original, builders, feature_id)
+            .iter()
+            .any(|platforms| !platforms.is_empty())
+    }
+
+    /// Applies `edit` to the given dependency, and returns true if `feature_id` was enabled in
+    /// some build and is still enabled on the same build platforms in every build of `builders`
+    /// afterwards. If it isn't, the edit is undone.
+    fn remove_if_redundant(
+        &mut self,
+        builders: &[&PackageId],
+        link: PackageLink<'_>,
+        kind: DependencyKind,
+        feature_id: FeatureId<'_>,
+        edit: impl FnOnce(&mut PackageGraphEditor<'_>) -> Result<(), Error>,
+    ) -> Result<bool, Error> {
+        if !self.is_enabled(builders, feature_id) {
+            return Ok(false);
+        }
+        let (from, to) = (link.from().id(), link.to().id());
+        let saved_req = match self.working_graph.edit().dependency_req(from, to, kind) {
+            Some(req) => req,
+            None => return Ok(false),
+        };
+        edit(&mut self.working_graph.edit())?;
+
+        let is_redundant = {
+            let new_builds = member_builds(
+                &self.working_graph,
+                builders.iter().copied(),
+                self.features,
+                self.opts,
+            )?;
+            let enabled_before = enabled_platforms(&self.original, builders, feature_id);
+            let enabled_after = enabled_platforms(&new_builds, builders, feature_id);
+            enabled_before
+                .iter()
+                .zip(&enabled_after)
+                .all(|(before, after)| before.iter().all(|platform| after.contains(platform)))
+        };
+        if !is_redundant {
+            self.working_graph
+                .edit()
+                .restore_dependency_req(from, to, kind, saved_req);
+        }
+        Ok(is_redundant)
+    }
+}
+
+/// Returns the build platforms `feature_id` is enabled on, for the build of each member in
+/// `builders`.
+fn enabled_platforms(
+    builds: &PerPackageCargoSets<'_>,
+    builders: &[&PackageId],
+    feature_id: FeatureId<'_>,
+) -> Vec<Vec<BuildPlatform>> {
+    builders
+        .iter()
+        .map(|member_id| match builds.get(member_id) {
+            Some(cargo_set) => cargo_set
+                .all_features()
+                .iter()
+                .filter(|(_, features)| features.contains(feature_id).unwrap_or(false))
+                .map(|(build_platform, _)| *build_platform)
+                .collect(),
+            None => vec![],
+        })
+        .collect()
+}
+
+/// Simulates a build of each workspace member on its own.
+fn member_builds<'g, 'a>(
+    package_graph: &'g PackageGraph,
+    members: impl IntoIterator<Item = &'a PackageId>,
+    features: StandardFeatures,
+    opts: &CargoOptions<'_>,
+) -> Result<PerPackageCargoSets<'g>, Error> {
+    let initials = package_graph.resolve_ids(members)?.to_feature_set(features);
+    let features_only = package_graph.feature_graph().resolve_none();
+    CargoSet::new_per_package(&initials, &features_only, opts)
+}
@@ -7,20 +7,26 @@
 //! For example, an optional feature not included a default build can potentially pull in a large
 //! number of extra dependencies. This module allows for those subgraphs to be filtered out.
 
+mod audit;
 mod build;
+mod closure;
 mod cycles;
 pub mod feature_list;
 mod graph_impl;
+mod hygiene;
 #[cfg(feature = "proptest1")]
 mod proptest_helpers;
 mod query;
 mod resolve;
 mod weak;
 
+pub use audit::*;
 pub(self) use build::*;
+pub(in crate::graph) use closure::ClosureCache;
 pub use cycles::*;
 pub use feature_list::FeatureList;
 pub use graph_impl::*;
+pub use hygiene::*;
 pub use query::*;
 pub use resolve::*;
 pub use weak::*;
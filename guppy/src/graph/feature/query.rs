@@ -14,8 +14,8 @@ use crate::{
     Error, PackageId,
 };
 use itertools::Itertools;
-use petgraph::graph::NodeIndex;
-use std::collections::HashSet;
+use petgraph::graph::{EdgeIndex, NodeIndex};
+use std::collections::{HashMap, HashSet};
 
 /// Trait representing whether a feature within a package should be selected.
 ///
@@ -333,6 +333,16 @@ impl<'g> FeatureQuery<'g> {
     // Helper methods
     // ---
 
+    /// Resolves this query using the provided resolver function, recording in `predecessors` the
+    /// edge through which each non-initial feature was first reached.
+    pub(in crate::graph) fn resolve_with_fn_tracking(
+        self,
+        resolver_fn: impl FnMut(&FeatureQuery<'g>, ConditionalLink<'g>) -> bool,
+        predecessors: &mut HashMap<NodeIndex<FeatureIx>, EdgeIndex<FeatureIx>>,
+    ) -> FeatureSet<'g> {
+        FeatureSet::with_resolver_tracking(self, ResolverFn(resolver_fn), predecessors)
+    }
+
     pub(in crate::graph) fn starts_from_package_ix(
         &self,
         package_ix: NodeIndex<PackageIx>,
@@ -18,7 +18,11 @@ use crate::{
 };
 use fixedbitset::FixedBitSet;
 use itertools::Either;
-use petgraph::{graph::NodeIndex, visit::EdgeRef};
+use petgraph::{
+    graph::{EdgeIndex, NodeIndex},
+    visit::EdgeRef,
+};
+use std::collections::HashMap;
 
 impl<'g> FeatureGraph<'g> {
     /// Creates a new `FeatureSet` consisting of all members of this feature graph.
@@ -80,8 +84,34 @@ impl<'g> FeatureSet<'g> {
     }
 
     pub(super) fn with_resolver(
+        query: FeatureQuery<'g>,
+        resolver: impl FeatureResolver<'g>,
+    ) -> Self {
+        Self::with_resolver_impl(query, resolver, |_| {})
+    }
+
+    /// Resolves this query, and records in `predecessors` the edge through which each
+    /// non-initial feature was first reached.
+    pub(super) fn with_resolver_tracking(
+        query: FeatureQuery<'g>,
+        resolver: impl FeatureResolver<'g>,
+        predecessors: &mut HashMap<NodeIndex<FeatureIx>, EdgeIndex<FeatureIx>>,
+    ) -> Self {
+        let params = query.params.clone();
+        Self::with_resolver_impl(query, resolver, |edge_ref| {
+            let target_ix = edge_ref.target();
+            if !params.has_initial(target_ix) {
+                predecessors
+                    .entry(target_ix)
+                    .or_insert_with(|| edge_ref.id());
+            }
+        })
+    }
+
+    fn with_resolver_impl(
         query: FeatureQuery<'g>,
         mut resolver: impl FeatureResolver<'g>,
+        mut on_follow: impl FnMut(FeatureEdgeReference<'g>),
     ) -> Self {
         let graph = query.graph;
         let params = query.params.clone();
@@ -93,7 +123,7 @@ impl<'g> FeatureSet<'g> {
             .new_buffer_states(|link| resolver.accept(&query, link));
 
         let filter_fn = |edge_ref: FeatureEdgeReference<'g>| {
-            match graph.edge_to_conditional_link(
+            let followed = match graph.edge_to_conditional_link(
                 edge_ref.source(),
                 edge_ref.target(),
                 edge_ref.id(),
@@ -104,8 +134,12 @@ impl<'g> FeatureSet<'g> {
                     // Feature links within the same package are always followed.
                     Either::Left(Some(edge_ref))
                 }
+            };
+            match &followed {
+                Either::Left(edge_ref) => edge_ref.iter().copied().for_each(&mut on_follow),
+                Either::Right(edge_refs) => edge_refs.iter().copied().for_each(&mut on_follow),
             }
-            .into_iter()
+            followed.into_iter()
         };
 
         let core = ResolveCore::with_buffered_edge_filter(
@@ -339,6 +373,57 @@ impl<'g> FeatureSet<'g> {
         )
     }
 
+    // ---
+    // Paths
+    // ---
+
+    /// Returns the shortest path from `feature_a` to `feature_b` that stays within this set.
+    ///
+    /// The path is returned as a list of conditional links, in the same manner as
+    /// [`FeatureGraph::shortest_path`]. Returns `None` if there is no such path (including if
+    /// either feature isn't in this set).
+    ///
+    /// This can be used to explain why a feature is present in, for example, the target features
+    /// of a `CargoSet`.
+    ///
+    /// Returns an error if either feature ID is unknown.
+    pub fn shortest_path<'a>(
+        &self,
+        feature_a: impl Into<FeatureId<'a>>,
+        feature_b: impl Into<FeatureId<'a>>,
+    ) -> Result<Option<Vec<ConditionalLink<'g>>>, Error> {
+        let a_ix = self.graph.feature_ix(feature_a.into())?;
+        let b_ix = self.graph.feature_ix(feature_b.into())?;
+        if !self.core.contains(a_ix) || !self.core.contains(b_ix) {
+            return Ok(None);
+        }
+        Ok(self
+            .graph
+            .shortest_path_ixs(a_ix, b_ix, |feature_ix| self.core.contains(feature_ix)))
+    }
+
+    /// Returns all distinct paths from `feature_a` to `feature_b` that stay within this set.
+    ///
+    /// Each path is returned as a list of conditional links, in the same manner as
+    /// [`FeatureGraph::all_paths`]. If `limit` is specified, at most that many paths are returned.
+    ///
+    /// Returns an error if either feature ID is unknown.
+    pub fn all_paths<'a>(
+        &self,
+        feature_a: impl Into<FeatureId<'a>>,
+        feature_b: impl Into<FeatureId<'a>>,
+        limit: Option<usize>,
+    ) -> Result<Vec<Vec<ConditionalLink<'g>>>, Error> {
+        let a_ix = self.graph.feature_ix(feature_a.into())?;
+        let b_ix = self.graph.feature_ix(feature_b.into())?;
+        if !self.core.contains(a_ix) || !self.core.contains(b_ix) {
+            return Ok(vec![]);
+        }
+        Ok(self.graph.all_paths_ixs(a_ix, b_ix, limit, |feature_ix| {
+            self.core.contains(feature_ix)
+        }))
+    }
+
     // ---
     // Queries around packages
     // ---
@@ -561,6 +646,29 @@ impl<'g> FeatureSet<'g> {
         self.core.included.ones().map(NodeIndex::new)
     }
 
+    /// Iterates over the links in this set, in the direction specified, along with the
+    /// conditional link for each link that crosses packages.
+    pub(in crate::graph) fn link_parts<'a>(
+        &'a self,
+        direction: DependencyDirection,
+    ) -> impl Iterator<
+        Item = (
+            FeatureMetadata<'g>,
+            FeatureMetadata<'g>,
+            Option<ConditionalLink<'g>>,
+        ),
+    > + 'a {
+        let graph = self.graph;
+        self.core
+            .links(graph.dep_graph(), graph.sccs(), direction)
+            .map(move |(_, _, edge_ix)| graph.edge_ix_to_parts(edge_ix))
+    }
+
+    /// Returns true if this feature set contains the given feature ix.
+    pub(in crate::graph) fn contains_ix(&self, feature_ix: NodeIndex<FeatureIx>) -> bool {
+        self.core.contains(feature_ix)
+    }
+
     /// Returns true if this feature set contains the given package ix.
     #[allow(dead_code)]
     pub(in crate::graph) fn contains_package_ix(&self, package_ix: NodeIndex<PackageIx>) -> bool {
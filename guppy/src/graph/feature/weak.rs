@@ -33,6 +33,10 @@ impl WeakDependencies {
         self.ixs.get_index_of(&edge_ix).map(WeakIndex)
     }
 
+    pub(super) fn len(&self) -> usize {
+        self.ixs.len()
+    }
+
     #[inline]
     pub(super) fn new_buffer_states<'g, F>(&self, accept_fn: F) -> WeakBufferStates<'g, '_, F>
     where
@@ -5,8 +5,9 @@ use crate::{
     graph::{
         cargo_version_matches,
         feature::{FeatureGraphImpl, FeatureId, FeatureLabel, FeatureNode},
-        BuildTarget, BuildTargetId, BuildTargetImpl, BuildTargetKind, Cycles, DependencyDirection,
-        OwnedBuildTargetId, PackageIx, PackageQuery, PackageSet,
+        ArtifactDep, ArtifactImpl, BuildTarget, BuildTargetId, BuildTargetImpl, BuildTargetKind,
+        Cycles, DependencyDirection, OverrideImpl, OwnedBuildTargetId, PackageIx, PackageOverride,
+        PackageQuery, PackageSet,
     },
     petgraph_support::{scc::Sccs, topo::TopoWithCycles, IxBitSet},
     platform::{EnabledTernary, PlatformSpec, PlatformStatus, PlatformStatusImpl},
@@ -62,9 +63,18 @@ impl PackageGraph {
         command.build_graph()
     }
 
+    /// Reads the workspace described by the given `OfflineMetadataCommand`, without running Cargo,
+    /// and constructs a `PackageGraph` from it.
+    ///
+    /// Requires the `offline` feature.
+    #[cfg(feature = "offline")]
+    pub fn from_offline_command(command: &crate::OfflineMetadataCommand) -> Result<Self, Error> {
+        command.build_graph()
+    }
+
     /// Parses the given `Metadata` and constructs a `PackageGraph` from it.
     pub fn from_metadata(metadata: CargoMetadata) -> Result<Self, Error> {
-        Self::build(metadata.0)
+        Self::build(metadata.inner, metadata.artifacts)
     }
 
     /// Constructs a package graph from the given JSON output of `cargo metadata`.
@@ -307,6 +317,40 @@ impl PackageGraph {
         Ok(self.dep_graph.contains_edge(a_ix, b_ix))
     }
 
+    /// Returns the shortest dependency path from `package_a` to `package_b`, if `package_a`
+    /// depends on `package_b`.
+    ///
+    /// The path is returned as a list of links, starting from `package_a`. Returns `None` if
+    /// `package_a` doesn't depend on `package_b`, and an empty path if they're the same.
+    ///
+    /// For more control over which links are followed, see `PackageQuery::shortest_path_to_with`.
+    pub fn shortest_path(
+        &self,
+        package_a: &PackageId,
+        package_b: &PackageId,
+    ) -> Result<Option<Vec<PackageLink<'_>>>, Error> {
+        self.query_forward(iter::once(package_a))?
+            .shortest_path_to(package_b)
+    }
+
+    /// Returns all simple dependency paths (paths that don't visit a package more than once)
+    /// from `package_a` to `package_b`.
+    ///
+    /// Each path is returned as a list of links, starting from `package_a`. Paths are sorted by
+    /// length. The number of paths can grow exponentially with the size of the graph: if `limit` is
+    /// specified, at most that many paths are returned.
+    ///
+    /// For more control over which links are followed, see `PackageQuery::all_paths_to_with`.
+    pub fn all_paths(
+        &self,
+        package_a: &PackageId,
+        package_b: &PackageId,
+        limit: Option<usize>,
+    ) -> Result<Vec<Vec<PackageLink<'_>>>, Error> {
+        self.query_forward(iter::once(package_a))?
+            .all_paths_to(package_b, limit)
+    }
+
     /// Returns information about dependency cycles in this graph.
     ///
     /// For more information, see the documentation for `Cycles`.
@@ -617,6 +661,22 @@ impl<'g> Workspace<'g> {
     pub fn metadata_table(&self) -> &'g JsonValue {
         &self.inner.metadata_table
     }
+
+    /// Returns an iterator over the `[patch]` and `[replace]` overrides in the workspace root's
+    /// `Cargo.toml`, in the order they're listed.
+    ///
+    /// Overrides are listed whether or not they're used. To find out whether an override was
+    /// applied, use [`PackageOverride::is_used`].
+    ///
+    /// `cargo metadata` doesn't report overrides, so this is empty unless they were loaded with
+    /// `PackageGraph::load_overrides` (requires the `overrides` feature).
+    pub fn overrides(&self) -> impl Iterator<Item = PackageOverride<'g>> + ExactSizeIterator {
+        let graph = self.graph;
+        self.inner
+            .overrides
+            .iter()
+            .map(move |inner| PackageOverride::new(graph, inner))
+    }
 }
 
 #[cfg(feature = "rayon1")]
@@ -678,6 +738,7 @@ pub(super) struct WorkspaceImpl {
     // This is a BTreeMap to allow presenting data in sorted order.
     pub(super) members_by_path: BTreeMap<Utf8PathBuf, PackageId>,
     pub(super) members_by_name: BTreeMap<Box<str>, PackageId>,
+    pub(super) overrides: Vec<OverrideImpl>,
     // Cache for members by name (only used for proptests)
     #[cfg(feature = "proptest1")]
     pub(super) name_list: OnceCell<Vec<Box<str>>>,
@@ -843,6 +904,23 @@ impl<'g> PackageMetadata<'g> {
         PackageSource::new(&self.inner.source)
     }
 
+    /// Returns the `[patch]` or `[replace]` override this package was retrieved from, if any.
+    ///
+    /// This is always `None` unless overrides were loaded: see [`Workspace::overrides`].
+    ///
+    /// If this returns `Some`, [`source`](Self::source) is the source of the override, and
+    /// [`PackageOverride::original_source`] is the source that was originally requested.
+    pub fn overridden_by(&self) -> Option<PackageOverride<'g>> {
+        let graph = self.graph;
+        graph
+            .data
+            .workspace
+            .overrides
+            .iter()
+            .find(|inner| inner.package_ids.contains(self.id()))
+            .map(|inner| PackageOverride::new(graph, inner))
+    }
+
     /// Returns true if this package is in the workspace.
     ///
     /// For more detailed information, use `source()`.
@@ -1939,6 +2017,16 @@ impl<'g> DependencyReq<'g> {
     pub fn feature_status(&self, feature: &str) -> EnabledStatus<'g> {
         self.inner.feature_status(feature)
     }
+
+    /// Returns artifact information if this is an [artifact
+    /// dependency](https://doc.rust-lang.org/nightly/cargo/reference/unstable.html#artifact-dependencies),
+    /// i.e. if the `artifact` key is specified.
+    ///
+    /// If the dependency is listed several times in this section (e.g. for different platforms),
+    /// the artifact kinds requested by each instance are combined.
+    pub fn artifact(&self) -> Option<ArtifactDep<'g>> {
+        self.inner.artifact.as_ref().map(ArtifactDep::new)
+    }
 }
 
 /// Whether a dependency or feature is required, optional, or disabled.
@@ -2096,6 +2184,7 @@ impl fmt::Display for NamedFeatureDep {
 pub(super) struct DependencyReqImpl {
     pub(super) required: DepRequiredOrOptional,
     pub(super) optional: DepRequiredOrOptional,
+    pub(super) artifact: Option<ArtifactImpl>,
 }
 
 impl DependencyReqImpl {
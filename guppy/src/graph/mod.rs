@@ -7,15 +7,23 @@
 //! documentation for more details.
 
 use crate::PackageId;
+use camino::{Utf8Component, Utf8Path, Utf8PathBuf};
 use petgraph::prelude::*;
 use std::fmt;
 
+mod artifact;
 mod build;
 mod build_targets;
+#[cfg(feature = "cache")]
+mod cache;
 pub mod cargo;
 mod cycles;
+pub mod diff;
+mod edit;
+pub mod export;
 pub mod feature;
 mod graph_impl;
+mod overrides;
 #[cfg(feature = "proptest1")]
 mod proptest_helpers;
 mod query;
@@ -26,10 +34,13 @@ mod resolve_core;
 pub mod summaries;
 
 pub use crate::petgraph_support::dot::DotWrite;
+pub use artifact::*;
 pub use build_targets::*;
 pub use cycles::*;
+pub use edit::*;
 pub use graph_impl::*;
 use once_cell::sync::Lazy;
+pub use overrides::*;
 use petgraph::graph::IndexType;
 #[cfg(feature = "proptest1")]
 pub use proptest_helpers::*;
@@ -145,8 +156,25 @@ impl GraphSpec for FeatureGraphSpec {
 // A requirement of "*" filters out pre-release versions with the semver crate,
 // but cargo accepts them.
 // See https://github.com/steveklabnik/semver/issues/98.
-fn cargo_version_matches(req: &VersionReq, version: &Version) -> bool {
+pub(crate) fn cargo_version_matches(req: &VersionReq, version: &Version) -> bool {
     static MAJOR_WILDCARD: Lazy<VersionReq> = Lazy::new(|| VersionReq::parse("*").unwrap());
 
     req == &*MAJOR_WILDCARD || req.matches(version)
 }
+
+/// Lexically normalizes a path, removing `.` and resolving `..` components.
+pub(crate) fn normalize_path(path: &Utf8Path) -> Utf8PathBuf {
+    let mut out = Utf8PathBuf::new();
+    for component in path.components() {
+        match component {
+            Utf8Component::CurDir => {}
+            Utf8Component::ParentDir => {
+                if !out.pop() {
+                    out.push("..");
+                }
+            }
+            other => out.push(other.as_str()),
+        }
+    }
+    out
+}
@@ -0,0 +1,475 @@
+// Copyright (c) The cargo-guppy Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Support for `[patch]` and `[replace]` overrides in the workspace root manifest.
+
+#[cfg(feature = "overrides")]
+use crate::Error;
+use crate::{
+    graph::{
+        normalize_path, ExternalSource, GitReq, PackageGraph, PackageMetadata, PackageMetadataImpl,
+        PackageSourceImpl,
+    },
+    PackageId,
+};
+use camino::Utf8Path;
+#[cfg(feature = "overrides")]
+use camino::Utf8PathBuf;
+use semver::Version;
+use std::collections::HashMap;
+#[cfg(feature = "overrides")]
+use toml::Value;
+
+impl PackageGraph {
+    /// Reads the `[patch]` and `[replace]` overrides from the workspace root manifest at
+    /// `manifest_path`, replacing any overrides loaded earlier.
+    ///
+    /// `cargo metadata` doesn't report overrides, so they're only available through
+    /// [`Workspace::overrides`](crate::graph::Workspace::overrides) and
+    /// [`PackageMetadata::overridden_by`] after this is called. Relative paths in the manifest are
+    /// resolved against the workspace root.
+    ///
+    /// Requires the `overrides` feature.
+    #[cfg(feature = "overrides")]
+    pub fn load_overrides(&mut self, manifest_path: impl AsRef<Utf8Path>) -> Result<(), Error> {
+        let manifest_path = manifest_path.as_ref();
+        let overrides_err = |msg: String| Error::OverridesError(manifest_path.to_path_buf(), msg);
+        let contents =
+            std::fs::read_to_string(manifest_path).map_err(|err| overrides_err(err.to_string()))?;
+        let mut overrides = OverrideImpl::parse_manifest(&contents).map_err(overrides_err)?;
+
+        let data = &mut self.data;
+        OverrideImpl::resolve(&mut overrides, &data.workspace.root, &data.packages);
+        data.workspace.overrides = overrides;
+        Ok(())
+    }
+}
+
+/// An override of a package's source, specified in the `[patch]` or `[replace]` section of the
+/// workspace root's `Cargo.toml`.
+///
+/// Returned by [`Workspace::overrides`](crate::graph::Workspace::overrides) and
+/// [`PackageMetadata::overridden_by`].
+///
+/// Cargo ignores patches that don't match any dependencies, or that are at versions incompatible
+/// with the version requirements of dependencies. Such patches are listed but are not
+/// [used](Self::is_used).
+#[derive(Copy, Clone, Debug)]
+pub struct PackageOverride<'g> {
+    graph: &'g PackageGraph,
+    inner: &'g OverrideImpl,
+}
+
+assert_covariant!(PackageOverride);
+
+impl<'g> PackageOverride<'g> {
+    pub(super) fn new(graph: &'g PackageGraph, inner: &'g OverrideImpl) -> Self {
+        Self { graph, inner }
+    }
+
+    /// Returns whether this is a `[patch]` or a `[replace]` override.
+    pub fn kind(&self) -> OverrideKind {
+        self.inner.kind
+    }
+
+    /// Returns the name of the package being overridden.
+    pub fn name(&self) -> &'g str {
+        &self.inner.name
+    }
+
+    /// Returns the source that was originally requested, as written in `Cargo.toml`.
+    ///
+    /// For patches, this is the key of the `[patch]` table: `"crates-io"`, the name of another
+    /// registry, or a URL. For replacements, this is the URL in the package ID specification, or
+    /// `"crates-io"` if the specification doesn't have one.
+    pub fn original_source(&self) -> &'g str {
+        &self.inner.original_source
+    }
+
+    /// For `[replace]` overrides, returns the version being replaced.
+    ///
+    /// Returns `None` for patches, and for replacements without a version.
+    pub fn replaced_version(&self) -> Option<&'g Version> {
+        self.inner.replaced_version.as_ref()
+    }
+
+    /// Returns the source that the package is redirected to.
+    pub fn replacement(&self) -> OverrideSource<'g> {
+        match &self.inner.replacement {
+            OverrideSourceImpl::Path(path) => OverrideSource::Path(path),
+            OverrideSourceImpl::Git { repository, req } => OverrideSource::Git {
+                repository,
+                req: match req {
+                    GitReqImpl::Branch(branch) => GitReq::Branch(branch),
+                    GitReqImpl::Tag(tag) => GitReq::Tag(tag),
+                    GitReqImpl::Rev(rev) => GitReq::Rev(rev),
+                    GitReqImpl::Default => GitReq::Default,
+                },
+            },
+            OverrideSourceImpl::Registry { registry, version } => OverrideSource::Registry {
+                registry: registry.as_deref(),
+                version: version.as_deref(),
+            },
+        }
+    }
+
+    /// Returns the packages in the graph that were retrieved from this override.
+    pub fn packages(&self) -> impl Iterator<Item = PackageMetadata<'g>> + 'g {
+        let graph = self.graph;
+        self.inner
+            .package_ids
+            .iter()
+            .map(move |id| graph.metadata(id).expect("valid package ID"))
+    }
+
+    /// Returns true if any packages in the graph were retrieved from this override.
+    ///
+    /// Unused overrides are often left behind after the issue they were working around has been
+    /// fixed upstream.
+    pub fn is_used(&self) -> bool {
+        !self.inner.package_ids.is_empty()
+    }
+}
+
+/// Whether a [`PackageOverride`] comes from a `[patch]` or a `[replace]` section.
+#[derive(Copy, Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum OverrideKind {
+    /// An override in the `[patch]` section.
+    Patch,
+
+    /// An override in the (deprecated) `[replace]` section.
+    Replace,
+}
+
+/// The source that a [`PackageOverride`] redirects a package to.
+#[derive(Copy, Clone, Debug, Eq, Hash, PartialEq)]
+#[non_exhaustive]
+pub enum OverrideSource<'g> {
+    /// A path, as written in `Cargo.toml`. Relative paths are relative to the workspace root.
+    Path(&'g Utf8Path),
+
+    /// A Git repository.
+    Git {
+        /// The repository URL.
+        repository: &'g str,
+
+        /// The branch, tag or revision requested.
+        req: GitReq<'g>,
+    },
+
+    /// A package registry.
+    Registry {
+        /// The name of the registry, or `None` for crates.io.
+        registry: Option<&'g str>,
+
+        /// The version requirement, if specified.
+        version: Option<&'g str>,
+    },
+}
+
+// ---
+// Implementation
+// ---
+
+#[derive(Clone, Debug)]
+pub(super) struct OverrideImpl {
+    pub(super) kind: OverrideKind,
+    pub(super) name: Box<str>,
+    pub(super) original_source: Box<str>,
+    pub(super) replaced_version: Option<Version>,
+    pub(super) replacement: OverrideSourceImpl,
+    // Filled out by `resolve`.
+    pub(super) package_ids: Vec<PackageId>,
+}
+
+#[derive(Clone, Debug)]
+pub(super) enum OverrideSourceImpl {
+    Path(Box<Utf8Path>),
+    Git {
+        repository: Box<str>,
+        req: GitReqImpl,
+    },
+    Registry {
+        registry: Option<Box<str>>,
+        version: Option<Box<str>>,
+    },
+}
+
+#[derive(Clone, Debug)]
+pub(super) enum GitReqImpl {
+    Branch(Box<str>),
+    Tag(Box<str>),
+    Rev(Box<str>),
+    Default,
+}
+
+impl OverrideImpl {
+    /// Parses the `[patch]` and `[replace]` sections from the contents of a root manifest.
+    #[cfg(feature = "overrides")]
+    pub(super) fn parse_manifest(contents: &str) -> Result<Vec<Self>, String> {
+        let manifest: Value = toml::from_str(contents).map_err(|err| err.to_string())?;
+        let mut overrides = vec![];
+
+        if let Some(patch) = manifest.get("patch") {
+            let patch = patch.as_table().ok_or("`patch` is not a table")?;
+            for (source, deps) in patch {
+                let deps = deps
+                    .as_table()
+                    .ok_or_else(|| format!("`patch.{}` is not a table", source))?;
+                for (key, spec) in deps {
+                    let (name, replacement) = parse_spec(key, spec)
+                        .map_err(|err| format!("in `patch.{}.{}`: {}", source, key, err))?;
+                    overrides.push(Self {
+                        kind: OverrideKind::Patch,
+                        name,
+                        original_source: source.as_str().into(),
+                        replaced_version: None,
+                        replacement,
+                        package_ids: vec![],
+                    });
+                }
+            }
+        }
+
+        if let Some(replace) = manifest.get("replace") {
+            let replace = replace.as_table().ok_or("`replace` is not a table")?;
+            for (package_spec, spec) in replace {
+                let (original_source, name_version) = match package_spec.rsplit_once('#') {
+                    Some((url, name_version)) => (url, name_version),
+                    None => ("crates-io", package_spec.as_str()),
+                };
+                let (name, replaced_version) =
+                    match name_version.split_once(|c| c == ':' || c == '@') {
+                        Some((name, version)) => {
+                            let version = Version::parse(version).map_err(|err| {
+                                format!(
+                                    "in `replace.\"{}\"`: invalid version: {}",
+                                    package_spec, err
+                                )
+                            })?;
+                            (name, Some(version))
+                        }
+                        None => (name_version, None),
+                    };
+                let (_, replacement) = parse_spec(name, spec)
+                    .map_err(|err| format!("in `replace.\"{}\"`: {}", package_spec, err))?;
+                overrides.push(Self {
+                    kind: OverrideKind::Replace,
+                    name: name.into(),
+                    original_source: original_source.into(),
+                    replaced_version,
+                    replacement,
+                    package_ids: vec![],
+                });
+            }
+        }
+
+        Ok(overrides)
+    }
+
+    /// Finds the packages that were retrieved from each override.
+    pub(super) fn resolve(
+        overrides: &mut [Self],
+        workspace_root: &Utf8Path,
+        packages: &HashMap<PackageId, PackageMetadataImpl>,
+    ) {
+        for override_ in overrides.iter_mut() {
+            let mut package_ids: Vec<_> = packages
+                .iter()
+                .filter(|(_, package)| override_.matches(workspace_root, package))
+                .map(|(id, _)| id.clone())
+                .collect();
+            package_ids.sort_unstable();
+            override_.package_ids = package_ids;
+        }
+    }
+
+    fn matches(&self, workspace_root: &Utf8Path, package: &PackageMetadataImpl) -> bool {
+        if *package.name != *self.name {
+            return false;
+        }
+        if let Some(version) = &self.replaced_version {
+            if package.version != *version {
+                return false;
+            }
+        }
+
+        match (&self.replacement, &package.source) {
+            (
+                OverrideSourceImpl::Path(path),
+                PackageSourceImpl::Workspace(_) | PackageSourceImpl::Path(_),
+            ) => {
+                let dir = normalize_path(&workspace_root.join(path));
+                package
+                    .manifest_path
+                    .parent()
+                    .map_or(false, |package_dir| normalize_path(package_dir) == dir)
+            }
+            // Cargo records the branch, tag or revision in the source, so a package from the same
+            // repository but requested differently doesn't come from this override.
+            (OverrideSourceImpl::Git { repository, req }, PackageSourceImpl::External(source)) => {
+                match ExternalSource::new(source) {
+                    Some(ExternalSource::Git {
+                        repository: package_repository,
+                        req: package_req,
+                        ..
+                    }) => {
+                        trim_url(package_repository) == trim_url(repository)
+                            && req.matches(package_req)
+                    }
+                    _ => false,
+                }
+            }
+            // The URLs of registries other than crates.io aren't known, so match any registry
+            // source other than the original one.
+            (OverrideSourceImpl::Registry { registry, .. }, source) => {
+                let is_crates_io = matches!(source, PackageSourceImpl::CratesIo);
+                let is_registry = match source {
+                    PackageSourceImpl::CratesIo => true,
+                    PackageSourceImpl::External(source) => {
+                        matches!(
+                            ExternalSource::new(source),
+                            Some(ExternalSource::Registry(_))
+                        )
+                    }
+                    _ => false,
+                };
+                let original_is_crates_io = &*self.original_source == "crates-io";
+                match registry {
+                    None => is_crates_io && !original_is_crates_io,
+                    Some(_) => is_registry && !(is_crates_io && original_is_crates_io),
+                }
+            }
+            _ => false,
+        }
+    }
+}
+
+impl GitReqImpl {
+    fn matches(&self, req: GitReq<'_>) -> bool {
+        match (self, req) {
+            (GitReqImpl::Branch(a), GitReq::Branch(b))
+            | (GitReqImpl::Tag(a), GitReq::Tag(b))
+            | (GitReqImpl::Rev(a), GitReq::Rev(b)) => &**a == b,
+            (GitReqImpl::Default, GitReq::Default) => true,
+            _ => false,
+        }
+    }
+}
+
+fn trim_url(url: &str) -> &str {
+    let url = url.trim_end_matches('/');
+    url.strip_suffix(".git").unwrap_or(url)
+}
+
+/// Parses a dependency specification, returning the name of the package and its source.
+#[cfg(feature = "overrides")]
+fn parse_spec(key: &str, spec: &Value) -> Result<(Box<str>, OverrideSourceImpl), String> {
+    let table = match spec {
+        Value::String(version) => {
+            return Ok((
+                key.into(),
+                OverrideSourceImpl::Registry {
+                    registry: None,
+                    version: Some(version.as_str().into()),
+                },
+            ))
+        }
+        Value::Table(table) => table,
+        _ => return Err("expected a string or a table".to_owned()),
+    };
+
+    let get_str = |field: &str| -> Result<Option<&str>, String> {
+        match table.get(field) {
+            Some(Value::String(s)) => Ok(Some(s)),
+            Some(_) => Err(format!("`{}` is not a string", field)),
+            None => Ok(None),
+        }
+    };
+
+    let name = get_str("package")?.unwrap_or(key).into();
+    let replacement = if let Some(path) = get_str("path")? {
+        OverrideSourceImpl::Path(Utf8PathBuf::from(path).into_boxed_path())
+    } else if let Some(repository) = get_str("git")? {
+        let req = match (get_str("branch")?, get_str("tag")?, get_str("rev")?) {
+            (Some(branch), _, _) => GitReqImpl::Branch(branch.into()),
+            (_, Some(tag), _) => GitReqImpl::Tag(tag.into()),
+            (_, _, Some(rev)) => GitReqImpl::Rev(rev.into()),
+            _ => GitReqImpl::Default,
+        };
+        OverrideSourceImpl::Git {
+            repository: repository.into(),
+            req,
+        }
+    } else {
+        OverrideSourceImpl::Registry {
+            registry: get_str("registry")?.map(Into::into),
+            version: get_str("version")?.map(Into::into),
+        }
+    };
+
+    Ok((name, replacement))
+}
+
+#[cfg(all(test, feature = "overrides"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_overrides() {
+        let manifest = r#"
+            [workspace]
+            members = ["a"]
+
+            [patch.crates-io]
+            serde = { path = "../serde" }
+            log2 = { git = "https://github.com/rust-lang/log", branch = "main", package = "log" }
+
+            [patch."https://github.com/foo/bar"]
+            bar = "1.2"
+
+            [replace]
+            "libc:0.2.62" = { git = "https://github.com/rust-lang/libc", tag = "v0.2.62" }
+        "#;
+        let overrides = OverrideImpl::parse_manifest(manifest).expect("manifest parsed");
+        let summary: Vec<_> = overrides
+            .iter()
+            .map(|o| {
+                (
+                    o.kind,
+                    &*o.name,
+                    &*o.original_source,
+                    o.replaced_version.as_ref().map(|v| v.to_string()),
+                )
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                (OverrideKind::Patch, "serde", "crates-io", None),
+                (OverrideKind::Patch, "log", "crates-io", None),
+                (
+                    OverrideKind::Patch,
+                    "bar",
+                    "https://github.com/foo/bar",
+                    None
+                ),
+                (
+                    OverrideKind::Replace,
+                    "libc",
+                    "crates-io",
+                    Some("0.2.62".to_owned())
+                ),
+            ]
+        );
+        assert!(matches!(
+            &overrides[1].replacement,
+            OverrideSourceImpl::Git {
+                req: GitReqImpl::Branch(branch),
+                ..
+            } if &**branch == "main"
+        ));
+
+        assert!(OverrideImpl::parse_manifest("[patch.crates-io]\nfoo = 1\n").is_err());
+    }
+}
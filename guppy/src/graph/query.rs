@@ -4,7 +4,7 @@
 use crate::{
     graph::{
         feature::{FeatureFilter, FeatureQuery},
-        query_core::QueryParams,
+        query_core::{all_paths_edges, shortest_path_edges, QueryParams},
         DependencyDirection, PackageGraph, PackageIx, PackageLink, PackageMetadata,
         PackageResolver, PackageSet, ResolverFn,
     },
@@ -205,4 +205,110 @@ impl<'g> PackageQuery<'g> {
     ) -> PackageSet<'g> {
         self.resolve_with(ResolverFn(resolver_fn))
     }
+
+    /// Returns the shortest path from the initials of this query to the given package, following
+    /// every link found along the way.
+    ///
+    /// The path is returned as a list of links in the order they were traversed. For forward
+    /// queries, the `to` of each link is the `from` of the next one; for reverse queries, it's the
+    /// other way round.
+    ///
+    /// Returns `None` if the package isn't reachable from the initials, and an empty path if it is
+    /// one of the initials. Returns an error if the package ID is unknown.
+    pub fn shortest_path_to(
+        &self,
+        package_id: &PackageId,
+    ) -> Result<Option<Vec<PackageLink<'g>>>, Error> {
+        self.shortest_path_to_with_fn(package_id, |_, _| true)
+    }
+
+    /// Returns the shortest path from the initials of this query to the given package, using the
+    /// provided resolver to determine which links are followed.
+    ///
+    /// For more, see the documentation for [`shortest_path_to`](Self::shortest_path_to).
+    pub fn shortest_path_to_with(
+        &self,
+        package_id: &PackageId,
+        mut resolver: impl PackageResolver<'g>,
+    ) -> Result<Option<Vec<PackageLink<'g>>>, Error> {
+        let graph = self.graph;
+        let target_ix = graph.package_ix(package_id)?;
+        let path = shortest_path_edges(graph.dep_graph(), &self.params, target_ix, |edge| {
+            resolver.accept(self, graph.edge_ref_to_link(edge))
+        });
+        Ok(path.map(|path| {
+            path.into_iter()
+                .map(|edge| graph.edge_ref_to_link(edge))
+                .collect()
+        }))
+    }
+
+    /// Returns the shortest path from the initials of this query to the given package, using the
+    /// provided resolver function to determine which links are followed.
+    ///
+    /// For more, see the documentation for [`shortest_path_to`](Self::shortest_path_to).
+    pub fn shortest_path_to_with_fn(
+        &self,
+        package_id: &PackageId,
+        resolver_fn: impl FnMut(&PackageQuery<'g>, PackageLink<'g>) -> bool,
+    ) -> Result<Option<Vec<PackageLink<'g>>>, Error> {
+        self.shortest_path_to_with(package_id, ResolverFn(resolver_fn))
+    }
+
+    /// Returns all simple paths (paths that don't visit a package more than once) from the
+    /// initials of this query to the given package, following every link found along the way.
+    ///
+    /// Each path is returned as a list of links in the order they were traversed, in the same
+    /// manner as [`shortest_path_to`](Self::shortest_path_to). Paths are sorted by length.
+    ///
+    /// The number of simple paths can grow exponentially with the size of the graph. If `limit` is
+    /// specified, at most that many paths are returned; in that case, the paths returned are not
+    /// guaranteed to be the shortest ones.
+    ///
+    /// Returns an error if the package ID is unknown.
+    pub fn all_paths_to(
+        &self,
+        package_id: &PackageId,
+        limit: Option<usize>,
+    ) -> Result<Vec<Vec<PackageLink<'g>>>, Error> {
+        self.all_paths_to_with_fn(package_id, limit, |_, _| true)
+    }
+
+    /// Returns all simple paths from the initials of this query to the given package, using the
+    /// provided resolver to determine which links are followed.
+    ///
+    /// For more, see the documentation for [`all_paths_to`](Self::all_paths_to).
+    pub fn all_paths_to_with(
+        &self,
+        package_id: &PackageId,
+        limit: Option<usize>,
+        mut resolver: impl PackageResolver<'g>,
+    ) -> Result<Vec<Vec<PackageLink<'g>>>, Error> {
+        let graph = self.graph;
+        let target_ix = graph.package_ix(package_id)?;
+        let paths = all_paths_edges(graph.dep_graph(), &self.params, target_ix, limit, |edge| {
+            resolver.accept(self, graph.edge_ref_to_link(edge))
+        });
+        Ok(paths
+            .into_iter()
+            .map(|path| {
+                path.into_iter()
+                    .map(|edge| graph.edge_ref_to_link(edge))
+                    .collect()
+            })
+            .collect())
+    }
+
+    /// Returns all simple paths from the initials of this query to the given package, using the
+    /// provided resolver function to determine which links are followed.
+    ///
+    /// For more, see the documentation for [`all_paths_to`](Self::all_paths_to).
+    pub fn all_paths_to_with_fn(
+        &self,
+        package_id: &PackageId,
+        limit: Option<usize>,
+        resolver_fn: impl FnMut(&PackageQuery<'g>, PackageLink<'g>) -> bool,
+    ) -> Result<Vec<Vec<PackageLink<'g>>>, Error> {
+        self.all_paths_to_with(package_id, limit, ResolverFn(resolver_fn))
+    }
 }
@@ -3,14 +3,17 @@
 
 use crate::{
     graph::{DependencyDirection, GraphSpec},
-    petgraph_support::dfs::{dfs_next_buffered_filter, BufferedEdgeFilter},
+    petgraph_support::{
+        dfs::{dfs_next_buffered_filter, BufferedEdgeFilter},
+        paths::{all_simple_paths, shortest_path},
+    },
     sorted_set::SortedSet,
 };
 use fixedbitset::FixedBitSet;
 use petgraph::{
-    graph::IndexType,
+    graph::{EdgeReference, IndexType},
     prelude::*,
-    visit::{IntoEdges, IntoNeighbors, Visitable},
+    visit::{IntoEdges, IntoNeighbors, Reversed, Visitable},
 };
 use std::fmt;
 
@@ -127,3 +130,58 @@ where
     let len = reachable.count_ones(..);
     (reachable, len)
 }
+
+/// Returns the shortest path from the initials in `params` to `target`, as a list of edges in the
+/// order they were traversed.
+///
+/// The arguments to the edge filter are unreversed, and so are the edges returned.
+pub(super) fn shortest_path_edges<'g, G: GraphSpec>(
+    graph: &'g Graph<G::Node, G::Edge, Directed, G::Ix>,
+    params: &QueryParams<G>,
+    target: NodeIndex<G::Ix>,
+    mut edge_filter: impl FnMut(EdgeReference<'g, G::Edge, G::Ix>) -> bool,
+) -> Option<Vec<EdgeReference<'g, G::Edge, G::Ix>>> {
+    let initials = params.initials().iter().copied();
+    match params {
+        QueryParams::Forward(_) => shortest_path(graph, initials, target, edge_filter),
+        QueryParams::Reverse(_) => {
+            let path = shortest_path(Reversed(graph), initials, target, |edge| {
+                edge_filter(edge.into_unreversed())
+            })?;
+            Some(
+                path.into_iter()
+                    .map(|edge| edge.into_unreversed())
+                    .collect(),
+            )
+        }
+    }
+}
+
+/// Returns all simple paths from the initials in `params` to `target`, each as a list of edges in
+/// the order they were traversed.
+///
+/// The arguments to the edge filter are unreversed, and so are the edges returned.
+pub(super) fn all_paths_edges<'g, G: GraphSpec>(
+    graph: &'g Graph<G::Node, G::Edge, Directed, G::Ix>,
+    params: &QueryParams<G>,
+    target: NodeIndex<G::Ix>,
+    limit: Option<usize>,
+    mut edge_filter: impl FnMut(EdgeReference<'g, G::Edge, G::Ix>) -> bool,
+) -> Vec<Vec<EdgeReference<'g, G::Edge, G::Ix>>> {
+    let initials = params.initials().iter().copied();
+    match params {
+        QueryParams::Forward(_) => all_simple_paths(graph, initials, target, limit, edge_filter),
+        QueryParams::Reverse(_) => {
+            all_simple_paths(Reversed(graph), initials, target, limit, |edge| {
+                edge_filter(edge.into_unreversed())
+            })
+            .into_iter()
+            .map(|path| {
+                path.into_iter()
+                    .map(|edge| edge.into_unreversed())
+                    .collect()
+            })
+            .collect()
+        }
+    }
+}
@@ -23,7 +23,7 @@ use petgraph::{
     prelude::*,
     visit::{NodeFiltered, NodeRef},
 };
-use std::fmt;
+use std::{fmt, iter};
 
 impl PackageGraph {
     /// Creates a new `PackageSet` consisting of all members of this package graph.
@@ -223,6 +223,56 @@ impl<'g> PackageSet<'g> {
         self.graph.query_from_parts(package_ixs, direction)
     }
 
+    // ---
+    // Paths
+    // ---
+
+    /// Returns the shortest dependency path from `package_a` to `package_b` that stays within
+    /// this set.
+    ///
+    /// The path is returned as a list of links, starting from `package_a`. Returns `None` if there
+    /// is no such path (including if either package isn't in this set), and an empty path if the
+    /// packages are the same.
+    ///
+    /// Returns an error if either package ID is unknown.
+    pub fn shortest_path(
+        &self,
+        package_a: &PackageId,
+        package_b: &PackageId,
+    ) -> Result<Option<Vec<PackageLink<'g>>>, Error> {
+        if !self.contains(package_a)? || !self.contains(package_b)? {
+            return Ok(None);
+        }
+        self.graph
+            .query_forward(iter::once(package_a))?
+            .shortest_path_to_with_fn(package_b, |_, link| {
+                self.contains_ix(link.to().package_ix())
+            })
+    }
+
+    /// Returns all simple dependency paths from `package_a` to `package_b` that stay within this
+    /// set.
+    ///
+    /// Each path is returned as a list of links, starting from `package_a`. Paths are sorted by
+    /// length. If `limit` is specified, at most that many paths are returned.
+    ///
+    /// Returns an error if either package ID is unknown.
+    pub fn all_paths(
+        &self,
+        package_a: &PackageId,
+        package_b: &PackageId,
+        limit: Option<usize>,
+    ) -> Result<Vec<Vec<PackageLink<'g>>>, Error> {
+        if !self.contains(package_a)? || !self.contains(package_b)? {
+            return Ok(vec![]);
+        }
+        self.graph
+            .query_forward(iter::once(package_a))?
+            .all_paths_to_with_fn(package_b, limit, |_, link| {
+                self.contains_ix(link.to().package_ix())
+            })
+    }
+
     // ---
     // Set operations
     // ---
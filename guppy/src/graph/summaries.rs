@@ -6,6 +6,7 @@
 //! Requires the `summaries` feature to be enabled.
 
 mod package_set;
+mod what_if;
 
 use crate::{
     graph::{
@@ -20,6 +21,7 @@ pub use guppy_summaries::*;
 pub use package_set::*;
 use serde::{Deserialize, Serialize};
 use std::collections::BTreeSet;
+pub use what_if::*;
 
 impl<'g> CargoSet<'g> {
     /// Creates a build summary with the given options.
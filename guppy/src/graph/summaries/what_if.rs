@@ -0,0 +1,215 @@
+// Copyright (c) The cargo-guppy Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use crate::{
+    graph::{
+        cargo::{CargoOptions, CargoSet},
+        feature::StandardFeatures,
+        PackageGraph, PackageGraphEditor,
+    },
+    Error,
+};
+use camino::{Utf8Path, Utf8PathBuf};
+use guppy_summaries::{diff::SummaryDiff, Summary};
+use std::collections::BTreeMap;
+
+impl PackageGraph {
+    /// Simulates a hypothetical edit to this graph, and reports how the build of each workspace
+    /// member would change.
+    ///
+    /// `edit` is applied to a copy of this graph through a [`PackageGraphEditor`], so this graph is
+    /// left untouched. Each workspace member is then built on its own, as with
+    /// `cargo build -p <member>`, using `features` and `opts`, both before and after the edit.
+    /// Any packages omitted through `opts` must be present in the edited graph as well.
+    ///
+    /// Requires the `summaries` feature to be enabled.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use guppy::{
+    ///     graph::{cargo::CargoOptions, feature::StandardFeatures, DependencyDirection},
+    ///     DependencyKind, MetadataCommand,
+    /// };
+    ///
+    /// let graph = MetadataCommand::new().build_graph().expect("guppy graph constructed");
+    /// let guppy_id = graph.workspace().member_by_path("guppy").expect("guppy found").id();
+    /// let serde_id = graph
+    ///     .resolve_package_name("serde")
+    ///     .package_ids(DependencyDirection::Forward)
+    ///     .next()
+    ///     .expect("serde found");
+    ///
+    /// // What if guppy enabled the "rc" feature of serde?
+    /// let report = graph
+    ///     .what_if(StandardFeatures::Default, &CargoOptions::new(), |editor| {
+    ///         editor.enable_dependency_features(guppy_id, serde_id, DependencyKind::Normal, ["rc"])
+    ///     })
+    ///     .expect("what-if analysis succeeded");
+    /// for member in report.changed_members() {
+    ///     println!("{} would change:\n{}", member.name(), member.diff().report());
+    /// }
+    /// ```
+    pub fn what_if(
+        &self,
+        features: StandardFeatures,
+        opts: &CargoOptions<'_>,
+        edit: impl FnOnce(&mut PackageGraphEditor<'_>) -> Result<(), Error>,
+    ) -> Result<WhatIfReport, Error> {
+        let mut new_graph = self.clone();
+        edit(&mut new_graph.edit())?;
+
+        let old_summaries = member_summaries(self, features, opts)?;
+        let mut new_summaries = member_summaries(&new_graph, features, opts)?;
+
+        let mut members = Vec::with_capacity(old_summaries.len());
+        for (workspace_path, (name, old)) in old_summaries {
+            let (new, removed) = match new_summaries.remove(&workspace_path) {
+                Some((_, new)) => (new, false),
+                None => (Summary::default(), true),
+            };
+            members.push(WhatIfMember {
+                name,
+                workspace_path,
+                old,
+                new,
+                added: false,
+                removed,
+            });
+        }
+        members.extend(
+            new_summaries
+                .into_iter()
+                .map(|(workspace_path, (name, new))| WhatIfMember {
+                    name,
+                    workspace_path,
+                    old: Summary::default(),
+                    new,
+                    added: true,
+                    removed: false,
+                }),
+        );
+        members.sort_by(|a, b| a.workspace_path.cmp(&b.workspace_path));
+
+        Ok(WhatIfReport { new_graph, members })
+    }
+}
+
+/// A report of how a hypothetical edit would change the build of each workspace member.
+///
+/// Created by [`PackageGraph::what_if`].
+///
+/// Requires the `summaries` feature to be enabled.
+#[derive(Clone, Debug)]
+pub struct WhatIfReport {
+    new_graph: PackageGraph,
+    members: Vec<WhatIfMember>,
+}
+
+impl WhatIfReport {
+    /// Returns the package graph with the edit applied.
+    pub fn new_graph(&self) -> &PackageGraph {
+        &self.new_graph
+    }
+
+    /// Iterates over workspace members present before or after the edit, in order of their
+    /// workspace paths.
+    pub fn members(&self) -> impl Iterator<Item = &WhatIfMember> + ExactSizeIterator {
+        self.members.iter()
+    }
+
+    /// Iterates over workspace members whose builds would change.
+    pub fn changed_members(&self) -> impl Iterator<Item = &WhatIfMember> {
+        self.members.iter().filter(|member| member.is_changed())
+    }
+
+    /// Returns true if the build of any workspace member would change.
+    pub fn is_changed(&self) -> bool {
+        self.members.iter().any(|member| member.is_changed())
+    }
+}
+
+/// How a hypothetical edit would change the build of a single workspace member.
+///
+/// Returned by [`WhatIfReport::members`].
+#[derive(Clone, Debug)]
+pub struct WhatIfMember {
+    name: String,
+    workspace_path: Utf8PathBuf,
+    old: Summary,
+    new: Summary,
+    added: bool,
+    removed: bool,
+}
+
+impl WhatIfMember {
+    /// Returns the name of this workspace member.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the path of this workspace member, relative to the workspace root.
+    pub fn workspace_path(&self) -> &Utf8Path {
+        &self.workspace_path
+    }
+
+    /// Returns true if the edit added this member to the workspace.
+    pub fn is_added(&self) -> bool {
+        self.added
+    }
+
+    /// Returns true if the edit removed this member from the workspace.
+    pub fn is_removed(&self) -> bool {
+        self.removed
+    }
+
+    /// Returns a summary of the packages and features built for this member before the edit, or
+    /// `None` if the edit added this member to the workspace.
+    pub fn old_summary(&self) -> Option<&Summary> {
+        (!self.added).then(|| &self.old)
+    }
+
+    /// Returns a summary of the packages and features built for this member after the edit, or
+    /// `None` if the edit removed this member from the workspace.
+    pub fn new_summary(&self) -> Option<&Summary> {
+        (!self.removed).then(|| &self.new)
+    }
+
+    /// Returns a diff of the packages and features built for this member.
+    ///
+    /// If the member was added or removed by the edit, the missing side is treated as an empty
+    /// build.
+    pub fn diff(&self) -> SummaryDiff<'_> {
+        SummaryDiff::new(&self.old, &self.new)
+    }
+
+    /// Returns true if the build of this member would change.
+    pub fn is_changed(&self) -> bool {
+        self.added || self.removed || self.diff().is_changed()
+    }
+}
+
+/// Returns a summary of each workspace member built on its own, keyed by workspace path.
+fn member_summaries(
+    graph: &PackageGraph,
+    features: StandardFeatures,
+    opts: &CargoOptions<'_>,
+) -> Result<BTreeMap<Utf8PathBuf, (String, Summary)>, Error> {
+    let initials = graph.resolve_workspace().to_feature_set(features);
+    let features_only = graph.feature_graph().resolve_none();
+    let per_package = CargoSet::new_per_package(&initials, &features_only, opts)?;
+    per_package
+        .iter()
+        .map(|(package, cargo_set)| {
+            let workspace_path = package
+                .source()
+                .workspace_path()
+                .expect("initials are workspace members")
+                .to_path_buf();
+            Ok((
+                workspace_path,
+                (package.name().to_owned(), cargo_set.to_summary(opts)?),
+            ))
+        })
+        .collect()
+}
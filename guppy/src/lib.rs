@@ -48,6 +48,13 @@
 //!
 //! # Optional features
 //!
+//! * `cache`: Support for saving package graphs to and loading them from binary caches, through
+//!   [`PackageGraph::load_cached`](crate::graph::PackageGraph::load_cached) and
+//!   [`PackageGraph::save_cache`](crate::graph::PackageGraph::save_cache).
+//! * `offline`: Support for constructing package graphs from `Cargo.toml` and `Cargo.lock` files
+//!   without running Cargo, through [`OfflineMetadataCommand`](crate::OfflineMetadataCommand).
+//! * `overrides`: Support for reading `[patch]` and `[replace]` overrides from the workspace root's
+//!   `Cargo.toml`, through [`PackageGraph::load_overrides`](crate::graph::PackageGraph::load_overrides).
 //! * `proptest1`: Support for [property-based testing](https://jessitron.com/2013/04/25/property-based-testing-what-is-it/)
 //!   using the [`proptest`](https://altsysrq.github.io/proptest-book/intro.html) framework.
 //! * `rayon1`: Support for parallel iterators through [Rayon](docs.rs/rayon/1) (preliminary work
@@ -97,6 +104,8 @@ mod dependency_kind;
 pub mod errors;
 pub mod graph;
 mod metadata_command;
+#[cfg(feature = "offline")]
+mod offline;
 mod package_id;
 pub(crate) mod petgraph_support;
 pub mod platform;
@@ -107,6 +116,8 @@ mod unit_tests;
 pub use dependency_kind::*;
 pub use errors::Error;
 pub use metadata_command::*;
+#[cfg(feature = "offline")]
+pub use offline::OfflineMetadataCommand;
 pub use package_id::PackageId;
 
 // Public re-exports for upstream crates used in APIs. The no_inline ensures that they show up as
@@ -1,9 +1,12 @@
 // Copyright (c) The cargo-guppy Contributors
 // SPDX-License-Identifier: MIT OR Apache-2.0
 
-use crate::{graph::PackageGraph, Error};
+use crate::{
+    graph::{MetadataArtifacts, PackageGraph},
+    Error, JsonValue,
+};
 use cargo_metadata::CargoOpt;
-use serde::{Deserialize, Serialize};
+use serde::{de::Error as _, ser::Error as _, Deserialize, Deserializer, Serialize, Serializer};
 use std::{convert::TryFrom, io, path::PathBuf, process::Command};
 
 /// A builder for configuring `cargo metadata` invocations.
@@ -105,8 +108,25 @@ impl MetadataCommand {
 
     /// Runs the configured `cargo metadata` and returns a deserialized `CargoMetadata`.
     pub fn exec(&self) -> Result<CargoMetadata, Error> {
-        let inner = self.inner.exec().map_err(Error::command_error)?;
-        Ok(CargoMetadata(inner))
+        // cargo_metadata's own exec() discards fields it doesn't know about (such as artifact
+        // dependencies), so run the command and parse its output here.
+        let output = self
+            .cargo_command()
+            .output()
+            .map_err(|err| Error::command_error(cargo_metadata::Error::Io(err)))?;
+        if !output.status.success() {
+            return Err(Error::command_error(cargo_metadata::Error::CargoMetadata {
+                stderr: String::from_utf8(output.stderr)
+                    .map_err(|err| Error::command_error(cargo_metadata::Error::ErrUtf8(err)))?,
+            }));
+        }
+        let stdout = std::str::from_utf8(&output.stdout)
+            .map_err(|err| Error::command_error(cargo_metadata::Error::Utf8(err)))?;
+        let json = stdout
+            .lines()
+            .find(|line| line.starts_with('{'))
+            .ok_or_else(|| Error::command_error(cargo_metadata::Error::NoJson))?;
+        CargoMetadata::parse_json(json)
     }
 
     /// Runs the configured `cargo metadata` and returns a parsed `PackageGraph`.
@@ -143,20 +163,44 @@ impl<'a> TryFrom<&'a MetadataCommand> for PackageGraph {
 /// Cargo workspace, use [`MetadataCommand`](MetadataCommand).
 ///
 /// This struct implements `serde::Serialize` and `Deserialize`.
-#[derive(Clone, Debug, Deserialize, Serialize)]
-#[serde(transparent)]
-pub struct CargoMetadata(pub(crate) cargo_metadata::Metadata);
+#[derive(Clone, Debug)]
+pub struct CargoMetadata {
+    pub(crate) inner: cargo_metadata::Metadata,
+    pub(crate) artifacts: MetadataArtifacts,
+}
 
 impl CargoMetadata {
     /// Deserializes this JSON blob into a `CargoMetadata`.
     pub fn parse_json(json: impl AsRef<str>) -> Result<Self, Error> {
-        let inner = serde_json::from_str(json.as_ref()).map_err(Error::MetadataParseError)?;
-        Ok(Self(inner))
+        let json = json.as_ref();
+        let inner = serde_json::from_str(json).map_err(Error::MetadataParseError)?;
+        // Artifact dependencies are unstable and rare, so only scan for them if the input could
+        // have any.
+        let artifacts = if json.contains("\"artifact\"") {
+            MetadataArtifacts::extract(&mut serde_json::Deserializer::from_str(json))
+                .map_err(Error::MetadataParseError)?
+        } else {
+            MetadataArtifacts::default()
+        };
+        Ok(Self { inner, artifacts })
     }
 
     /// Serializes this metadata into the given writer.
     pub fn serialize(&self, writer: &mut impl io::Write) -> Result<(), Error> {
-        serde_json::to_writer(writer, &self.0).map_err(Error::MetadataSerializeError)
+        let value = self.to_value().map_err(Error::MetadataSerializeError)?;
+        serde_json::to_writer(writer, &value).map_err(Error::MetadataSerializeError)
+    }
+
+    pub(crate) fn from_value(value: JsonValue) -> Result<Self, serde_json::Error> {
+        let artifacts = MetadataArtifacts::extract(&value)?;
+        let inner = serde_json::from_value(value)?;
+        Ok(Self { inner, artifacts })
+    }
+
+    fn to_value(&self) -> Result<JsonValue, serde_json::Error> {
+        let mut value = serde_json::to_value(&self.inner)?;
+        self.artifacts.insert_into(&mut value)?;
+        Ok(value)
     }
 
     /// Parses this metadata and builds a `PackageGraph` from it.
@@ -165,6 +209,27 @@ impl CargoMetadata {
     }
 }
 
+impl<'de> Deserialize<'de> for CargoMetadata {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let value = JsonValue::deserialize(deserializer)?;
+        Self::from_value(value).map_err(D::Error::custom)
+    }
+}
+
+impl Serialize for CargoMetadata {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        self.to_value()
+            .map_err(S::Error::custom)?
+            .serialize(serializer)
+    }
+}
+
 impl TryFrom<CargoMetadata> for PackageGraph {
     type Error = Error;
 
@@ -0,0 +1,245 @@
+// Copyright (c) The cargo-guppy Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Parsing for `Cargo.lock` files.
+
+use camino::Utf8Path;
+use semver::Version;
+use std::fs;
+use toml::Value;
+
+/// A parsed `Cargo.lock`.
+#[derive(Clone, Debug)]
+pub(super) struct Lockfile {
+    pub(super) packages: Vec<LockedPackage>,
+}
+
+/// A `[[package]]` entry in a lockfile.
+#[derive(Clone, Debug)]
+pub(super) struct LockedPackage {
+    pub(super) name: String,
+    pub(super) version: Version,
+    /// The source, e.g. `registry+https://github.com/rust-lang/crates.io-index`. `None` for path
+    /// packages.
+    pub(super) source: Option<String>,
+    /// Indexes into `Lockfile::packages`.
+    pub(super) dependencies: Vec<usize>,
+}
+
+impl Lockfile {
+    pub(super) fn read(path: &Utf8Path) -> Result<Self, String> {
+        let contents = fs::read_to_string(path).map_err(|err| {
+            format!(
+                "failed to read {}: {} (a lockfile is required to resolve dependencies without \
+                 running Cargo)",
+                path, err
+            )
+        })?;
+        Self::parse(&contents).map_err(|msg| format!("in {}: {}", path, msg))
+    }
+
+    pub(super) fn parse(contents: &str) -> Result<Self, String> {
+        let value: Value = contents.parse().map_err(|err| format!("{}", err))?;
+        let entries = match value.get("package") {
+            Some(Value::Array(entries)) => entries.as_slice(),
+            Some(_) => return Err("`package` is not an array".to_owned()),
+            None => &[],
+        };
+
+        let mut packages = Vec::with_capacity(entries.len());
+        let mut dep_specs = Vec::with_capacity(entries.len());
+        for entry in entries {
+            let name = str_field(entry, "name")?
+                .ok_or_else(|| "package entry without a name".to_owned())?;
+            let version = str_field(entry, "version")?
+                .ok_or_else(|| format!("package '{}' has no version", name))?;
+            let version = Version::parse(version)
+                .map_err(|err| format!("package '{}': invalid version: {}", name, err))?;
+            let source = str_field(entry, "source")?.map(|source| source.to_owned());
+
+            let deps = match entry.get("dependencies") {
+                Some(Value::Array(deps)) => deps
+                    .iter()
+                    .map(|dep| {
+                        dep.as_str().map(DepSpec::parse).ok_or_else(|| {
+                            format!("package '{}': dependency is not a string", name)
+                        })
+                    })
+                    .collect::<Result<Vec<_>, _>>()?,
+                Some(_) => {
+                    return Err(format!(
+                        "package '{}': `dependencies` is not an array",
+                        name
+                    ))
+                }
+                None => vec![],
+            };
+
+            packages.push(LockedPackage {
+                name: name.to_owned(),
+                version,
+                source,
+                dependencies: vec![],
+            });
+            dep_specs.push(deps);
+        }
+
+        // Resolve dependency specs to indexes now that all packages are known.
+        for (idx, specs) in dep_specs.into_iter().enumerate() {
+            let dependencies = specs
+                .into_iter()
+                .map(|spec| spec.resolve(&packages, &packages[idx].name))
+                .collect::<Result<_, _>>()?;
+            packages[idx].dependencies = dependencies;
+        }
+
+        Ok(Self { packages })
+    }
+}
+
+/// A reference to a package in the `dependencies` list of a lockfile entry.
+///
+/// Depending on the lockfile version and on how many packages share a name, this is one of `name`,
+/// `name version` or `name version (source)`.
+struct DepSpec<'a> {
+    name: &'a str,
+    version: Option<&'a str>,
+    source: Option<&'a str>,
+}
+
+impl<'a> DepSpec<'a> {
+    fn parse(spec: &'a str) -> Self {
+        let mut parts = spec.splitn(3, ' ');
+        let name = parts.next().unwrap_or_default();
+        let version = parts.next();
+        let source = parts
+            .next()
+            .map(|source| source.trim_start_matches('(').trim_end_matches(')'));
+        Self {
+            name,
+            version,
+            source,
+        }
+    }
+
+    fn resolve(&self, packages: &[LockedPackage], from: &str) -> Result<usize, String> {
+        let mut matches = packages.iter().enumerate().filter(|(_, package)| {
+            package.name == self.name
+                && self
+                    .version
+                    .map_or(true, |version| package.version.to_string() == version)
+                && self
+                    .source
+                    .map_or(true, |source| package.source.as_deref() == Some(source))
+        });
+        match (matches.next(), matches.next()) {
+            (Some((idx, _)), None) => Ok(idx),
+            (None, _) => Err(format!(
+                "package '{}': dependency '{}' not found in lockfile",
+                from,
+                self.display()
+            )),
+            (Some(_), Some(_)) => Err(format!(
+                "package '{}': dependency '{}' is ambiguous",
+                from,
+                self.display()
+            )),
+        }
+    }
+
+    fn display(&self) -> String {
+        let mut out = self.name.to_owned();
+        if let Some(version) = self.version {
+            out.push(' ');
+            out.push_str(version);
+        }
+        if let Some(source) = self.source {
+            out.push_str(&format!(" ({})", source));
+        }
+        out
+    }
+}
+
+fn str_field<'a>(value: &'a Value, key: &str) -> Result<Option<&'a str>, String> {
+    match value.get(key) {
+        Some(Value::String(s)) => Ok(Some(s)),
+        Some(_) => Err(format!("`{}` is not a string", key)),
+        None => Ok(None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_lockfile() {
+        let lockfile = Lockfile::parse(
+            r#"
+version = 3
+
+[[package]]
+name = "app"
+version = "0.1.0"
+dependencies = [
+ "dep 0.1.0",
+ "dep 0.2.0 (registry+https://github.com/rust-lang/crates.io-index)",
+ "other",
+]
+
+[[package]]
+name = "dep"
+version = "0.1.0"
+
+[[package]]
+name = "dep"
+version = "0.2.0"
+source = "registry+https://github.com/rust-lang/crates.io-index"
+
+[[package]]
+name = "other"
+version = "1.0.0"
+source = "git+https://github.com/example/other?branch=main#0123456789abcdef"
+"#,
+        )
+        .expect("lockfile is valid");
+
+        let app = &lockfile.packages[0];
+        assert_eq!(app.source, None);
+        assert_eq!(app.dependencies, vec![1, 2, 3]);
+        assert_eq!(
+            lockfile.packages[3].source.as_deref(),
+            Some("git+https://github.com/example/other?branch=main#0123456789abcdef")
+        );
+
+        let err = Lockfile::parse(
+            r#"
+[[package]]
+name = "app"
+version = "0.1.0"
+dependencies = ["missing"]
+"#,
+        )
+        .expect_err("missing dependency is an error");
+        assert!(err.contains("'missing' not found"), "error: {}", err);
+
+        let err = Lockfile::parse(
+            r#"
+[[package]]
+name = "app"
+version = "0.1.0"
+dependencies = ["dep"]
+
+[[package]]
+name = "dep"
+version = "0.1.0"
+
+[[package]]
+name = "dep"
+version = "0.2.0"
+"#,
+        )
+        .expect_err("ambiguous dependency is an error");
+        assert!(err.contains("ambiguous"), "error: {}", err);
+    }
+}
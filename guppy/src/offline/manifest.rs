@@ -0,0 +1,866 @@
+// Copyright (c) The cargo-guppy Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Parsing for `Cargo.toml` files, producing data in the same shape as `cargo metadata`.
+
+use crate::{graph::normalize_path, offline::CRATES_IO_SOURCE};
+use camino::{Utf8Path, Utf8PathBuf};
+use semver::Version;
+use serde_json::{json, Map as JsonMap, Value as JsonValue};
+use std::fs;
+use toml::{value::Table, Value};
+
+/// A `Cargo.toml` file read from disk.
+#[derive(Clone, Debug)]
+pub(super) struct Manifest {
+    pub(super) path: Utf8PathBuf,
+    pub(super) table: Table,
+}
+
+impl Manifest {
+    pub(super) fn read(path: &Utf8Path) -> Result<Self, String> {
+        let contents =
+            fs::read_to_string(path).map_err(|err| format!("failed to read {}: {}", path, err))?;
+        let table = contents
+            .parse::<Value>()
+            .map_err(|err| format!("failed to parse {}: {}", path, err))?;
+        match table {
+            Value::Table(table) => Ok(Self {
+                path: path.to_path_buf(),
+                table,
+            }),
+            _ => Err(format!("{} is not a TOML table", path)),
+        }
+    }
+
+    pub(super) fn dir(&self) -> &Utf8Path {
+        self.path.parent().expect("manifest paths have a parent")
+    }
+
+    pub(super) fn package(&self) -> Option<&Table> {
+        self.table.get("package").and_then(Value::as_table)
+    }
+
+    pub(super) fn workspace(&self) -> Option<&Table> {
+        self.table.get("workspace").and_then(Value::as_table)
+    }
+
+    /// Returns the package name and version, without looking at the rest of the manifest.
+    ///
+    /// This is used to match up manifests with lockfile entries. `None` is returned for virtual
+    /// manifests and for versions inherited from a workspace.
+    pub(super) fn name_and_version(&self) -> Option<(&str, Option<Version>)> {
+        let package = self.package()?;
+        let name = package.get("name")?.as_str()?;
+        let version = package
+            .get("version")
+            .and_then(Value::as_str)
+            .and_then(|version| Version::parse(version).ok());
+        Some((name, version))
+    }
+}
+
+/// Values that packages can inherit from their workspace through `key.workspace = true`.
+#[derive(Clone, Debug, Default)]
+pub(super) struct WorkspaceInherit {
+    pub(super) root_dir: Utf8PathBuf,
+    pub(super) package: Table,
+    pub(super) dependencies: Table,
+}
+
+impl WorkspaceInherit {
+    pub(super) fn new(root_dir: &Utf8Path, workspace: Option<&Table>) -> Self {
+        let subtable = |key: &str| {
+            workspace
+                .and_then(|workspace| workspace.get(key))
+                .and_then(Value::as_table)
+                .cloned()
+                .unwrap_or_default()
+        };
+        Self {
+            root_dir: root_dir.to_path_buf(),
+            package: subtable("package"),
+            dependencies: subtable("dependencies"),
+        }
+    }
+}
+
+/// A package manifest, interpreted the way Cargo would.
+#[derive(Clone, Debug)]
+pub(super) struct PackageManifest {
+    pub(super) name: String,
+    pub(super) version: Version,
+    pub(super) manifest_path: Utf8PathBuf,
+    pub(super) dependencies: Vec<DependencySpec>,
+    /// The name of the library target, if any.
+    pub(super) lib_name: Option<String>,
+    /// The `cargo metadata` representation of this package, minus the `id` and `source` fields.
+    json: JsonMap<String, JsonValue>,
+}
+
+impl PackageManifest {
+    pub(super) fn parse(manifest: &Manifest, inherit: &WorkspaceInherit) -> Result<Self, String> {
+        Self::parse_inner(manifest, inherit).map_err(|msg| format!("in {}: {}", manifest.path, msg))
+    }
+
+    fn parse_inner(manifest: &Manifest, inherit: &WorkspaceInherit) -> Result<Self, String> {
+        let package = manifest
+            .package()
+            .ok_or_else(|| "no [package] section".to_owned())?;
+        let dir = manifest.dir();
+        let fields = PackageFields {
+            package,
+            inherit,
+            dir,
+        };
+
+        let name = fields
+            .string("name")?
+            .ok_or_else(|| "package has no name".to_owned())?;
+        let version = match fields.string("version")? {
+            Some(version) => Version::parse(&version)
+                .map_err(|err| format!("invalid version '{}': {}", version, err))?,
+            // Cargo defaults the version to 0.0.0 if it isn't specified.
+            None => Version::new(0, 0, 0),
+        };
+        let edition = fields
+            .string("edition")?
+            .unwrap_or_else(|| "2015".to_owned());
+
+        let dependencies = parse_dependencies(&manifest.table, dir, inherit)?;
+        let targets = discover_targets(&manifest.table, package, dir, &name, &edition)?;
+        let lib_name = targets.iter().find_map(|target| {
+            let kinds = target["kind"].as_array()?;
+            let is_lib = !kinds.iter().any(|kind| {
+                matches!(
+                    kind.as_str(),
+                    Some("bin" | "example" | "test" | "bench" | "custom-build")
+                )
+            });
+            is_lib.then(|| target["name"].as_str().map(|name| name.to_owned()))?
+        });
+
+        let features = match manifest.table.get("features") {
+            Some(Value::Table(features)) => features
+                .iter()
+                .map(|(feature, deps)| Ok((feature.clone(), json!(string_list(deps)?))))
+                .collect::<Result<JsonMap<_, _>, String>>()?,
+            Some(_) => return Err("[features] is not a table".to_owned()),
+            None => JsonMap::new(),
+        };
+
+        let readme = match fields.get("readme")? {
+            Some(Value::String(readme)) => Some(fields.inherited_path("readme", readme)),
+            Some(Value::Boolean(true)) => Some("README.md".to_owned()),
+            Some(Value::Boolean(false)) => None,
+            Some(_) => return Err("`readme` is not a string or boolean".to_owned()),
+            // Cargo picks up a README automatically if one exists.
+            None => ["README.md", "README.txt", "README"]
+                .iter()
+                .find(|readme| dir.join(readme).is_file())
+                .map(|readme| readme.to_string()),
+        };
+        let license_file = fields
+            .string("license-file")?
+            .map(|path| fields.inherited_path("license-file", &path));
+        let publish = match fields.get("publish")? {
+            Some(Value::Boolean(true)) | None => JsonValue::Null,
+            Some(Value::Boolean(false)) => json!([]),
+            Some(registries) => json!(string_list(registries)?),
+        };
+        let metadata = match package.get("metadata") {
+            Some(metadata) => serde_json::to_value(metadata).map_err(|err| err.to_string())?,
+            None => JsonValue::Null,
+        };
+
+        let json = json!({
+            "name": name,
+            "version": version.to_string(),
+            "authors": fields.string_list("authors")?,
+            "description": fields.string("description")?,
+            "license": fields.string("license")?,
+            "license_file": license_file,
+            "targets": targets,
+            "features": features,
+            "manifest_path": manifest.path,
+            "categories": fields.string_list("categories")?,
+            "keywords": fields.string_list("keywords")?,
+            "readme": readme,
+            "repository": fields.string("repository")?,
+            "homepage": fields.string("homepage")?,
+            "documentation": fields.string("documentation")?,
+            "edition": edition,
+            "metadata": metadata,
+            "links": fields.string("links")?,
+            "publish": publish,
+            "default_run": fields.string("default-run")?,
+            "rust_version": fields.string("rust-version")?,
+        });
+        let json = match json {
+            JsonValue::Object(json) => json,
+            _ => unreachable!("json! with braces produces an object"),
+        };
+
+        Ok(Self {
+            name,
+            version,
+            manifest_path: manifest.path.clone(),
+            dependencies,
+            lib_name,
+            json,
+        })
+    }
+
+    /// Points path dependencies at the given registry.
+    ///
+    /// Manifests published to a registry may still have `path` keys, but Cargo ignores them and
+    /// uses the registry instead.
+    pub(super) fn use_registry_for_path_deps(&mut self, source: &str) {
+        for dep in &mut self.dependencies {
+            if dep.path.take().is_some() {
+                dep.source = Some(source.to_owned());
+            }
+        }
+    }
+
+    /// Returns the `cargo metadata` representation of this package.
+    pub(super) fn to_json(&self, id: &str, source: Option<&str>) -> JsonValue {
+        let mut json = self.json.clone();
+        json.insert("id".to_owned(), json!(id));
+        json.insert("source".to_owned(), json!(source));
+        json.insert(
+            "dependencies".to_owned(),
+            self.dependencies
+                .iter()
+                .map(DependencySpec::to_json)
+                .collect(),
+        );
+        JsonValue::Object(json)
+    }
+}
+
+/// Looks up `[package]` fields, following `key.workspace = true`.
+struct PackageFields<'a> {
+    package: &'a Table,
+    inherit: &'a WorkspaceInherit,
+    dir: &'a Utf8Path,
+}
+
+impl<'a> PackageFields<'a> {
+    fn get(&self, key: &str) -> Result<Option<&'a Value>, String> {
+        match self.package.get(key) {
+            Some(value) if is_workspace_inherited(value) => {
+                self.inherit.package.get(key).map(Some).ok_or_else(|| {
+                    format!(
+                        "`{}.workspace = true` is set, but [workspace.package] doesn't define `{}`",
+                        key, key
+                    )
+                })
+            }
+            other => Ok(other),
+        }
+    }
+
+    fn string(&self, key: &str) -> Result<Option<String>, String> {
+        match self.get(key)? {
+            Some(Value::String(s)) => Ok(Some(s.clone())),
+            Some(_) => Err(format!("`{}` is not a string", key)),
+            None => Ok(None),
+        }
+    }
+
+    fn string_list(&self, key: &str) -> Result<Vec<String>, String> {
+        match self.get(key)? {
+            Some(value) => string_list(value).map_err(|err| format!("`{}`: {}", key, err)),
+            None => Ok(vec![]),
+        }
+    }
+
+    /// Paths inherited from the workspace are relative to the workspace root: make them relative
+    /// to this package.
+    fn inherited_path(&self, key: &str, path: &str) -> String {
+        let inherited = self.package.get(key).map_or(false, is_workspace_inherited);
+        if !inherited {
+            return path.to_owned();
+        }
+        let abs_path = normalize_path(&self.inherit.root_dir.join(path));
+        pathdiff::diff_utf8_paths(&abs_path, self.dir)
+            .unwrap_or(abs_path)
+            .into_string()
+    }
+}
+
+fn is_workspace_inherited(value: &Value) -> bool {
+    value
+        .as_table()
+        .and_then(|table| table.get("workspace"))
+        .and_then(Value::as_bool)
+        == Some(true)
+}
+
+fn string_list(value: &Value) -> Result<Vec<String>, String> {
+    let array = value
+        .as_array()
+        .ok_or_else(|| "expected an array of strings".to_owned())?;
+    array
+        .iter()
+        .map(|item| {
+            item.as_str()
+                .map(|s| s.to_owned())
+                .ok_or_else(|| "expected an array of strings".to_owned())
+        })
+        .collect()
+}
+
+// ---
+// Dependencies
+// ---
+
+/// A single dependency declaration from a manifest.
+#[derive(Clone, Debug)]
+pub(super) struct DependencySpec {
+    /// The name of the package depended on.
+    pub(super) name: String,
+    /// The name the dependency is renamed to, if `package = "..."` is used.
+    pub(super) rename: Option<String>,
+    pub(super) req: String,
+    /// `None` for normal dependencies, otherwise `"build"` or `"dev"`.
+    pub(super) kind: Option<&'static str>,
+    pub(super) target: Option<String>,
+    optional: bool,
+    uses_default_features: bool,
+    features: Vec<String>,
+    source: Option<String>,
+    pub(super) path: Option<Utf8PathBuf>,
+}
+
+impl DependencySpec {
+    fn parse(
+        key: &str,
+        value: &Value,
+        kind: Option<&'static str>,
+        target: Option<&str>,
+        dir: &Utf8Path,
+        inherit: &WorkspaceInherit,
+    ) -> Result<Self, String> {
+        let context = |msg: String| format!("dependency '{}': {}", key, msg);
+
+        // Workspace dependencies are merged with the local declaration. Paths in them are relative
+        // to the workspace root.
+        let (table, base_dir, extra_features) = match value {
+            Value::String(version) => {
+                let mut table = Table::new();
+                table.insert("version".to_owned(), Value::String(version.clone()));
+                (table, dir, vec![])
+            }
+            Value::Table(local) if is_workspace_inherited(value) => {
+                let mut table = match inherit.dependencies.get(key) {
+                    Some(Value::String(version)) => {
+                        let mut table = Table::new();
+                        table.insert("version".to_owned(), Value::String(version.clone()));
+                        table
+                    }
+                    Some(Value::Table(table)) => table.clone(),
+                    Some(_) => return Err(context("invalid workspace dependency".to_owned())),
+                    None => {
+                        return Err(context(
+                            "`workspace = true` is set, but [workspace.dependencies] doesn't \
+                             define it"
+                                .to_owned(),
+                        ))
+                    }
+                };
+                if let Some(optional) = local.get("optional") {
+                    table.insert("optional".to_owned(), optional.clone());
+                }
+                let extra_features = match local.get("features") {
+                    Some(features) => string_list(features).map_err(context)?,
+                    None => vec![],
+                };
+                (table, inherit.root_dir.as_path(), extra_features)
+            }
+            Value::Table(table) => (table.clone(), dir, vec![]),
+            _ => return Err(context("expected a string or a table".to_owned())),
+        };
+
+        let get_str = |field: &str| -> Result<Option<&str>, String> {
+            match table.get(field) {
+                Some(Value::String(s)) => Ok(Some(s.as_str())),
+                Some(_) => Err(context(format!("`{}` is not a string", field))),
+                None => Ok(None),
+            }
+        };
+        let get_bool = |field: &str| -> Result<Option<bool>, String> {
+            match table.get(field) {
+                Some(Value::Boolean(b)) => Ok(Some(*b)),
+                Some(_) => Err(context(format!("`{}` is not a boolean", field))),
+                None => Ok(None),
+            }
+        };
+
+        let (name, rename) = match get_str("package")? {
+            Some(package) => (package.to_owned(), Some(key.to_owned())),
+            None => (key.to_owned(), None),
+        };
+        let path = get_str("path")?.map(|path| normalize_path(&base_dir.join(path)));
+        let source = if path.is_some() {
+            None
+        } else if let Some(git) = get_str("git")? {
+            let mut source = format!("git+{}", git);
+            for reference in ["branch", "tag", "rev"] {
+                if let Some(value) = get_str(reference)? {
+                    source.push_str(&format!("?{}={}", reference, value));
+                }
+            }
+            Some(source)
+        } else if get_str("registry")?.is_some() {
+            // The index URL of an alternate registry is only known to Cargo's configuration.
+            None
+        } else {
+            Some(CRATES_IO_SOURCE.to_owned())
+        };
+
+        let mut features = match table.get("features") {
+            Some(features) => string_list(features).map_err(context)?,
+            None => vec![],
+        };
+        features.extend(extra_features);
+        let default_features = match get_bool("default-features")? {
+            Some(default_features) => Some(default_features),
+            None => get_bool("default_features")?,
+        };
+
+        Ok(Self {
+            name,
+            rename,
+            req: get_str("version")?.unwrap_or("*").to_owned(),
+            kind,
+            target: target.map(|target| target.to_owned()),
+            optional: get_bool("optional")?.unwrap_or(false),
+            uses_default_features: default_features.unwrap_or(true),
+            features,
+            source,
+            path,
+        })
+    }
+
+    fn to_json(&self) -> JsonValue {
+        json!({
+            "name": self.name,
+            "source": self.source,
+            "req": self.req,
+            "kind": self.kind,
+            "rename": self.rename,
+            "optional": self.optional,
+            "uses_default_features": self.uses_default_features,
+            "features": self.features,
+            "target": self.target,
+            "registry": JsonValue::Null,
+            "path": self.path,
+        })
+    }
+}
+
+fn parse_dependencies(
+    manifest: &Table,
+    dir: &Utf8Path,
+    inherit: &WorkspaceInherit,
+) -> Result<Vec<DependencySpec>, String> {
+    let mut dependencies = vec![];
+    parse_dependency_sections(manifest, None, dir, inherit, &mut dependencies)?;
+
+    match manifest.get("target") {
+        Some(Value::Table(targets)) => {
+            for (target, sections) in targets {
+                let sections = sections
+                    .as_table()
+                    .ok_or_else(|| format!("[target.'{}'] is not a table", target))?;
+                parse_dependency_sections(sections, Some(target), dir, inherit, &mut dependencies)?;
+            }
+        }
+        Some(_) => return Err("[target] is not a table".to_owned()),
+        None => {}
+    }
+
+    Ok(dependencies)
+}
+
+fn parse_dependency_sections(
+    sections: &Table,
+    target: Option<&str>,
+    dir: &Utf8Path,
+    inherit: &WorkspaceInherit,
+    out: &mut Vec<DependencySpec>,
+) -> Result<(), String> {
+    const SECTIONS: &[(&str, Option<&str>)] = &[
+        ("dependencies", None),
+        ("dev-dependencies", Some("dev")),
+        ("dev_dependencies", Some("dev")),
+        ("build-dependencies", Some("build")),
+        ("build_dependencies", Some("build")),
+    ];
+
+    for (section, kind) in SECTIONS {
+        let deps = match sections.get(*section) {
+            Some(Value::Table(deps)) => deps,
+            Some(_) => return Err(format!("[{}] is not a table", section)),
+            None => continue,
+        };
+        for (key, value) in deps {
+            out.push(DependencySpec::parse(
+                key, value, *kind, target, dir, inherit,
+            )?);
+        }
+    }
+    Ok(())
+}
+
+// ---
+// Build targets
+// ---
+
+/// The kinds of build targets other than libraries and build scripts, along with the directory
+/// they're discovered in.
+#[derive(Clone, Copy)]
+enum TargetKind {
+    Bin,
+    Example,
+    Test,
+    Bench,
+}
+
+impl TargetKind {
+    fn kind(self) -> &'static str {
+        match self {
+            TargetKind::Bin => "bin",
+            TargetKind::Example => "example",
+            TargetKind::Test => "test",
+            TargetKind::Bench => "bench",
+        }
+    }
+
+    fn section(self) -> &'static str {
+        match self {
+            TargetKind::Bin => "bin",
+            TargetKind::Example => "example",
+            TargetKind::Test => "test",
+            TargetKind::Bench => "bench",
+        }
+    }
+
+    fn auto_key(self) -> &'static str {
+        match self {
+            TargetKind::Bin => "autobins",
+            TargetKind::Example => "autoexamples",
+            TargetKind::Test => "autotests",
+            TargetKind::Bench => "autobenches",
+        }
+    }
+
+    fn dir(self) -> &'static str {
+        match self {
+            TargetKind::Bin => "src/bin",
+            TargetKind::Example => "examples",
+            TargetKind::Test => "tests",
+            TargetKind::Bench => "benches",
+        }
+    }
+}
+
+/// Returns the build targets for a package, following Cargo's target auto-discovery rules.
+fn discover_targets(
+    manifest: &Table,
+    package: &Table,
+    dir: &Utf8Path,
+    package_name: &str,
+    edition: &str,
+) -> Result<Vec<JsonValue>, String> {
+    let mut targets = vec![];
+    let manifest = ManifestTargets {
+        manifest,
+        package,
+        dir,
+    };
+
+    if let Some(lib) = manifest.lib(package_name, edition)? {
+        targets.push(lib);
+    }
+    for kind in [
+        TargetKind::Bin,
+        TargetKind::Example,
+        TargetKind::Test,
+        TargetKind::Bench,
+    ] {
+        targets.extend(manifest.targets_of_kind(kind, package_name, edition)?);
+    }
+    if let Some(build) = manifest.build_script(edition)? {
+        targets.push(build);
+    }
+
+    Ok(targets)
+}
+
+struct ManifestTargets<'a> {
+    manifest: &'a Table,
+    package: &'a Table,
+    dir: &'a Utf8Path,
+}
+
+impl<'a> ManifestTargets<'a> {
+    fn lib(&self, package_name: &str, edition: &str) -> Result<Option<JsonValue>, String> {
+        let table = self.section_table("lib")?;
+        let default_path = "src/lib.rs";
+        let path = match table.and_then(|table| table.get("path")) {
+            Some(Value::String(path)) => path.as_str(),
+            Some(_) => return Err("[lib] path is not a string".to_owned()),
+            None if table.is_some() || self.dir.join(default_path).is_file() => default_path,
+            None => return Ok(None),
+        };
+
+        let get = |key: &str| table.and_then(|table| table.get(key));
+        let name = match get("name") {
+            Some(Value::String(name)) => name.clone(),
+            Some(_) => return Err("[lib] name is not a string".to_owned()),
+            None => package_name.replace('-', "_"),
+        };
+        let proc_macro = get("proc-macro")
+            .or_else(|| get("proc_macro"))
+            .and_then(Value::as_bool)
+            .unwrap_or(false);
+        let crate_types = if proc_macro {
+            vec!["proc-macro".to_owned()]
+        } else {
+            match get("crate-type").or_else(|| get("crate_type")) {
+                Some(crate_types) => string_list(crate_types)?,
+                None => vec!["lib".to_owned()],
+            }
+        };
+
+        Ok(Some(self.target_json(
+            &name,
+            crate_types.clone(),
+            crate_types,
+            path,
+            table,
+            edition,
+            TargetDefaults {
+                doctest: true,
+                test: true,
+                doc: true,
+            },
+        )?))
+    }
+
+    fn targets_of_kind(
+        &self,
+        kind: TargetKind,
+        package_name: &str,
+        edition: &str,
+    ) -> Result<Vec<JsonValue>, String> {
+        let explicit: Vec<&Table> = match self.section(kind.section()) {
+            Some(Value::Array(array)) => array
+                .iter()
+                .map(|item| {
+                    item.as_table()
+                        .ok_or_else(|| format!("[[{}]] entry is not a table", kind.section()))
+                })
+                .collect::<Result<_, _>>()?,
+            Some(_) => return Err(format!("[[{}]] is not an array", kind.section())),
+            None => vec![],
+        };
+
+        // Auto-discovery is on by default. In the 2015 edition, declaring any target of a kind
+        // turns off auto-discovery for that kind.
+        let auto = match self.package.get(kind.auto_key()) {
+            Some(Value::Boolean(auto)) => *auto,
+            Some(_) => return Err(format!("`{}` is not a boolean", kind.auto_key())),
+            None => edition != "2015" || explicit.is_empty(),
+        };
+        let inferred = if auto {
+            self.infer_targets(kind, package_name)
+        } else {
+            vec![]
+        };
+
+        let mut targets: Vec<(String, String, Option<&Table>)> = vec![];
+        for table in explicit {
+            let name = match table.get("name") {
+                Some(Value::String(name)) => name.clone(),
+                Some(_) => return Err(format!("[[{}]] name is not a string", kind.section())),
+                None => return Err(format!("[[{}]] entry has no name", kind.section())),
+            };
+            let path = match table.get("path") {
+                Some(Value::String(path)) => path.clone(),
+                Some(_) => return Err(format!("[[{}]] path is not a string", kind.section())),
+                None => match inferred.iter().find(|(inferred, _)| inferred == &name) {
+                    Some((_, path)) => path.clone(),
+                    None => format!("{}/{}.rs", kind.dir(), name),
+                },
+            };
+            targets.push((name, path, Some(table)));
+        }
+        for (name, path) in inferred {
+            let declared = targets
+                .iter()
+                .any(|(other_name, other_path, _)| other_name == &name || other_path == &path);
+            if !declared {
+                targets.push((name, path, None));
+            }
+        }
+
+        targets
+            .into_iter()
+            .map(|(name, path, table)| {
+                let crate_types = match kind {
+                    TargetKind::Example => {
+                        match table.and_then(|table| {
+                            table.get("crate-type").or_else(|| table.get("crate_type"))
+                        }) {
+                            Some(crate_types) => string_list(crate_types)?,
+                            None => vec!["bin".to_owned()],
+                        }
+                    }
+                    _ => vec!["bin".to_owned()],
+                };
+                let defaults = TargetDefaults {
+                    doctest: false,
+                    test: matches!(kind, TargetKind::Bin | TargetKind::Test),
+                    doc: matches!(kind, TargetKind::Bin),
+                };
+                self.target_json(
+                    &name,
+                    vec![kind.kind().to_owned()],
+                    crate_types,
+                    &path,
+                    table,
+                    edition,
+                    defaults,
+                )
+            })
+            .collect()
+    }
+
+    /// Finds targets of the given kind through the standard directory layout.
+    fn infer_targets(&self, kind: TargetKind, package_name: &str) -> Vec<(String, String)> {
+        let mut targets = vec![];
+        if matches!(kind, TargetKind::Bin) && self.dir.join("src/main.rs").is_file() {
+            targets.push((package_name.to_owned(), "src/main.rs".to_owned()));
+        }
+
+        let entries = match self.dir.join(kind.dir()).read_dir_utf8() {
+            Ok(entries) => entries,
+            Err(_) => return targets,
+        };
+        let mut found = vec![];
+        for entry in entries.flatten() {
+            let path = entry.path();
+            let file_name = entry.file_name();
+            if path.is_file() {
+                if let Some(stem) = file_name.strip_suffix(".rs") {
+                    found.push((stem.to_owned(), format!("{}/{}", kind.dir(), file_name)));
+                }
+            } else if path.join("main.rs").is_file() {
+                found.push((
+                    file_name.to_owned(),
+                    format!("{}/{}/main.rs", kind.dir(), file_name),
+                ));
+            }
+        }
+        found.sort();
+        targets.extend(found);
+        targets
+    }
+
+    fn build_script(&self, edition: &str) -> Result<Option<JsonValue>, String> {
+        let path = match self.package.get("build") {
+            Some(Value::String(path)) => path.clone(),
+            Some(Value::Boolean(false)) => return Ok(None),
+            Some(Value::Boolean(true)) | None => {
+                if self.dir.join("build.rs").is_file() {
+                    "build.rs".to_owned()
+                } else {
+                    return Ok(None);
+                }
+            }
+            Some(_) => return Err("`build` is not a string or boolean".to_owned()),
+        };
+        let kinds = vec!["custom-build".to_owned()];
+        Ok(Some(self.target_json(
+            "build-script-build",
+            kinds,
+            vec!["bin".to_owned()],
+            &path,
+            None,
+            edition,
+            TargetDefaults {
+                doctest: false,
+                test: false,
+                doc: false,
+            },
+        )?))
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn target_json(
+        &self,
+        name: &str,
+        kind: Vec<String>,
+        crate_types: Vec<String>,
+        path: &str,
+        table: Option<&Table>,
+        edition: &str,
+        defaults: TargetDefaults,
+    ) -> Result<JsonValue, String> {
+        let get_bool = |key: &str, default: bool| -> Result<bool, String> {
+            match table.and_then(|table| table.get(key)) {
+                Some(Value::Boolean(b)) => Ok(*b),
+                Some(_) => Err(format!("target '{}': `{}` is not a boolean", name, key)),
+                None => Ok(default),
+            }
+        };
+        let edition = match table.and_then(|table| table.get("edition")) {
+            Some(Value::String(edition)) => edition.as_str(),
+            Some(_) => return Err(format!("target '{}': `edition` is not a string", name)),
+            None => edition,
+        };
+        let required_features = match table.and_then(|table| {
+            table
+                .get("required-features")
+                .or_else(|| table.get("required_features"))
+        }) {
+            Some(features) => string_list(features)?,
+            None => vec![],
+        };
+
+        Ok(json!({
+            "name": name,
+            "kind": kind,
+            "crate_types": crate_types,
+            "required-features": required_features,
+            "src_path": self.dir.join(path),
+            "edition": edition,
+            "doctest": get_bool("doctest", defaults.doctest)?,
+            "test": get_bool("test", defaults.test)?,
+            "doc": get_bool("doc", defaults.doc)?,
+        }))
+    }
+
+    fn section(&self, key: &str) -> Option<&'a Value> {
+        self.manifest.get(key)
+    }
+
+    fn section_table(&self, key: &str) -> Result<Option<&'a Table>, String> {
+        match self.section(key) {
+            Some(Value::Table(table)) => Ok(Some(table)),
+            Some(_) => Err(format!("[{}] is not a table", key)),
+            None => Ok(None),
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+struct TargetDefaults {
+    doctest: bool,
+    test: bool,
+    doc: bool,
+}
@@ -0,0 +1,647 @@
+// Copyright (c) The cargo-guppy Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Construct package graphs from `Cargo.toml` and `Cargo.lock` files, without running Cargo.
+
+mod lockfile;
+mod manifest;
+mod workspace;
+
+use crate::{
+    graph::{cargo_version_matches, normalize_path, PackageGraph},
+    CargoMetadata, Error,
+};
+use camino::{Utf8Path, Utf8PathBuf};
+use lockfile::{LockedPackage, Lockfile};
+use manifest::{Manifest, PackageManifest, WorkspaceInherit};
+use semver::VersionReq;
+use serde_json::{json, Value as JsonValue};
+use std::{
+    collections::{BTreeSet, HashSet, VecDeque},
+    convert::TryFrom,
+    env,
+    path::PathBuf,
+};
+use toml::Value;
+use workspace::WorkspaceLayout;
+
+/// A builder for constructing `cargo metadata`-equivalent output without running Cargo.
+///
+/// `OfflineMetadataCommand` reads workspace manifests and `Cargo.lock` directly, and looks up the
+/// manifests of third-party packages in vendor directories and in Cargo's local registry and git
+/// caches. This is useful in environments where `cargo metadata` can't run, for example
+/// sandboxes without network access.
+///
+/// The resulting `PackageGraph` has the same structure as one produced by
+/// [`MetadataCommand`](crate::MetadataCommand), with some limitations:
+/// * `Cargo.lock` must exist and be up-to-date. Resolution isn't performed -- the lockfile is
+///   taken as the source of truth.
+/// * The manifest for every third-party package must be available locally.
+/// * Information only present in Cargo's configuration, such as the index URLs of alternate
+///   registries, isn't available.
+///
+/// Anything that can't be determined results in an
+/// [`OfflineMetadataError`](Error::OfflineMetadataError).
+///
+/// Requires the `offline` feature.
+///
+/// ## Examples
+///
+/// ```rust,no_run
+/// use guppy::OfflineMetadataCommand;
+///
+/// let package_graph = OfflineMetadataCommand::new()
+///     .manifest_path("path/to/Cargo.toml")
+///     .vendor_dir("path/to/vendor")
+///     .build_graph()
+///     .unwrap();
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct OfflineMetadataCommand {
+    manifest_path: Option<PathBuf>,
+    current_dir: Option<PathBuf>,
+    vendor_dirs: Vec<PathBuf>,
+    cargo_home: Option<PathBuf>,
+}
+
+impl OfflineMetadataCommand {
+    /// Creates a default offline metadata builder.
+    ///
+    /// By default, this will look for `Cargo.toml` in the ancestors of this process's current
+    /// directory.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the path to `Cargo.toml`.
+    ///
+    /// This doesn't need to be the root `Cargo.toml` in a workspace -- any member of the workspace
+    /// is fine.
+    pub fn manifest_path(&mut self, path: impl Into<PathBuf>) -> &mut Self {
+        self.manifest_path = Some(path.into());
+        self
+    }
+
+    /// Sets the directory that relative paths and manifest discovery are based on.
+    ///
+    /// By default, this is the current directory of this process.
+    pub fn current_dir(&mut self, path: impl Into<PathBuf>) -> &mut Self {
+        self.current_dir = Some(path.into());
+        self
+    }
+
+    /// Adds a directory containing vendored third-party packages, as produced by `cargo vendor`.
+    ///
+    /// Directory sources configured in `.cargo/config.toml` files within the workspace and its
+    /// ancestors are also searched, so this is only needed for vendor directories that Cargo isn't
+    /// configured to use.
+    pub fn vendor_dir(&mut self, path: impl Into<PathBuf>) -> &mut Self {
+        self.vendor_dirs.push(path.into());
+        self
+    }
+
+    /// Sets the Cargo home directory, which contains the registry and git caches that third-party
+    /// manifests are looked up in.
+    ///
+    /// By default, this is `$CARGO_HOME`, or `~/.cargo` if that isn't set.
+    pub fn cargo_home(&mut self, path: impl Into<PathBuf>) -> &mut Self {
+        self.cargo_home = Some(path.into());
+        self
+    }
+
+    /// Reads the workspace and returns a `CargoMetadata` equivalent to `cargo metadata` output.
+    pub fn exec(&self) -> Result<CargoMetadata, Error> {
+        let json = self.metadata_json().map_err(Error::OfflineMetadataError)?;
+        CargoMetadata::from_value(json).map_err(Error::MetadataParseError)
+    }
+
+    /// Reads the workspace and returns a parsed `PackageGraph`.
+    pub fn build_graph(&self) -> Result<PackageGraph, Error> {
+        let metadata = self.exec()?;
+        metadata.build_graph()
+    }
+
+    fn metadata_json(&self) -> Result<JsonValue, String> {
+        let current_dir = match &self.current_dir {
+            Some(dir) => utf8_path(dir.clone())?,
+            None => utf8_path(
+                env::current_dir()
+                    .map_err(|err| format!("failed to get current directory: {}", err))?,
+            )?,
+        };
+        let start = match &self.manifest_path {
+            Some(path) => normalize_path(&current_dir.join(utf8_path(path.clone())?)),
+            None => find_manifest(&current_dir)?,
+        };
+        let layout = WorkspaceLayout::discover(&start)?;
+        let local = LocalPackages::discover(&layout)?;
+        let lockfile = Lockfile::read(&layout.root_dir.join("Cargo.lock"))?;
+
+        let mut vendor_dirs = self
+            .vendor_dirs
+            .iter()
+            .map(|dir| Ok(normalize_path(&current_dir.join(utf8_path(dir.clone())?))))
+            .collect::<Result<Vec<_>, String>>()?;
+        let cargo_home = self.cargo_home_dir(&current_dir)?;
+        vendor_dirs.extend(configured_vendor_dirs(
+            &current_dir,
+            &layout.root_dir,
+            cargo_home.as_deref(),
+        )?);
+        let third_party = ThirdPartyLookup {
+            vendor_dirs,
+            cargo_home,
+        };
+
+        let packages = lockfile
+            .packages
+            .iter()
+            .map(|locked| {
+                let (resolved, member) = match &locked.source {
+                    None => local.find(locked)?,
+                    Some(_) => (third_party.find(locked)?, false),
+                };
+                Ok(ResolvedPackage {
+                    id: resolved.id,
+                    manifest: resolved.manifest,
+                    member,
+                })
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+        local.check_members_locked(&packages)?;
+
+        let nodes = lockfile
+            .packages
+            .iter()
+            .zip(&packages)
+            .map(|(locked, package)| resolve_node(locked, package, &lockfile, &packages))
+            .collect::<Result<Vec<_>, String>>()?;
+
+        let workspace_members: Vec<_> = packages
+            .iter()
+            .filter(|package| package.member)
+            .map(|package| package.id.as_str())
+            .collect();
+        let root = packages
+            .iter()
+            .find(|package| {
+                package.member && package.manifest.manifest_path == layout.root_manifest.path
+            })
+            .map(|package| package.id.as_str());
+        let target_directory = match env::var("CARGO_TARGET_DIR") {
+            Ok(dir) => normalize_path(&current_dir.join(dir)),
+            Err(_) => layout.root_dir.join("target"),
+        };
+        let metadata = match layout.metadata() {
+            Some(metadata) => serde_json::to_value(metadata).map_err(|err| {
+                format!(
+                    "in {}: failed to convert [workspace.metadata]: {}",
+                    layout.root_manifest.path, err
+                )
+            })?,
+            None => JsonValue::Null,
+        };
+
+        Ok(json!({
+            "packages": lockfile
+                .packages
+                .iter()
+                .zip(&packages)
+                .map(|(locked, package)| package.manifest.to_json(&package.id, locked.source.as_deref()))
+                .collect::<Vec<_>>(),
+            "workspace_members": workspace_members,
+            "resolve": {
+                "nodes": nodes,
+                "root": root,
+            },
+            "workspace_root": layout.root_dir,
+            "target_directory": target_directory,
+            "metadata": metadata,
+            "version": 1,
+        }))
+    }
+
+    fn cargo_home_dir(&self, current_dir: &Utf8Path) -> Result<Option<Utf8PathBuf>, String> {
+        let cargo_home = match &self.cargo_home {
+            Some(dir) => Some(dir.clone()),
+            None => env::var_os("CARGO_HOME").map(PathBuf::from).or_else(|| {
+                env::var_os("HOME")
+                    .or_else(|| env::var_os("USERPROFILE"))
+                    .map(|home| PathBuf::from(home).join(".cargo"))
+            }),
+        };
+        cargo_home
+            .map(|dir| Ok(normalize_path(&current_dir.join(utf8_path(dir)?))))
+            .transpose()
+    }
+}
+
+impl TryFrom<OfflineMetadataCommand> for PackageGraph {
+    type Error = Error;
+
+    fn try_from(command: OfflineMetadataCommand) -> Result<Self, Self::Error> {
+        command.build_graph()
+    }
+}
+
+impl<'a> TryFrom<&'a OfflineMetadataCommand> for PackageGraph {
+    type Error = Error;
+
+    fn try_from(command: &'a OfflineMetadataCommand) -> Result<Self, Self::Error> {
+        command.build_graph()
+    }
+}
+
+/// The source string Cargo uses for crates.io.
+const CRATES_IO_SOURCE: &str = "registry+https://github.com/rust-lang/crates.io-index";
+
+fn utf8_path(path: PathBuf) -> Result<Utf8PathBuf, String> {
+    Utf8PathBuf::try_from(path)
+        .map_err(|err| format!("path {} is not valid UTF-8", err.as_path().display()))
+}
+
+/// Returns the sorted list of subdirectories of `dir`, or an empty list if it can't be read.
+fn subdirs(dir: &Utf8Path) -> Vec<Utf8PathBuf> {
+    let mut subdirs: Vec<_> = match dir.read_dir_utf8() {
+        Ok(entries) => entries
+            .flatten()
+            .map(|entry| entry.path().to_path_buf())
+            .filter(|path| path.is_dir())
+            .collect(),
+        Err(_) => vec![],
+    };
+    subdirs.sort();
+    subdirs
+}
+
+fn find_manifest(current_dir: &Utf8Path) -> Result<Utf8PathBuf, String> {
+    current_dir
+        .ancestors()
+        .map(|dir| dir.join("Cargo.toml"))
+        .find(|path| path.is_file())
+        .ok_or_else(|| {
+            format!(
+                "could not find Cargo.toml in {} or any parent directory",
+                current_dir
+            )
+        })
+}
+
+/// Returns the package ID Cargo uses for a third-party package, in the package ID spec format.
+fn package_id(source: &str, package: &LockedPackage) -> String {
+    // Git sources in lockfiles have the precise revision as a fragment, which isn't part of the
+    // ID.
+    let url = source.split_once('#').map_or(source, |(url, _)| url);
+    format_package_id(url, &package.name, &package.version.to_string())
+}
+
+fn path_package_id(manifest: &PackageManifest) -> String {
+    let dir = manifest
+        .manifest_path
+        .parent()
+        .expect("manifest paths have a parent");
+    let url = if dir.as_str().starts_with('/') {
+        format!("path+file://{}", dir)
+    } else {
+        format!("path+file:///{}", dir.as_str().replace('\\', "/"))
+    };
+    format_package_id(&url, &manifest.name, &manifest.version.to_string())
+}
+
+fn format_package_id(url: &str, name: &str, version: &str) -> String {
+    // The name is omitted if it's the same as the last path segment of the URL.
+    let path = url.split(|c| c == '?' || c == '#').next().unwrap_or(url);
+    let last_segment = path.trim_end_matches('/').rsplit('/').next();
+    if last_segment == Some(name) {
+        format!("{}#{}", url, version)
+    } else {
+        format!("{}#{}@{}", url, name, version)
+    }
+}
+
+/// Workspace members and path dependencies, reachable from the workspace root.
+struct LocalPackages {
+    packages: Vec<(PackageManifest, bool)>,
+}
+
+impl LocalPackages {
+    fn discover(layout: &WorkspaceLayout) -> Result<Self, String> {
+        let no_inherit = WorkspaceInherit::default();
+        let mut packages = vec![];
+        let mut seen = HashSet::new();
+        let mut queue: VecDeque<_> = layout
+            .members
+            .iter()
+            .map(|path| (path.clone(), true))
+            .collect();
+
+        while let Some((manifest_path, member)) = queue.pop_front() {
+            if !seen.insert(manifest_path.clone()) {
+                continue;
+            }
+            let manifest = Manifest::read(&manifest_path)?;
+            let inherit = if member { &layout.inherit } else { &no_inherit };
+            let package = PackageManifest::parse(&manifest, inherit)?;
+            for dep in &package.dependencies {
+                // Dev-dependencies are only resolved for workspace members.
+                if !member && dep.kind == Some("dev") {
+                    continue;
+                }
+                if let Some(dir) = &dep.path {
+                    queue.push_back((dir.join("Cargo.toml"), layout.contains_path_dep(dir)));
+                }
+            }
+            packages.push((package, member));
+        }
+
+        Ok(Self { packages })
+    }
+
+    fn find(&self, locked: &LockedPackage) -> Result<(ResolvedManifest, bool), String> {
+        self.packages
+            .iter()
+            .find(|(package, _)| package.name == locked.name && package.version == locked.version)
+            .map(|(package, member)| {
+                (
+                    ResolvedManifest {
+                        id: path_package_id(package),
+                        manifest: package.clone(),
+                    },
+                    *member,
+                )
+            })
+            .ok_or_else(|| {
+                format!(
+                    "path package {} {} in Cargo.lock isn't a workspace member or a path \
+                     dependency (Cargo.lock may be out of date)",
+                    locked.name, locked.version
+                )
+            })
+    }
+
+    fn check_members_locked(&self, resolved: &[ResolvedPackage]) -> Result<(), String> {
+        for (package, member) in &self.packages {
+            let locked = resolved
+                .iter()
+                .any(|resolved| resolved.manifest.manifest_path == package.manifest_path);
+            if *member && !locked {
+                return Err(format!(
+                    "workspace member {} {} isn't in Cargo.lock (Cargo.lock may be out of date)",
+                    package.name, package.version
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A package manifest along with its package ID.
+struct ResolvedManifest {
+    id: String,
+    manifest: PackageManifest,
+}
+
+struct ResolvedPackage {
+    id: String,
+    manifest: PackageManifest,
+    member: bool,
+}
+
+/// Looks up manifests for third-party packages.
+struct ThirdPartyLookup {
+    vendor_dirs: Vec<Utf8PathBuf>,
+    cargo_home: Option<Utf8PathBuf>,
+}
+
+impl ThirdPartyLookup {
+    fn find(&self, locked: &LockedPackage) -> Result<ResolvedManifest, String> {
+        let source = locked.source.as_deref().unwrap_or_default();
+        let mut searched = vec![];
+
+        let mut candidates = vec![];
+        for dir in &self.vendor_dirs {
+            candidates.push(dir.join(&locked.name));
+            candidates.push(dir.join(format!("{}-{}", locked.name, locked.version)));
+            searched.push(dir.clone());
+        }
+        if let Some(cargo_home) = &self.cargo_home {
+            if source.starts_with("git+") {
+                let checkouts = cargo_home.join("git").join("checkouts");
+                if let Some((_, rev)) = source.split_once('#') {
+                    let short_rev = &rev[..rev.len().min(7)];
+                    for checkout in subdirs(&checkouts) {
+                        collect_manifest_dirs(&checkout.join(short_rev), &mut candidates);
+                    }
+                }
+                searched.push(checkouts);
+            } else {
+                let registry_src = cargo_home.join("registry").join("src");
+                for index in subdirs(&registry_src) {
+                    candidates.push(index.join(format!("{}-{}", locked.name, locked.version)));
+                }
+                searched.push(registry_src);
+            }
+        }
+
+        for dir in candidates {
+            let manifest_path = dir.join("Cargo.toml");
+            if !manifest_path.is_file() {
+                continue;
+            }
+            let manifest = Manifest::read(&manifest_path)?;
+            let matches = match manifest.name_and_version() {
+                Some((name, version)) => {
+                    name == locked.name && version.map_or(true, |v| v == locked.version)
+                }
+                None => false,
+            };
+            if !matches {
+                continue;
+            }
+
+            // Registry packages are normalized, but packages in git repositories may inherit
+            // values from their workspace.
+            let inherit = match WorkspaceLayout::discover(&manifest_path) {
+                Ok(layout) if source.starts_with("git+") => layout.inherit,
+                _ => WorkspaceInherit::default(),
+            };
+            let mut package = PackageManifest::parse(&manifest, &inherit)?;
+            if !source.starts_with("git+") {
+                package.use_registry_for_path_deps(source);
+            }
+            if package.version == locked.version {
+                return Ok(ResolvedManifest {
+                    id: package_id(source, locked),
+                    manifest: package,
+                });
+            }
+        }
+
+        let searched = if searched.is_empty() {
+            "no vendor directories or Cargo home were available".to_owned()
+        } else {
+            format!(
+                "searched in: {}",
+                searched
+                    .iter()
+                    .map(|dir| dir.as_str())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )
+        };
+        Err(format!(
+            "manifest for {} {} ({}) not found locally ({}); run `cargo vendor` and pass in the \
+             vendor directory, or fetch dependencies with `cargo fetch`",
+            locked.name, locked.version, source, searched
+        ))
+    }
+}
+
+/// Collects all directories under `dir` containing a `Cargo.toml`, skipping build output and
+/// hidden directories.
+fn collect_manifest_dirs(dir: &Utf8Path, out: &mut Vec<Utf8PathBuf>) {
+    if dir.join("Cargo.toml").is_file() {
+        out.push(dir.to_path_buf());
+    }
+    for subdir in subdirs(dir) {
+        let skip = subdir
+            .file_name()
+            .map_or(true, |name| name == "target" || name.starts_with('.'));
+        if !skip {
+            collect_manifest_dirs(&subdir, out);
+        }
+    }
+}
+
+/// Returns directory sources configured in Cargo config files.
+///
+/// Cargo reads configuration from the current directory and its ancestors. Configuration within the
+/// workspace root and its ancestors is also used, so that the workspace can be read from anywhere.
+fn configured_vendor_dirs(
+    current_dir: &Utf8Path,
+    root_dir: &Utf8Path,
+    cargo_home: Option<&Utf8Path>,
+) -> Result<Vec<Utf8PathBuf>, String> {
+    let mut config_paths = vec![];
+    for dir in current_dir.ancestors().chain(root_dir.ancestors()) {
+        config_paths.push((dir.join(".cargo").join("config.toml"), dir));
+        config_paths.push((dir.join(".cargo").join("config"), dir));
+    }
+    if let Some(cargo_home) = cargo_home {
+        let base = cargo_home.parent().unwrap_or(cargo_home);
+        config_paths.push((cargo_home.join("config.toml"), base));
+        config_paths.push((cargo_home.join("config"), base));
+    }
+
+    let mut vendor_dirs = vec![];
+    let mut seen = HashSet::new();
+    for (config_path, base) in config_paths {
+        if !seen.insert(config_path.clone()) || !config_path.is_file() {
+            continue;
+        }
+        let config = Manifest::read(&config_path)?;
+        let sources = match config.table.get("source").and_then(Value::as_table) {
+            Some(sources) => sources,
+            None => continue,
+        };
+        for source in sources.values() {
+            if let Some(directory) = source.get("directory").and_then(Value::as_str) {
+                // Cargo joins these paths without normalizing them.
+                let dir = base.join(directory);
+                if !vendor_dirs.contains(&dir) {
+                    vendor_dirs.push(dir);
+                }
+            }
+        }
+    }
+    Ok(vendor_dirs)
+}
+
+/// Dependency kinds and targets, as they appear in the `dep_kinds` field of `cargo metadata`.
+type DepKinds<'a> = BTreeSet<(Option<&'a str>, Option<&'a str>)>;
+
+/// Produces the `resolve.nodes` entry for a package.
+fn resolve_node(
+    locked: &LockedPackage,
+    package: &ResolvedPackage,
+    lockfile: &Lockfile,
+    packages: &[ResolvedPackage],
+) -> Result<JsonValue, String> {
+    let mut dependencies = BTreeSet::new();
+    let mut deps = vec![];
+
+    for &dep_idx in &locked.dependencies {
+        let dep_locked = &lockfile.packages[dep_idx];
+        let dep = &packages[dep_idx];
+        let dep_dir = dep.manifest.manifest_path.parent();
+
+        // Group the manifest entries for this dependency by the name they're imported under.
+        let mut by_name: Vec<(String, DepKinds<'_>)> = vec![];
+        for spec in &package.manifest.dependencies {
+            if spec.name != dep_locked.name || (!package.member && spec.kind == Some("dev")) {
+                continue;
+            }
+            if let Some(path) = &spec.path {
+                if Some(path.as_path()) != dep_dir {
+                    continue;
+                }
+            }
+            let req = VersionReq::parse(&spec.req).map_err(|err| {
+                format!(
+                    "in {}: invalid version requirement '{}' for {}: {}",
+                    package.manifest.manifest_path, spec.req, spec.name, err
+                )
+            })?;
+            if !cargo_version_matches(&req, &dep_locked.version) {
+                continue;
+            }
+
+            let name = match &spec.rename {
+                Some(rename) => rename.replace('-', "_"),
+                None => dep
+                    .manifest
+                    .lib_name
+                    .clone()
+                    .unwrap_or_else(|| dep_locked.name.replace('-', "_")),
+            };
+            let kind = (spec.kind, spec.target.as_deref());
+            match by_name.iter_mut().find(|(other, _)| other == &name) {
+                Some((_, kinds)) => {
+                    kinds.insert(kind);
+                }
+                None => by_name.push((name, std::iter::once(kind).collect())),
+            }
+        }
+
+        if by_name.is_empty() {
+            return Err(format!(
+                "{} {} depends on {} {} in Cargo.lock, but no matching dependency was found in \
+                 {} (Cargo.lock may be out of date)",
+                locked.name,
+                locked.version,
+                dep_locked.name,
+                dep_locked.version,
+                package.manifest.manifest_path
+            ));
+        }
+
+        dependencies.insert(dep.id.as_str());
+        for (name, kinds) in by_name {
+            deps.push(json!({
+                "name": name,
+                "pkg": dep.id,
+                "dep_kinds": kinds
+                    .into_iter()
+                    .map(|(kind, target)| json!({ "kind": kind, "target": target }))
+                    .collect::<Vec<_>>(),
+            }));
+        }
+    }
+
+    Ok(json!({
+        "id": package.id,
+        "dependencies": dependencies,
+        "deps": deps,
+        "features": [],
+    }))
+}
@@ -0,0 +1,243 @@
+// Copyright (c) The cargo-guppy Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Discovery of the workspace root and its members.
+
+use crate::{
+    graph::normalize_path,
+    offline::{
+        manifest::{Manifest, WorkspaceInherit},
+        subdirs,
+    },
+};
+use camino::{Utf8Path, Utf8PathBuf};
+use toml::Value;
+
+/// The layout of a workspace, as described by its root manifest.
+#[derive(Clone, Debug)]
+pub(super) struct WorkspaceLayout {
+    pub(super) root_dir: Utf8PathBuf,
+    pub(super) root_manifest: Manifest,
+    pub(super) inherit: WorkspaceInherit,
+    /// Manifest paths for the root package and for members matched by `workspace.members`.
+    pub(super) members: Vec<Utf8PathBuf>,
+    exclude: Vec<Utf8PathBuf>,
+    /// Whether the root manifest has a `[workspace]` section.
+    explicit: bool,
+}
+
+impl WorkspaceLayout {
+    /// Finds the workspace that the manifest at `start` is a part of.
+    pub(super) fn discover(start: &Utf8Path) -> Result<Self, String> {
+        let start_manifest = Manifest::read(start)?;
+        let root_manifest = if start_manifest.workspace().is_some() {
+            start_manifest
+        } else if let Some(root) = start_manifest
+            .package()
+            .and_then(|package| package.get("workspace"))
+        {
+            let root = root
+                .as_str()
+                .ok_or_else(|| format!("in {}: `package.workspace` is not a string", start))?;
+            let root_path = normalize_path(&start_manifest.dir().join(root).join("Cargo.toml"));
+            Manifest::read(&root_path)?
+        } else {
+            match find_ancestor_workspace(start_manifest.dir())? {
+                Some(root_manifest) => root_manifest,
+                None => start_manifest,
+            }
+        };
+
+        Self::new(root_manifest)
+    }
+
+    fn new(root_manifest: Manifest) -> Result<Self, String> {
+        let root_dir = root_manifest.dir().to_path_buf();
+        let workspace = root_manifest.workspace();
+        let inherit = WorkspaceInherit::new(&root_dir, workspace);
+
+        let patterns = |key: &str| -> Result<Vec<String>, String> {
+            match workspace.and_then(|workspace| workspace.get(key)) {
+                Some(Value::Array(patterns)) => patterns
+                    .iter()
+                    .map(|pattern| {
+                        pattern.as_str().map(|s| s.to_owned()).ok_or_else(|| {
+                            format!(
+                                "in {}: `workspace.{}` must be an array of strings",
+                                root_manifest.path, key
+                            )
+                        })
+                    })
+                    .collect(),
+                Some(_) => Err(format!(
+                    "in {}: `workspace.{}` must be an array of strings",
+                    root_manifest.path, key
+                )),
+                None => Ok(vec![]),
+            }
+        };
+
+        let exclude = patterns("exclude")?
+            .iter()
+            .map(|path| normalize_path(&root_dir.join(path)))
+            .collect::<Vec<_>>();
+
+        let mut members = vec![];
+        if root_manifest.package().is_some() {
+            members.push(root_manifest.path.clone());
+        }
+        for pattern in patterns("members")? {
+            let dirs = expand_glob(&root_dir, &pattern).map_err(|msg| {
+                format!(
+                    "in {}: invalid workspace member '{}': {}",
+                    root_manifest.path, pattern, msg
+                )
+            })?;
+            for dir in dirs {
+                if exclude.iter().any(|exclude| dir.starts_with(exclude)) {
+                    continue;
+                }
+                let manifest_path = dir.join("Cargo.toml");
+                if !manifest_path.is_file() {
+                    // Cargo ignores glob matches without a manifest, but errors out on literal
+                    // paths.
+                    if is_glob(&pattern) {
+                        continue;
+                    }
+                    return Err(format!(
+                        "in {}: workspace member {} has no Cargo.toml",
+                        root_manifest.path, dir
+                    ));
+                }
+                if !members.contains(&manifest_path) {
+                    members.push(manifest_path);
+                }
+            }
+        }
+
+        Ok(Self {
+            root_dir,
+            explicit: workspace.is_some(),
+            root_manifest,
+            inherit,
+            members,
+            exclude,
+        })
+    }
+
+    /// Returns the `[workspace.metadata]` table, if any.
+    pub(super) fn metadata(&self) -> Option<&Value> {
+        self.root_manifest
+            .workspace()
+            .and_then(|workspace| workspace.get("metadata"))
+    }
+
+    /// Returns true if a path dependency in `dir` is a member of this workspace.
+    ///
+    /// Path dependencies within the workspace root are members unless excluded.
+    pub(super) fn contains_path_dep(&self, dir: &Utf8Path) -> bool {
+        self.explicit
+            && dir.starts_with(&self.root_dir)
+            && !self.exclude.iter().any(|exclude| dir.starts_with(exclude))
+    }
+}
+
+fn find_ancestor_workspace(dir: &Utf8Path) -> Result<Option<Manifest>, String> {
+    for ancestor in dir.ancestors().skip(1) {
+        let manifest_path = ancestor.join("Cargo.toml");
+        if !manifest_path.is_file() {
+            continue;
+        }
+        let manifest = Manifest::read(&manifest_path)?;
+        if manifest.workspace().is_some() {
+            let layout = WorkspaceLayout::new(manifest)?;
+            let member_manifest = dir.join("Cargo.toml");
+            if layout.members.contains(&member_manifest) || layout.contains_path_dep(dir) {
+                return Ok(Some(layout.root_manifest));
+            }
+            // Cargo doesn't look past the first workspace it finds.
+            return Ok(None);
+        }
+    }
+    Ok(None)
+}
+
+fn is_glob(pattern: &str) -> bool {
+    pattern.contains(|c| matches!(c, '*' | '?' | '['))
+}
+
+/// Expands a `workspace.members` glob into a sorted list of directories.
+///
+/// Supports `*`, `?` and `**` -- character classes are rare in practice and aren't supported.
+fn expand_glob(root: &Utf8Path, pattern: &str) -> Result<Vec<Utf8PathBuf>, String> {
+    if pattern.contains('[') {
+        return Err("character classes in globs aren't supported".to_owned());
+    }
+
+    let mut current = vec![root.to_path_buf()];
+    for component in pattern.split('/').filter(|c| !c.is_empty() && *c != ".") {
+        let mut next = vec![];
+        for dir in &current {
+            if component == "**" {
+                next.push(dir.clone());
+                collect_subdirs(dir, &mut next);
+            } else if is_glob(component) {
+                for subdir in subdirs(dir) {
+                    let matches = subdir
+                        .file_name()
+                        .map_or(false, |name| wildcard_match(component, name));
+                    if matches {
+                        next.push(subdir);
+                    }
+                }
+            } else {
+                next.push(normalize_path(&dir.join(component)));
+            }
+        }
+        current = next;
+    }
+
+    current.sort();
+    current.dedup();
+    Ok(current)
+}
+
+fn collect_subdirs(dir: &Utf8Path, out: &mut Vec<Utf8PathBuf>) {
+    for subdir in subdirs(dir) {
+        out.push(subdir.clone());
+        collect_subdirs(&subdir, out);
+    }
+}
+
+fn wildcard_match(pattern: &str, name: &str) -> bool {
+    fn matches(pattern: &[char], name: &[char]) -> bool {
+        match (pattern.first(), name.first()) {
+            (None, None) => true,
+            (Some('*'), _) => {
+                matches(&pattern[1..], name) || (!name.is_empty() && matches(pattern, &name[1..]))
+            }
+            (Some('?'), Some(_)) => matches(&pattern[1..], &name[1..]),
+            (Some(p), Some(n)) if p == n => matches(&pattern[1..], &name[1..]),
+            _ => false,
+        }
+    }
+
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    matches(&pattern, &name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wildcards() {
+        assert!(wildcard_match("*", "foo"));
+        assert!(wildcard_match("foo-*", "foo-bar"));
+        assert!(!wildcard_match("foo-*", "bar-foo"));
+        assert!(wildcard_match("f?o", "foo"));
+        assert!(!wildcard_match("f?o", "fo"));
+        assert!(wildcard_match("*-sys", "openssl-sys"));
+    }
+}
@@ -19,7 +19,7 @@ pub trait DotVisitor<NR, ER> {
     /// `DotWrite`.
     fn visit_edge(&self, edge: ER, f: &mut DotWrite<'_, '_>) -> fmt::Result;
 
-    // TODO: allow more customizations? more labels, colors etc to be set?
+    // For colors and other attributes, see the exporters in `graph::export`.
 }
 
 /// A visitor for formatting graph labels that outputs `fmt::Display` impls for node and edge
@@ -127,7 +127,7 @@ pub struct DotWrite<'a, 'b> {
 }
 
 impl<'a, 'b> DotWrite<'a, 'b> {
-    fn new(f: &'a mut fmt::Formatter<'b>) -> Self {
+    pub(crate) fn new(f: &'a mut fmt::Formatter<'b>) -> Self {
         Self {
             f,
             escape_backslashes: true,
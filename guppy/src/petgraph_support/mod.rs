@@ -13,6 +13,7 @@ use std::iter::FromIterator;
 pub mod dfs;
 pub mod dot;
 pub mod edge_ref;
+pub mod paths;
 pub mod scc;
 pub mod topo;
 pub mod walk;
@@ -0,0 +1,205 @@
+// Copyright (c) The cargo-guppy Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use petgraph::{
+    prelude::*,
+    visit::{IntoEdges, IntoEdgesDirected, VisitMap, Visitable},
+};
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    hash::Hash,
+};
+
+/// Finds the shortest path from any of `sources` to `target`, following only the edges for which
+/// `edge_filter` returns true.
+///
+/// The path is returned as a list of edges in the order they were traversed. Returns an empty list
+/// if `target` is one of the sources, and `None` if `target` isn't reachable.
+pub fn shortest_path<G>(
+    graph: G,
+    sources: impl IntoIterator<Item = G::NodeId>,
+    target: G::NodeId,
+    mut edge_filter: impl FnMut(G::EdgeRef) -> bool,
+) -> Option<Vec<G::EdgeRef>>
+where
+    G: IntoEdges + Visitable,
+    G::NodeId: Eq + Hash,
+{
+    let mut discovered = graph.visit_map();
+    // Map of node -> the edge through which it was first discovered.
+    let mut predecessors: HashMap<G::NodeId, G::EdgeRef> = HashMap::new();
+    let mut queue = VecDeque::new();
+
+    for source in sources {
+        if source == target {
+            return Some(vec![]);
+        }
+        if discovered.visit(source) {
+            queue.push_back(source);
+        }
+    }
+
+    while let Some(node) = queue.pop_front() {
+        for edge in graph.edges(node) {
+            let next = edge.target();
+            if discovered.is_visited(&next) || !edge_filter(edge) {
+                continue;
+            }
+            discovered.visit(next);
+            predecessors.insert(next, edge);
+            if next == target {
+                return Some(unwind_path(&predecessors, target));
+            }
+            queue.push_back(next);
+        }
+    }
+
+    None
+}
+
+fn unwind_path<N, ER>(predecessors: &HashMap<N, ER>, target: N) -> Vec<ER>
+where
+    N: Copy + Eq + Hash,
+    ER: EdgeRef<NodeId = N>,
+{
+    let mut path = vec![];
+    let mut current = target;
+    while let Some(edge) = predecessors.get(&current) {
+        path.push(*edge);
+        current = edge.source();
+    }
+    path.reverse();
+    path
+}
+
+/// Finds all simple paths (paths without any repeated nodes) from any of `sources` to `target`,
+/// following only the edges for which `edge_filter` returns true.
+///
+/// Each path is returned as a list of edges in the order they were traversed. If `limit` is
+/// specified, at most that many paths are returned.
+///
+/// Paths are returned in increasing order of length, but if `limit` is hit the paths returned are
+/// not guaranteed to be the shortest ones.
+pub fn all_simple_paths<G>(
+    graph: G,
+    sources: impl IntoIterator<Item = G::NodeId>,
+    target: G::NodeId,
+    limit: Option<usize>,
+    edge_filter: impl FnMut(G::EdgeRef) -> bool,
+) -> Vec<Vec<G::EdgeRef>>
+where
+    G: IntoEdgesDirected + Visitable,
+    G::NodeId: Eq + Hash,
+    G::EdgeId: Eq + Hash,
+{
+    all_simple_paths_filter_map(graph, sources, target, limit, edge_filter, |path| {
+        Some(path.to_vec())
+    })
+}
+
+/// Like [`all_simple_paths`], but passes each path found through `filter_map` as it is found.
+///
+/// Paths for which `filter_map` returns `None` are skipped and don't count towards `limit`. This
+/// can be used to deduplicate paths while searching, so that up to `limit` distinct paths are
+/// returned.
+pub fn all_simple_paths_filter_map<G, T>(
+    graph: G,
+    sources: impl IntoIterator<Item = G::NodeId>,
+    target: G::NodeId,
+    limit: Option<usize>,
+    mut edge_filter: impl FnMut(G::EdgeRef) -> bool,
+    mut filter_map: impl FnMut(&[G::EdgeRef]) -> Option<T>,
+) -> Vec<T>
+where
+    G: IntoEdgesDirected + Visitable,
+    G::NodeId: Eq + Hash,
+    G::EdgeId: Eq + Hash,
+{
+    let limit = limit.unwrap_or(usize::MAX);
+    // Each path found is stored alongside its length, for sorting at the end.
+    let mut paths: Vec<(usize, T)> = vec![];
+    if limit == 0 {
+        return vec![];
+    }
+
+    // The number of simple paths can be exponential in the size of the graph, so first figure out
+    // which edges can lead to the target at all. This avoids exploring parts of the graph that
+    // are irrelevant.
+    let mut can_reach = graph.visit_map();
+    let mut useful_edges: HashSet<G::EdgeId> = HashSet::new();
+    let mut stack = vec![target];
+    can_reach.visit(target);
+    while let Some(node) = stack.pop() {
+        for edge in graph.edges_directed(node, Incoming) {
+            if !edge_filter(edge) {
+                continue;
+            }
+            useful_edges.insert(edge.id());
+            if can_reach.visit(edge.source()) {
+                stack.push(edge.source());
+            }
+        }
+    }
+
+    let mut seen_sources = graph.visit_map();
+    for source in sources {
+        if !can_reach.is_visited(&source) || !seen_sources.visit(source) {
+            continue;
+        }
+        if source == target {
+            if let Some(found) = filter_map(&[]) {
+                paths.push((0, found));
+            }
+            if paths.len() >= limit {
+                break;
+            }
+            continue;
+        }
+
+        // Iterative DFS: each entry on the stack is the list of remaining edges out of the node at
+        // that depth.
+        let mut on_path: HashSet<G::NodeId> = HashSet::new();
+        on_path.insert(source);
+        let mut path: Vec<G::EdgeRef> = vec![];
+        let mut edge_stack = vec![graph.edges(source)];
+
+        while let Some(edges) = edge_stack.last_mut() {
+            match edges.next() {
+                Some(edge) => {
+                    let next = edge.target();
+                    if !useful_edges.contains(&edge.id()) || on_path.contains(&next) {
+                        continue;
+                    }
+                    if next == target {
+                        path.push(edge);
+                        if let Some(found) = filter_map(&path) {
+                            paths.push((path.len(), found));
+                        }
+                        path.pop();
+                        if paths.len() >= limit {
+                            break;
+                        }
+                    } else {
+                        on_path.insert(next);
+                        path.push(edge);
+                        edge_stack.push(graph.edges(next));
+                    }
+                }
+                None => {
+                    edge_stack.pop();
+                    if let Some(edge) = path.pop() {
+                        on_path.remove(&edge.target());
+                    }
+                }
+            }
+        }
+
+        if paths.len() >= limit {
+            break;
+        }
+    }
+
+    // This is a stable sort, so paths of the same length stay in DFS order.
+    paths.sort_by_key(|(len, _)| *len);
+    paths.into_iter().map(|(_, found)| found).collect()
+}
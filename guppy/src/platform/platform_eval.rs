@@ -2,7 +2,10 @@
 // SPDX-License-Identifier: MIT OR Apache-2.0
 
 use crate::platform::{Platform, PlatformSpec};
-use std::ops::{BitAnd, BitOr};
+use std::{
+    borrow::Cow,
+    ops::{BitAnd, BitOr},
+};
 use target_spec::TargetSpec;
 
 /// The status of a dependency or feature, which is possibly platform-dependent.
@@ -173,6 +176,23 @@ impl<'g> PlatformEval<'g> {
         }
         res
     }
+
+    /// Returns the platform specifications this evaluator is made up of, as strings.
+    ///
+    /// These are either target triples like `x86_64-unknown-linux-gnu`, or expressions like
+    /// `cfg(unix)`. The dependency or feature is enabled if any of these specifications match.
+    pub fn target_spec_strs(&self) -> impl ExactSizeIterator<Item = Cow<'g, str>> + 'g {
+        self.specs.iter().map(target_spec_str)
+    }
+}
+
+/// Returns the string a target spec was parsed from.
+pub(crate) fn target_spec_str(spec: &TargetSpec) -> Cow<'_, str> {
+    match spec {
+        TargetSpec::Triple(triple) => Cow::Borrowed(triple.as_str()),
+        // The expression string doesn't include the surrounding cfg().
+        TargetSpec::Expression(expr) => Cow::Owned(format!("cfg({})", expr.expression_str())),
+    }
 }
 
 #[derive(Clone, Debug)]
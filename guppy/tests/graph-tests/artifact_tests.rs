@@ -0,0 +1,286 @@
+// Copyright (c) The cargo-guppy Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use fixtures::{
+    json::{self, JsonFixture},
+    package_id,
+};
+use guppy::{
+    graph::{
+        cargo::{BuildPlatform, CargoOptions, CargoResolverVersion, CargoSet},
+        feature::StandardFeatures,
+        ArtifactKind, ArtifactTarget, DependencyDirection, PackageGraph,
+    },
+    CargoMetadata, DependencyKind, JsonValue,
+};
+use target_spec::{Platform, TargetFeatures};
+
+#[test]
+fn artifact_metadata() {
+    let graph = JsonFixture::metadata_artifact_deps().graph();
+    check_artifact_metadata(graph);
+
+    // Artifact information survives a round trip through CargoMetadata.
+    let metadata =
+        CargoMetadata::parse_json(JsonFixture::metadata_artifact_deps().json()).expect("parsed");
+    let mut json = vec![];
+    metadata.serialize(&mut json).expect("serialized");
+    let graph = PackageGraph::from_json(String::from_utf8(json).expect("valid UTF-8"))
+        .expect("graph constructed");
+    check_artifact_metadata(&graph);
+}
+
+fn check_artifact_metadata(graph: &PackageGraph) {
+    let consumer = graph
+        .metadata(&package_id(json::METADATA_ARTIFACT_DEPS_CONSUMER))
+        .expect("consumer is present");
+    let tool_link = consumer
+        .direct_links()
+        .find(|link| link.dep_name() == "tool")
+        .expect("consumer depends on tool");
+
+    let normal = tool_link
+        .normal()
+        .artifact()
+        .expect("normal dependency on tool is an artifact dependency");
+    assert_eq!(
+        normal.kinds().collect::<Vec<_>>(),
+        vec![ArtifactKind::Bin { name: None }]
+    );
+    assert!(!normal.lib(), "normal dependency doesn't request the lib");
+    assert_eq!(
+        normal.target(),
+        ArtifactTarget::Triple("wasm32-unknown-unknown")
+    );
+
+    let build = tool_link
+        .build()
+        .artifact()
+        .expect("build dependency on tool is an artifact dependency");
+    assert_eq!(
+        build.kinds().collect::<Vec<_>>(),
+        vec![
+            ArtifactKind::Bin {
+                name: Some("tool-cli")
+            },
+            ArtifactKind::Cdylib
+        ]
+    );
+    assert!(build.lib(), "build dependency requests the lib");
+    assert_eq!(build.target(), ArtifactTarget::Target);
+
+    let shared_link = consumer
+        .direct_links()
+        .find(|link| link.dep_name() == "shared")
+        .expect("consumer depends on shared");
+    let shared_artifact = shared_link
+        .normal()
+        .artifact()
+        .expect("dependency on shared is an artifact dependency");
+    assert_eq!(shared_artifact.target(), ArtifactTarget::Default);
+
+    let tool = tool_link.to();
+    let tool_to_shared = tool.direct_links().next().expect("tool depends on shared");
+    assert!(
+        tool_to_shared.normal().artifact().is_none(),
+        "tool -> shared is a regular dependency"
+    );
+}
+
+#[test]
+fn artifact_build_dep_on_target() {
+    // Cross-compiling to wasm: the build dependency on tool asks for its artifacts to be built on
+    // the target, and also for its library (which is built on the host as usual).
+    let graph = JsonFixture::metadata_artifact_deps().graph();
+    let cargo_set = make_cargo_set(graph, "wasm32-unknown-unknown", "x86_64-unknown-linux-gnu");
+    assert_packages(
+        &cargo_set,
+        BuildPlatform::Target,
+        &[
+            json::METADATA_ARTIFACT_DEPS_CONSUMER,
+            json::METADATA_ARTIFACT_DEPS_SHARED,
+            json::METADATA_ARTIFACT_DEPS_TOOL,
+        ],
+    );
+    assert_packages(
+        &cargo_set,
+        BuildPlatform::Host,
+        &[
+            json::METADATA_ARTIFACT_DEPS_SHARED,
+            json::METADATA_ARTIFACT_DEPS_TOOL,
+        ],
+    );
+
+    // Without the library, tool is only built on the target.
+    let graph = edited_graph(|consumer_deps| {
+        consumer_deps[1]["artifact"]["lib"] = false.into();
+    });
+    let cargo_set = make_cargo_set(&graph, "wasm32-unknown-unknown", "x86_64-unknown-linux-gnu");
+    assert_packages(&cargo_set, BuildPlatform::Host, &[]);
+}
+
+#[test]
+fn artifact_normal_dep_on_host() {
+    // Cross-compiling from x86_64 to aarch64, with the normal dependency on tool asking for the
+    // host's triple: tool is only built on the host.
+    let graph = edited_graph(|consumer_deps| {
+        consumer_deps[0]["artifact"]["target"] = "x86_64-unknown-linux-gnu".into();
+        consumer_deps[1]
+            .as_object_mut()
+            .expect("dependency is an object")
+            .remove("artifact");
+    });
+    let cargo_set = make_cargo_set(
+        &graph,
+        "aarch64-unknown-linux-gnu",
+        "x86_64-unknown-linux-gnu",
+    );
+    assert_packages(
+        &cargo_set,
+        BuildPlatform::Target,
+        &[
+            json::METADATA_ARTIFACT_DEPS_CONSUMER,
+            json::METADATA_ARTIFACT_DEPS_SHARED,
+        ],
+    );
+    assert_packages(
+        &cargo_set,
+        BuildPlatform::Host,
+        &[
+            json::METADATA_ARTIFACT_DEPS_SHARED,
+            json::METADATA_ARTIFACT_DEPS_TOOL,
+        ],
+    );
+}
+
+#[test]
+fn artifact_followed_links() {
+    let graph = JsonFixture::metadata_artifact_deps().graph();
+    let cargo_set = make_cargo_set(graph, "wasm32-unknown-unknown", "x86_64-unknown-linux-gnu");
+
+    let mut target_links: Vec<_> = cargo_set
+        .followed_links(BuildPlatform::Target)
+        .map(|followed| {
+            (
+                followed.link().from().name(),
+                followed.link().to().name(),
+                followed.kind(),
+                followed.to_build_platform(),
+            )
+        })
+        .collect();
+    target_links.sort();
+    assert_eq!(
+        target_links,
+        vec![
+            (
+                "consumer",
+                "tool",
+                DependencyKind::Normal,
+                BuildPlatform::Target
+            ),
+            (
+                "consumer",
+                "tool",
+                DependencyKind::Build,
+                BuildPlatform::Target
+            ),
+            (
+                "consumer",
+                "tool",
+                DependencyKind::Build,
+                BuildPlatform::Host
+            ),
+            (
+                "tool",
+                "shared",
+                DependencyKind::Normal,
+                BuildPlatform::Target
+            ),
+        ],
+        "links followed from the target"
+    );
+
+    let host_links: Vec<_> = cargo_set
+        .followed_links(BuildPlatform::Host)
+        .map(|followed| {
+            assert_eq!(followed.from_build_platform(), BuildPlatform::Host);
+            (
+                followed.link().from().name(),
+                followed.link().to().name(),
+                followed.to_build_platform(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        host_links,
+        vec![("tool", "shared", BuildPlatform::Host)],
+        "links followed from the host"
+    );
+
+    // Every package built on a platform is reachable through the followed links.
+    for build_platform in BuildPlatform::VALUES {
+        for package in cargo_set
+            .platform_features(*build_platform)
+            .to_package_set()
+            .packages(DependencyDirection::Forward)
+        {
+            let is_initial =
+                package.name() == "consumer" && *build_platform == BuildPlatform::Target;
+            let is_followed = BuildPlatform::VALUES.iter().any(|from| {
+                cargo_set.followed_links(*from).any(|followed| {
+                    followed.link().to().id() == package.id()
+                        && followed.to_build_platform() == *build_platform
+                })
+            });
+            assert!(
+                is_initial || is_followed,
+                "{} on {:?} is reachable",
+                package.name(),
+                build_platform
+            );
+        }
+    }
+}
+
+/// Returns a graph for the fixture, after editing the dependencies of `consumer`.
+fn edited_graph(edit: impl FnOnce(&mut Vec<JsonValue>)) -> PackageGraph {
+    let mut json: JsonValue =
+        serde_json::from_str(JsonFixture::metadata_artifact_deps().json()).expect("valid JSON");
+    let consumer = json["packages"]
+        .as_array_mut()
+        .expect("packages is an array")
+        .iter_mut()
+        .find(|package| package["name"] == "consumer")
+        .expect("consumer is present");
+    edit(
+        consumer["dependencies"]
+            .as_array_mut()
+            .expect("dependencies is an array"),
+    );
+    PackageGraph::from_json(json.to_string()).expect("graph constructed")
+}
+
+fn make_cargo_set<'g>(graph: &'g PackageGraph, target: &str, host: &str) -> CargoSet<'g> {
+    let mut cargo_options = CargoOptions::new();
+    cargo_options
+        .set_resolver(CargoResolverVersion::V2)
+        .set_target_platform(Platform::new(target.to_owned(), TargetFeatures::Unknown).unwrap())
+        .set_host_platform(Platform::new(host.to_owned(), TargetFeatures::Unknown).unwrap());
+    graph
+        .resolve_ids([&package_id(json::METADATA_ARTIFACT_DEPS_CONSUMER)])
+        .expect("valid package ID")
+        .to_feature_set(StandardFeatures::Default)
+        .into_cargo_set(&cargo_options)
+        .expect("resolving cargo should work")
+}
+
+fn assert_packages(cargo_set: &CargoSet<'_>, build_platform: BuildPlatform, expected: &[&str]) {
+    let mut actual: Vec<_> = cargo_set
+        .platform_features(build_platform)
+        .packages_with_features(DependencyDirection::Forward)
+        .map(|package| package.package().id().repr().to_owned())
+        .collect();
+    actual.sort();
+    assert_eq!(actual, expected, "packages on {:?}", build_platform);
+}
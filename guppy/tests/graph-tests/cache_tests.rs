@@ -0,0 +1,71 @@
+// Copyright (c) The cargo-guppy Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use camino::Utf8Path;
+use fixtures::{dep_helpers::assert_graphs_equal, json::JsonFixture};
+use guppy::graph::PackageGraph;
+
+#[test]
+fn cache_roundtrip() {
+    let temp_dir = tempfile::tempdir().expect("created temp dir");
+    let dir = Utf8Path::from_path(temp_dir.path()).expect("temp dir is UTF-8");
+
+    for (&name, fixture) in JsonFixture::all_fixtures() {
+        let graph = fixture.graph();
+        let path = dir.join(format!("{}.cache", name));
+        graph.save_cache(&path, name).expect("cache saved");
+        // The fixture workspaces don't exist on disk, so the cache is keyed on missing files.
+        let loaded = PackageGraph::load_cached(&path, name)
+            .expect("cache loaded")
+            .expect("cache is up-to-date");
+        assert_graphs_equal(graph, &loaded, name);
+        // A cache saved for a different workspace or with different options isn't used.
+        assert!(
+            PackageGraph::load_cached(&path, "other")
+                .expect("cache loaded")
+                .is_none(),
+            "{}: cache with a different context is out of date",
+            name
+        );
+    }
+}
+
+#[test]
+fn cache_errors_are_warnings() {
+    let temp_dir = tempfile::tempdir().expect("created temp dir");
+    let dir = Utf8Path::from_path(temp_dir.path()).expect("temp dir is UTF-8");
+
+    // The cache can't be written inside a directory that doesn't exist.
+    let path = dir.join("missing-dir/graph.cache");
+    let mut warnings = vec![];
+    let graph = PackageGraph::load_or_build_cached(
+        &path,
+        "metadata1",
+        || Ok::<_, ()>(JsonFixture::metadata1().graph().clone()),
+        |err| warnings.push(err.to_string()),
+    )
+    .expect("graph built");
+    assert_eq!(
+        graph.package_count(),
+        JsonFixture::metadata1().graph().package_count()
+    );
+    assert_eq!(warnings.len(), 1, "saving the cache failed: {:?}", warnings);
+
+    // A cache that can be read is used instead of building the graph.
+    let path = dir.join("graph.cache");
+    JsonFixture::metadata1()
+        .graph()
+        .save_cache(&path, "metadata1")
+        .expect("cache saved");
+    let graph = PackageGraph::load_or_build_cached(
+        &path,
+        "metadata1",
+        || Err("graph should be loaded from the cache"),
+        |err| panic!("unexpected cache error: {}", err),
+    )
+    .expect("graph loaded");
+    assert_eq!(
+        graph.package_count(),
+        JsonFixture::metadata1().graph().package_count()
+    );
+}
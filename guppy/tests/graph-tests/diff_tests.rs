@@ -0,0 +1,122 @@
+// Copyright (c) The cargo-guppy Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use fixtures::json::JsonFixture;
+use guppy::{
+    graph::diff::{DiffEnabledStatus, DiffPlatformStatus},
+    DependencyKind,
+};
+
+#[test]
+fn diff_unchanged() {
+    for (&name, fixture) in JsonFixture::all_fixtures() {
+        let graph = fixture.graph();
+        let diff = graph.diff(graph);
+        assert!(
+            diff.is_unchanged(),
+            "{}: diff against itself is empty",
+            name
+        );
+        assert_eq!(
+            diff.report().to_string(),
+            "",
+            "{}: empty diff has an empty report",
+            name
+        );
+    }
+}
+
+#[test]
+fn diff_libra() {
+    let old = JsonFixture::metadata_libra_f0091a4().graph();
+    let new = JsonFixture::metadata_libra_9ffd93b().graph();
+    let diff = old.diff(new);
+    assert!(diff.is_changed(), "libra graphs differ");
+
+    assert!(
+        diff.added
+            .iter()
+            .any(|package| package.name == "executor-types" && package.in_workspace),
+        "new workspace package is added"
+    );
+
+    let bzip2_sys = diff
+        .version_changes
+        .iter()
+        .find(|change| change.name == "bzip2-sys")
+        .expect("bzip2-sys version changed");
+    assert!(bzip2_sys.is_upgrade(), "bzip2-sys was upgraded");
+    assert_ne!(
+        bzip2_sys.old_source, bzip2_sys.new_source,
+        "bzip2-sys git revision changed"
+    );
+
+    let service = diff
+        .changed
+        .iter()
+        .find(|changes| changes.name == "admission-control-service")
+        .expect("admission-control-service changed");
+    assert!(
+        service
+            .added_links
+            .iter()
+            .any(|link| link.dep_name == "serde_json"),
+        "serde_json dependency added"
+    );
+    assert!(
+        service
+            .removed_links
+            .iter()
+            .any(|link| link.dep_name == "num_cpus"),
+        "num_cpus dependency removed"
+    );
+    let mempool = service
+        .changed_links
+        .iter()
+        .find(|change| change.new.dep_name == "libra-mempool")
+        .expect("libra-mempool link changed");
+    let always = DiffPlatformStatus::Always;
+    let never = DiffPlatformStatus::Never;
+    assert_eq!(
+        mempool
+            .old
+            .req_for_kind(DependencyKind::Normal)
+            .map(|req| &req.status),
+        Some(&DiffEnabledStatus {
+            required: never.clone(),
+            optional: always.clone(),
+        }),
+        "libra-mempool was optional"
+    );
+    assert_eq!(
+        mempool
+            .new
+            .req_for_kind(DependencyKind::Normal)
+            .map(|req| &req.status),
+        Some(&DiffEnabledStatus {
+            required: always,
+            optional: never,
+        }),
+        "libra-mempool is now required"
+    );
+    assert!(
+        service.removed_features.contains_key("libra-mempool"),
+        "implicit feature for optional dependency removed"
+    );
+    let fuzzing = &service.changed_features["fuzzing"];
+    assert!(fuzzing.old.contains(&"libra-mempool".to_owned()));
+    assert!(!fuzzing.new.contains(&"libra-mempool".to_owned()));
+
+    let report = diff.report().to_string();
+    assert!(
+        report.contains("  ^ bitvec 0.10.2 -> 0.17.3\n"),
+        "report lists upgrades: {}",
+        report
+    );
+
+    let json = serde_json::to_value(&diff).expect("diff serialized");
+    assert!(
+        json["version-changes"].is_array(),
+        "serialized diff has version changes"
+    );
+}
@@ -0,0 +1,328 @@
+// Copyright (c) The cargo-guppy Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use fixtures::{
+    json::{self, JsonFixture},
+    package_id,
+};
+use guppy::{
+    graph::{
+        feature::{FeatureId, StandardFeatures},
+        DependencyDirection, DependencySpec, NewWorkspaceMember, PackageGraph,
+    },
+    DependencyKind, Error, PackageId,
+};
+use semver::{Version, VersionReq};
+use std::collections::BTreeSet;
+
+#[test]
+fn edit_remove_dependency() {
+    let mut graph = metadata1();
+    let testcrate = package_id(json::METADATA1_TESTCRATE);
+    let datatest = package_id(json::METADATA1_DATATEST);
+    let outside_before = outside_workspace_closure(&graph);
+
+    graph
+        .edit()
+        .remove_dependency(&testcrate, &datatest)
+        .expect("dependency removed");
+    graph.verify().expect("graph verified");
+
+    assert!(
+        graph.metadata(&datatest).is_err(),
+        "datatest is no longer in the graph"
+    );
+    let testcrate_metadata = graph.metadata(&testcrate).expect("testcrate is present");
+    assert_eq!(testcrate_metadata.direct_links().count(), 0);
+    assert_eq!(
+        testcrate_metadata.named_features().count(),
+        0,
+        "implicit feature for optional build dependency on datatest removed"
+    );
+    // Packages that weren't reachable from the workspace in the first place are kept.
+    assert_eq!(graph.package_count(), 1 + outside_before.len());
+    assert_eq!(outside_workspace_closure(&graph), outside_before);
+
+    assert!(
+        graph
+            .edit()
+            .remove_dependency(&testcrate, &datatest)
+            .is_err(),
+        "removing a missing dependency fails"
+    );
+}
+
+#[test]
+fn edit_add_optional_dependency() {
+    let mut graph = metadata1();
+    let testcrate = package_id(json::METADATA1_TESTCRATE);
+    let region = package_id(json::METADATA1_REGION);
+    // Build the feature graph so the edit has to invalidate it.
+    assert!(graph
+        .feature_graph()
+        .metadata(FeatureId::named(&testcrate, "region"))
+        .is_err());
+
+    let mut spec = DependencySpec::new(DependencyKind::Normal);
+    spec.set_optional(true).set_default_features(false);
+    graph
+        .edit()
+        .add_dependency(&testcrate, &region, &spec)
+        .expect("dependency added");
+    graph.verify().expect("graph verified");
+
+    let link = graph
+        .metadata(&testcrate)
+        .expect("testcrate is present")
+        .direct_links()
+        .find(|link| *link.to().id() == region)
+        .expect("testcrate depends on region");
+    assert_eq!(link.dep_name(), "region");
+    assert_eq!(link.version_req(), &VersionReq::parse("^2.1.2").unwrap());
+    assert!(link.normal().is_present());
+    assert!(link.normal().status().required_status().is_never());
+    assert!(link.normal().status().optional_status().is_always());
+    assert!(!link.build().is_present());
+
+    let feature_graph = graph.feature_graph();
+    feature_graph
+        .metadata(FeatureId::named(&testcrate, "region"))
+        .expect("implicit feature for region added");
+    let with_region = feature_graph
+        .query_forward([FeatureId::named(&testcrate, "region")])
+        .expect("valid feature ID")
+        .resolve();
+    assert!(with_region
+        .contains(FeatureId::base(&region))
+        .expect("valid feature ID"));
+    assert!(
+        !with_region
+            .contains(FeatureId::named(&region, "default"))
+            .unwrap_or(false),
+        "default features of region aren't enabled"
+    );
+
+    // Adding an optional dev-dependency fails, and leaves the graph unchanged.
+    let mut dev_spec = DependencySpec::new(DependencyKind::Development);
+    dev_spec.set_optional(true);
+    let err = graph
+        .edit()
+        .add_dependency(&testcrate, &region, &dev_spec)
+        .expect_err("optional dev-dependencies aren't allowed");
+    assert!(matches!(err, Error::PackageGraphEditError(_)));
+    let link = graph
+        .metadata(&testcrate)
+        .expect("testcrate is present")
+        .direct_links()
+        .find(|link| *link.to().id() == region)
+        .expect("testcrate depends on region");
+    assert!(!link.dev().is_present());
+
+    // A different name for an existing dependency is rejected.
+    let mut renamed_spec = DependencySpec::new(DependencyKind::Build);
+    renamed_spec.set_rename("region2");
+    assert!(graph
+        .edit()
+        .add_dependency(&testcrate, &region, &renamed_spec)
+        .is_err());
+}
+
+#[test]
+fn edit_dependency_features() {
+    let mut graph = metadata1();
+    let testcrate = package_id(json::METADATA1_TESTCRATE);
+    let datatest = package_id(json::METADATA1_DATATEST);
+    let datatest_default = FeatureId::named(&datatest, "default");
+    let datatest_runner = FeatureId::named(&datatest, "unsafe_test_runner");
+
+    let features = testcrate_features(&graph);
+    assert!(features.contains(&datatest_default));
+    assert!(features.contains(&datatest_runner));
+
+    for kind in [DependencyKind::Normal, DependencyKind::Development] {
+        graph
+            .edit()
+            .set_dependency_features(&testcrate, &datatest, kind, false, [])
+            .expect("features set");
+    }
+    graph.verify().expect("graph verified");
+    let features = testcrate_features(&graph);
+    assert!(!features.contains(&datatest_default));
+    assert!(!features.contains(&datatest_runner));
+
+    graph
+        .edit()
+        .set_dependency_features(
+            &testcrate,
+            &datatest,
+            DependencyKind::Normal,
+            false,
+            ["unsafe_test_runner"],
+        )
+        .expect("features set");
+    let features = testcrate_features(&graph);
+    assert!(!features.contains(&datatest_default));
+    assert!(features.contains(&datatest_runner));
+
+    // The build dependency is optional and not enabled by default.
+    graph
+        .edit()
+        .set_dependency_features(&testcrate, &datatest, DependencyKind::Build, true, [])
+        .expect("features set");
+    let features = testcrate_features(&graph);
+    assert!(!features.contains(&datatest_default));
+}
+
+#[test]
+fn edit_version_req() {
+    let mut graph = metadata1();
+    let testcrate = package_id(json::METADATA1_TESTCRATE);
+    let datatest = package_id(json::METADATA1_DATATEST);
+
+    let err = graph
+        .edit()
+        .set_version_req(&testcrate, &datatest, VersionReq::parse("^0.5").unwrap())
+        .expect_err("0.4.2 doesn't match ^0.5");
+    assert!(matches!(err, Error::PackageGraphEditError(_)));
+    assert_eq!(datatest_version_req(&graph), "^0.4.2");
+
+    graph
+        .edit()
+        .set_version_req(&testcrate, &datatest, VersionReq::parse("=0.4.2").unwrap())
+        .expect("version req set");
+    assert_eq!(datatest_version_req(&graph), "=0.4.2");
+}
+
+#[test]
+fn edit_workspace_members() {
+    let mut graph = metadata1();
+    let testcrate = package_id(json::METADATA1_TESTCRATE);
+    let datatest = package_id(json::METADATA1_DATATEST);
+    let package_count = graph.package_count();
+
+    let mut member = NewWorkspaceMember::new("new-crate", Version::new(0, 2, 0), "crates/new");
+    member
+        .add_feature("default", ["extra"])
+        .add_feature("extra", Vec::<String>::new());
+    let new_crate = graph
+        .edit()
+        .add_workspace_member(&member)
+        .expect("member added");
+    graph.verify().expect("graph verified");
+
+    assert_eq!(
+        new_crate.repr(),
+        "path+file:///fakepath/testcrate/crates/new#new-crate@0.2.0"
+    );
+    assert_eq!(graph.package_count(), package_count + 1);
+    let workspace = graph.workspace();
+    assert_eq!(workspace.member_count(), 2);
+    assert_eq!(
+        workspace
+            .member_by_path("crates/new")
+            .expect("member found")
+            .id(),
+        &new_crate
+    );
+    let new_metadata = workspace.member_by_name("new-crate").expect("member found");
+    assert!(new_metadata.in_workspace());
+    assert!(new_metadata.has_default_feature());
+    assert_eq!(
+        new_metadata.named_features().collect::<Vec<_>>(),
+        vec!["default", "extra"]
+    );
+
+    // Duplicate members are rejected.
+    assert!(graph.edit().add_workspace_member(&member).is_err());
+    let bad_path = NewWorkspaceMember::new("other", Version::new(0, 1, 0), "../other");
+    assert!(graph.edit().add_workspace_member(&bad_path).is_err());
+
+    graph
+        .edit()
+        .add_dependency(
+            &new_crate,
+            &datatest,
+            &DependencySpec::new(DependencyKind::Normal),
+        )
+        .expect("dependency added");
+    graph
+        .edit()
+        .add_dependency(
+            &testcrate,
+            &new_crate,
+            &DependencySpec::new(DependencyKind::Normal),
+        )
+        .expect("dependency added");
+    graph.verify().expect("graph verified");
+
+    // new-crate is depended on by testcrate, so it is kept as a path dependency.
+    graph
+        .edit()
+        .remove_workspace_member(&new_crate)
+        .expect("member removed");
+    graph.verify().expect("graph verified");
+    let new_metadata = graph.metadata(&new_crate).expect("new-crate is present");
+    assert!(!new_metadata.in_workspace());
+    assert!(new_metadata.source().is_path());
+    assert_eq!(graph.workspace().member_count(), 1);
+    assert!(graph.edit().remove_workspace_member(&new_crate).is_err());
+
+    // Adding it back promotes the path dependency.
+    let readded = graph
+        .edit()
+        .add_workspace_member(&member)
+        .expect("member added");
+    assert_eq!(readded, new_crate);
+    assert_eq!(graph.package_count(), package_count + 1);
+
+    // testcrate has no dependents, so removing it removes it from the graph. datatest is still
+    // depended on by new-crate.
+    graph
+        .edit()
+        .remove_workspace_member(&testcrate)
+        .expect("member removed");
+    graph.verify().expect("graph verified");
+    assert!(graph.metadata(&testcrate).is_err());
+    assert!(graph.metadata(&datatest).is_ok());
+    assert_eq!(graph.package_count(), package_count);
+    assert_eq!(
+        graph.workspace().member_ids().collect::<Vec<_>>(),
+        vec![&new_crate]
+    );
+}
+
+fn metadata1() -> PackageGraph {
+    PackageGraph::from_json(JsonFixture::metadata1().json()).expect("graph constructed")
+}
+
+/// Returns packages that aren't reachable from the workspace.
+fn outside_workspace_closure(graph: &PackageGraph) -> BTreeSet<PackageId> {
+    let reachable = graph.query_workspace().resolve();
+    graph
+        .packages()
+        .filter(|package| !reachable.contains(package.id()).expect("valid package ID"))
+        .map(|package| package.id().clone())
+        .collect()
+}
+
+/// Returns the features built for testcrate with default features, including dev-dependencies.
+fn testcrate_features(graph: &PackageGraph) -> BTreeSet<FeatureId<'_>> {
+    graph
+        .feature_graph()
+        .query_workspace(StandardFeatures::Default)
+        .resolve()
+        .features(DependencyDirection::Forward)
+        .map(|feature| feature.feature_id())
+        .collect()
+}
+
+fn datatest_version_req(graph: &PackageGraph) -> String {
+    graph
+        .metadata(&package_id(json::METADATA1_TESTCRATE))
+        .expect("testcrate is present")
+        .direct_links()
+        .find(|link| link.dep_name() == "datatest")
+        .expect("testcrate depends on datatest")
+        .version_req()
+        .to_string()
+}
@@ -0,0 +1,227 @@
+// Copyright (c) The cargo-guppy Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use fixtures::json::JsonFixture;
+use guppy::graph::{
+    export::{ExportFormat, ExportGraph, ExportLink, ExportNode},
+    feature::StandardFeatures,
+    DependencyDirection,
+};
+use std::{collections::HashSet, fmt::Write};
+
+#[test]
+fn export_package_set() {
+    let graph = JsonFixture::metadata1().graph();
+    let package_set = graph.resolve_all();
+    let export_graph = package_set.to_export_graph();
+
+    assert_eq!(export_graph.nodes.len(), package_set.len());
+    assert_eq!(
+        export_graph.links.len(),
+        package_set.links(DependencyDirection::Forward).count()
+    );
+    check_links(&export_graph);
+
+    let testcrate = node_by_label(&export_graph, "testcrate v0.1.0");
+    assert_eq!(testcrate.attributes["source-kind"], "workspace");
+    let datatest = node_by_label(&export_graph, "datatest v0.4.2");
+    assert_eq!(datatest.attributes["source-kind"], "registry");
+    assert_eq!(datatest.attributes["name"], "datatest");
+
+    let datatest_link = export_graph
+        .links
+        .iter()
+        .find(|link| link.source == testcrate.id && link.target == datatest.id)
+        .expect("testcrate depends on datatest");
+    assert_eq!(datatest_link.label, "datatest");
+    assert_eq!(datatest_link.attributes["kinds"], "normal,build,dev");
+
+    let dot = export_graph.display(ExportFormat::Dot).to_string();
+    assert!(dot.contains("[label=\"testcrate v0.1.0\", color=\"blue\"]"));
+    assert!(dot.contains("[label=\"datatest\", style=\"solid\"]"));
+}
+
+#[test]
+fn export_feature_set() {
+    let graph = JsonFixture::metadata1().graph();
+    let feature_set = graph
+        .feature_graph()
+        .query_workspace(StandardFeatures::Default)
+        .resolve();
+    let export_graph = feature_set.to_export_graph();
+
+    assert_eq!(export_graph.nodes.len(), feature_set.len());
+    check_links(&export_graph);
+
+    let base = node_by_label(&export_graph, "regex v1.3.1/[base]");
+    assert_eq!(base.attributes["feature"], "[base]");
+    let default = node_by_label(&export_graph, "regex v1.3.1/default");
+
+    // Links within a package have no label or attributes.
+    let internal_link = export_graph
+        .links
+        .iter()
+        .find(|link| link.source == default.id && link.target == base.id)
+        .expect("regex/default depends on regex/[base]");
+    assert_eq!(internal_link.label, "");
+    assert!(internal_link.attributes.is_empty());
+
+    // Links across packages are labeled with the dependency name.
+    let cross_link = export_graph
+        .links
+        .iter()
+        .find(|link| {
+            link.target == base.id
+                && node_by_id(&export_graph, &link.source).attributes["name"] != "regex"
+        })
+        .expect("regex/[base] has a dependent in another package");
+    assert_eq!(cross_link.label, "regex");
+    assert_eq!(cross_link.attributes["dep-name"], "regex");
+}
+
+#[test]
+fn export_formats() {
+    let export_graph = sample_graph();
+
+    assert_eq!(
+        export_graph.display(ExportFormat::Dot).to_string(),
+        r#"digraph {
+    0 [label="foo \"v1\"", color="blue"]
+    1 [label="bar|baz <1>"]
+    0 -> 1 [label="bar", style="dashed"]
+}
+"#
+    );
+
+    assert_eq!(
+        export_graph.display(ExportFormat::GraphMl).to_string(),
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<graphml xmlns="http://graphml.graphdrawing.org/xmlns">
+    <key id="label" for="all" attr.name="label" attr.type="string"/>
+    <key id="node-source-kind" for="node" attr.name="source-kind" attr.type="string"/>
+    <key id="edge-kinds" for="edge" attr.name="kinds" attr.type="string"/>
+    <graph id="G" edgedefault="directed">
+        <node id="n0">
+            <data key="label">foo &quot;v1&quot;</data>
+            <data key="node-source-kind">workspace</data>
+        </node>
+        <node id="n1">
+            <data key="label">bar|baz &lt;1&gt;</data>
+        </node>
+        <edge id="e0" source="n0" target="n1">
+            <data key="label">bar</data>
+            <data key="edge-kinds">dev,build</data>
+        </edge>
+    </graph>
+</graphml>
+"#
+    );
+
+    assert_eq!(
+        export_graph.display(ExportFormat::Mermaid).to_string(),
+        r#"flowchart TD
+    n0["foo #quot;v1#quot;"]
+    n1["bar#124;baz <1>"]
+    n0 ==>|"bar"| n1
+    classDef workspace stroke:blue
+    class n0 workspace
+"#
+    );
+
+    let json = export_graph.display(ExportFormat::Json).to_string();
+    let parsed: serde_json::Value = serde_json::from_str(&json).expect("valid JSON");
+    assert_eq!(
+        parsed,
+        serde_json::json!({
+            "nodes": [
+                {
+                    "id": "foo",
+                    "label": "foo \"v1\"",
+                    "attributes": { "source-kind": "workspace" },
+                },
+                { "id": "bar", "label": "bar|baz <1>", "attributes": {} },
+            ],
+            "links": [
+                {
+                    "source": "foo",
+                    "target": "bar",
+                    "label": "bar",
+                    "attributes": { "kinds": "dev,build" },
+                },
+            ],
+        })
+    );
+}
+
+#[test]
+fn export_unknown_node() {
+    let mut export_graph = sample_graph();
+    export_graph.links[0].target = "unknown".to_owned();
+
+    for format in ExportFormat::VALUES {
+        let mut out = String::new();
+        assert!(
+            write!(out, "{}", export_graph.display(format)).is_err(),
+            "{} export fails for a link to an unknown node",
+            format
+        );
+    }
+}
+
+fn sample_graph() -> ExportGraph {
+    let mut foo_node = ExportNode {
+        id: "foo".to_owned(),
+        label: "foo \"v1\"".to_owned(),
+        ..ExportNode::default()
+    };
+    foo_node
+        .attributes
+        .insert("source-kind".to_owned(), "workspace".to_owned());
+    let bar_node = ExportNode {
+        id: "bar".to_owned(),
+        label: "bar|baz <1>".to_owned(),
+        ..ExportNode::default()
+    };
+    let mut link = ExportLink {
+        source: "foo".to_owned(),
+        target: "bar".to_owned(),
+        label: "bar".to_owned(),
+        ..ExportLink::default()
+    };
+    link.attributes
+        .insert("kinds".to_owned(), "dev,build".to_owned());
+
+    ExportGraph {
+        nodes: vec![foo_node, bar_node],
+        links: vec![link],
+    }
+}
+
+fn node_by_label<'a>(export_graph: &'a ExportGraph, label: &str) -> &'a ExportNode {
+    export_graph
+        .nodes
+        .iter()
+        .find(|node| node.label == label)
+        .unwrap_or_else(|| panic!("node {} not found", label))
+}
+
+fn node_by_id<'a>(export_graph: &'a ExportGraph, id: &str) -> &'a ExportNode {
+    export_graph
+        .nodes
+        .iter()
+        .find(|node| node.id == id)
+        .unwrap_or_else(|| panic!("node {} not found", id))
+}
+
+fn check_links(export_graph: &ExportGraph) {
+    let ids: HashSet<_> = export_graph
+        .nodes
+        .iter()
+        .map(|node| node.id.as_str())
+        .collect();
+    assert_eq!(ids.len(), export_graph.nodes.len(), "node IDs are unique");
+    for link in &export_graph.links {
+        assert!(ids.contains(link.source.as_str()));
+        assert!(ids.contains(link.target.as_str()));
+    }
+}
@@ -0,0 +1,150 @@
+// Copyright (c) The cargo-guppy Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use fixtures::json::JsonFixture;
+use guppy::graph::feature::{FeatureAuditOptions, FeatureLabel, FeatureMetadata};
+
+#[test]
+fn feature_audit_metadata1() {
+    let graph = JsonFixture::metadata1().graph();
+    let audit = graph
+        .feature_graph()
+        .audit(&FeatureAuditOptions::new())
+        .expect("audit succeeded");
+
+    assert!(audit.cycles().is_empty());
+
+    let unreachable = feature_names(
+        audit
+            .unreachable_features()
+            .filter(|feature| feature.package().name() == "serde"),
+    );
+    assert_eq!(
+        unreachable,
+        vec!["serde/alloc", "serde/rc", "serde/unstable"]
+    );
+    assert!(
+        audit
+            .unreachable_features()
+            .all(|feature| feature.package().name() != "datatest"),
+        "datatest's features are enabled through the dev-dependency"
+    );
+
+    let aliases: Vec<_> = audit
+        .aliases()
+        .map(|alias| {
+            (
+                alias.alias().package().name(),
+                alias.alias().label(),
+                alias.target().label(),
+            )
+        })
+        .collect();
+    assert!(aliases.contains(&(
+        "datatest",
+        FeatureLabel::Named("unsafe_test_runner"),
+        FeatureLabel::Named("region"),
+    )));
+    assert!(aliases.contains(&(
+        "regex",
+        FeatureLabel::Named("use_std"),
+        FeatureLabel::Named("std"),
+    )));
+    assert!(
+        aliases
+            .iter()
+            .all(|(_, alias, _)| *alias != FeatureLabel::Named("default")),
+        "default is never reported as an alias"
+    );
+
+    let always_enabled: Vec<_> = audit
+        .always_enabled_optional_deps()
+        .map(|dep| {
+            (
+                dep.feature().package().name(),
+                dep.feature().label(),
+                dep.builds(),
+            )
+        })
+        .collect();
+    assert!(always_enabled.contains(&("regex", FeatureLabel::OptionalDependency("memchr"), 1)));
+    assert!(always_enabled.contains(&("syn", FeatureLabel::OptionalDependency("quote"), 1)));
+
+    // No default features pull in 10 or more packages.
+    assert_eq!(audit.large_defaults().len(), 0);
+}
+
+#[test]
+fn feature_audit_large_defaults() {
+    let graph = JsonFixture::metadata1().graph();
+    let mut opts = FeatureAuditOptions::new();
+    opts.set_large_default_threshold(1);
+    let audit = graph.feature_graph().audit(&opts).expect("audit succeeded");
+
+    let large_defaults: Vec<_> = audit
+        .large_defaults()
+        .map(|large| {
+            let mut extra: Vec<_> = large
+                .extra_packages()
+                .iter()
+                .map(|package| package.name())
+                .collect();
+            extra.sort_unstable();
+            (large.package().name(), extra)
+        })
+        .collect();
+    assert_eq!(
+        large_defaults,
+        vec![
+            (
+                "regex",
+                vec!["aho-corasick", "lazy_static", "memchr", "thread_local"]
+            ),
+            ("syn", vec!["quote"]),
+        ],
+        "largest defaults come first"
+    );
+
+    opts.set_large_default_threshold(2);
+    let audit = graph.feature_graph().audit(&opts).expect("audit succeeded");
+    assert_eq!(audit.large_defaults().len(), 1);
+}
+
+#[test]
+fn feature_audit_cycles() {
+    let graph = JsonFixture::metadata_cycle_features().graph();
+    let audit = graph
+        .feature_graph()
+        .audit(&FeatureAuditOptions::new())
+        .expect("audit succeeded");
+
+    assert_eq!(audit.cycles().len(), 1);
+    let cycle = feature_names(audit.cycles()[0].iter().copied());
+    assert!(cycle.contains(&"testcycles-base/default".to_owned()));
+    assert!(cycle.contains(&"testcycles-helper/[base]".to_owned()));
+
+    let aliases: Vec<_> = audit
+        .aliases()
+        .map(|alias| {
+            (
+                alias.alias().label().to_string(),
+                alias.target().label().to_string(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        aliases,
+        vec![
+            ("default-enable".to_owned(), "default-transitive".to_owned()),
+            ("helper-enable".to_owned(), "helper-transitive".to_owned()),
+        ]
+    );
+    assert!(!audit.is_empty());
+}
+
+fn feature_names<'g>(features: impl IntoIterator<Item = FeatureMetadata<'g>>) -> Vec<String> {
+    features
+        .into_iter()
+        .map(|feature| format!("{}/{}", feature.package().name(), feature.label()))
+        .collect()
+}
@@ -0,0 +1,88 @@
+// Copyright (c) The cargo-guppy Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use fixtures::{
+    json::{self, JsonFixture},
+    package_id,
+};
+use guppy::{
+    graph::{
+        cargo::{CargoOptions, CargoResolverVersion},
+        feature::StandardFeatures,
+    },
+    DependencyKind,
+};
+
+#[test]
+fn feature_hygiene_metadata1() {
+    let graph = JsonFixture::metadata1().graph();
+    let testcrate = package_id(json::METADATA1_TESTCRATE);
+    let datatest = package_id(json::METADATA1_DATATEST);
+
+    // Without dev-dependencies, the normal dependency is the only way datatest is built.
+    let hygiene = graph
+        .feature_graph()
+        .hygiene(StandardFeatures::Default, &v2_options(false))
+        .expect("hygiene analysis succeeded");
+    assert!(hygiene.is_empty(), "no redundant features found");
+
+    // With dev-dependencies, the normal and dev-dependencies both enable default features. Only
+    // one of them can be removed.
+    let hygiene = graph
+        .feature_graph()
+        .hygiene(StandardFeatures::Default, &v2_options(true))
+        .expect("hygiene analysis succeeded");
+    let links: Vec<_> = hygiene
+        .links()
+        .map(|link_hygiene| {
+            let link = link_hygiene.link();
+            assert_eq!(link.from().id(), &testcrate);
+            assert_eq!(link.to().id(), &datatest);
+            (
+                link_hygiene.kind(),
+                link_hygiene.redundant_features().to_vec(),
+                link_hygiene.is_default_redundant(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        links,
+        vec![(DependencyKind::Normal, vec![], true)],
+        "unsafe_test_runner is only enabled by the dev-dependency, and the optional build \
+         dependency isn't enabled"
+    );
+}
+
+#[test]
+fn feature_hygiene_redundant_feature() {
+    let mut graph = JsonFixture::metadata1().graph().clone();
+    let testcrate = package_id(json::METADATA1_TESTCRATE);
+    let datatest = package_id(json::METADATA1_DATATEST);
+
+    // Also request unsafe_test_runner (and through it, region) on the normal dependency.
+    graph
+        .edit()
+        .enable_dependency_features(
+            &testcrate,
+            &datatest,
+            DependencyKind::Normal,
+            ["unsafe_test_runner", "region"],
+        )
+        .expect("features enabled");
+    let hygiene = graph
+        .feature_graph()
+        .hygiene(StandardFeatures::Default, &v2_options(false))
+        .expect("hygiene analysis succeeded");
+    let link_hygiene = hygiene.links().next().expect("one link found");
+    assert_eq!(hygiene.links().len(), 1);
+    assert_eq!(link_hygiene.kind(), DependencyKind::Normal);
+    assert_eq!(link_hygiene.redundant_features(), &["region"]);
+    assert!(!link_hygiene.is_default_redundant());
+}
+
+fn v2_options(include_dev: bool) -> CargoOptions<'static> {
+    let mut opts = CargoOptions::new();
+    opts.set_resolver(CargoResolverVersion::V2)
+        .set_include_dev(include_dev);
+    opts
+}
@@ -341,6 +341,14 @@ mod small {
     }
 
     // No need for proptests because this is a really simple test.
+
+    #[test]
+    fn metadata_artifact_deps() {
+        let metadata = JsonFixture::metadata_artifact_deps();
+        metadata.verify();
+    }
+
+    proptest_suite!(metadata_artifact_deps);
 }
 
 mod large {
@@ -12,7 +12,23 @@ macro_rules! proptest_suite {
     };
 }
 
+mod artifact_tests;
+#[cfg(feature = "cache")]
+mod cache_tests;
+mod diff_tests;
+mod edit_tests;
+mod export_tests;
+mod feature_audit_tests;
 mod feature_helpers;
+mod feature_hygiene_tests;
 mod graph_tests;
 mod invalid_tests;
+#[cfg(feature = "offline")]
+mod offline_tests;
+#[cfg(feature = "overrides")]
+mod overrides_tests;
+mod path_tests;
+mod per_package_tests;
 mod weak_namespaced;
+#[cfg(feature = "summaries")]
+mod what_if_tests;
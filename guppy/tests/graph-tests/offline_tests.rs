@@ -0,0 +1,130 @@
+// Copyright (c) The cargo-guppy Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use camino::{Utf8Path, Utf8PathBuf};
+use fixtures::dep_helpers::assert_graphs_equal;
+use guppy::{Error, MetadataCommand, OfflineMetadataCommand};
+use std::fs;
+
+// Checksums from the fixture's Cargo.lock.
+static VENDORED: &[(&str, &str, &str, &str)] = &[
+    (
+        "bytes",
+        "0.5.4",
+        "130aac562c0dd69c56b3b1cc8ffd2e17be31d0b6c25b61c96b76231aa23e39e1",
+        "",
+    ),
+    (
+        "lazy_static",
+        "1.4.0",
+        "e2abad23fbc42b3700f2f279844dc832adb2b2eb069b2df918f455c4e18cc646",
+        "[dependencies.spin]\nversion = \"0.5.0\"\noptional = true\n\n\
+         [features]\nspin_no_std = [\"spin\"]\n",
+    ),
+    (
+        "spin",
+        "0.5.2",
+        "6e63cff320ae2c57904679ba7cb63280a3dc4613885beafb148ee7bf9aa9042d",
+        "",
+    ),
+];
+
+#[test]
+fn offline_matches_cargo_metadata() {
+    let temp_dir = tempfile::tempdir().expect("created temp dir");
+    let dir = Utf8Path::from_path(temp_dir.path()).expect("temp dir is UTF-8");
+    let manifest_path = setup_inside_outside(dir, true);
+    // Cargo reads .cargo/config.toml relative to the current directory.
+    let workspace_dir = manifest_path.parent().expect("manifest path has a parent");
+
+    let offline = OfflineMetadataCommand::new()
+        .manifest_path(&manifest_path)
+        .current_dir(workspace_dir)
+        .build_graph()
+        .expect("offline graph built");
+    let expected = MetadataCommand::new()
+        .manifest_path(&manifest_path)
+        .current_dir(workspace_dir)
+        .other_options(["--offline"])
+        .build_graph()
+        .expect("cargo metadata succeeded");
+
+    assert_graphs_equal(&expected, &offline, "offline graph");
+}
+
+#[test]
+fn offline_missing_vendored_manifest() {
+    let temp_dir = tempfile::tempdir().expect("created temp dir");
+    let dir = Utf8Path::from_path(temp_dir.path()).expect("temp dir is UTF-8");
+    let manifest_path = setup_inside_outside(dir, false);
+
+    let err = OfflineMetadataCommand::new()
+        .manifest_path(&manifest_path)
+        .cargo_home(dir.join("cargo-home"))
+        .build_graph()
+        .expect_err("third-party manifests are missing");
+    assert!(
+        matches!(err, Error::OfflineMetadataError(_)),
+        "unexpected error: {}",
+        err
+    );
+    assert!(
+        err.to_string().contains("cargo vendor"),
+        "error suggests vendoring: {}",
+        err
+    );
+}
+
+/// Copies the inside-outside fixture into `dir`, optionally vendoring its third-party
+/// dependencies. Returns the path to the workspace manifest.
+fn setup_inside_outside(dir: &Utf8Path, vendor: bool) -> Utf8PathBuf {
+    let fixture_dir =
+        Utf8Path::new(env!("CARGO_MANIFEST_DIR")).join("../fixtures/workspace/inside-outside");
+    copy_dir(&fixture_dir, dir);
+
+    if vendor {
+        let vendor_dir = dir.join("vendor");
+        for (name, version, checksum, extra) in VENDORED {
+            let crate_dir = vendor_dir.join(name);
+            fs::create_dir_all(crate_dir.join("src")).expect("created vendored crate");
+            fs::write(
+                crate_dir.join("Cargo.toml"),
+                format!(
+                    "[package]\nname = \"{}\"\nversion = \"{}\"\n\n{}",
+                    name, version, extra
+                ),
+            )
+            .expect("wrote vendored manifest");
+            fs::write(crate_dir.join("src/lib.rs"), "").expect("wrote vendored lib.rs");
+            fs::write(
+                crate_dir.join(".cargo-checksum.json"),
+                format!("{{\"files\":{{}},\"package\":\"{}\"}}", checksum),
+            )
+            .expect("wrote vendored checksum");
+        }
+
+        let config_dir = dir.join("workspace/.cargo");
+        fs::create_dir_all(&config_dir).expect("created .cargo");
+        fs::write(
+            config_dir.join("config.toml"),
+            "[source.crates-io]\nreplace-with = \"vendored-sources\"\n\n\
+             [source.vendored-sources]\ndirectory = \"../vendor\"\n",
+        )
+        .expect("wrote cargo config");
+    }
+
+    dir.join("workspace/Cargo.toml")
+}
+
+fn copy_dir(from: &Utf8Path, to: &Utf8Path) {
+    fs::create_dir_all(to).expect("created directory");
+    for entry in from.read_dir_utf8().expect("read fixture directory") {
+        let entry = entry.expect("read fixture entry");
+        let dest = to.join(entry.file_name());
+        if entry.path().is_dir() {
+            copy_dir(entry.path(), &dest);
+        } else {
+            fs::copy(entry.path(), &dest).expect("copied fixture file");
+        }
+    }
+}
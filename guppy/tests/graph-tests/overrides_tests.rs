@@ -0,0 +1,149 @@
+// Copyright (c) The cargo-guppy Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use camino::Utf8Path;
+use fixtures::json::JsonFixture;
+use guppy::{
+    graph::{DependencyDirection, OverrideKind, OverrideSource, PackageGraph},
+    MetadataCommand,
+};
+use std::fs;
+
+static ROOT_MANIFEST: &str = r#"[workspace]
+members = ["a"]
+
+[patch.crates-io]
+dep = { path = "patches/dep" }
+unused = { path = "patches/unused" }
+"#;
+
+#[test]
+fn patch_overrides() {
+    let temp_dir = tempfile::tempdir().expect("created temp dir");
+    let dir = Utf8Path::from_path(temp_dir.path()).expect("temp dir is UTF-8");
+    write(dir, "Cargo.toml", ROOT_MANIFEST);
+    write(
+        dir,
+        "a/Cargo.toml",
+        "[package]\nname = \"a\"\nversion = \"0.1.0\"\n\n[dependencies]\ndep = \"0.1\"\n",
+    );
+    write(dir, "a/src/lib.rs", "");
+    for name in ["dep", "unused"] {
+        write(
+            dir,
+            &format!("patches/{}/Cargo.toml", name),
+            &format!("[package]\nname = \"{}\"\nversion = \"0.1.0\"\n", name),
+        );
+        write(dir, &format!("patches/{}/src/lib.rs", name), "");
+    }
+    // Point crates.io at an empty directory so that Cargo doesn't need the network.
+    write(
+        dir,
+        ".cargo/config.toml",
+        "[source.crates-io]\nreplace-with = \"vendored\"\n\n[source.vendored]\ndirectory = \"vendor\"\n",
+    );
+    fs::create_dir_all(dir.join("vendor")).expect("created vendor dir");
+
+    let mut graph = MetadataCommand::new()
+        .manifest_path(dir.join("Cargo.toml"))
+        .current_dir(dir)
+        .other_options(["--offline"])
+        .build_graph()
+        .expect("cargo metadata succeeded");
+    // Building the graph doesn't read the manifest.
+    assert_eq!(graph.workspace().overrides().len(), 0);
+    graph
+        .load_overrides(dir.join("Cargo.toml"))
+        .expect("overrides loaded");
+    check_overrides(&graph);
+
+    graph
+        .load_overrides(dir.join("missing/Cargo.toml"))
+        .expect_err("missing manifest is an error");
+
+    // Overrides survive a round trip through the cache.
+    #[cfg(feature = "cache")]
+    {
+        let cache_path = dir.join("graph.cache");
+        graph
+            .save_cache(&cache_path, "overrides")
+            .expect("cache saved");
+        let cached = PackageGraph::load_cached(&cache_path, "overrides")
+            .expect("cache loaded")
+            .expect("cache is up to date");
+        check_overrides(&cached);
+    }
+}
+
+fn check_overrides(graph: &PackageGraph) {
+    let overrides: Vec<_> = graph.workspace().overrides().collect();
+    assert_eq!(overrides.len(), 2, "both patches are listed");
+
+    let dep = overrides[0];
+    assert_eq!(dep.kind(), OverrideKind::Patch);
+    assert_eq!(dep.name(), "dep");
+    assert_eq!(dep.original_source(), "crates-io");
+    assert_eq!(
+        dep.replacement(),
+        OverrideSource::Path(Utf8Path::new("patches/dep"))
+    );
+    assert!(dep.is_used(), "dep patch is used");
+    let packages: Vec<_> = dep.packages().collect();
+    assert_eq!(packages.len(), 1, "dep patch resolves to one package");
+    assert_eq!(packages[0].name(), "dep");
+
+    let unused = overrides[1];
+    assert_eq!(unused.name(), "unused");
+    assert!(!unused.is_used(), "unused patch isn't used");
+    assert_eq!(unused.packages().count(), 0);
+
+    let a = graph
+        .workspace()
+        .member_by_path("a")
+        .expect("a is a workspace member");
+    assert!(a.overridden_by().is_none(), "a isn't overridden");
+    let dep_package = graph
+        .resolve_package_name("dep")
+        .packages(DependencyDirection::Forward)
+        .next()
+        .expect("dep is in the graph");
+    let overridden_by = dep_package
+        .overridden_by()
+        .expect("dep is retrieved from a patch");
+    assert_eq!(overridden_by.name(), "dep");
+}
+
+#[test]
+fn git_patch_overrides() {
+    // metadata1 has walkdir from git, at tag 2.2.9.
+    let temp_dir = tempfile::tempdir().expect("created temp dir");
+    let dir = Utf8Path::from_path(temp_dir.path()).expect("temp dir is UTF-8");
+    write(
+        dir,
+        "Cargo.toml",
+        r#"[patch.crates-io]
+walkdir = { git = "https://github.com/BurntSushi/walkdir.git", tag = "2.2.9" }
+
+[patch."https://github.com/foo/bar"]
+walkdir = { git = "https://github.com/BurntSushi/walkdir", branch = "master" }
+"#,
+    );
+
+    let mut graph = JsonFixture::metadata1().graph().clone();
+    graph
+        .load_overrides(dir.join("Cargo.toml"))
+        .expect("overrides loaded");
+    let overrides: Vec<_> = graph.workspace().overrides().collect();
+    assert!(overrides[0].is_used(), "patch at the same tag is used");
+    assert_eq!(overrides[0].packages().count(), 1);
+    assert!(
+        !overrides[1].is_used(),
+        "patch from the same repository at a different branch isn't used"
+    );
+}
+
+fn write(dir: &Utf8Path, path: &str, contents: &str) {
+    let path = dir.join(path);
+    fs::create_dir_all(path.parent().expect("path has a parent")).expect("created parent dir");
+    fs::write(&path, contents).expect("wrote file");
+}
@@ -0,0 +1,485 @@
+// Copyright (c) The cargo-guppy Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use fixtures::{
+    json::{self, JsonFixture},
+    package_id,
+};
+use guppy::{
+    graph::{
+        cargo::{BuildPlatform, CargoOptions, CargoResolverVersion, FeatureExplanation},
+        feature::{ConditionalLink, FeatureId, StandardFeatures},
+        DependencyDirection, PackageGraph, PackageLink,
+    },
+    PackageId,
+};
+use pretty_assertions::assert_eq;
+use std::collections::HashSet;
+
+/// Returns the names of the packages along this path, starting from the initial.
+fn path_names(path: &[PackageLink<'_>]) -> Vec<String> {
+    let mut names: Vec<_> = path
+        .iter()
+        .map(|link| link.from().name().to_string())
+        .collect();
+    if let Some(last) = path.last() {
+        names.push(last.to().name().to_string());
+    }
+    names
+}
+
+/// Returns the feature IDs at either end of each conditional link in these paths.
+fn feature_link_ids<'g>(
+    paths: &[Vec<ConditionalLink<'g>>],
+) -> HashSet<Vec<(FeatureId<'g>, FeatureId<'g>)>> {
+    paths
+        .iter()
+        .map(|path| {
+            path.iter()
+                .map(|link| (link.from().feature_id(), link.to().feature_id()))
+                .collect()
+        })
+        .collect()
+}
+
+fn assert_forward_path(graph: &PackageGraph, path: &[PackageLink<'_>], target: &PackageId) {
+    let first = path.first().expect("path is non-empty");
+    assert!(
+        first.from().in_workspace(),
+        "path starts from a workspace member"
+    );
+    assert_eq!(
+        path.last().unwrap().to().id(),
+        target,
+        "path ends at target"
+    );
+    for window in path.windows(2) {
+        assert_eq!(window[0].to().id(), window[1].from().id(), "links chain");
+    }
+    for link in path {
+        assert!(
+            graph
+                .directly_depends_on(link.from().id(), link.to().id())
+                .unwrap(),
+            "link is a real dependency"
+        );
+    }
+}
+
+#[test]
+fn metadata1_shortest_path() {
+    let graph = JsonFixture::metadata1().graph();
+    let dtoa = package_id(json::METADATA1_DTOA);
+
+    let path = graph
+        .query_workspace()
+        .shortest_path_to(&dtoa)
+        .expect("valid package ID")
+        .expect("dtoa is reachable");
+    assert_forward_path(graph, &path, &dtoa);
+    assert_eq!(
+        path_names(&path),
+        vec!["testcrate", "datatest", "serde_yaml", "dtoa"]
+    );
+
+    // The path to an initial is empty.
+    let testcrate = package_id(json::METADATA1_TESTCRATE);
+    let path = graph
+        .query_workspace()
+        .shortest_path_to(&testcrate)
+        .expect("valid package ID");
+    assert_eq!(
+        path.map(|path| path.len()),
+        Some(0),
+        "empty path to initial"
+    );
+
+    // The workspace isn't reachable from dtoa.
+    let path = graph
+        .query_forward(vec![&dtoa])
+        .expect("valid package ID")
+        .shortest_path_to(&testcrate)
+        .expect("valid package ID");
+    assert!(path.is_none(), "testcrate isn't reachable from dtoa");
+
+    // Reverse queries return links in the order they were traversed.
+    let path = graph
+        .query_reverse(vec![&dtoa])
+        .expect("valid package ID")
+        .shortest_path_to(&testcrate)
+        .expect("valid package ID")
+        .expect("testcrate is reachable in reverse");
+    let ids: Vec<_> = path.iter().map(|link| link.to().name()).collect();
+    assert_eq!(ids, vec!["dtoa", "serde_yaml", "datatest"]);
+
+    assert!(
+        graph
+            .query_workspace()
+            .shortest_path_to(&package_id("unknown-package"))
+            .is_err(),
+        "unknown package ID"
+    );
+}
+
+#[test]
+fn metadata1_all_paths() {
+    let graph = JsonFixture::metadata1().graph();
+    let query = graph.query_workspace();
+
+    let libc = graph
+        .packages()
+        .find(|package| package.name() == "libc")
+        .expect("libc exists");
+    let paths = query
+        .all_paths_to(libc.id(), None)
+        .expect("valid package ID");
+    for path in &paths {
+        assert_forward_path(graph, path, libc.id());
+    }
+    let names: Vec<_> = paths.iter().map(|path| path_names(path)).collect();
+    assert_eq!(
+        names,
+        vec![
+            vec!["testcrate", "datatest", "region", "libc"],
+            vec!["testcrate", "datatest", "region", "mach", "libc"],
+        ]
+    );
+
+    let linked_hash_map = graph
+        .packages()
+        .find(|package| package.name() == "linked-hash-map")
+        .expect("linked-hash-map exists");
+    let paths = query
+        .all_paths_to(linked_hash_map.id(), None)
+        .expect("valid package ID");
+    assert_eq!(paths.len(), 3, "three paths to linked-hash-map");
+    let shortest = query
+        .shortest_path_to(linked_hash_map.id())
+        .expect("valid package ID")
+        .expect("linked-hash-map is reachable");
+    assert_eq!(paths[0].len(), shortest.len(), "paths are sorted by length");
+
+    let paths = query
+        .all_paths_to(linked_hash_map.id(), Some(2))
+        .expect("valid package ID");
+    assert_eq!(paths.len(), 2, "limit is respected");
+
+    // Filtering out links into serde_yaml leaves only the path through yaml-rust.
+    let paths = query
+        .all_paths_to_with_fn(linked_hash_map.id(), None, |_, link| {
+            link.to().name() != "serde_yaml"
+        })
+        .expect("valid package ID");
+    let names: Vec<_> = paths.iter().map(|path| path_names(path)).collect();
+    assert_eq!(
+        names,
+        vec![vec![
+            "testcrate",
+            "datatest",
+            "yaml-rust",
+            "linked-hash-map"
+        ]]
+    );
+}
+
+#[test]
+fn cycle_all_paths() {
+    let graph = JsonFixture::metadata_cycle2().graph();
+    let lower_b = package_id(json::METADATA_CYCLE2_LOWER_B);
+    let paths = graph
+        .query_workspace()
+        .all_paths_to(&lower_b, None)
+        .expect("valid package ID");
+    assert!(!paths.is_empty(), "lower-b is reachable");
+    for path in &paths {
+        let mut seen: Vec<_> = path.iter().map(|link| link.from().id()).collect();
+        seen.push(&lower_b);
+        let len = seen.len();
+        seen.sort();
+        seen.dedup();
+        assert_eq!(seen.len(), len, "paths are simple");
+    }
+}
+
+#[test]
+fn metadata1_conditional_links_for() {
+    let graph = JsonFixture::metadata1().graph();
+    let feature_graph = graph.feature_graph();
+
+    for link in graph.resolve_all().links(DependencyDirection::Forward) {
+        for conditional_link in feature_graph.conditional_links_for(link) {
+            let package_link = conditional_link.package_link();
+            assert_eq!(package_link.from().id(), link.from().id());
+            assert_eq!(package_link.to().id(), link.to().id());
+        }
+    }
+
+    // datatest -> serde_yaml is activated through datatest's base feature.
+    let datatest = graph
+        .metadata(&package_id(json::METADATA1_DATATEST))
+        .expect("datatest exists");
+    let link = datatest
+        .direct_links()
+        .find(|link| link.to().name() == "serde_yaml")
+        .expect("datatest depends on serde_yaml");
+    assert!(
+        feature_graph
+            .conditional_links_for(link)
+            .any(|conditional_link| conditional_link.from().feature_id().is_base()),
+        "serde_yaml is pulled in by datatest's base feature"
+    );
+}
+
+#[test]
+fn metadata1_graph_and_set_paths() {
+    let graph = JsonFixture::metadata1().graph();
+    let testcrate = package_id(json::METADATA1_TESTCRATE);
+    let region = package_id(json::METADATA1_REGION);
+    let libc = graph
+        .packages()
+        .find(|package| package.name() == "libc")
+        .expect("libc exists")
+        .id();
+
+    let path = graph
+        .shortest_path(&testcrate, libc)
+        .expect("valid package IDs")
+        .expect("libc is reachable");
+    assert_eq!(
+        path_names(&path),
+        vec!["testcrate", "datatest", "region", "libc"]
+    );
+    let paths = graph
+        .all_paths(&region, libc, None)
+        .expect("valid package IDs");
+    let names: Vec<_> = paths.iter().map(|path| path_names(path)).collect();
+    assert_eq!(
+        names,
+        vec![vec!["region", "libc"], vec!["region", "mach", "libc"]]
+    );
+
+    // Without region in the set, libc is no longer reachable from testcrate.
+    let mach = graph
+        .packages()
+        .find(|package| package.name() == "mach")
+        .expect("mach exists")
+        .id();
+    let without_region = graph
+        .resolve_all()
+        .filter(DependencyDirection::Forward, |package| {
+            *package.id() != region
+        });
+    assert!(
+        without_region
+            .shortest_path(&testcrate, libc)
+            .expect("valid package IDs")
+            .is_none(),
+        "libc isn't reachable without region"
+    );
+    let paths = without_region
+        .all_paths(mach, libc, None)
+        .expect("valid package IDs");
+    assert_eq!(paths.len(), 1, "mach -> libc is still present");
+    assert!(
+        without_region
+            .all_paths(&region, libc, None)
+            .expect("valid package IDs")
+            .is_empty(),
+        "region isn't in the set"
+    );
+}
+
+#[test]
+fn metadata1_feature_paths() {
+    let graph = JsonFixture::metadata1().graph();
+    let feature_graph = graph.feature_graph();
+    let testcrate = package_id(json::METADATA1_TESTCRATE);
+    let dtoa = package_id(json::METADATA1_DTOA);
+
+    let path = feature_graph
+        .shortest_path(FeatureId::base(&testcrate), FeatureId::base(&dtoa))
+        .expect("valid feature IDs")
+        .expect("dtoa is reachable");
+    let packages: Vec<_> = path
+        .iter()
+        .map(|link| link.from().package().name())
+        .chain(path.last().map(|link| link.to().package().name()))
+        .collect();
+    assert_eq!(
+        packages,
+        vec!["testcrate", "datatest", "serde_yaml", "dtoa"]
+    );
+    for window in path.windows(2) {
+        assert_eq!(
+            window[0].to().package_id(),
+            window[1].from().package_id(),
+            "consecutive links end and start in the same package"
+        );
+    }
+
+    let paths = feature_graph
+        .all_paths(FeatureId::base(&testcrate), FeatureId::base(&dtoa), None)
+        .expect("valid feature IDs");
+    assert_eq!(paths[0].len(), path.len(), "paths are sorted by length");
+
+    // Limits apply to distinct paths, not to paths through the underlying feature graph.
+    let all_ids = feature_link_ids(&paths);
+    assert_eq!(all_ids.len(), paths.len(), "paths are distinct");
+    for limit in 1..=paths.len() {
+        let limited = feature_graph
+            .all_paths(
+                FeatureId::base(&testcrate),
+                FeatureId::base(&dtoa),
+                Some(limit),
+            )
+            .expect("valid feature IDs");
+        assert_eq!(limited.len(), limit, "limit {} is reached", limit);
+        let limited_ids = feature_link_ids(&limited);
+        assert_eq!(limited_ids.len(), limit, "limited paths are distinct");
+        assert!(
+            limited_ids.is_subset(&all_ids),
+            "limited paths are a subset of all paths"
+        );
+    }
+
+    // The path stays within a resolved feature set.
+    let feature_set = feature_graph
+        .query_workspace(StandardFeatures::Default)
+        .resolve();
+    let set_path = feature_set
+        .shortest_path(FeatureId::base(&testcrate), FeatureId::base(&dtoa))
+        .expect("valid feature IDs")
+        .expect("dtoa is in the default feature set");
+    for link in &set_path {
+        assert!(feature_set.contains(link.to().feature_id()).unwrap());
+    }
+    let empty = feature_graph.resolve_none();
+    assert!(
+        empty
+            .shortest_path(FeatureId::base(&testcrate), FeatureId::base(&dtoa))
+            .expect("valid feature IDs")
+            .is_none(),
+        "no paths in an empty set"
+    );
+}
+
+fn assert_explanation_chains(explanation: &FeatureExplanation<'_>) {
+    let mut current = (explanation.root(), explanation.root_build_platform());
+    for step in explanation.steps() {
+        assert_eq!(
+            step.from().feature_id(),
+            current.0.feature_id(),
+            "steps chain"
+        );
+        assert_eq!(step.from_build_platform(), current.1, "platforms chain");
+        current = (step.to(), step.to_build_platform());
+    }
+    assert_eq!(
+        current.0.feature_id(),
+        explanation.feature().feature_id(),
+        "chain ends at the feature"
+    );
+    assert_eq!(current.1, explanation.build_platform());
+}
+
+#[test]
+fn metadata1_explain_feature() {
+    let graph = JsonFixture::metadata1().graph();
+    let testcrate = package_id(json::METADATA1_TESTCRATE);
+    let dtoa = package_id(json::METADATA1_DTOA);
+
+    for resolver in [CargoResolverVersion::V1, CargoResolverVersion::V2] {
+        let mut opts = CargoOptions::new();
+        opts.set_resolver(resolver);
+        let cargo_set = graph
+            .resolve_workspace()
+            .to_feature_set(StandardFeatures::Default)
+            .into_cargo_set(&opts)
+            .expect("valid cargo set");
+
+        let explanation = cargo_set
+            .explain_feature(FeatureId::base(&dtoa))
+            .expect("valid feature ID")
+            .expect("dtoa is built");
+        assert_explanation_chains(&explanation);
+        assert_eq!(explanation.root().package_id(), &testcrate);
+        assert_eq!(explanation.build_platform(), BuildPlatform::Target);
+        let last = explanation.steps().last().expect("dtoa isn't an initial");
+        let link = last
+            .conditional_link()
+            .expect("dtoa is reached via a dependency");
+        assert_eq!(link.package_link().from().name(), "serde_yaml");
+
+        // Every built feature can be explained.
+        for (build_platform, features) in cargo_set.all_features() {
+            for feature_id in features.feature_ids(DependencyDirection::Forward) {
+                let explanation = cargo_set
+                    .explain_platform_feature(feature_id, build_platform)
+                    .expect("valid feature ID")
+                    .expect("feature is built");
+                assert_explanation_chains(&explanation);
+                assert!(
+                    cargo_set
+                        .initials()
+                        .contains(explanation.root().feature_id())
+                        .unwrap(),
+                    "root is an initial"
+                );
+            }
+        }
+
+        // Initials are explained by themselves.
+        let explanation = cargo_set
+            .explain_feature(FeatureId::base(&testcrate))
+            .expect("valid feature ID")
+            .expect("testcrate is built");
+        assert!(explanation.steps().is_empty(), "initials have no steps");
+    }
+
+    let cargo_set = graph
+        .feature_graph()
+        .resolve_none()
+        .into_cargo_set(&CargoOptions::new())
+        .expect("valid cargo set");
+    assert!(
+        cargo_set
+            .explain_feature(FeatureId::base(&dtoa))
+            .expect("valid feature ID")
+            .is_none(),
+        "dtoa isn't built"
+    );
+}
+
+#[test]
+fn proc_macro_explain_feature() {
+    let graph = JsonFixture::metadata_proc_macro1().graph();
+    let macro_id = package_id(json::METADATA_PROC_MACRO1_MACRO);
+
+    for user in [
+        json::METADATA_PROC_MACRO1_NORMAL_USER,
+        json::METADATA_PROC_MACRO1_DEV_USER,
+    ] {
+        let user = package_id(user);
+        let mut opts = CargoOptions::new();
+        opts.set_resolver(CargoResolverVersion::V2)
+            .set_include_dev(true);
+        let cargo_set = graph
+            .resolve_ids([&user])
+            .expect("valid package ID")
+            .to_feature_set(StandardFeatures::Default)
+            .into_cargo_set(&opts)
+            .expect("valid cargo set");
+
+        let explanation = cargo_set
+            .explain_feature(FeatureId::base(&macro_id))
+            .expect("valid feature ID")
+            .expect("macro is built");
+        assert_explanation_chains(&explanation);
+        assert_eq!(explanation.build_platform(), BuildPlatform::Host);
+        assert_eq!(explanation.root().package_id(), &user);
+        assert_eq!(explanation.root_build_platform(), BuildPlatform::Target);
+        let last = explanation.steps().last().expect("macro isn't an initial");
+        assert_eq!(last.from_build_platform(), BuildPlatform::Target);
+        assert_eq!(last.to_build_platform(), BuildPlatform::Host);
+    }
+}
@@ -0,0 +1,218 @@
+// Copyright (c) The cargo-guppy Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use fixtures::json::JsonFixture;
+use guppy::graph::{
+    cargo::{BuildPlatform, CargoOptions, CargoResolverVersion, CargoSet, PerPackageCargoSets},
+    feature::StandardFeatures,
+    DependencyDirection, PackageGraph,
+};
+
+#[test]
+fn per_package_guppy() {
+    check_per_package(
+        JsonFixture::metadata_guppy_c9b4f76().graph(),
+        StandardFeatures::Default,
+        false,
+    );
+}
+
+#[test]
+fn per_package_libra() {
+    check_per_package(
+        JsonFixture::metadata_libra().graph(),
+        StandardFeatures::Default,
+        false,
+    );
+}
+
+#[test]
+fn per_package_small() {
+    // These fixtures cover weak and namespaced features, build dependencies, proc macros,
+    // platform-specific dependencies and artifact dependencies.
+    for fixture in [
+        JsonFixture::metadata1(),
+        JsonFixture::metadata_builddep(),
+        JsonFixture::metadata_cycle_features(),
+        JsonFixture::metadata_targets1(),
+        JsonFixture::metadata_proc_macro1(),
+        JsonFixture::metadata_weak_namespaced_features(),
+        JsonFixture::metadata_artifact_deps(),
+    ] {
+        for features in [
+            StandardFeatures::None,
+            StandardFeatures::Default,
+            StandardFeatures::All,
+        ] {
+            for include_dev in [false, true] {
+                check_per_package(fixture.graph(), features, include_dev);
+            }
+        }
+    }
+}
+
+#[test]
+fn sharing_matrix() {
+    let graph = JsonFixture::metadata_guppy_c9b4f76().graph();
+    let per_package = make_per_package(
+        graph,
+        StandardFeatures::Default,
+        CargoOptions::new().set_resolver(CargoResolverVersion::V2),
+    );
+    let matrix = per_package.sharing_matrix();
+    assert_eq!(matrix.packages().len(), per_package.len());
+
+    for row in matrix.rows() {
+        let feature = row.feature();
+        assert!(
+            !feature.package().in_workspace(),
+            "only third-party features are present"
+        );
+        assert!(!row.is_empty(), "rows aren't empty");
+        for (package, cargo_set) in per_package.iter() {
+            let built = cargo_set
+                .platform_features(row.build_platform())
+                .contains(feature.feature_id())
+                .expect("valid feature ID");
+            assert_eq!(
+                row.contains(package.id()),
+                built,
+                "for package {} and feature {} on {}, matrix matches the CargoSet",
+                package.name(),
+                feature.feature_id(),
+                row.build_platform(),
+            );
+        }
+        let looked_up = matrix
+            .row(feature.feature_id(), row.build_platform())
+            .expect("valid feature ID")
+            .expect("row is present");
+        assert_eq!(
+            looked_up.packages().collect::<Vec<_>>().len(),
+            row.len(),
+            "looked up row matches"
+        );
+    }
+
+    // Every third-party feature built by some package is present in the matrix.
+    for (_, cargo_set) in per_package.iter() {
+        for build_platform in BuildPlatform::VALUES {
+            for feature in cargo_set
+                .platform_features(*build_platform)
+                .features(DependencyDirection::Forward)
+            {
+                let row = matrix
+                    .row(feature.feature_id(), *build_platform)
+                    .expect("valid feature ID");
+                assert_eq!(
+                    row.is_some(),
+                    !feature.package().in_workspace(),
+                    "feature {} on {} is present iff it's third-party",
+                    feature.feature_id(),
+                    build_platform
+                );
+            }
+        }
+    }
+}
+
+/// Checks that per-package Cargo sets are the same as building each package separately.
+fn check_per_package(graph: &PackageGraph, features: StandardFeatures, include_dev: bool) {
+    for resolver in [
+        CargoResolverVersion::V1,
+        CargoResolverVersion::V1Install,
+        CargoResolverVersion::V2,
+    ] {
+        let mut opts = CargoOptions::new();
+        opts.set_resolver(resolver).set_include_dev(include_dev);
+        let per_package = make_per_package(graph, features, &opts);
+        assert_eq!(
+            per_package.len(),
+            graph.workspace().member_count(),
+            "one CargoSet per workspace member"
+        );
+
+        for (package, cargo_set) in per_package.iter() {
+            let expected = graph
+                .resolve_ids([package.id()])
+                .expect("valid package ID")
+                .to_feature_set(features)
+                .into_cargo_set(&opts)
+                .expect("cargo set constructed");
+            assert_eq!(
+                cargo_set.initials(),
+                expected.initials(),
+                "for {} with {:?}, initials match",
+                package.name(),
+                resolver
+            );
+            assert_eq!(
+                cargo_set.target_features(),
+                expected.target_features(),
+                "for {} with {:?}, target features match",
+                package.name(),
+                resolver
+            );
+            assert_eq!(
+                cargo_set.host_features(),
+                expected.host_features(),
+                "for {} with {:?}, host features match",
+                package.name(),
+                resolver
+            );
+            assert_eq!(
+                cargo_set.target_direct_deps(),
+                expected.target_direct_deps(),
+                "for {} with {:?}, target direct deps match",
+                package.name(),
+                resolver
+            );
+            assert_eq!(
+                cargo_set.host_direct_deps(),
+                expected.host_direct_deps(),
+                "for {} with {:?}, host direct deps match",
+                package.name(),
+                resolver
+            );
+            // Explanations may differ from those of the separate build, but must still lead back
+            // to an initial.
+            for (build_platform, features) in cargo_set.all_features() {
+                for feature in features.features(DependencyDirection::Forward) {
+                    let explanation = cargo_set
+                        .explain_platform_feature(feature.feature_id(), build_platform)
+                        .expect("valid feature ID")
+                        .expect("feature is built on this platform");
+                    assert!(
+                        cargo_set
+                            .initials()
+                            .contains(explanation.root().feature_id())
+                            .expect("valid feature ID"),
+                        "for {} with {:?}, explanation for {} on {} starts from an initial",
+                        package.name(),
+                        resolver,
+                        feature.feature_id(),
+                        build_platform,
+                    );
+                }
+            }
+            assert_eq!(
+                per_package
+                    .get(package.id())
+                    .expect("package is present")
+                    .target_features(),
+                cargo_set.target_features(),
+                "get returns the same set"
+            );
+        }
+    }
+}
+
+fn make_per_package<'g>(
+    graph: &'g PackageGraph,
+    features: StandardFeatures,
+    opts: &CargoOptions<'_>,
+) -> PerPackageCargoSets<'g> {
+    let initials = graph.resolve_workspace().to_feature_set(features);
+    let features_only = graph.feature_graph().resolve_none();
+    CargoSet::new_per_package(&initials, &features_only, opts).expect("cargo sets constructed")
+}
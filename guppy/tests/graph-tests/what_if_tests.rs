@@ -0,0 +1,197 @@
+// Copyright (c) The cargo-guppy Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use fixtures::{
+    json::{self, JsonFixture},
+    package_id,
+};
+use guppy::{
+    graph::{
+        cargo::{CargoOptions, CargoResolverVersion},
+        feature::StandardFeatures,
+        summaries::{diff::SummaryDiffStatus, SummaryId, SummarySource},
+        NewWorkspaceMember,
+    },
+    DependencyKind,
+};
+use semver::Version;
+
+#[test]
+fn what_if_enable_feature() {
+    let graph = JsonFixture::metadata1().graph();
+    let testcrate = package_id(json::METADATA1_TESTCRATE);
+    let datatest = package_id(json::METADATA1_DATATEST);
+
+    let report = graph
+        .what_if(StandardFeatures::Default, &v2_options(), |editor| {
+            editor.enable_dependency_features(
+                &testcrate,
+                &datatest,
+                DependencyKind::Normal,
+                ["unsafe_test_runner"],
+            )
+        })
+        .expect("what-if succeeded");
+    assert!(report.is_changed());
+    assert_eq!(report.members().len(), 1);
+
+    let member = report.changed_members().next().expect("testcrate changed");
+    assert_eq!(member.name(), "testcrate");
+    assert!(!member.is_added() && !member.is_removed());
+    let diff = member.diff();
+    assert!(diff.host_packages.is_unchanged());
+    let datatest_summary_id = graph
+        .metadata(&datatest)
+        .expect("datatest is present")
+        .to_summary_id();
+    let status = diff
+        .target_packages
+        .changed
+        .get(&datatest_summary_id)
+        .expect("datatest changed");
+    match status {
+        SummaryDiffStatus::Modified {
+            added_features,
+            removed_features,
+            ..
+        } => {
+            assert_eq!(
+                added_features.iter().copied().collect::<Vec<_>>(),
+                vec!["region", "unsafe_test_runner"]
+            );
+            assert!(removed_features.is_empty());
+        }
+        other => panic!("unexpected status for datatest: {:?}", other),
+    }
+    // unsafe_test_runner pulls in region.
+    let region_summary_id = graph
+        .metadata(&package_id(json::METADATA1_REGION))
+        .expect("region is present")
+        .to_summary_id();
+    assert!(matches!(
+        diff.target_packages.changed.get(&region_summary_id),
+        Some(SummaryDiffStatus::Added { .. })
+    ));
+
+    // The original graph is unchanged.
+    let link = graph
+        .metadata(&testcrate)
+        .expect("testcrate is present")
+        .direct_links()
+        .next()
+        .expect("testcrate depends on datatest");
+    assert_eq!(
+        link.normal().features().collect::<Vec<_>>(),
+        Vec::<&str>::new()
+    );
+}
+
+#[test]
+fn what_if_default_features() {
+    let graph = JsonFixture::metadata1().graph();
+    let testcrate = package_id(json::METADATA1_TESTCRATE);
+    let datatest = package_id(json::METADATA1_DATATEST);
+
+    // With dev-dependencies included, datatest's default features are still enabled through the
+    // dev-dependency.
+    let mut opts = v2_options();
+    opts.set_include_dev(true);
+    let report = graph
+        .what_if(StandardFeatures::Default, &opts, |editor| {
+            editor.set_default_features(&testcrate, &datatest, DependencyKind::Normal, false)
+        })
+        .expect("what-if succeeded");
+    assert!(!report.is_changed(), "no changes with dev-dependencies");
+
+    let report = graph
+        .what_if(StandardFeatures::Default, &v2_options(), |editor| {
+            editor.set_default_features(&testcrate, &datatest, DependencyKind::Normal, false)
+        })
+        .expect("what-if succeeded");
+    let member = report.changed_members().next().expect("testcrate changed");
+    let datatest_summary_id = graph
+        .metadata(&datatest)
+        .expect("datatest is present")
+        .to_summary_id();
+    let new_summary = member.new_summary().expect("testcrate is still a member");
+    let datatest_info = &new_summary.target_packages[&datatest_summary_id];
+    assert!(!datatest_info.features.contains("default"));
+    let old_summary = member.old_summary().expect("testcrate was a member");
+    assert!(old_summary.target_packages[&datatest_summary_id]
+        .features
+        .contains("default"));
+
+    // The edited graph is available from the report.
+    let new_link = report
+        .new_graph()
+        .metadata(&testcrate)
+        .expect("testcrate is present")
+        .direct_links()
+        .next()
+        .expect("testcrate depends on datatest");
+    assert!(new_link.normal().default_features().is_never());
+}
+
+#[test]
+fn what_if_workspace_members() {
+    let graph = JsonFixture::metadata1().graph();
+    let testcrate = package_id(json::METADATA1_TESTCRATE);
+
+    let report = graph
+        .what_if(StandardFeatures::Default, &CargoOptions::new(), |editor| {
+            editor.add_workspace_member(&NewWorkspaceMember::new(
+                "new-crate",
+                Version::new(0, 1, 0),
+                "new-crate",
+            ))?;
+            editor.remove_workspace_member(&testcrate)
+        })
+        .expect("what-if succeeded");
+
+    let members: Vec<_> = report
+        .members()
+        .map(|member| {
+            (
+                member.name(),
+                member.workspace_path().as_str(),
+                member.is_added(),
+                member.is_removed(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        members,
+        vec![
+            ("testcrate", "", false, true),
+            ("new-crate", "new-crate", true, false)
+        ]
+    );
+    for member in report.members() {
+        assert!(member.is_changed());
+    }
+
+    let added = report.members().nth(1).expect("new-crate is present");
+    assert!(added.old_summary().is_none());
+    let new_summary = added.new_summary().expect("new-crate has a summary");
+    assert_eq!(
+        new_summary.target_packages.keys().collect::<Vec<_>>(),
+        vec![&SummaryId::new(
+            "new-crate",
+            Version::new(0, 1, 0),
+            SummarySource::workspace("new-crate"),
+        )]
+    );
+
+    // Errors are passed through.
+    graph
+        .what_if(StandardFeatures::Default, &CargoOptions::new(), |editor| {
+            editor.remove_workspace_member(&package_id(json::METADATA1_DATATEST))
+        })
+        .expect_err("datatest isn't a workspace member");
+}
+
+fn v2_options() -> CargoOptions<'static> {
+    let mut opts = CargoOptions::new();
+    opts.set_resolver(CargoResolverVersion::V2);
+    opts
+}
@@ -107,6 +107,8 @@ impl Fixture {
         }
         let metadata_opts = CargoMetadataOptions {
             manifest_path: Some(workspace_dir.join("Cargo.toml")),
+            graph_cache: None,
+            offline_metadata: false,
         };
         let graph = metadata_opts
             .make_command()
@@ -0,0 +1,352 @@
+// Copyright (c) The cargo-guppy Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use camino::{Utf8Path, Utf8PathBuf};
+use clap::{ArgEnum, Parser};
+use color_eyre::eyre::{bail, eyre, Result, WrapErr};
+use determinator::{
+    rules::DeterminatorRules, AffectedPackageTargets, Determinator, DeterminatorSet, Utf8Paths0,
+};
+use guppy::graph::{
+    feature::StandardFeatures, BuildTargetId, DependencyDirection, PackageGraph, PackageSet,
+};
+use guppy_cmdlib::{git, CargoMetadataOptions};
+use serde::Serialize;
+use std::{
+    collections::BTreeMap,
+    convert::TryFrom,
+    fs,
+    io::{self, Read},
+};
+
+/// Figure out which workspace packages changed between two revisions.
+///
+/// For more about cargo-determinator, see <https://docs.rs/cargo-determinator>.
+#[derive(Debug, Parser)]
+#[clap(author, version, about)]
+pub struct Args {
+    /// The old `cargo metadata` JSON file
+    ///
+    /// Changed paths, relative to the workspace root, are read from standard input separated by
+    /// null characters.
+    #[clap(
+        name = "OLD",
+        requires = "NEW",
+        required_unless_present = "base",
+        conflicts_with = "base"
+    )]
+    old: Option<Utf8PathBuf>,
+
+    /// The new `cargo metadata` JSON file
+    #[clap(name = "NEW")]
+    new: Option<Utf8PathBuf>,
+
+    /// Git revision to compare against, checked out into a temporary worktree
+    #[clap(long)]
+    base: Option<String>,
+
+    /// Git revision to compare (default: the current checkout, including uncommitted changes and
+    /// untracked files)
+    #[clap(long, requires = "base")]
+    head: Option<String>,
+
+    /// Path to a determinator rules file (default: the default rules)
+    #[clap(long)]
+    rules: Option<Utf8PathBuf>,
+
+    /// Workspace packages to only use for feature unification, such as a workspace-hack
+    #[clap(long, multiple_occurrences = true)]
+    features_only: Vec<String>,
+
+    /// A JSON object with string values, recording the environment of the old revision
+    ///
+    /// Keys that differ from `--new-env` are matched against environment rules.
+    #[clap(long, requires = "new-env")]
+    old_env: Option<Utf8PathBuf>,
+
+    /// A JSON object with string values, recording the environment of the new revision
+    #[clap(long, requires = "old-env")]
+    new_env: Option<Utf8PathBuf>,
+
+    /// Trace `mod` declarations and includes to map changed files to the targets that use them
+    ///
+    /// Source files are read from the new workspace on disk, so this can't be combined with
+    /// `--head`.
+    #[clap(long, conflicts_with = "head")]
+    trace_imports: bool,
+
+    /// Output format
+    #[clap(long, arg_enum, default_value = "text")]
+    output_format: OutputFormat,
+
+    #[clap(flatten)]
+    metadata_opts: CargoMetadataOptions,
+}
+
+#[derive(ArgEnum, Copy, Clone, Debug)]
+enum OutputFormat {
+    /// Human-readable lists of packages
+    Text,
+    /// A JSON object with the changed and affected packages
+    Json,
+    /// The affected packages as `-p <name>` arguments, one argument per line
+    PackageArgs,
+    /// An explanation of why each affected package is affected
+    Explain,
+    /// A `cargo nextest` filter expression matching the affected test binaries
+    NextestFilter,
+}
+
+impl Args {
+    /// Executes the command.
+    pub fn exec(self) -> Result<()> {
+        let (old_graph, new_graph, changed_paths) = match (&self.old, &self.new, &self.base) {
+            (Some(old), Some(new), _) => {
+                let mut buf = Vec::new();
+                io::stdin()
+                    .read_to_end(&mut buf)
+                    .wrap_err("reading changed paths from standard input failed")?;
+                (read_json_graph(old)?, read_json_graph(new)?, buf)
+            }
+            (_, _, Some(base)) => {
+                let old_graph = self.metadata_opts.build_graph_at(base)?;
+                let new_graph = match &self.head {
+                    Some(head) => self.metadata_opts.build_graph_at(head)?,
+                    None => self.metadata_opts.build_graph()?,
+                };
+                let changed_paths = self.git_changed_paths(base)?;
+                (old_graph, new_graph, changed_paths)
+            }
+            _ => bail!("either OLD and NEW or --base must be specified"),
+        };
+        let changed_paths = Utf8Paths0::from_bytes(changed_paths)
+            .map_err(|(_, err)| eyre!("changed paths are not valid UTF-8: {}", err))?;
+
+        let rules = match &self.rules {
+            Some(path) => {
+                let contents = fs::read_to_string(path)
+                    .wrap_err_with(|| format!("reading rules {} failed", path))?;
+                DeterminatorRules::parse(&contents)
+                    .wrap_err_with(|| format!("parsing rules {} failed", path))?
+            }
+            None => DeterminatorRules::default(),
+        };
+
+        let mut determinator = Determinator::new(&old_graph, &new_graph);
+        determinator.add_changed_paths(&changed_paths);
+        determinator.set_rules(&rules)?;
+        determinator.set_trace_imports(self.trace_imports);
+        if let (Some(old_env), Some(new_env)) = (&self.old_env, &self.new_env) {
+            determinator.set_env_snapshots(read_env(old_env)?, read_env(new_env)?);
+        }
+        if !self.features_only.is_empty() {
+            determinator.set_features_only(
+                self.features_only.iter().map(|name| name.as_str()),
+                StandardFeatures::All,
+            )?;
+        }
+        let determinator_set = determinator.compute();
+
+        match self.output_format {
+            OutputFormat::Text => print_text(&determinator_set),
+            OutputFormat::Json => {
+                let json = DeterminatorJson::new(&determinator_set);
+                println!("{}", serde_json::to_string_pretty(&json)?);
+            }
+            OutputFormat::PackageArgs => {
+                for name in sorted_names(&determinator_set.affected_set) {
+                    println!("-p");
+                    println!("{}", name);
+                }
+            }
+            OutputFormat::Explain => print_explanations(&determinator_set),
+            OutputFormat::NextestFilter => println!("{}", nextest_filter(&determinator_set)),
+        }
+        Ok(())
+    }
+
+    /// Returns the null-separated list of paths changed since `base`, relative to the workspace
+    /// root.
+    ///
+    /// Without `--head`, untracked files that aren't ignored count as changed too.
+    fn git_changed_paths(&self, base: &str) -> Result<Vec<u8>> {
+        let abs_manifest_path = Utf8PathBuf::try_from(self.metadata_opts.abs_manifest_path()?)?;
+        let workspace_dir = abs_manifest_path
+            .parent()
+            .ok_or_else(|| eyre!("manifest path {} has no parent", abs_manifest_path))?;
+        let mut args = vec!["diff", "-z", "--name-only", "--relative", base];
+        args.extend(self.head.as_deref());
+        let mut changed_paths = git::git_bytes(workspace_dir, &args)
+            .wrap_err_with(|| format!("listing paths changed since {} failed", base))?;
+        if self.head.is_none() {
+            let untracked_paths = git::git_bytes(
+                workspace_dir,
+                &["ls-files", "-z", "--others", "--exclude-standard"],
+            )
+            .wrap_err("listing untracked paths failed")?;
+            changed_paths.extend(untracked_paths);
+        }
+        Ok(changed_paths)
+    }
+}
+
+fn print_text(determinator_set: &DeterminatorSet<'_>) {
+    let sets = [
+        ("path changed", &determinator_set.path_changed_set),
+        ("env changed", &determinator_set.env_changed_set),
+        ("summary changed", &determinator_set.summary_changed_set),
+        ("affected", &determinator_set.affected_set),
+    ];
+    for (heading, set) in sets {
+        println!("{} ({}):", heading, set.len());
+        for name in sorted_names(set) {
+            println!("    {}", name);
+        }
+    }
+}
+
+fn print_explanations(determinator_set: &DeterminatorSet<'_>) {
+    let mut packages: Vec<_> = determinator_set
+        .affected_set
+        .packages(DependencyDirection::Forward)
+        .collect();
+    packages.sort_unstable_by_key(|package| package.name());
+    for (idx, package) in packages.into_iter().enumerate() {
+        if idx > 0 {
+            println!();
+        }
+        let explanation = determinator_set
+            .explain(package.id())
+            .expect("package is in the affected set");
+        print!("{}", explanation);
+    }
+}
+
+/// Returns a nextest filter expression that matches the tests in affected targets.
+fn nextest_filter(determinator_set: &DeterminatorSet<'_>) -> String {
+    let mut packages: Vec<_> = determinator_set.affected_targets.iter().collect();
+    packages.sort_unstable_by_key(|(package, _)| package.name());
+    let exprs: Vec<_> = packages
+        .into_iter()
+        .map(|(package, targets)| match targets {
+            AffectedPackageTargets::All => format!("package(={})", package.name()),
+            AffectedPackageTargets::Only(target_ids) => {
+                let target_exprs: Vec<_> = target_ids
+                    .iter()
+                    .filter_map(|target_id| {
+                        let (kind, name) = target_kind_and_name(*target_id)?;
+                        Some(format!("(kind({}) & binary(={}))", kind, name))
+                    })
+                    .collect();
+                format!(
+                    "(package(={}) & ({}))",
+                    package.name(),
+                    target_exprs.join(" | ")
+                )
+            }
+        })
+        .collect();
+    if exprs.is_empty() {
+        "none()".to_owned()
+    } else {
+        exprs.join(" | ")
+    }
+}
+
+/// Returns the Cargo target kind and the name for a build target.
+///
+/// Returns `None` for the library and build script, which are never in an `Only` set.
+fn target_kind_and_name(target_id: BuildTargetId<'_>) -> Option<(&'static str, &str)> {
+    match target_id {
+        BuildTargetId::Binary(name) => Some(("bin", name)),
+        BuildTargetId::Example(name) => Some(("example", name)),
+        BuildTargetId::Test(name) => Some(("test", name)),
+        BuildTargetId::Benchmark(name) => Some(("bench", name)),
+        _ => None,
+    }
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "kebab-case")]
+struct DeterminatorJson<'g> {
+    path_changed: Vec<&'g str>,
+    env_changed: Vec<&'g str>,
+    summary_changed: Vec<&'g str>,
+    affected: Vec<&'g str>,
+    affected_targets: BTreeMap<&'g str, TargetsJson<'g>>,
+}
+
+impl<'g> DeterminatorJson<'g> {
+    fn new(determinator_set: &DeterminatorSet<'g>) -> Self {
+        let affected_targets = determinator_set
+            .affected_targets
+            .iter()
+            .map(|(package, targets)| {
+                let targets = match targets {
+                    AffectedPackageTargets::All => TargetsJson::All(AllJson::All),
+                    AffectedPackageTargets::Only(target_ids) => TargetsJson::Only(
+                        target_ids
+                            .iter()
+                            .filter_map(|target_id| {
+                                let (kind, name) = target_kind_and_name(*target_id)?;
+                                Some(TargetJson { kind, name })
+                            })
+                            .collect(),
+                    ),
+                };
+                (package.name(), targets)
+            })
+            .collect();
+        Self {
+            path_changed: sorted_names(&determinator_set.path_changed_set),
+            env_changed: sorted_names(&determinator_set.env_changed_set),
+            summary_changed: sorted_names(&determinator_set.summary_changed_set),
+            affected: sorted_names(&determinator_set.affected_set),
+            affected_targets,
+        }
+    }
+}
+
+/// Either `"all"` or a list of targets.
+#[derive(Serialize)]
+#[serde(untagged)]
+enum TargetsJson<'g> {
+    All(AllJson),
+    Only(Vec<TargetJson<'g>>),
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "kebab-case")]
+enum AllJson {
+    All,
+}
+
+#[derive(Serialize)]
+struct TargetJson<'g> {
+    kind: &'static str,
+    name: &'g str,
+}
+
+/// Returns the names of the packages in this set, sorted.
+///
+/// The determinator only returns workspace packages, so names are unique.
+fn sorted_names<'g>(set: &PackageSet<'g>) -> Vec<&'g str> {
+    let mut names: Vec<_> = set
+        .packages(DependencyDirection::Forward)
+        .map(|package| package.name())
+        .collect();
+    names.sort_unstable();
+    names
+}
+
+fn read_env(path: &Utf8Path) -> Result<BTreeMap<String, String>> {
+    let json = fs::read_to_string(path)
+        .wrap_err_with(|| format!("reading environment {} failed", path))?;
+    serde_json::from_str(&json).wrap_err_with(|| format!("parsing environment {} failed", path))
+}
+
+fn read_json_graph(path: &Utf8Path) -> Result<PackageGraph> {
+    let json =
+        fs::read_to_string(path).wrap_err_with(|| format!("reading metadata {} failed", path))?;
+    PackageGraph::from_json(&json).wrap_err_with(|| format!("parsing metadata {} failed", path))
+}
@@ -0,0 +1,106 @@
+// Copyright (c) The cargo-guppy Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! `cargo determinator` figures out which packages in a workspace changed between two revisions,
+//! using the [`determinator`](https://docs.rs/determinator) library.
+//!
+//! Use it in CI to only build and test the packages affected by a change.
+//!
+//! # Usage
+//!
+//! Compare the current checkout, including uncommitted changes and untracked files, against a
+//! revision:
+//!
+//! ```sh
+//! cargo determinator --base $(git merge-base origin/main HEAD)
+//! ```
+//!
+//! Compare two revisions:
+//!
+//! ```sh
+//! cargo determinator --base <old rev> --head <new rev>
+//! ```
+//!
+//! Each revision is checked out into a temporary Git worktree to compute its package graph, and
+//! the list of changed files is obtained through `git diff`. Without `--head`, untracked files
+//! that aren't ignored are treated as changed too. With `--head`, untracked files are ignored.
+//!
+//! For other source control systems, pass in `cargo metadata` output for the old and new
+//! revisions, and a null-separated list of changed paths on standard input. Paths are relative to
+//! the workspace root.
+//!
+//! ```sh
+//! hg status --print0 -mard --no-status --rev <old rev> | cargo determinator old.json new.json
+//! ```
+//!
+//! # Rules
+//!
+//! Custom determinator rules can be passed in with `--rules <path>`. For the format, see the
+//! [`determinator::rules` module](https://docs.rs/determinator/latest/determinator/rules). If
+//! `--rules` is not specified, the default rules are used.
+//!
+//! Workspace-hack packages, such as those managed by [`cargo hakari`](https://docs.rs/cargo-hakari),
+//! should be passed in with `--features-only <name>`.
+//!
+//! # Environment changes
+//!
+//! Changes to the environment, such as the Rust version or `RUSTFLAGS`, can be passed in as JSON
+//! objects with string values through `--old-env` and `--new-env`:
+//!
+//! ```json
+//! { "rustc-version": "rustc 1.61.0", "RUSTFLAGS": "-C target-cpu=native" }
+//! ```
+//!
+//! Keys that changed are matched against the `[[env-rule]]` entries in the rules file. By
+//! default, all packages are marked changed if keys such as `rustc-version`, `target` or
+//! `RUSTFLAGS` change.
+//!
+//! # Tracing imports
+//!
+//! By default, a changed file marks the nearest package containing it changed. With
+//! `--trace-imports`, `mod` declarations and `include!`, `include_str!` and `include_bytes!`
+//! macros are followed from each build target's entry point instead. Changed files are then
+//! matched to the packages and targets that include them, even from outside the package, and Rust
+//! files in library and binary source directories not included by any target are ignored. For
+//! more, see
+//! [`Determinator::set_trace_imports`](https://docs.rs/determinator/latest/determinator/struct.Determinator.html#method.set_trace_imports).
+//!
+//! Source files are read from the new workspace, so it must be checked out on disk.
+//!
+//! # Output formats
+//!
+//! The `--output-format` option controls what is printed:
+//! * `text` (the default): the packages changed because a file changed, the packages changed
+//!   because the environment changed, the packages changed because a simulated Cargo build
+//!   changed, and all affected packages.
+//! * `json`: the same sets, as a JSON object with the keys `"path-changed"`, `"env-changed"`,
+//!   `"summary-changed"` and `"affected"`. The `"affected-targets"` key maps each affected package
+//!   to either `"all"`, or a list of the only build targets affected in it.
+//! * `package-args`: the affected packages as arguments for `cargo` or `cargo nextest`, one
+//!   argument per line.
+//! * `explain`: for each affected package, the chain of changed paths, rules and dependencies
+//!   that caused it to be affected.
+//! * `nextest-filter`: a [`cargo nextest` filter
+//!   expression](https://nexte.st/book/filter-expressions) that matches the tests in affected
+//!   build targets. If the only files changed in a package belong to specific binaries, examples,
+//!   tests or benchmarks, only those are matched.
+//!
+//! For example, to test just the affected packages:
+//!
+//! ```sh
+//! cargo determinator --base $(git merge-base origin/main HEAD) --output-format package-args \
+//!     | xargs --no-run-if-empty cargo nextest run
+//! ```
+//!
+//! Or, to narrow the run down further to affected build targets:
+//!
+//! ```sh
+//! cargo nextest run -E "$(cargo determinator --base $(git merge-base origin/main HEAD) \
+//!     --output-format nextest-filter)"
+//! ```
+
+mod command;
+
+// Not part of the stable API.
+#[doc(hidden)]
+pub use command::Args;
@@ -0,0 +1,100 @@
+// Copyright (c) The cargo-guppy Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Tests that run `cargo determinator` on fixture metadata.
+
+use fixtures::json::JsonFixture;
+
+/// Changed paths, in the format read from standard input.
+static CHANGED_PATHS: &[u8] = b"guppy/tests/graph-tests/foo.rs\0guppy-cmdlib/src/lib.rs\0README.md";
+
+fn run(output_format: &str) -> String {
+    let old = JsonFixture::metadata_guppy_869476c();
+    let new = JsonFixture::metadata_guppy_c9b4f76();
+    duct::cmd!(
+        env!("CARGO_BIN_EXE_cargo-determinator"),
+        old.abs_path(),
+        new.abs_path(),
+        "--output-format",
+        output_format,
+    )
+    .stdin_bytes(CHANGED_PATHS)
+    .read()
+    .expect("cargo determinator succeeded")
+}
+
+#[test]
+fn output_text() {
+    assert_eq!(
+        run("text"),
+        "path changed (2):
+    guppy
+    guppy-cmdlib
+env changed (0):
+summary changed (0):
+affected (7):
+    cargo-compare
+    cargo-guppy
+    fixture-manager
+    fixtures
+    guppy
+    guppy-benchmarks
+    guppy-cmdlib"
+    );
+}
+
+#[test]
+fn output_json() {
+    // README.md is ignored by the default rules, and the only file changed in guppy belongs to an
+    // integration test.
+    assert_eq!(
+        run("json"),
+        r#"{
+  "path-changed": [
+    "guppy",
+    "guppy-cmdlib"
+  ],
+  "env-changed": [],
+  "summary-changed": [],
+  "affected": [
+    "cargo-compare",
+    "cargo-guppy",
+    "fixture-manager",
+    "fixtures",
+    "guppy",
+    "guppy-benchmarks",
+    "guppy-cmdlib"
+  ],
+  "affected-targets": {
+    "cargo-compare": "all",
+    "cargo-guppy": "all",
+    "fixture-manager": "all",
+    "guppy": [
+      {
+        "kind": "test",
+        "name": "graph-tests"
+      }
+    ],
+    "guppy-cmdlib": "all"
+  }
+}"#
+    );
+}
+
+#[test]
+fn output_package_args() {
+    let args: Vec<_> = run("package-args").lines().map(str::to_owned).collect();
+    let expected: Vec<_> = [
+        "cargo-compare",
+        "cargo-guppy",
+        "fixture-manager",
+        "fixtures",
+        "guppy",
+        "guppy-benchmarks",
+        "guppy-cmdlib",
+    ]
+    .into_iter()
+    .flat_map(|name| ["-p".to_owned(), name.to_owned()])
+    .collect();
+    assert_eq!(args, expected);
+}
@@ -19,7 +19,7 @@ use hakari::{
     summaries::{HakariConfig, DEFAULT_CONFIG_PATH, FALLBACK_CONFIG_PATH},
     HakariBuilder, HakariCargoToml, HakariOutputOptions, TomlOutError,
 };
-use log::{error, info};
+use log::{error, info, warn};
 use owo_colors::OwoColorize;
 use std::convert::TryFrom;
 
@@ -58,7 +58,8 @@ impl Args {
     ///
     /// Returns the exit status, or an error on failure.
     pub fn exec(self) -> Result<i32> {
-        self.command.exec(self.global.output)
+        self.command
+            .exec(self.global.output, self.global.graph_cache.as_deref())
     }
 }
 
@@ -66,6 +67,28 @@ impl Args {
 struct GlobalOpts {
     #[clap(flatten)]
     output: OutputOpts,
+
+    /// Path to a cache of the package graph, reused while Cargo.lock and Cargo.toml files are
+    /// unchanged
+    #[clap(long, global = true)]
+    graph_cache: Option<Utf8PathBuf>,
+}
+
+/// Builds the package graph, going through the graph cache if one was specified.
+fn build_package_graph(graph_cache: Option<&Utf8Path>) -> Result<PackageGraph> {
+    let build = || -> Result<PackageGraph> { Ok(MetadataCommand::new().build_graph()?) };
+    match graph_cache {
+        Some(cache_path) => {
+            // cargo metadata looks for the workspace from the current directory.
+            let current_dir = std::env::current_dir()
+                .wrap_err("failed to get current directory")?
+                .canonicalize()
+                .wrap_err("failed to canonicalize current directory")?;
+            let context = format!("current-dir={}", current_dir.display());
+            PackageGraph::load_or_build_cached(cache_path, &context, build, |err| warn!("{}", err))
+        }
+        None => build(),
+    }
 }
 
 /// Manage workspace-hack crates.
@@ -102,12 +125,10 @@ enum Command {
 }
 
 impl Command {
-    fn exec(self, output: OutputOpts) -> Result<i32> {
+    fn exec(self, output: OutputOpts, graph_cache: Option<&Utf8Path>) -> Result<i32> {
         let output = output.init();
-        let metadata_command = MetadataCommand::new();
-        let package_graph = metadata_command
-            .build_graph()
-            .context("building package graph failed")?;
+        let package_graph =
+            build_package_graph(graph_cache).context("building package graph failed")?;
 
         match self {
             Command::Initialize {
@@ -2,18 +2,23 @@
 // SPDX-License-Identifier: MIT OR Apache-2.0
 
 use crate::{
+    env::{env_changes, EnvChange},
     errors::RulesError,
+    explain::{BuildSummaryChange, ChangeReason, Explanation},
+    imports::ImportMap,
     rules::{
         DeterminatorPostRule, DeterminatorRules, MarkChangedImpl, PathMatch, PathRuleImpl,
-        RulesImpl,
+        RuleIndex, RulesImpl,
     },
+    targets::{in_source_dir, match_targets, AffectedPackageTargets, AffectedTargets},
 };
 use camino::Utf8Path;
 use globset::Candidate;
 use guppy::{
     graph::{
-        cargo::{CargoOptions, CargoSet},
+        cargo::{BuildPlatform, CargoOptions, CargoSet},
         feature::{FeatureFilter, FeatureSet, StandardFeatures},
+        summaries::diff::SummaryDiffStatus,
         DependencyDirection, PackageGraph, PackageMetadata, PackageSet, Workspace,
     },
     platform::PlatformSpec,
@@ -21,7 +26,7 @@ use guppy::{
 };
 use petgraph::{graphmap::GraphMap, Directed};
 use rayon::prelude::*;
-use std::collections::{hash_map::Entry, HashMap, HashSet};
+use std::collections::{hash_map::Entry, BTreeMap, HashMap, HashSet};
 
 /// Determine target dependencies from changed files and packages in a workspace.
 ///
@@ -41,6 +46,8 @@ pub struct Determinator<'g, 'a> {
     old_features_only: Option<FeatureSet<'a>>,
     new_features_only: Option<FeatureSet<'g>>,
     changed_paths: Vec<&'a Utf8Path>,
+    imports: Option<ImportMap<'g>>,
+    env_changes: Vec<EnvChange>,
 }
 
 impl<'g, 'a> Determinator<'g, 'a> {
@@ -55,6 +62,8 @@ impl<'g, 'a> Determinator<'g, 'a> {
             old_features_only: None,
             new_features_only: None,
             changed_paths: vec![],
+            imports: None,
+            env_changes: vec![],
         }
     }
 
@@ -111,13 +120,17 @@ impl<'g, 'a> Determinator<'g, 'a> {
     pub fn match_path(
         &self,
         path: impl AsRef<Utf8Path>,
-        match_cb: impl FnMut(&'g PackageId),
+        mut match_cb: impl FnMut(&'g PackageId),
     ) -> PathMatch {
         process_path(
             path.as_ref(),
             &self.new.workspace(),
             &self.rules.path_rules,
-            match_cb,
+            self.imports.as_ref(),
+            |marked| match marked {
+                PathMarked::Package(id, _) | PathMarked::Included(id, _) => match_cb(id),
+                PathMarked::All(_) => {}
+            },
         )
     }
 
@@ -130,6 +143,73 @@ impl<'g, 'a> Determinator<'g, 'a> {
         Ok(self)
     }
 
+    /// Enables or disables tracing imports in the new workspace.
+    ///
+    /// By default, a changed file marks the nearest package containing it changed. With import
+    /// tracing enabled, the determinator starts from the entry point of each build target in the
+    /// new workspace, and follows `mod` declarations, `#[path]` attributes, and `include!`,
+    /// `include_str!` and `include_bytes!` macros to find the files that each target is made of.
+    /// Then, for each changed file that doesn't match a rule:
+    /// * If any build targets include the file, the packages containing those targets are marked
+    ///   changed. This includes packages that include files from outside of them, for example with
+    ///   `include_str!("../../fixtures/data.json")`.
+    /// * Otherwise, if the file is a Rust source file within the directory of a package's library
+    ///   or binary entry point (other than the package root), such as `src/`, nothing is marked
+    ///   changed, since the file isn't part of any build target.
+    /// * Otherwise, the nearest package is marked changed as usual, since files such as test
+    ///   fixtures, including Rust files like `tests/ui/*.rs` for `trybuild`, may be read at
+    ///   runtime.
+    ///
+    /// Source files are read from the new workspace's root directory, so it must be checked out
+    /// on disk. Paths to includes must be string literals, optionally in the form
+    /// `concat!(env!("CARGO_MANIFEST_DIR"), "...")`. Packages where a source file couldn't be
+    /// read or an import couldn't be resolved fall back to being marked changed as usual.
+    ///
+    /// Modules declared through macros, other than in the invocation itself, can't be traced.
+    pub fn set_trace_imports(&mut self, trace_imports: bool) -> &mut Self {
+        self.imports = if trace_imports {
+            Some(ImportMap::new(self.new))
+        } else {
+            None
+        };
+        self
+    }
+
+    /// Sets snapshots of the environment for the old and new revisions.
+    ///
+    /// The determinator assumes that the environment stays the same between runs, other than
+    /// through changed files. Environment snapshots are a way to represent other changes to the
+    /// environment: they are key-value maps that may contain environment variables, the version of
+    /// the Rust toolchain, the target platform, or anything else that can influence a build or test
+    /// run.
+    ///
+    /// Keys that are different between the two snapshots are matched against
+    /// [environment rules](crate::rules::EnvRule), and the packages marked changed by them are
+    /// returned in [`DeterminatorSet::env_changed_set`]. Changed keys that don't match any rules
+    /// are ignored. The [default rules](crate::rules::DeterminatorRules::DEFAULT_RULES_TOML) mark
+    /// all packages changed for keys such as `rustc-version`, `target` and `RUSTFLAGS`.
+    pub fn set_env_snapshots<K, V>(
+        &mut self,
+        old: impl IntoIterator<Item = (K, V)>,
+        new: impl IntoIterator<Item = (K, V)>,
+    ) -> &mut Self
+    where
+        K: Into<String>,
+        V: Into<String>,
+    {
+        fn collect<K: Into<String>, V: Into<String>>(
+            snapshot: impl IntoIterator<Item = (K, V)>,
+        ) -> BTreeMap<String, String> {
+            snapshot
+                .into_iter()
+                .map(|(key, value)| (key.into(), value.into()))
+                .collect()
+        }
+
+        self.env_changes = env_changes(collect(old), collect(new));
+        self
+    }
+
     /// Configures Cargo options.
     ///
     /// These options are used to determine if the build for a particular package has changed.
@@ -204,50 +284,135 @@ impl<'g, 'a> Determinator<'g, 'a> {
     pub fn compute(&self) -> DeterminatorSet<'g> {
         let mut build_state = BuildState::new(self);
 
-        // 1-2. Process every changed path.
+        // 1. Match changes to the environment against environment rules.
+        let env_changes = self.match_env_changes();
+        build_state = match build_state.process_env_changes(&env_changes) {
+            Ok(build_state) => build_state,
+            Err(all_reason) => {
+                // The build state was discarded, which means that the entire workspace is changed
+                // and affected.
+                return self.all_changed(
+                    self.new.resolve_none(),
+                    self.new.resolve_workspace(),
+                    env_changes,
+                    all_reason,
+                );
+            }
+        };
+        let env_changed_set = self
+            .new
+            .resolve_ids(build_state.env_changed_ids.iter().copied())
+            .expect("package IDs are all valid");
+
+        // 2-3. Process every changed path.
         for path in &self.changed_paths {
             build_state = match build_state.process_path(path) {
-                Some(build_state) => build_state,
-                None => {
+                Ok(build_state) => build_state,
+                Err(all_reason) => {
                     // The build state was discarded, which means that the entire workspace is
                     // changed and affected.
-                    let path_changed_set = self.new.resolve_workspace();
-                    let affected_set = path_changed_set.clone();
-                    return DeterminatorSet {
-                        path_changed_set,
-                        // This is an empty set.
-                        summary_changed_set: self.new.resolve_none(),
-                        affected_set,
-                    };
+                    return self.all_changed(
+                        self.new.resolve_workspace(),
+                        env_changed_set,
+                        env_changes,
+                        all_reason,
+                    );
                 }
             }
         }
 
-        // 3. Construct the path changed set from the given IDs.
+        // 4. Construct the path changed set from the given IDs.
         let path_changed_set = self
             .new
             .resolve_ids(build_state.path_changed_ids.iter().copied())
             .expect("package IDs are all valid");
 
-        // 4. Use build summaries as another source of changes.
+        // 5. Use build summaries as another source of changes.
         build_state.process_build_summaries();
         let summary_changed_set = self
             .new
             .resolve_ids(build_state.summary_changed_ids.iter().copied())
             .expect("package IDs are all valid");
 
-        // 5. The affected set is the transitive closure of the graph constructed by looking at both
+        // 6. The affected set is the transitive closure of the graph constructed by looking at both
         // the build cache and Cargo rules.
-        let affected_set = build_state.reverse_index.affected_closure(
+        let initial: Vec<_> = build_state
+            .summary_changed_ids
+            .iter()
+            .map(|id| (*id, FollowCargoBuild::NotAllowed))
+            .chain(
+                build_state
+                    .path_changed_ids
+                    .iter()
+                    .chain(&build_state.env_changed_ids)
+                    .map(|id| (*id, FollowCargoBuild::Allowed)),
+            )
+            .collect();
+        let (affected_set, all_reason) = match build_state.reverse_index.affected_closure(
             self.new,
-            &build_state.path_changed_ids,
-            &build_state.summary_changed_ids,
-        );
+            initial,
+            Some(&mut build_state.reasons),
+        ) {
+            Ok(closure) => (
+                self.new
+                    .resolve_ids(closure.finished.iter().copied())
+                    .expect("package IDs are all valid"),
+                None,
+            ),
+            Err(all_reason) => (self.new.resolve_workspace(), Some(all_reason)),
+        };
+
+        // 7. Figure out which targets within the affected set are affected.
+        let affected_targets = build_state.affected_targets(&affected_set, all_reason.is_some());
 
         DeterminatorSet {
             path_changed_set,
+            env_changed_set,
             summary_changed_set,
             affected_set,
+            affected_targets,
+            env_changes,
+            reasons: build_state.reasons,
+            all_reason,
+        }
+    }
+
+    // ---
+    // Helper methods
+    // ---
+
+    fn match_env_changes(&self) -> Vec<EnvChange> {
+        let mut env_changes = self.env_changes.clone();
+        for change in &mut env_changes {
+            change.rule_index = self
+                .rules
+                .env_rules
+                .iter()
+                .find(|rule| rule.glob_set.is_match(&change.key))
+                .map(|rule| rule.rule_index);
+        }
+        env_changes
+    }
+
+    fn all_changed(
+        &self,
+        path_changed_set: PackageSet<'g>,
+        env_changed_set: PackageSet<'g>,
+        env_changes: Vec<EnvChange>,
+        all_reason: ChangeReason<'g>,
+    ) -> DeterminatorSet<'g> {
+        let affected_set = self.new.resolve_workspace();
+        let affected_targets = all_targets(self.new, &affected_set);
+        DeterminatorSet {
+            path_changed_set,
+            env_changed_set,
+            // This is an empty set.
+            summary_changed_set: self.new.resolve_none(),
+            affected_set,
+            affected_targets,
+            env_changes,
+            reasons: HashMap::new(),
+            all_reason: Some(all_reason),
         }
     }
 }
@@ -256,6 +421,7 @@ impl<'g, 'a> Determinator<'g, 'a> {
 ///
 /// The lifetime `'g` is tied to the *new* `PackageGraph` passed to a `Determinator`.
 #[derive(Clone, Debug)]
+#[non_exhaustive]
 pub struct DeterminatorSet<'g> {
     /// The packages that were affected, directly or indirectly. This set is what most consumers
     /// care about.
@@ -269,12 +435,61 @@ pub struct DeterminatorSet<'g> {
     /// Either a file inside this package changed or a path rule was matched.
     pub path_changed_set: PackageSet<'g>,
 
+    /// The packages that were marked changed because a key in the [environment
+    /// snapshots](Determinator::set_env_snapshots) changed and matched an environment rule.
+    ///
+    /// If an environment rule caused all packages to be marked changed, this is the entire
+    /// workspace.
+    pub env_changed_set: PackageSet<'g>,
+
     /// The packages that were marked changed becuase a simulated Cargo build's summary showed
     /// changes in dependencies.
     ///
-    /// This does not include packages marked changed through a path. For example, if a path rule
+    /// This does not include packages marked changed through a path or an environment rule. For
+    /// example, if a path rule
     /// caused all packages to be marked changed, further steps aren't run and this set is empty.
     pub summary_changed_set: PackageSet<'g>,
+
+    /// The build targets that were affected, within the packages in `affected_set`.
+    ///
+    /// For packages where the only changed files belong to specific binaries, examples, tests or
+    /// benchmarks, only those targets are affected, and packages that depend on them aren't
+    /// affected through them. Every other package in `affected_set` has all of its targets
+    /// affected.
+    pub affected_targets: AffectedTargets<'g>,
+
+    /// The keys that changed between the [environment snapshots](Determinator::set_env_snapshots),
+    /// ordered by key, along with the environment rules they matched.
+    pub env_changes: Vec<EnvChange>,
+
+    // The reasons packages were marked changed or affected, recorded for `explain`.
+    reasons: HashMap<&'g PackageId, Vec<ChangeReason<'g>>>,
+    // The reason for packages without an entry in `reasons` if all packages were marked changed.
+    all_reason: Option<ChangeReason<'g>>,
+}
+
+impl<'g> DeterminatorSet<'g> {
+    /// Explains why a package is in the affected set.
+    ///
+    /// The explanation traces the package back to the changed paths, path rules, build summary
+    /// differences or package rules that caused it to be affected.
+    ///
+    /// Returns `None` if the package isn't in the affected set.
+    pub fn explain(&self, package_id: &PackageId) -> Option<Explanation<'g, '_>> {
+        let package = self
+            .affected_set
+            .packages(DependencyDirection::Forward)
+            .find(|package| package.id() == package_id)?;
+        Some(Explanation::new(self, package))
+    }
+
+    pub(crate) fn reasons_for(&self, package_id: &PackageId) -> &[ChangeReason<'g>] {
+        match (self.reasons.get(package_id), &self.all_reason) {
+            (Some(reasons), _) => reasons,
+            (None, Some(all_reason)) => std::slice::from_ref(all_reason),
+            (None, None) => &[],
+        }
+    }
 }
 
 // ---
@@ -285,7 +500,11 @@ pub struct DeterminatorSet<'g> {
 struct BuildState<'g, 'a, 'b> {
     determinator: &'b Determinator<'g, 'a>,
     path_changed_ids: HashSet<&'g PackageId>,
+    env_changed_ids: HashSet<&'g PackageId>,
     summary_changed_ids: HashSet<&'g PackageId>,
+    // The targets affected by changed paths, for each package in path_changed_ids.
+    changed_targets: HashMap<&'g PackageId, AffectedPackageTargets<'g>>,
+    reasons: HashMap<&'g PackageId, Vec<ChangeReason<'g>>>,
     build_cache: CargoBuildCache<'g>,
     reverse_index: ReverseIndex<'g>,
 }
@@ -297,50 +516,287 @@ impl<'g, 'a, 'b> BuildState<'g, 'a, 'b> {
         Self {
             determinator,
             path_changed_ids: HashSet::new(),
+            env_changed_ids: HashSet::new(),
             summary_changed_ids: HashSet::new(),
+            changed_targets: HashMap::new(),
+            reasons: HashMap::new(),
             build_cache,
             reverse_index,
         }
     }
 
-    // A return value of None stands for all packages in the workspace changed.
-    fn process_path(mut self, path: &Utf8Path) -> Option<Self> {
+    // An error stands for all packages in the workspace changed, with the reason why.
+    fn process_env_changes(mut self, env_changes: &[EnvChange]) -> Result<Self, ChangeReason<'g>> {
+        for change in env_changes {
+            let rule_index = match change.rule_index {
+                Some(rule_index) => rule_index,
+                None => continue,
+            };
+            let rule = self
+                .determinator
+                .rules
+                .env_rules
+                .iter()
+                .find(|rule| rule.rule_index == rule_index)
+                .expect("rule index is valid");
+            match &rule.mark_changed {
+                MarkChangedImpl::Packages(packages) => {
+                    for package in packages {
+                        let id = package.id();
+                        self.env_changed_ids.insert(id);
+                        // An environment change can't be narrowed down to targets.
+                        self.changed_targets.insert(id, AffectedPackageTargets::All);
+                        self.reasons
+                            .entry(id)
+                            .or_default()
+                            .push(ChangeReason::EnvRule {
+                                key: change.key.clone(),
+                                rule_index,
+                            });
+                    }
+                }
+                MarkChangedImpl::All => {
+                    return Err(ChangeReason::EnvMarkedAll {
+                        key: change.key.clone(),
+                        rule_index,
+                    });
+                }
+            }
+        }
+        Ok(self)
+    }
+
+    // An error stands for all packages in the workspace changed, with the reason why.
+    fn process_path(mut self, path: &Utf8Path) -> Result<Self, ChangeReason<'g>> {
+        let mut all_rule_index = None;
         let status = process_path(
             path,
             &self.determinator.new.workspace(),
             &self.determinator.rules.path_rules,
-            |id| {
-                self.path_changed_ids.insert(id);
+            self.determinator.imports.as_ref(),
+            |marked| match marked {
+                PathMarked::Package(id, rule_index) => {
+                    self.path_changed_ids.insert(id);
+                    // Path rules can't be narrowed down to targets.
+                    let targets = match rule_index {
+                        Some(_) => AffectedPackageTargets::All,
+                        None => match_targets(
+                            self.determinator
+                                .new
+                                .metadata(id)
+                                .expect("package ID is valid"),
+                            self.determinator.new.workspace().root(),
+                            path,
+                        ),
+                    };
+                    match self.changed_targets.entry(id) {
+                        Entry::Occupied(mut entry) => entry.get_mut().union_with(targets),
+                        Entry::Vacant(entry) => {
+                            entry.insert(targets);
+                        }
+                    }
+
+                    let path = path.to_path_buf();
+                    let reason = match rule_index {
+                        Some(rule_index) => ChangeReason::PathRule { path, rule_index },
+                        None => ChangeReason::PathInPackage { path },
+                    };
+                    self.reasons.entry(id).or_default().push(reason);
+                }
+                PathMarked::Included(id, targets) => {
+                    self.path_changed_ids.insert(id);
+                    match self.changed_targets.entry(id) {
+                        Entry::Occupied(mut entry) => entry.get_mut().union_with(targets),
+                        Entry::Vacant(entry) => {
+                            entry.insert(targets);
+                        }
+                    }
+                    let reason = ChangeReason::PathIncluded {
+                        path: path.to_path_buf(),
+                    };
+                    self.reasons.entry(id).or_default().push(reason);
+                }
+                PathMarked::All(rule_index) => {
+                    all_rule_index = Some(rule_index);
+                }
             },
         );
         match status {
-            PathMatch::RuleMatchedAll | PathMatch::NoMatches => None,
-            PathMatch::RuleMatched(_) | PathMatch::AncestorMatched => Some(self),
+            PathMatch::RuleMatchedAll | PathMatch::NoMatches => Err(ChangeReason::PathMarkedAll {
+                path: path.to_path_buf(),
+                rule_index: all_rule_index,
+            }),
+            PathMatch::RuleMatched(_) | PathMatch::AncestorMatched | PathMatch::ImportsTraced => {
+                Ok(self)
+            }
         }
     }
 
     fn process_build_summaries(&mut self) {
         // For each workspace package, if its build summaries have changed mark it changed.
-        let summary_changed_ids: Vec<_> = self
+        let summary_changed: Vec<_> = self
             .determinator
             .new
             .workspace()
             .par_iter_by_name()
             .filter_map(|(name, package)| {
-                // Don't include packages already marked as changed through paths. (This is documented.)
-                if !self.path_changed_ids.contains(package.id())
-                    && self.build_summaries_changed(name, package)
-                {
-                    Some(package.id())
-                } else {
-                    None
+                // Don't include packages already marked as changed through paths or environment
+                // rules. (This is documented.) Packages with only some targets changed still need
+                // to be checked, since a build summary change affects all of their targets.
+                match self.changed_targets.get(package.id()) {
+                    Some(AffectedPackageTargets::All) => return None,
+                    Some(AffectedPackageTargets::Only(_)) | None => {}
+                }
+                self.build_summaries_changed(name, package)
+                    .map(|reason| (package.id(), reason))
+            })
+            .collect();
+        for (id, reason) in summary_changed {
+            let targets = match &reason {
+                ChangeReason::BuildSummaryChanged { changes } => self.summary_changed_targets(
+                    self.determinator
+                        .new
+                        .metadata(id)
+                        .expect("package ID is valid"),
+                    changes,
+                ),
+                _ => AffectedPackageTargets::All,
+            };
+            if self.path_changed_ids.contains(id) {
+                // Only some targets were marked changed through paths.
+                self.changed_targets
+                    .get_mut(id)
+                    .expect("path changed packages have targets")
+                    .union_with(targets);
+                continue;
+            }
+            self.changed_targets.insert(id, targets);
+            self.summary_changed_ids.insert(id);
+            self.reasons.insert(id, vec![reason]);
+        }
+    }
+
+    /// Returns the targets of a package that are built by the builds whose summaries changed.
+    ///
+    /// Targets whose required features aren't enabled in any of those builds aren't affected.
+    fn summary_changed_targets(
+        &self,
+        package: PackageMetadata<'g>,
+        changes: &[BuildSummaryChange],
+    ) -> AffectedPackageTargets<'g> {
+        let build_result = &self.build_cache.result_cache[package.id()];
+        let enabled_features: Vec<HashSet<&str>> = build_result
+            .all_cargo_sets()
+            .filter(|(features, _)| changes.iter().any(|change| change.features == *features))
+            .map(|(_, cargo_set)| {
+                let mut enabled = HashSet::new();
+                for (_, features) in cargo_set.all_features() {
+                    if let Some(feature_list) = features
+                        .features_for(package.id())
+                        .expect("package ID is valid")
+                    {
+                        enabled.extend(feature_list.named_features());
+                    }
+                }
+                enabled
+            })
+            .collect();
+
+        let mut all_enabled = true;
+        let target_ids = package
+            .build_targets()
+            .filter(|target| {
+                let enabled = enabled_features.iter().any(|enabled| {
+                    // Requirements on the features of dependencies (`dep/feature`) are assumed to
+                    // be met.
+                    target
+                        .required_features()
+                        .iter()
+                        .all(|feature| feature.contains('/') || enabled.contains(feature.as_str()))
+                });
+                all_enabled &= enabled;
+                enabled
+            })
+            .map(|target| target.id())
+            .collect();
+        if all_enabled {
+            AffectedPackageTargets::All
+        } else {
+            AffectedPackageTargets::Only(target_ids)
+        }
+    }
+
+    fn affected_targets(
+        &self,
+        affected_set: &PackageSet<'g>,
+        all_affected: bool,
+    ) -> AffectedTargets<'g> {
+        let graph = self.determinator.new;
+        let partial: HashSet<_> = self
+            .changed_targets
+            .iter()
+            .filter(|(_, targets)| !targets.affects_dependents())
+            .map(|(id, _)| *id)
+            .collect();
+        let narrowed = self
+            .changed_targets
+            .values()
+            .any(|targets| matches!(targets, AffectedPackageTargets::Only(_)));
+        if all_affected || !narrowed {
+            return all_targets(graph, affected_set);
+        }
+
+        // Compute the closure again, except that packages with only some targets changed don't
+        // affect packages that depend on them. Package rules still apply to them.
+        let initial: Vec<_> = self
+            .summary_changed_ids
+            .iter()
+            .map(|id| (*id, FollowCargoBuild::NotAllowed))
+            .chain(
+                self.path_changed_ids
+                    .iter()
+                    .chain(&self.env_changed_ids)
+                    .map(|id| {
+                        if partial.contains(id) {
+                            (*id, FollowCargoBuild::NotAllowed)
+                        } else {
+                            (*id, FollowCargoBuild::Allowed)
+                        }
+                    }),
+            )
+            .collect();
+        let closure = match self.reverse_index.affected_closure(graph, initial, None) {
+            Ok(closure) => closure,
+            Err(_) => return all_targets(graph, affected_set),
+        };
+
+        let packages = closure
+            .finished
+            .iter()
+            .filter_map(|id| {
+                // A package that was reached from another affected package has all its targets
+                // affected.
+                let targets = match self.changed_targets.get(id) {
+                    Some(targets) if !closure.reached.contains(id) => targets.clone(),
+                    _ => AffectedPackageTargets::All,
+                };
+                match &targets {
+                    // None of the targets of a package whose build summaries changed may be
+                    // built with the features the change was in.
+                    AffectedPackageTargets::Only(target_ids) if target_ids.is_empty() => None,
+                    _ => Some((*id, targets)),
                 }
             })
             .collect();
-        self.summary_changed_ids.extend(summary_changed_ids);
+        AffectedTargets::new(graph, packages)
     }
 
-    fn build_summaries_changed(&self, name: &str, package: PackageMetadata<'g>) -> bool {
+    // Returns None if the build summaries are unchanged.
+    fn build_summaries_changed(
+        &self,
+        name: &str,
+        package: PackageMetadata<'g>,
+    ) -> Option<ChangeReason<'g>> {
         // Look up the package in the old metadata by path. (Workspace packages are uniquely
         // identified by both name and path -- this could be done by name as well).
         let old_workspace = self.determinator.old.workspace();
@@ -348,7 +804,7 @@ impl<'g, 'a, 'b> BuildState<'g, 'a, 'b> {
             Ok(package) => package,
             Err(_) => {
                 // Member not found: this is new or renamed.
-                return true;
+                return Some(ChangeReason::NewPackage);
             }
         };
 
@@ -364,15 +820,32 @@ impl<'g, 'a, 'b> BuildState<'g, 'a, 'b> {
 
         let old_result = BuildResult::new(old_package, cargo_options, features_only);
         let new_result = &self.build_cache.result_cache[package.id()];
-        new_result.is_changed(&old_result, cargo_options)
+        let changes = new_result.summary_changes(&old_result, cargo_options);
+        if changes.is_empty() {
+            None
+        } else {
+            Some(ChangeReason::BuildSummaryChanged { changes })
+        }
     }
 }
 
+/// What a path caused to be marked changed, as reported to the callback of `process_path`.
+enum PathMarked<'g> {
+    /// This package, through the path rule at this index or (if `None`) through being the nearest
+    /// ancestor.
+    Package(&'g PackageId, Option<RuleIndex>),
+    /// This package, through build targets that include the path.
+    Included(&'g PackageId, AffectedPackageTargets<'g>),
+    /// All packages, through the path rule at this index.
+    All(RuleIndex),
+}
+
 fn process_path<'g>(
     path: &Utf8Path,
     workspace: &Workspace<'g>,
     path_rules: &[PathRuleImpl<'g>],
-    mut match_cb: impl FnMut(&'g PackageId),
+    imports: Option<&ImportMap<'g>>,
+    mut match_cb: impl FnMut(PathMarked<'g>),
 ) -> PathMatch {
     let candidate = Candidate::new(path);
 
@@ -383,11 +856,12 @@ fn process_path<'g>(
             match &rule.mark_changed {
                 MarkChangedImpl::Packages(packages) => {
                     for package in packages {
-                        match_cb(package.id());
+                        match_cb(PathMarked::Package(package.id(), Some(rule.rule_index)));
                     }
                 }
                 MarkChangedImpl::All => {
                     // Mark all packages changed.
+                    match_cb(PathMarked::All(rule.rule_index));
                     return PathMatch::RuleMatchedAll;
                 }
             }
@@ -411,16 +885,48 @@ fn process_path<'g>(
         }
     }
 
-    // 2. Map the path to its nearest ancestor package.
-    for ancestor in path.ancestors() {
-        if let Ok(package) = workspace.member_by_path(ancestor) {
-            match_cb(package.id());
-            return PathMatch::AncestorMatched;
+    let ancestor = path
+        .ancestors()
+        .find_map(|ancestor| workspace.member_by_path(ancestor).ok());
+
+    // 2. If imports were traced, map the path to the packages with build targets that include it.
+    let mut included = false;
+    if let Some(imports) = imports {
+        let ancestor_traced = match ancestor {
+            Some(package) => imports.is_traced(package.id()),
+            None => true,
+        };
+        for (id, targets) in imports.included_by(path) {
+            if !ancestor_traced && ancestor.map(|package| package.id()) == Some(id) {
+                // This package is matched through its path in step 3.
+                continue;
+            }
+            match_cb(PathMarked::Included(id, targets));
+            included = true;
         }
+        let unused_source = path.extension() == Some("rs")
+            && ancestor.map_or(false, |package| {
+                in_source_dir(package, workspace.root(), path)
+            });
+        if ancestor_traced && (included || unused_source) {
+            // Rust source files next to library and binary sources that aren't included by any
+            // targets don't affect anything.
+            return PathMatch::ImportsTraced;
+        }
+    }
+
+    // 3. Map the path to its nearest ancestor package.
+    if let Some(package) = ancestor {
+        match_cb(PathMarked::Package(package.id(), None));
+        return PathMatch::AncestorMatched;
     }
 
-    // 3. If a file didn't match anything so far, rebuild everything.
-    PathMatch::NoMatches
+    // 4. If a file didn't match anything so far, rebuild everything.
+    if included {
+        PathMatch::ImportsTraced
+    } else {
+        PathMatch::NoMatches
+    }
 }
 
 /// Stores a build cache of every package in a workspace.
@@ -506,38 +1012,76 @@ impl<'g> BuildResult<'g> {
     fn unified_workspace_set(&self, workspace_set: &PackageSet<'g>) -> PackageSet<'g> {
         let target_set = self
             .all_cargo_sets()
-            .map(|x| x.target_features().to_package_set())
+            .map(|(_, x)| x.target_features().to_package_set())
             .reduce(|a, b| a.union(&b))
             .expect("at least one set");
         let host_set = self
             .all_cargo_sets()
-            .map(|x| x.host_features().to_package_set())
+            .map(|(_, x)| x.host_features().to_package_set())
             .reduce(|a, b| a.union(&b))
             .expect("at least one set");
 
         target_set.union(&host_set).intersection(workspace_set)
     }
 
-    fn is_changed(&self, other: &BuildResult<'_>, cargo_options: &CargoOptions<'_>) -> bool {
-        for (a, b) in self.all_cargo_sets().zip(other.all_cargo_sets()) {
-            let a_summary = a
+    /// Returns the differences between the builds in `old` and this result. An empty list means
+    /// that the builds are unchanged.
+    fn summary_changes(
+        &self,
+        old: &BuildResult<'_>,
+        cargo_options: &CargoOptions<'_>,
+    ) -> Vec<BuildSummaryChange> {
+        let mut changes = vec![];
+        for ((features, new), (_, old)) in self.all_cargo_sets().zip(old.all_cargo_sets()) {
+            let new_summary = new
                 .to_summary(cargo_options)
                 .expect("custom platforms currently unsupported");
-            let b_summary = b
+            let old_summary = old
                 .to_summary(cargo_options)
                 .expect("custom platforms currently unsupported");
-            let diff = a_summary.diff(&b_summary);
-            if diff.is_changed() {
-                return true;
+            let diff = old_summary.diff(&new_summary);
+            let package_diffs = [
+                (BuildPlatform::Target, &diff.target_packages),
+                (BuildPlatform::Host, &diff.host_packages),
+            ];
+            for (build_platform, package_diff) in package_diffs {
+                changes.extend(package_diff.changed.iter().map(|(summary_id, status)| {
+                    let (old_version, added_features, removed_features) = match status {
+                        SummaryDiffStatus::Modified {
+                            old_version,
+                            added_features,
+                            removed_features,
+                            ..
+                        } => (
+                            old_version.cloned(),
+                            added_features.iter().map(|f| f.to_string()).collect(),
+                            removed_features.iter().map(|f| f.to_string()).collect(),
+                        ),
+                        SummaryDiffStatus::Added { .. } | SummaryDiffStatus::Removed { .. } => {
+                            Default::default()
+                        }
+                    };
+                    BuildSummaryChange {
+                        features,
+                        build_platform,
+                        summary_id: (*summary_id).clone(),
+                        tag: status.tag(),
+                        old_version,
+                        added_features,
+                        removed_features,
+                    }
+                }));
             }
         }
-        false
+        changes
     }
 
-    fn all_cargo_sets<'a>(&'a self) -> impl Iterator<Item = &'a CargoSet<'g>> + 'a {
-        std::iter::once(&self.none)
-            .chain(std::iter::once(&self.default))
-            .chain(std::iter::once(&self.all))
+    fn all_cargo_sets<'a>(
+        &'a self,
+    ) -> impl Iterator<Item = (StandardFeatures, &'a CargoSet<'g>)> + 'a {
+        std::iter::once((StandardFeatures::None, &self.none))
+            .chain(std::iter::once((StandardFeatures::Default, &self.default)))
+            .chain(std::iter::once((StandardFeatures::All, &self.all)))
     }
 }
 
@@ -563,8 +1107,9 @@ struct ReverseIndex<'g> {
 /// Edges in the reverse index graph.
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
 enum ReverseIndexEdge {
-    /// This edge was added as a package rule. This always takes precedence over `CargoBuild`.
-    PackageRule,
+    /// This edge was added as the package rule at this index. This always takes precedence over
+    /// `CargoBuild`.
+    PackageRule(RuleIndex),
     /// This edge was added through the Cargo build cache.
     CargoBuild,
 }
@@ -599,7 +1144,7 @@ impl<'g> ReverseIndex<'g> {
                             (
                                 Some(on_affected),
                                 Some(package.id()),
-                                ReverseIndexEdge::PackageRule,
+                                ReverseIndexEdge::PackageRule(package_rule.rule_index),
                             )
                         }));
                     }
@@ -608,7 +1153,7 @@ impl<'g> ReverseIndex<'g> {
                         reverse_index.add_edge(
                             Some(on_affected),
                             None,
-                            ReverseIndexEdge::PackageRule,
+                            ReverseIndexEdge::PackageRule(package_rule.rule_index),
                         );
                     }
                 }
@@ -618,35 +1163,31 @@ impl<'g> ReverseIndex<'g> {
         Self { reverse_index }
     }
 
+    /// Returns the affected closure of the initial set, or a reason if all packages are affected.
+    ///
+    /// If `reasons` is passed in, the first package that each affected package was reached from is
+    /// recorded in it.
     fn affected_closure(
         &self,
         package_graph: &'g PackageGraph,
-        path_changed: &HashSet<&'g PackageId>,
-        summary_changed: &HashSet<&'g PackageId>,
-    ) -> PackageSet<'g> {
+        initial: Vec<(&'g PackageId, FollowCargoBuild)>,
+        mut reasons: Option<&mut HashMap<&'g PackageId, Vec<ChangeReason<'g>>>>,
+    ) -> Result<AffectedClosure<'g>, ChangeReason<'g>> {
         // This is a *really* interesting DFS, in that there's one restriction: you can't follow
-        // two CargoBuild edges consecutively. Also, in the initial set, path_changed allows
-        // CargoBuild to be followed once while summary_changed doesn't allow it to be followed.
-
-        #[derive(Copy, Clone, Debug, Eq, PartialEq)]
-        enum FollowCargoBuild {
-            Allowed,
-            NotAllowed,
-        }
+        // two CargoBuild edges consecutively. Also, in the initial set, path-changed packages
+        // typically allow CargoBuild to be followed once while summary-changed packages don't
+        // allow it to be followed.
 
         use FollowCargoBuild::*;
 
         // The order of what goes in the stack doesn't matter for correctness, but putting Allowed
         // at the end (and therefore popping it first) lowers the chance of an upgrade re-traversal.
-        let mut stack: Vec<_> = summary_changed
-            .iter()
-            .map(|id| (*id, NotAllowed))
-            .chain(path_changed.iter().map(|id| (*id, Allowed)))
-            .collect();
+        let mut stack = initial;
 
         // Do a DFS with two maps, in case there are cycles (can happen with dev deps).
         let mut discovered = HashMap::new();
         let mut finished = HashSet::new();
+        let mut reached = HashSet::new();
 
         while let Some(&(id, follow)) = stack.last() {
             let push_neighbors = match discovered.entry(id) {
@@ -673,6 +1214,7 @@ impl<'g> ReverseIndex<'g> {
             };
 
             if push_neighbors {
+                let package = package_graph.metadata(id).expect("valid package ID");
                 for (_, neighbor, &edge) in self.reverse_index.edges(Some(id)) {
                     if edge == ReverseIndexEdge::CargoBuild && follow == NotAllowed {
                         // Can't follow two consecutive CargoBuild edges.
@@ -682,9 +1224,32 @@ impl<'g> ReverseIndex<'g> {
                         Some(neighbor) => {
                             let neighbor_follow = match edge {
                                 ReverseIndexEdge::CargoBuild => NotAllowed,
-                                ReverseIndexEdge::PackageRule => Allowed,
+                                ReverseIndexEdge::PackageRule(_) => Allowed,
                             };
 
+                            if neighbor != id {
+                                reached.insert(neighbor);
+                            }
+
+                            // Only record the first package a neighbor is reached from. Every
+                            // package has a reason before its neighbors are pushed, so the
+                            // recorded reasons can't form a cycle.
+                            if let Some(reasons) = reasons.as_deref_mut() {
+                                reasons.entry(neighbor).or_insert_with(|| {
+                                    vec![match edge {
+                                        ReverseIndexEdge::CargoBuild => {
+                                            ChangeReason::Dependency { package }
+                                        }
+                                        ReverseIndexEdge::PackageRule(rule_index) => {
+                                            ChangeReason::PackageRule {
+                                                rule_index,
+                                                package,
+                                            }
+                                        }
+                                    }]
+                                });
+                            }
+
                             match (discovered.get(&neighbor), neighbor_follow) {
                                 (None, _) => {
                                     // Node has not been discovered yet. Add it to the stack to
@@ -702,7 +1267,16 @@ impl<'g> ReverseIndex<'g> {
                         }
                         None => {
                             // Build everything, can just exit here.
-                            return package_graph.resolve_workspace();
+                            let rule_index = match edge {
+                                ReverseIndexEdge::PackageRule(rule_index) => rule_index,
+                                ReverseIndexEdge::CargoBuild => {
+                                    unreachable!("only package rules mark all packages changed")
+                                }
+                            };
+                            return Err(ChangeReason::PackageRuleMarkedAll {
+                                rule_index,
+                                package,
+                            });
                         }
                     }
                 }
@@ -713,8 +1287,30 @@ impl<'g> ReverseIndex<'g> {
         }
 
         // At the end of this process, finished contains all nodes discovered.
-        package_graph
-            .resolve_ids(finished.iter().copied())
-            .expect("all IDs are valid")
+        Ok(AffectedClosure { finished, reached })
     }
 }
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+enum FollowCargoBuild {
+    Allowed,
+    NotAllowed,
+}
+
+/// The result of `ReverseIndex::affected_closure`.
+#[derive(Debug)]
+struct AffectedClosure<'g> {
+    // All packages in the closure.
+    finished: HashSet<&'g PackageId>,
+    // Packages reached from another package in the closure, through any edge.
+    reached: HashSet<&'g PackageId>,
+}
+
+/// Returns all targets of every package in `package_set`.
+fn all_targets<'g>(graph: &'g PackageGraph, package_set: &PackageSet<'g>) -> AffectedTargets<'g> {
+    let packages: BTreeMap<_, _> = package_set
+        .package_ids(DependencyDirection::Forward)
+        .map(|id| (id, AffectedPackageTargets::All))
+        .collect();
+    AffectedTargets::new(graph, packages)
+}
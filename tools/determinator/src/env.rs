@@ -0,0 +1,54 @@
+// Copyright (c) The cargo-guppy Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use crate::rules::RuleIndex;
+use std::collections::BTreeMap;
+
+/// A key that changed between the old and new [environment
+/// snapshots](crate::Determinator::set_env_snapshots).
+///
+/// Returned as part of a [`DeterminatorSet`](crate::DeterminatorSet).
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[non_exhaustive]
+pub struct EnvChange {
+    /// The key that changed.
+    pub key: String,
+
+    /// The value in the old snapshot, or `None` if the key was added.
+    pub old_value: Option<String>,
+
+    /// The value in the new snapshot, or `None` if the key was removed.
+    pub new_value: Option<String>,
+
+    /// The environment rule that matched this change, or `None` if no rules matched and the
+    /// change was ignored.
+    pub rule_index: Option<RuleIndex>,
+}
+
+/// Returns the keys that changed between two snapshots, ordered by key, without any rules matched.
+pub(crate) fn env_changes(
+    old: BTreeMap<String, String>,
+    mut new: BTreeMap<String, String>,
+) -> Vec<EnvChange> {
+    let mut changes = vec![];
+    for (key, old_value) in old {
+        let new_value = new.remove(&key);
+        if new_value.as_ref() != Some(&old_value) {
+            changes.push(EnvChange {
+                key,
+                old_value: Some(old_value),
+                new_value,
+                rule_index: None,
+            });
+        }
+    }
+    // Any keys left over were added.
+    changes.extend(new.into_iter().map(|(key, new_value)| EnvChange {
+        key,
+        old_value: None,
+        new_value: Some(new_value),
+        rule_index: None,
+    }));
+    changes.sort_by(|a, b| a.key.cmp(&b.key));
+    changes
+}
@@ -0,0 +1,330 @@
+// Copyright (c) The cargo-guppy Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use crate::{rules::RuleIndex, DeterminatorSet};
+use camino::Utf8PathBuf;
+use guppy::{
+    graph::{
+        cargo::BuildPlatform,
+        feature::StandardFeatures,
+        summaries::{diff::SummaryDiffTag, SummaryId},
+        PackageMetadata,
+    },
+    Version,
+};
+use std::{
+    collections::{BTreeSet, HashSet},
+    fmt,
+};
+
+/// The reason a package was marked changed or affected by the determinator.
+///
+/// Returned by [`Explanation::steps`].
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub enum ChangeReason<'g> {
+    /// A changed path is inside this package.
+    PathInPackage {
+        /// The changed path.
+        path: Utf8PathBuf,
+    },
+
+    /// A changed path is included by build targets in this package, as found by [tracing
+    /// imports](crate::Determinator::set_trace_imports).
+    PathIncluded {
+        /// The changed path.
+        path: Utf8PathBuf,
+    },
+
+    /// A changed path matched a path rule that marked this package changed.
+    PathRule {
+        /// The changed path.
+        path: Utf8PathBuf,
+        /// The index of the rule that matched.
+        rule_index: RuleIndex,
+    },
+
+    /// A changed path caused every package in the workspace to be marked changed.
+    PathMarkedAll {
+        /// The changed path.
+        path: Utf8PathBuf,
+        /// The index of the path rule that marked all packages changed, or `None` if the path
+        /// didn't match any rules or packages.
+        rule_index: Option<RuleIndex>,
+    },
+
+    /// A key changed in the environment snapshots and matched an environment rule that marked this
+    /// package changed.
+    EnvRule {
+        /// The key that changed.
+        key: String,
+        /// The index of the rule that matched.
+        rule_index: RuleIndex,
+    },
+
+    /// A key changed in the environment snapshots and matched an environment rule that marked
+    /// every package in the workspace changed.
+    EnvMarkedAll {
+        /// The key that changed.
+        key: String,
+        /// The index of the rule that matched.
+        rule_index: RuleIndex,
+    },
+
+    /// This package is not in the old workspace: it was either added or renamed.
+    NewPackage,
+
+    /// Simulated Cargo builds of this package produced different results.
+    BuildSummaryChanged {
+        /// The differences in the builds.
+        changes: Vec<BuildSummaryChange>,
+    },
+
+    /// A simulated Cargo build of this package includes a package that was marked changed.
+    Dependency {
+        /// The package that was marked changed.
+        package: PackageMetadata<'g>,
+    },
+
+    /// A package rule marked this package changed, because another package was affected.
+    PackageRule {
+        /// The index of the package rule.
+        rule_index: RuleIndex,
+        /// The affected package that triggered the rule.
+        package: PackageMetadata<'g>,
+    },
+
+    /// A package rule marked every package in the workspace changed, because another package was
+    /// affected.
+    PackageRuleMarkedAll {
+        /// The index of the package rule.
+        rule_index: RuleIndex,
+        /// The affected package that triggered the rule.
+        package: PackageMetadata<'g>,
+    },
+}
+
+impl<'g> ChangeReason<'g> {
+    /// Returns the package that this reason propagated from, if any.
+    ///
+    /// This is `None` for reasons that originate in the package itself, such as changed paths or
+    /// build summaries.
+    pub fn propagated_from(&self) -> Option<PackageMetadata<'g>> {
+        match self {
+            ChangeReason::Dependency { package }
+            | ChangeReason::PackageRule { package, .. }
+            | ChangeReason::PackageRuleMarkedAll { package, .. } => Some(*package),
+            ChangeReason::PathInPackage { .. }
+            | ChangeReason::PathIncluded { .. }
+            | ChangeReason::PathRule { .. }
+            | ChangeReason::PathMarkedAll { .. }
+            | ChangeReason::EnvRule { .. }
+            | ChangeReason::EnvMarkedAll { .. }
+            | ChangeReason::NewPackage
+            | ChangeReason::BuildSummaryChanged { .. } => None,
+        }
+    }
+}
+
+impl<'g> fmt::Display for ChangeReason<'g> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ChangeReason::PathInPackage { path } => {
+                write!(f, "changed path {} is in this package", path)
+            }
+            ChangeReason::PathIncluded { path } => {
+                write!(f, "changed path {} is included by this package", path)
+            }
+            ChangeReason::PathRule { path, rule_index } => {
+                write!(f, "changed path {} matched {}", path, rule_index)
+            }
+            ChangeReason::PathMarkedAll {
+                path,
+                rule_index: Some(rule_index),
+            } => write!(
+                f,
+                "changed path {} matched {}, which marks all packages changed",
+                path, rule_index
+            ),
+            ChangeReason::PathMarkedAll {
+                path,
+                rule_index: None,
+            } => write!(
+                f,
+                "changed path {} didn't match any rules or packages, so all packages are marked changed",
+                path
+            ),
+            ChangeReason::EnvRule { key, rule_index } => {
+                write!(f, "environment key {} changed and matched {}", key, rule_index)
+            }
+            ChangeReason::EnvMarkedAll { key, rule_index } => write!(
+                f,
+                "environment key {} changed and matched {}, which marks all packages changed",
+                key, rule_index
+            ),
+            ChangeReason::NewPackage => write!(f, "this package is new or renamed"),
+            ChangeReason::BuildSummaryChanged { changes } => {
+                write!(f, "simulated builds changed:")?;
+                // The same change is usually seen with several feature sets: only show it once.
+                let mut seen = BTreeSet::new();
+                for change in changes {
+                    if seen.insert((
+                        change.build_platform,
+                        &change.summary_id,
+                        change.tag,
+                        &change.old_version,
+                        &change.added_features,
+                        &change.removed_features,
+                    )) {
+                        write!(f, "\n    {}", change)?;
+                    }
+                }
+                Ok(())
+            }
+            ChangeReason::Dependency { package } => write!(
+                f,
+                "simulated builds include {} {}, which was marked changed",
+                package.name(),
+                package.version()
+            ),
+            ChangeReason::PackageRule {
+                rule_index,
+                package,
+            } => write!(
+                f,
+                "{} {} is affected and {} marks this package changed",
+                package.name(),
+                package.version(),
+                rule_index
+            ),
+            ChangeReason::PackageRuleMarkedAll {
+                rule_index,
+                package,
+            } => write!(
+                f,
+                "{} {} is affected and {} marks all packages changed",
+                package.name(),
+                package.version(),
+                rule_index
+            ),
+        }
+    }
+}
+
+/// A difference between simulated Cargo builds of a workspace package in the old and new
+/// graphs.
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[non_exhaustive]
+pub struct BuildSummaryChange {
+    /// The features the workspace package was built with.
+    pub features: StandardFeatures,
+
+    /// The platform on which the changed package is built.
+    pub build_platform: BuildPlatform,
+
+    /// The package whose build changed.
+    pub summary_id: SummaryId,
+
+    /// Whether the package was added to, removed from, or modified in the build.
+    pub tag: SummaryDiffTag,
+
+    /// The old version of the package, if it was modified and the version changed.
+    pub old_version: Option<Version>,
+
+    /// The features added to the package, if it was modified.
+    pub added_features: BTreeSet<String>,
+
+    /// The features removed from the package, if it was modified.
+    pub removed_features: BTreeSet<String>,
+}
+
+impl fmt::Display for BuildSummaryChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let tag = match self.tag {
+            SummaryDiffTag::Added => "added",
+            SummaryDiffTag::Modified => "modified",
+            SummaryDiffTag::Removed => "removed",
+        };
+        write!(f, "{} {} ", tag, self.summary_id.name)?;
+        if let Some(old_version) = &self.old_version {
+            write!(f, "{} -> ", old_version)?;
+        }
+        write!(
+            f,
+            "{} ({}, {})",
+            self.summary_id.version, self.summary_id.source, self.build_platform
+        )?;
+        if !self.added_features.is_empty() {
+            write!(f, ", added features: {}", join(&self.added_features))?;
+        }
+        if !self.removed_features.is_empty() {
+            write!(f, ", removed features: {}", join(&self.removed_features))?;
+        }
+        Ok(())
+    }
+}
+
+/// An explanation of why a package is in a [`DeterminatorSet`]'s affected set.
+///
+/// Returned by [`DeterminatorSet::explain`]. The `Display` implementation prints out a
+/// human-readable report.
+#[derive(Clone, Debug)]
+pub struct Explanation<'g, 'a> {
+    steps: Vec<(PackageMetadata<'g>, &'a [ChangeReason<'g>])>,
+}
+
+impl<'g, 'a> Explanation<'g, 'a> {
+    pub(crate) fn new(set: &'a DeterminatorSet<'g>, package: PackageMetadata<'g>) -> Self {
+        let mut steps = vec![];
+        let mut visited = HashSet::new();
+        let mut next = Some(package);
+        while let Some(package) = next {
+            if !visited.insert(package.id()) {
+                break;
+            }
+            let reasons = set.reasons_for(package.id());
+            next = match reasons {
+                [reason] => reason.propagated_from(),
+                _ => None,
+            };
+            steps.push((package, reasons));
+        }
+        Self { steps }
+    }
+
+    /// Returns the package being explained.
+    pub fn package(&self) -> PackageMetadata<'g> {
+        self.steps[0].0
+    }
+
+    /// Returns the chain of packages and reasons that caused this package to be affected.
+    ///
+    /// The first step is for the package being explained. Each following step is for the package
+    /// that the previous step's reason [propagated from](ChangeReason::propagated_from), and the
+    /// last step has the reasons the change originated from.
+    pub fn steps(
+        &self,
+    ) -> impl ExactSizeIterator<Item = (PackageMetadata<'g>, &'a [ChangeReason<'g>])> + '_ {
+        self.steps.iter().copied()
+    }
+}
+
+impl<'g, 'a> fmt::Display for Explanation<'g, 'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (package, reasons) in &self.steps {
+            writeln!(f, "{} {}:", package.name(), package.version())?;
+            for reason in *reasons {
+                writeln!(f, "  {}", reason)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn join(features: &BTreeSet<String>) -> String {
+    features
+        .iter()
+        .map(|feature| feature.as_str())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
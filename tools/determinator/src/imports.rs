@@ -0,0 +1,437 @@
+// Copyright (c) The cargo-guppy Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Tracing `mod` declarations and `include!`-style macros to find the files each build target
+//! is made of.
+
+use crate::targets::AffectedPackageTargets;
+use camino::{Utf8Component, Utf8Path, Utf8PathBuf};
+use guppy::{
+    graph::{BuildTargetId, PackageGraph, PackageMetadata},
+    PackageId,
+};
+use proc_macro2::{Delimiter, Literal, TokenStream, TokenTree};
+use rayon::prelude::*;
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
+    fs,
+    str::FromStr,
+};
+
+/// The files that each build target in a workspace is made of, found by following `mod`
+/// declarations and `include!`, `include_str!` and `include_bytes!` macros from each target's
+/// entry point.
+#[derive(Clone, Debug)]
+pub(crate) struct ImportMap<'g> {
+    // Maps paths relative to the workspace root to the build targets that include them.
+    files: HashMap<Utf8PathBuf, Vec<(&'g PackageId, BuildTargetId<'g>)>>,
+    // Packages for which every target was traced completely.
+    traced: HashSet<&'g PackageId>,
+}
+
+impl<'g> ImportMap<'g> {
+    /// Traces every build target in the workspace of `graph`, reading source files from disk.
+    pub(crate) fn new(graph: &'g PackageGraph) -> Self {
+        let workspace = graph.workspace();
+        let workspace_root = workspace.root();
+        let results: Vec<_> = workspace
+            .par_iter()
+            .map(|package| trace_package(package, workspace_root))
+            .collect();
+
+        let mut files: HashMap<_, Vec<_>> = HashMap::new();
+        let mut traced = HashSet::new();
+        for (package_id, target_files, complete) in results {
+            if complete {
+                traced.insert(package_id);
+            }
+            for (target_id, target_files) in target_files {
+                for file in target_files {
+                    files.entry(file).or_default().push((package_id, target_id));
+                }
+            }
+        }
+        Self { files, traced }
+    }
+
+    /// Returns true if every target in this package was traced completely.
+    ///
+    /// A package is not traced completely if a source file couldn't be read or parsed, or if a
+    /// module or included path couldn't be resolved.
+    pub(crate) fn is_traced(&self, package_id: &PackageId) -> bool {
+        self.traced.contains(package_id)
+    }
+
+    /// Returns the build targets that include this path, grouped by package.
+    ///
+    /// `path` is relative to the workspace root.
+    pub(crate) fn included_by(
+        &self,
+        path: &Utf8Path,
+    ) -> BTreeMap<&'g PackageId, AffectedPackageTargets<'g>> {
+        let mut included_by = BTreeMap::new();
+        let targets = match self.files.get(&normalize(path)) {
+            Some(targets) => targets,
+            None => return included_by,
+        };
+        for &(package_id, target_id) in targets {
+            let affected = match target_id {
+                // Changes to the library or build script affect the whole package.
+                BuildTargetId::Library | BuildTargetId::BuildScript => AffectedPackageTargets::All,
+                _ => AffectedPackageTargets::Only(std::iter::once(target_id).collect()),
+            };
+            included_by
+                .entry(package_id)
+                .or_insert_with(|| AffectedPackageTargets::Only(BTreeSet::new()))
+                .union_with(affected);
+        }
+        included_by
+    }
+}
+
+/// Traces every build target in a package.
+///
+/// Returns the files for each target relative to the workspace root, and whether every target
+/// was traced completely.
+fn trace_package<'g>(
+    package: PackageMetadata<'g>,
+    workspace_root: &Utf8Path,
+) -> (
+    &'g PackageId,
+    Vec<(BuildTargetId<'g>, Vec<Utf8PathBuf>)>,
+    bool,
+) {
+    let package_root = package
+        .manifest_path()
+        .parent()
+        .expect("manifest path has a parent");
+    let mut complete = true;
+    let target_files = package
+        .build_targets()
+        .map(|target| {
+            let mut tracer = Tracer {
+                package_root,
+                files: BTreeSet::new(),
+                complete: true,
+            };
+            // Crate roots behave like mod.rs files.
+            tracer.trace_module(&normalize(target.path()), true);
+            complete &= tracer.complete;
+
+            let files = tracer
+                .files
+                .into_iter()
+                .filter_map(|file| Some(file.strip_prefix(workspace_root).ok()?.to_path_buf()))
+                .collect();
+            (target.id(), files)
+        })
+        .collect();
+    (package.id(), target_files, complete)
+}
+
+#[derive(Debug)]
+struct Tracer<'a> {
+    package_root: &'a Utf8Path,
+    // Absolute paths to every file included in the target.
+    files: BTreeSet<Utf8PathBuf>,
+    complete: bool,
+}
+
+/// Where paths in a source file are resolved from.
+#[derive(Clone, Debug)]
+struct ScanContext {
+    // The directory containing the file being scanned, used for include! paths.
+    file_dir: Utf8PathBuf,
+    // The directory that `mod foo;` looks for foo.rs and foo/mod.rs in.
+    mod_dir: Utf8PathBuf,
+    // The directory that #[path] attributes are relative to.
+    path_dir: Utf8PathBuf,
+}
+
+impl<'a> Tracer<'a> {
+    /// Traces a module file. `mod_rs` is true for crate roots, `mod.rs` files and files loaded
+    /// through a `#[path]` attribute, whose submodules are in the same directory.
+    fn trace_module(&mut self, file: &Utf8Path, mod_rs: bool) {
+        let file_dir = file.parent().expect("file has a parent").to_path_buf();
+        let mod_dir = match (mod_rs, file.file_stem()) {
+            (false, Some(stem)) => file_dir.join(stem),
+            _ => file_dir.clone(),
+        };
+        let ctx = ScanContext {
+            path_dir: file_dir.clone(),
+            file_dir,
+            mod_dir,
+        };
+        self.trace_rust_file(file, &ctx);
+    }
+
+    /// Reads and scans a Rust source file, with its modules resolved according to `ctx`.
+    fn trace_rust_file(&mut self, file: &Utf8Path, ctx: &ScanContext) {
+        if !self.files.insert(file.to_path_buf()) {
+            // Already seen.
+            return;
+        }
+        let tokens = fs::read_to_string(file)
+            .ok()
+            .and_then(|contents| TokenStream::from_str(strip_shebang(&contents)).ok());
+        match tokens {
+            Some(tokens) => self.scan(tokens, ctx),
+            None => self.complete = false,
+        }
+    }
+
+    fn scan(&mut self, tokens: TokenStream, ctx: &ScanContext) {
+        let tokens: Vec<_> = tokens.into_iter().collect();
+        // The value of a #[path] attribute preceding the current item.
+        let mut path_attr = None;
+
+        let mut idx = 0;
+        while idx < tokens.len() {
+            match &tokens[idx] {
+                TokenTree::Punct(punct) if punct.as_char() == '#' => {
+                    // Skip over the ! in inner attributes.
+                    let next = match tokens.get(idx + 1) {
+                        Some(TokenTree::Punct(punct)) if punct.as_char() == '!' => idx + 2,
+                        _ => idx + 1,
+                    };
+                    if let Some(TokenTree::Group(group)) = tokens.get(next) {
+                        if group.delimiter() == Delimiter::Bracket {
+                            if let Some(path) = parse_path_attr(group.stream()) {
+                                path_attr = Some(path);
+                            }
+                            // Attributes may contain includes, e.g. #[doc = include_str!(...)].
+                            self.scan(group.stream(), ctx);
+                            idx = next + 1;
+                            continue;
+                        }
+                    }
+                }
+                TokenTree::Ident(ident) if ident == "mod" => {
+                    if let Some(TokenTree::Ident(name)) = tokens.get(idx + 1) {
+                        let name = name.to_string();
+                        let name = name.trim_start_matches("r#");
+                        match tokens.get(idx + 2) {
+                            Some(TokenTree::Punct(punct)) if punct.as_char() == ';' => {
+                                self.trace_mod_decl(name, path_attr.take(), ctx);
+                                idx += 3;
+                                continue;
+                            }
+                            Some(TokenTree::Group(group))
+                                if group.delimiter() == Delimiter::Brace =>
+                            {
+                                // An inline module: its submodules are in a subdirectory.
+                                let mod_dir = match path_attr.take() {
+                                    Some(path) => ctx.path_dir.join(path),
+                                    None => ctx.mod_dir.join(name),
+                                };
+                                let inner_ctx = ScanContext {
+                                    file_dir: ctx.file_dir.clone(),
+                                    path_dir: mod_dir.clone(),
+                                    mod_dir,
+                                };
+                                self.scan(group.stream(), &inner_ctx);
+                                idx += 3;
+                                continue;
+                            }
+                            _ => {}
+                        }
+                    }
+                }
+                TokenTree::Ident(ident)
+                    if ident == "include" || ident == "include_str" || ident == "include_bytes" =>
+                {
+                    if let (Some(TokenTree::Punct(punct)), Some(TokenTree::Group(group))) =
+                        (tokens.get(idx + 1), tokens.get(idx + 2))
+                    {
+                        if punct.as_char() == '!' {
+                            self.trace_include(ident == "include", group.stream(), ctx);
+                            idx += 3;
+                            continue;
+                        }
+                    }
+                }
+                TokenTree::Group(group) => {
+                    self.scan(group.stream(), ctx);
+                    if group.delimiter() == Delimiter::Brace {
+                        path_attr = None;
+                    }
+                }
+                TokenTree::Punct(punct) if punct.as_char() == ';' => path_attr = None,
+                _ => {}
+            }
+            idx += 1;
+        }
+    }
+
+    fn trace_mod_decl(&mut self, name: &str, path_attr: Option<String>, ctx: &ScanContext) {
+        if let Some(path) = path_attr {
+            self.trace_module(&normalize(&ctx.path_dir.join(path)), true);
+            return;
+        }
+
+        let file = ctx.mod_dir.join(format!("{}.rs", name));
+        if file.is_file() {
+            self.trace_module(&normalize(&file), false);
+            return;
+        }
+        let file = ctx.mod_dir.join(name).join("mod.rs");
+        if file.is_file() {
+            self.trace_module(&normalize(&file), true);
+            return;
+        }
+        // The module may be behind a cfg that's off, but assume the worst.
+        self.complete = false;
+    }
+
+    fn trace_include(&mut self, is_rust: bool, args: TokenStream, ctx: &ScanContext) {
+        let path = match parse_include_path(args, self.package_root) {
+            IncludePath::Path(path) => path,
+            // Generated files aren't part of the source tree.
+            IncludePath::OutDir => return,
+            IncludePath::Unknown => {
+                self.complete = false;
+                return;
+            }
+        };
+        let file = normalize(&ctx.file_dir.join(path));
+        if is_rust {
+            // Code in included files uses the including file's modules, but nested includes are
+            // relative to the included file.
+            let included_ctx = ScanContext {
+                file_dir: file.parent().expect("file has a parent").to_path_buf(),
+                ..ctx.clone()
+            };
+            self.trace_rust_file(&file, &included_ctx);
+        } else {
+            self.files.insert(file);
+        }
+    }
+}
+
+enum IncludePath {
+    Path(Utf8PathBuf),
+    OutDir,
+    Unknown,
+}
+
+/// Parses the argument to an `include!`-style macro.
+///
+/// Supports string literals, and `concat!(env!("CARGO_MANIFEST_DIR"), ...)` and
+/// `concat!(env!("OUT_DIR"), ...)` with string literals following.
+fn parse_include_path(args: TokenStream, package_root: &Utf8Path) -> IncludePath {
+    let tokens: Vec<_> = args.into_iter().collect();
+    match tokens.as_slice() {
+        [TokenTree::Literal(literal)] => match parse_str_literal(literal) {
+            Some(path) => IncludePath::Path(path.into()),
+            None => IncludePath::Unknown,
+        },
+        [TokenTree::Ident(concat), TokenTree::Punct(bang), TokenTree::Group(group)]
+            if concat == "concat" && bang.as_char() == '!' =>
+        {
+            parse_concat_path(group.stream(), package_root)
+        }
+        _ => IncludePath::Unknown,
+    }
+}
+
+fn parse_concat_path(args: TokenStream, package_root: &Utf8Path) -> IncludePath {
+    let tokens: Vec<_> = args.into_iter().collect();
+    let (base, rest) = match tokens.as_slice() {
+        [TokenTree::Ident(env), TokenTree::Punct(bang), TokenTree::Group(group), rest @ ..]
+            if env == "env" && bang.as_char() == '!' =>
+        {
+            let var = match group.stream().into_iter().collect::<Vec<_>>().as_slice() {
+                [TokenTree::Literal(literal)] => parse_str_literal(literal),
+                _ => None,
+            };
+            match var.as_deref() {
+                Some("CARGO_MANIFEST_DIR") => (package_root, rest),
+                Some("OUT_DIR") => return IncludePath::OutDir,
+                _ => return IncludePath::Unknown,
+            }
+        }
+        _ => return IncludePath::Unknown,
+    };
+
+    let mut path = base.as_str().to_owned();
+    for token in rest {
+        match token {
+            TokenTree::Punct(punct) if punct.as_char() == ',' => {}
+            TokenTree::Literal(literal) => match parse_str_literal(literal) {
+                Some(part) => path.push_str(&part),
+                None => return IncludePath::Unknown,
+            },
+            _ => return IncludePath::Unknown,
+        }
+    }
+    IncludePath::Path(path.into())
+}
+
+/// Parses `path = "..."` in an attribute.
+fn parse_path_attr(tokens: TokenStream) -> Option<String> {
+    let tokens: Vec<_> = tokens.into_iter().collect();
+    match tokens.as_slice() {
+        [TokenTree::Ident(ident), TokenTree::Punct(eq), TokenTree::Literal(literal)]
+            if ident == "path" && eq.as_char() == '=' =>
+        {
+            parse_str_literal(literal)
+        }
+        _ => None,
+    }
+}
+
+/// Returns the value of a string literal, or `None` if this isn't a string literal or it has
+/// escapes other than `\\` and `\"`.
+fn parse_str_literal(literal: &Literal) -> Option<String> {
+    let repr = literal.to_string();
+    if let Some(raw) = repr.strip_prefix('r') {
+        let hashes = raw.len() - raw.trim_start_matches('#').len();
+        let inner = raw.get(hashes..raw.len().checked_sub(hashes)?)?;
+        return Some(inner.strip_prefix('"')?.strip_suffix('"')?.to_owned());
+    }
+
+    let inner = repr.strip_prefix('"')?.strip_suffix('"')?;
+    let mut value = String::with_capacity(inner.len());
+    let mut chars = inner.chars();
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            match chars.next()? {
+                c @ ('\\' | '"') => value.push(c),
+                _ => return None,
+            }
+        } else {
+            value.push(c);
+        }
+    }
+    Some(value)
+}
+
+fn strip_shebang(contents: &str) -> &str {
+    let contents = contents.strip_prefix('\u{feff}').unwrap_or(contents);
+    // #![ starts an inner attribute, not a shebang.
+    if contents.starts_with("#!") && !contents.starts_with("#![") {
+        match contents.find('\n') {
+            Some(idx) => &contents[idx..],
+            None => "",
+        }
+    } else {
+        contents
+    }
+}
+
+/// Lexically normalizes a path, resolving `.` and `..` components.
+fn normalize(path: &Utf8Path) -> Utf8PathBuf {
+    let mut normalized = Utf8PathBuf::new();
+    for component in path.components() {
+        match component {
+            Utf8Component::CurDir => {}
+            Utf8Component::ParentDir => {
+                if !normalized.pop() {
+                    normalized.push(component);
+                }
+            }
+            component => normalized.push(component),
+        }
+    }
+    normalized
+}
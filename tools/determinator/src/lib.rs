@@ -67,6 +67,10 @@
 //! * If the file is outside a package, the determinator assumes that everything needs to be
 //!   rebuilt.
 //!
+//! With [import tracing](crate::Determinator::set_trace_imports) enabled, the determinator
+//! instead follows `mod` declarations and `include!`-style macros from each build target, and
+//! matches files to the packages that actually include them.
+//!
 //! The list of file changes can be obtained from a source control system such as Git. This crate
 //! provides a helper which simplifies the process of enumerating file lists while handling some
 //! gnarly edge cases. For more information, see the documentation for
@@ -111,8 +115,14 @@
 //!
 //! **By default, the determinator assumes that the environment stays the same between runs.**
 //!
-//! To represent changes to the environment, you may need to find ways to represent those changes
-//! as files checked into the repository, and add [custom rules](#customizing-behavior) for them.
+//! One way to represent changes to the environment is to pass in key-value snapshots of it for the
+//! old and new revisions through
+//! [`Determinator::set_env_snapshots`](crate::Determinator::set_env_snapshots). Changed keys are
+//! matched against [environment rules](crate::rules::EnvRule), which can mark packages changed. The
+//! default rules do a full run if keys such as `rustc-version` or `RUSTFLAGS` change.
+//!
+//! Otherwise, you may need to find ways to represent changes to the environment as files checked
+//! into the repository, and add [custom rules](#customizing-behavior) for them.
 //! For example:
 //!
 //! * Use a [`rust-toolchain` file](https://doc.rust-lang.org/edition-guide/rust-2018/rustup-for-managing-rust-versions.html#managing-versions)
@@ -124,6 +134,28 @@
 //! * As far as possible, make tests hermetic and not reach out to the network. If you only have a
 //!   few tests that make network calls, run them unconditionally.
 //!
+//! ## Build targets
+//!
+//! Within the affected set, the determinator also figures out which
+//! [build targets](guppy::graph::BuildTarget) of each package are affected, and returns them as
+//! [`AffectedTargets`](crate::AffectedTargets). A changed file belongs to a target if it is the
+//! target's entry point or is in the directory containing it. For example, if the only file
+//! changed in a package is `tests/foo/helpers.rs`, only the `foo` integration test is affected.
+//! Since no other package can depend on an integration test, dependents of the package aren't
+//! affected either.
+//!
+//! All targets in a package are affected if:
+//! * a changed file belongs to the library or build script, or to no target at all (e.g.
+//!   `Cargo.toml`)
+//! * a path rule marked the package changed
+//! * it was marked changed through a dependency or a package rule.
+//!
+//! If the package's build summaries changed, the targets affected are those whose
+//! [required features](guppy::graph::BuildTarget::required_features) are enabled in at least one
+//! of the changed builds. Targets that can't be built in any of them are left out.
+//!
+//! This makes it possible to filter test runs more tightly than by package.
+//!
 //! # Customizing behavior
 //!
 //! The standard rules followed by the determinator may need to be tweaked in some situations:
@@ -245,8 +277,12 @@
 //! Facebook's main source repository.
 
 mod determinator;
+mod env;
 pub mod errors;
+mod explain;
+mod imports;
 mod paths0;
 pub mod rules;
+mod targets;
 
-pub use crate::{determinator::*, paths0::*};
+pub use crate::{determinator::*, env::*, explain::*, paths0::*, targets::*};
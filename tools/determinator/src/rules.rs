@@ -17,8 +17,11 @@
 //!
 //! These custom behaviors can be specified through *determinator rules*.
 //!
-//! There are two sorts of determinator rules:
+//! There are three sorts of determinator rules:
 //! * **Path rules** match on changed paths, and are applied **in order**, before regular matches.
+//! * **Environment rules** match on changed keys in [environment
+//!   snapshots](crate::Determinator::set_env_snapshots). For each changed key, the first
+//!   matching rule is applied.
 //! * **Package rules** match based on changed packages, and are applied as required until
 //!   exhausted (i.e. a fixpoint is reached).
 //!
@@ -27,8 +30,9 @@
 //! # Default path rules
 //!
 //! The determinator ships with a set of default path rules for common files such as `.gitignore`
-//! and `Cargo.lock`. These rules are applied *after* custom rules, so custom rules matching the
-//! same paths can override them.
+//! and `Cargo.lock`, and default environment rules for keys such as `RUSTFLAGS`. These rules are
+//! applied *after* custom rules, so custom rules matching the same paths or keys can override
+//! them.
 //!
 //! The default rules can be [viewed here](DeterminatorRules::DEFAULT_RULES_TOML).
 //!
@@ -86,6 +90,38 @@
 //! mark-changed = ["guppy"]
 //! ```
 //!
+//! # Examples for environment rules
+//!
+//! Environment snapshots are key-value maps supplied by the caller, for example environment
+//! variables, the output of `rustc --version`, or the target triple. Keys are matched against
+//! [`globset`](https://docs.rs/globset/0.4) globs.
+//!
+//! To build everything if the Rust compiler version changes:
+//!
+//! ```toml
+//! [[env-rule]]
+//! keys = ["rustc-version"]
+//! mark-changed = "all"
+//! ```
+//!
+//! To mark a package changed if an environment variable it reads at runtime changes:
+//!
+//! ```toml
+//! [[env-rule]]
+//! keys = ["GUPPY_TEST_*"]
+//! mark-changed = ["guppy"]
+//! ```
+//!
+//! To ignore changes to `RUSTDOCFLAGS`, overriding the default rules:
+//!
+//! ```toml
+//! [[env-rule]]
+//! keys = ["RUSTDOCFLAGS"]
+//! mark-changed = []
+//! ```
+//!
+//! Changed keys that don't match any rules are ignored.
+//!
 //! # Examples for package rules
 //!
 //! To add a "virtual dependency" that Cargo may not know about:
@@ -118,6 +154,7 @@ use std::fmt;
 /// For more about determinator rules, see [the module-level documentation](index.html).
 #[derive(Clone, Debug, Eq, PartialEq, Deserialize, Serialize)]
 #[serde(deny_unknown_fields)]
+#[non_exhaustive]
 pub struct DeterminatorRules {
     /// Whether to use the default rules, as specified by `DEFAULT_RULES_TOML` and `default_rules`.
     ///
@@ -129,6 +166,10 @@ pub struct DeterminatorRules {
     #[serde(default, rename = "path-rule")]
     pub path_rules: Vec<PathRule>,
 
+    /// A list of rules that each changed key in the environment snapshots is matched against.
+    #[serde(default, rename = "env-rule")]
+    pub env_rules: Vec<EnvRule>,
+
     /// A list of rules that each affected package is matched against.
     ///
     /// Sometimes, dependencies between workspace packages aren't expressed in Cargo.tomls. The
@@ -146,6 +187,7 @@ impl Default for DeterminatorRules {
         Self {
             use_default_rules: true,
             path_rules: vec![],
+            env_rules: vec![],
             package_rules: vec![],
         }
     }
@@ -299,6 +341,55 @@ impl Default for DeterminatorPostRule {
     }
 }
 
+/// Environment-based rules for the determinator.
+///
+/// These rules customize the behavior of the determinator based on changes to the
+/// [environment snapshots](crate::Determinator::set_env_snapshots) passed in.
+///
+/// # Examples
+///
+/// ```toml
+/// [[env-rule]]
+/// keys = ["RUSTFLAGS", "CARGO_BUILD_*"]
+/// mark-changed = "all"
+/// ```
+///
+/// For more examples, see [the module-level documentation](index.html).
+#[derive(Clone, Debug, Eq, PartialEq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub struct EnvRule {
+    /// The keys to match against.
+    ///
+    /// A changed key matches a rule if it matches any of the globs on this list.
+    ///
+    /// # Examples
+    ///
+    /// In TOML format, this is specified as [`globset`](https://docs.rs/globset/0.4) globs:
+    ///
+    /// ```toml
+    /// keys = ["rustc-version", "CARGO_*"]
+    /// ```
+    pub keys: Vec<String>,
+
+    /// The set of packages to mark as changed.
+    ///
+    /// # Examples
+    ///
+    /// In TOML format, this may be the string `"all"`:
+    ///
+    /// ```toml
+    /// mark-changed = "all"
+    /// ```
+    ///
+    /// or an array of workspace package names:
+    ///
+    /// ```toml
+    /// mark-changed = ["guppy", "determinator"]
+    /// ```
+    #[serde(with = "mark_changed_impl")]
+    pub mark_changed: DeterminatorMarkChanged,
+}
+
 /// Package-based rules for the determinator.
 ///
 /// These rules customize the behavior of the determinator based on affected packages, and can be
@@ -397,6 +488,11 @@ pub enum PathMatch {
     RuleMatched(RuleIndex),
     /// The path was matched to a package through inspecting the parent directories of each path.
     AncestorMatched,
+    /// The path was matched through [traced imports](crate::Determinator::set_trace_imports).
+    ///
+    /// The path was either included by build targets, or is a Rust source file that isn't
+    /// included by any targets.
+    ImportsTraced,
     /// The path wasn't matched to a rule or a nearby package, causing everything to be rebuilt.
     NoMatches,
 }
@@ -405,11 +501,16 @@ pub enum PathMatch {
 ///
 /// Used in `PathMatch` and while returning errors.
 #[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+#[non_exhaustive]
 pub enum RuleIndex {
     /// The custom path rule at this index.
     CustomPath(usize),
     /// The default path rule at this index.
     DefaultPath(usize),
+    /// The custom environment rule at this index.
+    CustomEnv(usize),
+    /// The default environment rule at this index.
+    DefaultEnv(usize),
     /// The package rule at this index.
     ///
     /// All package rules are custom: there are no default package rules.
@@ -421,6 +522,8 @@ impl fmt::Display for RuleIndex {
         match self {
             RuleIndex::CustomPath(index) => write!(f, "custom path rule {}", index),
             RuleIndex::DefaultPath(index) => write!(f, "default path rule {}", index),
+            RuleIndex::CustomEnv(index) => write!(f, "custom env rule {}", index),
+            RuleIndex::DefaultEnv(index) => write!(f, "default env rule {}", index),
             RuleIndex::Package(index) => write!(f, "package rule {}", index),
         }
     }
@@ -434,6 +537,7 @@ impl fmt::Display for RuleIndex {
 #[derive(Clone, Debug)]
 pub(crate) struct RulesImpl<'g> {
     pub(crate) path_rules: Vec<PathRuleImpl<'g>>,
+    pub(crate) env_rules: Vec<EnvRuleImpl<'g>>,
     pub(crate) package_rules: Vec<PackageRuleImpl<'g>>,
 }
 
@@ -500,6 +604,49 @@ impl<'g> RulesImpl<'g> {
             )
             .collect::<Result<Vec<_>, _>>()?;
 
+        let custom_env_rules = options
+            .env_rules
+            .iter()
+            .enumerate()
+            .map(|(idx, rule)| (RuleIndex::CustomEnv(idx), rule));
+
+        let default_env_rules = if options.use_default_rules {
+            let default_rules = DeterminatorRules::default_rules();
+            default_rules.env_rules.as_slice()
+        } else {
+            &[]
+        };
+
+        let default_env_rules = default_env_rules
+            .iter()
+            .enumerate()
+            .map(|(idx, rule)| (RuleIndex::DefaultEnv(idx), rule));
+
+        // Default rules come after custom ones.
+        let mut env_rules = vec![];
+        for (rule_index, EnvRule { keys, mark_changed }) in
+            custom_env_rules.chain(default_env_rules)
+        {
+            let mut builder = GlobSetBuilder::new();
+            for key in keys {
+                let glob = Glob::new(key).map_err(|err| RulesError::glob_parse(rule_index, err))?;
+                builder.add(glob);
+            }
+
+            let glob_set = builder
+                .build()
+                .map_err(|err| RulesError::glob_parse(rule_index, err))?;
+
+            let mark_changed = MarkChangedImpl::new(&workspace, mark_changed)
+                .map_err(|err| RulesError::resolve_ref(rule_index, err))?;
+
+            env_rules.push(EnvRuleImpl {
+                rule_index,
+                glob_set,
+                mark_changed,
+            });
+        }
+
         let package_rules = options
             .package_rules
             .iter()
@@ -519,6 +666,7 @@ impl<'g> RulesImpl<'g> {
                     let mark_changed = MarkChangedImpl::new(&workspace, mark_changed)
                         .map_err(|err| RulesError::resolve_ref(rule_index, err))?;
                     Ok(PackageRuleImpl {
+                        rule_index,
                         on_affected,
                         mark_changed,
                     })
@@ -528,6 +676,7 @@ impl<'g> RulesImpl<'g> {
 
         Ok(Self {
             path_rules,
+            env_rules,
             package_rules,
         })
     }
@@ -541,8 +690,16 @@ pub(crate) struct PathRuleImpl<'g> {
     pub(crate) post_rule: DeterminatorPostRule,
 }
 
+#[derive(Clone, Debug)]
+pub(crate) struct EnvRuleImpl<'g> {
+    pub(crate) rule_index: RuleIndex,
+    pub(crate) glob_set: GlobSet,
+    pub(crate) mark_changed: MarkChangedImpl<'g>,
+}
+
 #[derive(Clone, Debug)]
 pub(crate) struct PackageRuleImpl<'g> {
+    pub(crate) rule_index: RuleIndex,
     pub(crate) on_affected: PackageSet<'g>,
     pub(crate) mark_changed: MarkChangedImpl<'g>,
 }
@@ -631,6 +788,14 @@ mod tests {
         globs = ["none/**/test", "foo/bar"]
         mark-changed = []
 
+        [[env-rule]]
+        keys = ["RUSTFLAGS", "CARGO_*"]
+        mark-changed = "all"
+
+        [[env-rule]]
+        keys = ["FOO"]
+        mark-changed = ["foo"]
+
         [[package-rule]]
         on-affected = ["foo"]
         mark-changed = ["wat"]
@@ -659,6 +824,16 @@ mod tests {
                     post_rule: DeterminatorPostRule::Skip,
                 },
             ],
+            env_rules: vec![
+                EnvRule {
+                    keys: vec!["RUSTFLAGS".to_owned(), "CARGO_*".to_owned()],
+                    mark_changed: DeterminatorMarkChanged::All,
+                },
+                EnvRule {
+                    keys: vec!["FOO".to_owned()],
+                    mark_changed: DeterminatorMarkChanged::Packages(vec!["foo".to_owned()]),
+                },
+            ],
             package_rules: vec![
                 PackageRule {
                     on_affected: vec!["foo".to_string()],
@@ -764,6 +939,30 @@ mod tests {
             post-rule = []
             "#,
             //
+            // *****************
+            // Environment rules
+            // *****************
+            //
+            // unrecognized key
+            r#"[[env-rule]]
+            keys = ["FOO"]
+            mark-changed = []
+            foo = "bar"
+            "#,
+            // keys is not a list
+            r#"[[env-rule]]
+            keys = "FOO"
+            mark-changed = []
+            "#,
+            // keys missing
+            r#"[[env-rule]]
+            mark-changed = "all"
+            "#,
+            // mark-changed missing
+            r#"[[env-rule]]
+            keys = ["FOO"]
+            "#,
+            //
             // *************
             // Package rules
             // *************
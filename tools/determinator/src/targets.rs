@@ -0,0 +1,236 @@
+// Copyright (c) The cargo-guppy Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use camino::Utf8Path;
+use guppy::{
+    graph::{BuildTargetId, PackageGraph, PackageMetadata, PackageSet},
+    PackageId,
+};
+use std::collections::{BTreeMap, BTreeSet};
+
+/// The build targets affected by a change, within the packages in the affected set.
+///
+/// A package's targets are tracked individually if the only changes to it are files that belong to
+/// specific binary, example, test or benchmark targets, and nothing else marked it changed. For
+/// example, if the only changed file is `tests/foo.rs`, only `Test("foo")` is affected, and
+/// packages that depend on this one aren't affected at all.
+///
+/// If a package's build summaries changed, targets whose
+/// [required features](guppy::graph::BuildTarget::required_features) aren't enabled in any of the
+/// changed builds aren't affected. Such a package isn't present if none of its targets are
+/// affected.
+///
+/// All other affected packages have all of their targets affected.
+///
+/// Returned as part of a [`DeterminatorSet`](crate::DeterminatorSet).
+#[derive(Clone, Debug)]
+pub struct AffectedTargets<'g> {
+    graph: &'g PackageGraph,
+    packages: BTreeMap<&'g PackageId, AffectedPackageTargets<'g>>,
+}
+
+impl<'g> AffectedTargets<'g> {
+    pub(crate) fn new(
+        graph: &'g PackageGraph,
+        packages: BTreeMap<&'g PackageId, AffectedPackageTargets<'g>>,
+    ) -> Self {
+        Self { graph, packages }
+    }
+
+    /// Returns true if no targets are affected.
+    pub fn is_empty(&self) -> bool {
+        self.packages.is_empty()
+    }
+
+    /// Returns the number of packages with affected targets.
+    pub fn len(&self) -> usize {
+        self.packages.len()
+    }
+
+    /// Returns the set of packages with affected targets.
+    ///
+    /// This is a subset of the affected set.
+    pub fn package_set(&self) -> PackageSet<'g> {
+        self.graph
+            .resolve_ids(self.packages.keys().copied())
+            .expect("package IDs are all valid")
+    }
+
+    /// Returns the affected targets of a package, or `None` if none of its targets are affected.
+    pub fn get(&self, package_id: &PackageId) -> Option<&AffectedPackageTargets<'g>> {
+        self.packages.get(package_id)
+    }
+
+    /// Returns true if a build target in a package is affected.
+    pub fn contains(&self, package_id: &PackageId, target_id: BuildTargetId<'_>) -> bool {
+        match self.packages.get(package_id) {
+            Some(AffectedPackageTargets::All) => true,
+            Some(AffectedPackageTargets::Only(target_ids)) => target_ids
+                .iter()
+                .any(|affected_id| *affected_id == target_id),
+            None => false,
+        }
+    }
+
+    /// Iterates over the packages with affected targets, ordered by package ID.
+    pub fn iter<'a>(
+        &'a self,
+    ) -> impl Iterator<Item = (PackageMetadata<'g>, &'a AffectedPackageTargets<'g>)> + 'a {
+        self.packages.iter().map(move |(package_id, targets)| {
+            let package = self
+                .graph
+                .metadata(package_id)
+                .expect("package IDs are all valid");
+            (package, targets)
+        })
+    }
+
+    /// Iterates over every affected build target, ordered by package ID.
+    ///
+    /// For packages with all of their targets affected, every build target is returned.
+    pub fn target_ids<'a>(
+        &'a self,
+    ) -> impl Iterator<Item = (PackageMetadata<'g>, BuildTargetId<'g>)> + 'a {
+        self.iter().flat_map(|(package, targets)| {
+            let target_ids: Vec<_> = match targets {
+                AffectedPackageTargets::All => {
+                    package.build_targets().map(|target| target.id()).collect()
+                }
+                AffectedPackageTargets::Only(target_ids) => target_ids.iter().copied().collect(),
+            };
+            target_ids
+                .into_iter()
+                .map(move |target_id| (package, target_id))
+        })
+    }
+}
+
+/// The build targets affected within a package.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum AffectedPackageTargets<'g> {
+    /// All targets in the package are affected.
+    All,
+
+    /// Only these targets are affected.
+    ///
+    /// Changed files in the library or build script affect the whole package. This set only
+    /// includes the library and build script if the package's build summaries changed, in which
+    /// case it leaves out targets whose required features aren't enabled.
+    Only(BTreeSet<BuildTargetId<'g>>),
+}
+
+impl<'g> AffectedPackageTargets<'g> {
+    /// Returns true if packages that depend on this one are affected as well.
+    pub(crate) fn affects_dependents(&self) -> bool {
+        match self {
+            AffectedPackageTargets::All => true,
+            AffectedPackageTargets::Only(target_ids) => target_ids.iter().any(|target_id| {
+                matches!(
+                    target_id,
+                    BuildTargetId::Library | BuildTargetId::BuildScript
+                )
+            }),
+        }
+    }
+
+    /// Adds the targets in `other` to this set.
+    pub(crate) fn union_with(&mut self, other: AffectedPackageTargets<'g>) {
+        match (&mut *self, other) {
+            (AffectedPackageTargets::All, _) => {}
+            (_, AffectedPackageTargets::All) => *self = AffectedPackageTargets::All,
+            (AffectedPackageTargets::Only(target_ids), AffectedPackageTargets::Only(other)) => {
+                target_ids.extend(other)
+            }
+        }
+    }
+}
+
+/// Returns the build targets in `package` that a changed path affects.
+///
+/// `path` is relative to the workspace root. A path belongs to a target if it is the target's
+/// entry point, or is within the directory containing the entry point. If several targets share
+/// the nearest directory, such as `tests/` with several integration tests in it, all of them are
+/// affected.
+///
+/// Paths that don't belong to any target, like `Cargo.toml`, and paths that belong to the library
+/// or build script affect the whole package.
+pub(crate) fn match_targets<'g>(
+    package: PackageMetadata<'g>,
+    workspace_root: &Utf8Path,
+    path: &Utf8Path,
+) -> AffectedPackageTargets<'g> {
+    let path = workspace_root.join(path);
+    let package_root = package
+        .manifest_path()
+        .parent()
+        .expect("manifest path has a parent");
+
+    let mut entry_point_matches = BTreeSet::new();
+    let mut dir_matches = BTreeSet::new();
+    let mut dir_depth = 0;
+    for target in package.build_targets() {
+        if target.path() == path {
+            entry_point_matches.insert(target.id());
+            continue;
+        }
+        let dir = match target.path().parent() {
+            // Files directly within the package root, like Cargo.toml, don't belong to any target.
+            Some(dir) if dir != package_root && path.starts_with(dir) => dir,
+            _ => continue,
+        };
+        let depth = dir.components().count();
+        if depth > dir_depth {
+            dir_depth = depth;
+            dir_matches.clear();
+        }
+        if depth == dir_depth {
+            dir_matches.insert(target.id());
+        }
+    }
+
+    let target_ids = if entry_point_matches.is_empty() {
+        dir_matches
+    } else {
+        entry_point_matches
+    };
+    let affects_package = target_ids.is_empty()
+        || target_ids.iter().any(|target_id| {
+            matches!(
+                target_id,
+                BuildTargetId::Library | BuildTargetId::BuildScript
+            )
+        });
+    if affects_package {
+        AffectedPackageTargets::All
+    } else {
+        AffectedPackageTargets::Only(target_ids)
+    }
+}
+
+/// Returns true if `path` is within the directory containing the entry point of the library or a
+/// binary in `package`, other than the package root.
+///
+/// `path` is relative to the workspace root. Rust source files in these directories are only
+/// compiled if a `mod` declaration or include refers to them. Elsewhere, as with `tests/ui/*.rs`
+/// files used by `trybuild`, they may be read at runtime.
+pub(crate) fn in_source_dir(
+    package: PackageMetadata<'_>,
+    workspace_root: &Utf8Path,
+    path: &Utf8Path,
+) -> bool {
+    let path = workspace_root.join(path);
+    let package_root = package
+        .manifest_path()
+        .parent()
+        .expect("manifest path has a parent");
+
+    package.build_targets().any(|target| {
+        matches!(
+            target.id(),
+            BuildTargetId::Library | BuildTargetId::Binary(_)
+        ) && match target.path().parent() {
+            Some(dir) => dir != package_root && path.starts_with(dir),
+            None => false,
+        }
+    })
+}
@@ -3,13 +3,19 @@
 
 //! Higher-level unit tests for the target determinator.
 
+use camino::Utf8Path;
 use cfg_if::cfg_if;
 use determinator::{
     rules::{DeterminatorRules, PathMatch, RuleIndex},
-    Determinator, Utf8Paths0,
+    AffectedPackageTargets, ChangeReason, Determinator, Utf8Paths0,
 };
 use fixtures::json::JsonFixture;
-use guppy::{graph::feature::StandardFeatures, CargoMetadata};
+use guppy::{
+    graph::{
+        feature::StandardFeatures, summaries::diff::SummaryDiffTag, BuildTargetId, PackageGraph,
+    },
+    CargoMetadata, MetadataCommand, PackageId,
+};
 
 #[test]
 fn guppy_no_rules() {
@@ -439,6 +445,551 @@ fn guppy_match_paths() {
     }
 }
 
+#[test]
+fn guppy_explain_paths() {
+    let old = JsonFixture::metadata_guppy_869476c();
+    let new = JsonFixture::metadata_guppy_c9b4f76();
+    let opts = read_options(new, "package-rules.toml");
+
+    let mut determinator = Determinator::new(old.graph(), new.graph());
+    determinator.set_rules(&opts).expect("rules set correctly");
+    determinator.add_changed_paths(vec!["cargo-guppy/foo.rs", "fixtures/src/main.rs"]);
+    let determinator_set = determinator.compute();
+
+    // cargo-compare depends on guppy-cmdlib, which is marked changed through a package rule because
+    // fixtures changed.
+    let explanation = determinator_set
+        .explain(workspace_id(new, "cargo-compare"))
+        .expect("cargo-compare is affected");
+    let steps: Vec<_> = explanation.steps().collect();
+    assert_eq!(
+        steps
+            .iter()
+            .map(|(package, _)| package.name())
+            .collect::<Vec<_>>(),
+        vec!["cargo-compare", "guppy-cmdlib", "fixtures"],
+        "explanation goes through guppy-cmdlib and fixtures"
+    );
+    assert!(matches!(
+        steps[0].1,
+        [ChangeReason::Dependency { package }] if package.name() == "guppy-cmdlib"
+    ));
+    assert!(matches!(
+        steps[1].1,
+        [ChangeReason::PackageRule { rule_index: RuleIndex::Package(0), package }]
+            if package.name() == "fixtures"
+    ));
+    assert!(matches!(
+        steps[2].1,
+        [ChangeReason::PathInPackage { path }] if path == "fixtures/src/main.rs"
+    ));
+
+    let report = explanation.to_string();
+    assert!(
+        report.contains("changed path fixtures/src/main.rs is in this package"),
+        "report mentions the changed path: {}",
+        report
+    );
+
+    // target-spec isn't affected.
+    assert!(determinator_set
+        .explain(workspace_id(new, "target-spec"))
+        .is_none());
+}
+
+#[test]
+fn guppy_explain_all() {
+    let old = JsonFixture::metadata_guppy_869476c();
+    let new = JsonFixture::metadata_guppy_c9b4f76();
+
+    // rust-toolchain matches a default rule that causes a full build.
+    let mut determinator = Determinator::new(old.graph(), new.graph());
+    determinator.add_changed_paths(vec!["rust-toolchain"]);
+    let determinator_set = determinator.compute();
+    let explanation = determinator_set
+        .explain(workspace_id(new, "guppy"))
+        .expect("guppy is affected");
+    let steps: Vec<_> = explanation.steps().collect();
+    assert_eq!(steps.len(), 1, "full builds from paths aren't propagated");
+    assert!(matches!(
+        steps[0].1,
+        [ChangeReason::PathMarkedAll {
+            path,
+            rule_index: Some(RuleIndex::DefaultPath(_)),
+        }] if path == "rust-toolchain"
+    ));
+
+    // foo/fake-trigger marks proptest-ext changed, which causes guppy-benchmarks to be affected,
+    // which according to a package rule means everything gets rebuilt.
+    let opts = read_options(new, "package-rules.toml");
+    let mut determinator = Determinator::new(old.graph(), new.graph());
+    determinator.set_rules(&opts).expect("rules set correctly");
+    determinator.add_changed_paths(vec!["foo/fake-trigger"]);
+    let determinator_set = determinator.compute();
+    let explanation = determinator_set
+        .explain(workspace_id(new, "target-spec"))
+        .expect("target-spec is affected");
+    let steps: Vec<_> = explanation.steps().collect();
+    assert!(matches!(
+        steps[0].1,
+        [ChangeReason::PackageRuleMarkedAll { rule_index: RuleIndex::Package(1), package }]
+            if package.name() == "guppy-benchmarks"
+    ));
+    let (package, reasons) = steps.last().expect("at least one step");
+    assert_eq!(package.name(), "proptest-ext");
+    assert!(matches!(
+        reasons,
+        [ChangeReason::PathRule { path, rule_index: RuleIndex::CustomPath(0) }]
+            if path == "foo/fake-trigger"
+    ));
+}
+
+#[test]
+fn guppy_explain_summaries() {
+    // new updates the version of toml.
+    let old = JsonFixture::metadata_guppy_78cb7e8();
+    let new = JsonFixture::metadata_guppy_869476c();
+    let opts = read_options(new, "path-rules.toml");
+
+    let mut determinator = Determinator::new(old.graph(), new.graph());
+    determinator.set_rules(&opts).expect("rules set correctly");
+    let determinator_set = determinator.compute();
+
+    let explanation = determinator_set
+        .explain(workspace_id(new, "guppy-summaries"))
+        .expect("guppy-summaries is affected");
+    let steps: Vec<_> = explanation.steps().collect();
+    assert_eq!(steps.len(), 1, "summary changes aren't propagated");
+    let changes = match steps[0].1 {
+        [ChangeReason::BuildSummaryChanged { changes }] => changes,
+        other => panic!("unexpected reasons: {:?}", other),
+    };
+    let toml_change = changes
+        .iter()
+        .find(|change| change.summary_id.name == "toml")
+        .expect("toml changed");
+    assert_eq!(toml_change.tag, SummaryDiffTag::Modified);
+    assert!(toml_change.old_version.is_some(), "toml version changed");
+    assert!(
+        explanation.to_string().contains("modified toml "),
+        "report mentions toml"
+    );
+}
+
+#[test]
+fn guppy_affected_targets() {
+    let old = JsonFixture::metadata_guppy_869476c();
+    let new = JsonFixture::metadata_guppy_c9b4f76();
+
+    let only = |target_ids: &[BuildTargetId<'static>]| {
+        AffectedPackageTargets::Only(target_ids.iter().copied().collect())
+    };
+
+    for (paths, expected_targets) in [
+        // A file in an integration test's directory only affects that test, and no other packages.
+        (
+            vec!["guppy/tests/graph-tests/foo.rs"],
+            vec![("guppy", only(&[BuildTargetId::Test("graph-tests")]))],
+        ),
+        (
+            vec!["guppy/examples/print_by_level.rs"],
+            vec![("guppy", only(&[BuildTargetId::Example("print_by_level")]))],
+        ),
+        (
+            vec![
+                "guppy/examples/print_by_level.rs",
+                "guppy/tests/graph-tests/main.rs",
+            ],
+            vec![(
+                "guppy",
+                only(&[
+                    BuildTargetId::Example("print_by_level"),
+                    BuildTargetId::Test("graph-tests"),
+                ]),
+            )],
+        ),
+        (
+            vec!["internal-tools/fixture-manager/src/main.rs"],
+            vec![(
+                "fixture-manager",
+                only(&[BuildTargetId::Binary("fixture-manager")]),
+            )],
+        ),
+        // Changes to the library, the build script or other files affect the whole package.
+        (
+            vec!["guppy/tests/graph-tests/foo.rs", "guppy/src/lib.rs"],
+            vec![("guppy", AffectedPackageTargets::All)],
+        ),
+        (
+            vec!["target-spec/build.rs"],
+            vec![("target-spec", AffectedPackageTargets::All)],
+        ),
+        (
+            vec!["guppy/Cargo.toml"],
+            vec![("guppy", AffectedPackageTargets::All)],
+        ),
+    ] {
+        let mut determinator = Determinator::new(old.graph(), new.graph());
+        determinator.add_changed_paths(paths.clone());
+        let determinator_set = determinator.compute();
+        let affected_targets = &determinator_set.affected_targets;
+
+        for (name, expected) in &expected_targets {
+            assert_eq!(
+                affected_targets.get(workspace_id(new, name)),
+                Some(expected),
+                "for paths {:?}, affected targets for {} match",
+                paths,
+                name,
+            );
+        }
+
+        if expected_targets
+            .iter()
+            .all(|(_, targets)| *targets != AffectedPackageTargets::All)
+        {
+            assert_eq!(
+                affected_targets.len(),
+                expected_targets.len(),
+                "for paths {:?}, no other packages have affected targets",
+                paths,
+            );
+        } else {
+            assert_eq!(
+                affected_targets.package_set(),
+                determinator_set.affected_set,
+                "for paths {:?}, all affected packages have affected targets",
+                paths,
+            );
+        }
+    }
+}
+
+#[test]
+fn guppy_affected_targets_reached() {
+    let old = JsonFixture::metadata_guppy_869476c();
+    let new = JsonFixture::metadata_guppy_c9b4f76();
+    let opts = read_options(new, "package-rules.toml");
+
+    // guppy has a dev-dependency on fixtures, so guppy's tests are affected by the change to
+    // fixtures as well.
+    let mut determinator = Determinator::new(old.graph(), new.graph());
+    determinator.set_rules(&opts).expect("rules set correctly");
+    determinator.add_changed_paths(vec![
+        "guppy/tests/graph-tests/foo.rs",
+        "fixtures/src/lib.rs",
+    ]);
+    let determinator_set = determinator.compute();
+    let affected_targets = &determinator_set.affected_targets;
+
+    let guppy_id = workspace_id(new, "guppy");
+    assert_eq!(
+        affected_targets.get(guppy_id),
+        Some(&AffectedPackageTargets::All),
+        "all guppy targets affected"
+    );
+    assert!(affected_targets.contains(guppy_id, BuildTargetId::Library));
+    assert_eq!(
+        affected_targets.package_set(),
+        determinator_set.affected_set,
+        "all affected packages have affected targets"
+    );
+}
+
+#[test]
+fn affected_targets_required_features() {
+    // debug-ignore has a test that requires the serde feature, which also enables
+    // unicode-xid/bench. The new graph enables that feature unconditionally, which changes the
+    // builds without serde but not the one with it.
+    let features = r#""features":{"serde":["dep:serde","unicode-xid/bench"]}"#;
+    let old_json = JsonFixture::metadata_alternate_registries()
+        .json()
+        .replacen(r#""features":{}"#, features, 1);
+    let new_json = old_json.replacen(
+        r#""name":"unicode-xid","source":"registry+https://github.com/rust-lang/crates.io-index","req":"^0.2","kind":null,"rename":null,"optional":false,"uses_default_features":true,"features":[]"#,
+        r#""name":"unicode-xid","source":"registry+https://github.com/rust-lang/crates.io-index","req":"^0.2","kind":null,"rename":null,"optional":false,"uses_default_features":true,"features":["bench"]"#,
+        1,
+    );
+    let build_graph = |json: &str| {
+        CargoMetadata::parse_json(json)
+            .expect("metadata parsed")
+            .build_graph()
+            .expect("package graph built")
+    };
+    let old = build_graph(&old_json);
+    let new = build_graph(&new_json);
+
+    let determinator = Determinator::new(&old, &new);
+    let determinator_set = determinator.compute();
+    let debug_ignore_id = graph_workspace_id(&new, "debug-ignore");
+    assert!(
+        determinator_set
+            .summary_changed_set
+            .contains(debug_ignore_id)
+            .expect("valid package ID"),
+        "debug-ignore's build summaries changed"
+    );
+    assert_eq!(
+        determinator_set.affected_targets.get(debug_ignore_id),
+        Some(&AffectedPackageTargets::Only(
+            [BuildTargetId::Library].into_iter().collect()
+        )),
+        "the serde test isn't built in the changed builds"
+    );
+}
+
+#[test]
+fn guppy_env_rules() {
+    let old = JsonFixture::metadata_guppy_869476c();
+    let new = JsonFixture::metadata_guppy_c9b4f76();
+    let rules = DeterminatorRules::parse(
+        r#"
+        [[env-rule]]
+        keys = ["GUPPY_*"]
+        mark-changed = ["guppy"]
+
+        [[env-rule]]
+        keys = ["RUSTDOCFLAGS"]
+        mark-changed = []
+        "#,
+    )
+    .expect("rules parsed");
+
+    let mut determinator = Determinator::new(old.graph(), new.graph());
+    determinator.set_rules(&rules).expect("rules set correctly");
+    determinator.set_env_snapshots(
+        vec![
+            ("GUPPY_TEST", "1"),
+            ("HOME", "/home/a"),
+            ("RUSTDOCFLAGS", "--cfg a"),
+            ("SAME", "1"),
+        ],
+        vec![
+            ("GUPPY_TEST", "2"),
+            ("HOME", "/home/b"),
+            ("RUSTDOCFLAGS", "--cfg b"),
+            ("SAME", "1"),
+        ],
+    );
+    let determinator_set = determinator.compute();
+
+    assert_eq!(
+        determinator_set
+            .env_changes
+            .iter()
+            .map(|change| (change.key.as_str(), change.rule_index))
+            .collect::<Vec<_>>(),
+        vec![
+            ("GUPPY_TEST", Some(RuleIndex::CustomEnv(0))),
+            ("HOME", None),
+            // Custom rules override the default ones.
+            ("RUSTDOCFLAGS", Some(RuleIndex::CustomEnv(1))),
+        ],
+        "env changes are correct"
+    );
+    assert_eq!(
+        determinator_set.env_changed_set,
+        new.graph()
+            .resolve_workspace_names(["guppy"])
+            .expect("workspace names resolved"),
+        "only guppy is marked changed through env rules"
+    );
+    assert!(
+        determinator_set
+            .affected_set
+            .contains(workspace_id(new, "cargo-guppy"))
+            .expect("valid package ID"),
+        "packages that depend on guppy are affected"
+    );
+
+    let explanation = determinator_set
+        .explain(workspace_id(new, "guppy"))
+        .expect("guppy is affected");
+    assert!(matches!(
+        explanation.steps().next().expect("at least one step").1,
+        [ChangeReason::EnvRule { key, rule_index: RuleIndex::CustomEnv(0) }] if key == "GUPPY_TEST"
+    ));
+}
+
+#[test]
+fn guppy_env_rules_all() {
+    let old = JsonFixture::metadata_guppy_869476c();
+    let new = JsonFixture::metadata_guppy_c9b4f76();
+
+    // The default rules mark everything changed if the Rust version changes.
+    let mut determinator = Determinator::new(old.graph(), new.graph());
+    determinator.set_env_snapshots(
+        [("rustc-version", "rustc 1.60.0")],
+        [("rustc-version", "rustc 1.61.0")],
+    );
+    let determinator_set = determinator.compute();
+
+    let workspace_set = new.graph().resolve_workspace();
+    assert_eq!(
+        determinator_set.env_changed_set, workspace_set,
+        "all packages marked changed"
+    );
+    assert_eq!(
+        determinator_set.affected_set, workspace_set,
+        "all packages affected"
+    );
+    let explanation = determinator_set
+        .explain(workspace_id(new, "guppy"))
+        .expect("guppy is affected");
+    assert!(matches!(
+        explanation.steps().next().expect("at least one step").1,
+        [ChangeReason::EnvMarkedAll { key, rule_index: RuleIndex::DefaultEnv(0) }]
+            if key == "rustc-version"
+    ));
+
+    // Changing the compiler wrapper, e.g. to turn on sccache, doesn't affect build outputs.
+    let mut determinator = Determinator::new(old.graph(), new.graph());
+    determinator.set_env_snapshots(
+        [("RUSTC_WRAPPER", ""), ("CARGO_BUILD_RUSTC_WRAPPER", "")],
+        [
+            ("RUSTC_WRAPPER", "sccache"),
+            ("CARGO_BUILD_RUSTC_WRAPPER", "sccache"),
+        ],
+    );
+    let determinator_set = determinator.compute();
+    assert!(
+        determinator_set.env_changed_set.is_empty(),
+        "no packages marked changed through the environment"
+    );
+}
+
+#[test]
+fn imports_match_paths() {
+    let graph = imports_graph();
+    let mut determinator = Determinator::new(&graph, &graph);
+    determinator.set_trace_imports(true);
+
+    for (path, expected_names, expected_match) in [
+        // Modules, including #[path] and inline modules, belong to the library.
+        ("alpha/src/foo.rs", vec!["alpha"], PathMatch::ImportsTraced),
+        (
+            "alpha/src/foo/sub.rs",
+            vec!["alpha"],
+            PathMatch::ImportsTraced,
+        ),
+        (
+            "alpha/src/other/bar_impl.rs",
+            vec!["alpha"],
+            PathMatch::ImportsTraced,
+        ),
+        (
+            "alpha/src/inline/nested.rs",
+            vec!["alpha"],
+            PathMatch::ImportsTraced,
+        ),
+        (
+            "alpha/data/alpha.txt",
+            vec!["alpha"],
+            PathMatch::ImportsTraced,
+        ),
+        // This file is included by both alpha and beta's tests.
+        (
+            "alpha/tests/data/input.txt",
+            vec!["alpha", "beta"],
+            PathMatch::ImportsTraced,
+        ),
+        // Files outside packages are matched to the packages that include them.
+        ("shared/config.txt", vec!["beta"], PathMatch::ImportsTraced),
+        // Rust files next to library sources that aren't included by any targets don't match
+        // anything.
+        ("alpha/src/scratch.rs", vec![], PathMatch::ImportsTraced),
+        // Elsewhere, they may be read at runtime, as with trybuild tests.
+        (
+            "alpha/tests/ui/missing_field.rs",
+            vec!["alpha"],
+            PathMatch::AncestorMatched,
+        ),
+        // Other files that aren't included are matched to the nearest package, since they may be
+        // read at runtime.
+        (
+            "alpha/fixtures/notes.txt",
+            vec!["alpha"],
+            PathMatch::AncestorMatched,
+        ),
+        // gamma has an include that can't be resolved, so its files are matched as usual.
+        ("gamma/data.txt", vec!["gamma"], PathMatch::AncestorMatched),
+        (
+            "gamma/src/scratch.rs",
+            vec!["gamma"],
+            PathMatch::AncestorMatched,
+        ),
+        ("shared/other.txt", vec![], PathMatch::NoMatches),
+    ] {
+        let mut names = vec![];
+        let path_match = determinator.match_path(path, |id| {
+            names.push(graph.metadata(id).expect("valid package ID").name());
+        });
+        names.sort_unstable();
+        assert_eq!(
+            path_match, expected_match,
+            "for {}, path match is correct",
+            path
+        );
+        assert_eq!(
+            names, expected_names,
+            "for {}, matched packages are correct",
+            path
+        );
+    }
+}
+
+#[test]
+fn imports_affected_targets() {
+    let graph = imports_graph();
+    let alpha_id = graph_workspace_id(&graph, "alpha");
+    let beta_id = graph_workspace_id(&graph, "beta");
+
+    let mut determinator = Determinator::new(&graph, &graph);
+    determinator.set_trace_imports(true);
+    determinator.add_changed_paths(vec![
+        "alpha/tests/data/input.txt",
+        "alpha/tests/ui/missing_field.rs",
+        "alpha/src/scratch.rs",
+    ]);
+    let determinator_set = determinator.compute();
+
+    // Both alpha and beta include the file through tests, so beta isn't affected through alpha.
+    let affected_targets = &determinator_set.affected_targets;
+    assert_eq!(
+        affected_targets.get(alpha_id),
+        Some(&AffectedPackageTargets::Only(
+            std::iter::once(BuildTargetId::Test("integration")).collect()
+        )),
+        "alpha's integration test is affected"
+    );
+    assert_eq!(
+        affected_targets.get(beta_id),
+        Some(&AffectedPackageTargets::Only(
+            std::iter::once(BuildTargetId::Test("beta_test")).collect()
+        )),
+        "beta's test is affected"
+    );
+    assert_eq!(affected_targets.len(), 2, "no other packages are affected");
+
+    let explanation = determinator_set.explain(beta_id).expect("beta is affected");
+    assert!(matches!(
+        explanation.steps().next().expect("at least one step").1,
+        [ChangeReason::PathIncluded { path }] if path == "alpha/tests/data/input.txt"
+    ));
+
+    // Without import tracing, beta is only affected as a dependent of alpha.
+    let mut determinator = Determinator::new(&graph, &graph);
+    determinator.add_changed_paths(vec!["alpha/tests/data/input.txt"]);
+    let determinator_set = determinator.compute();
+    assert_eq!(
+        determinator_set.path_changed_set,
+        graph
+            .resolve_workspace_names(["alpha"])
+            .expect("workspace names resolved"),
+        "only alpha changed without tracing"
+    );
+}
+
 static GIT_MATCH_PATHS_DIFF: &str =
     include_str!("../../../fixtures/determinator-paths/git-diff.out");
 
@@ -512,6 +1063,29 @@ fn git_match_paths_impl(paths: Utf8Paths0) {
     );
 }
 
+fn workspace_id<'g>(fixture: &'g JsonFixture, name: &str) -> &'g PackageId {
+    graph_workspace_id(fixture.graph(), name)
+}
+
+fn graph_workspace_id<'g>(graph: &'g PackageGraph, name: &str) -> &'g PackageId {
+    graph
+        .workspace()
+        .member_by_name(name)
+        .expect("workspace member found")
+        .id()
+}
+
+/// Builds the graph for the on-disk workspace used by the import tracing tests.
+fn imports_graph() -> PackageGraph {
+    let workspace_dir = Utf8Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("../../fixtures/workspace/determinator-imports");
+    MetadataCommand::new()
+        .current_dir(workspace_dir)
+        .other_options(["--offline"])
+        .build_graph()
+        .expect("package graph built")
+}
+
 fn read_options(fixture: &JsonFixture, toml_name: &str) -> DeterminatorRules {
     // Path to the determinator.toml file.
     let mut toml_path = fixture.abs_path().to_path_buf();